@@ -0,0 +1,34 @@
+/* The C API of the rng_core library. Link against the rng_core shared library built by
+ * `cargo build --release`. */
+
+#ifndef RNG_CORE_H
+#define RNG_CORE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Rng rng_t;
+
+/* Creates a generator for `algorithm`, named like on the command line. Seeded with `seed_len`
+ * bytes from `seed` like `--seed-hex`, or from the OS if `seed` is NULL.
+ * Returns NULL on failure. */
+rng_t *rng_new(const char *algorithm, const uint8_t *seed, size_t seed_len);
+
+/* Like rng_new, but seeded with a number like `--seed`. */
+rng_t *rng_new_u64(const char *algorithm, uint64_t seed);
+
+/* Fills `dest` with `len` random bytes. Returns zero on success. */
+int rng_fill_bytes(rng_t *rng, uint8_t *dest, size_t len);
+
+/* Frees a generator created by rng_new or rng_new_u64. */
+void rng_destroy(rng_t *rng);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
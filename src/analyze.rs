@@ -0,0 +1,346 @@
+//! The `analyze` subcommand. Statistics showing how random a sequence of bytes looks, in the
+//! style of the `ent` program by John Walker.
+
+use crate::{sources, stats, Algorithm};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The radius of the circle the Monte Carlo value for pi is computed with. Points are made of
+/// two 24 bit coordinates, like in `ent`.
+const MONTE_CARLO_RADIUS: f64 = 16_777_215.0;
+
+/// The width of the longest bar in the histogram bar chart, in characters.
+const BAR_WIDTH: u64 = 64;
+
+/// Analyzes the data in `file`, on stdin if no file is given, or generated by the `generate`
+/// algorithm. Stops after `bytes` bytes if given.
+pub fn run(
+    file: Option<&Path>,
+    generate: Option<Algorithm>,
+    bytes: Option<u64>,
+) -> Result<Analysis, Box<dyn Error>> {
+    let mut analysis = Analysis::new();
+    let input = open_input(file, generate)?;
+    analysis.read_from(input.take(bytes.unwrap_or(u64::MAX)))?;
+    if analysis.total == 0 {
+        return Err("No data to analyze".into());
+    }
+    Ok(analysis)
+}
+
+/// Opens the data to analyze. The output of the `generate` algorithm if given, otherwise
+/// `file`, or stdin if no file is given either. Shared with the other subcommands analyzing
+/// data.
+pub fn open_input(
+    file: Option<&Path>,
+    generate: Option<Algorithm>,
+) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    Ok(match (file, generate) {
+        (_, Some(algorithm)) => Box::new(sources::open_any(algorithm)?),
+        (Some(path), None) => Box::new(
+            fs::File::open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?,
+        ),
+        (None, None) => Box::new(io::stdin()),
+    })
+}
+
+/// Accumulates the statistics of a stream of bytes.
+pub struct Analysis {
+    counts: [u64; 256],
+    total: u64,
+    /// Sums for the serial correlation coefficient, of each byte, its square and its product
+    /// with the next byte. The product of the last and first bytes is added at the end.
+    sum: u64,
+    sum_squares: u128,
+    sum_products: u128,
+    first: Option<u8>,
+    last: u8,
+    /// Bytes collected towards the next Monte Carlo point, and the points inside the circle.
+    point: [u8; 6],
+    point_len: usize,
+    points: u64,
+    points_inside: u64,
+}
+
+impl Analysis {
+    pub fn new() -> Self {
+        Analysis {
+            counts: [0; 256],
+            total: 0,
+            sum: 0,
+            sum_squares: 0,
+            sum_products: 0,
+            first: None,
+            last: 0,
+            point: [0; 6],
+            point_len: 0,
+            points: 0,
+            points_inside: 0,
+        }
+    }
+
+    /// Analyzes all data from `reader`.
+    pub fn read_from(&mut self, mut reader: impl Read) -> io::Result<()> {
+        let mut buf = vec![0u8; crate::BUFFER_SIZE];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => self.update(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.counts[usize::from(byte)] += 1;
+            self.sum += u64::from(byte);
+            self.sum_squares += u128::from(byte) * u128::from(byte);
+            match self.first {
+                None => self.first = Some(byte),
+                Some(_) => self.sum_products += u128::from(self.last) * u128::from(byte),
+            }
+            self.last = byte;
+
+            self.point[self.point_len] = byte;
+            self.point_len += 1;
+            if self.point_len == self.point.len() {
+                self.add_point();
+            }
+        }
+        self.total += data.len() as u64;
+    }
+
+    fn add_point(&mut self) {
+        let coordinate = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .fold(0.0, |value, &byte| value * 256.0 + f64::from(byte))
+        };
+        let x = coordinate(&self.point[..3]);
+        let y = coordinate(&self.point[3..]);
+        self.points += 1;
+        if x * x + y * y <= MONTE_CARLO_RADIUS * MONTE_CARLO_RADIUS {
+            self.points_inside += 1;
+        }
+        self.point_len = 0;
+    }
+
+    /// The number of times each byte value occurred.
+    pub fn histogram(&self, format: HistogramFormat, bars: bool) -> Histogram<'_> {
+        Histogram {
+            counts: &self.counts,
+            format,
+            bars,
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        let total = self.total as f64;
+        let expected = total / 256.0;
+        let mut entropy = 0.0;
+        let mut chi_square = 0.0;
+        for &count in self.counts.iter() {
+            if count > 0 {
+                let p = count as f64 / total;
+                entropy -= p * p.log2();
+            }
+            let deviation = count as f64 - expected;
+            chi_square += deviation * deviation / expected;
+        }
+
+        let sum_products =
+            self.sum_products + u128::from(self.last) * u128::from(self.first.unwrap_or(0));
+        let sum = self.sum as f64;
+        let serial_correlation = (total * sum_products as f64 - sum * sum)
+            / (total * self.sum_squares as f64 - sum * sum);
+
+        Report {
+            bytes: self.total,
+            entropy,
+            chi_square,
+            chi_square_probability: stats::chi_square_upper_tail(chi_square, 255.0),
+            mean: sum / total,
+            pi: 4.0 * self.points_inside as f64 / self.points as f64,
+            serial_correlation,
+        }
+    }
+}
+
+/// The statistics of the analyzed data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub bytes: u64,
+    /// Shannon entropy, in bits per byte.
+    pub entropy: f64,
+    pub chi_square: f64,
+    /// The probability that truly random data gives a larger chi-square value.
+    pub chi_square_probability: f64,
+    pub mean: f64,
+    /// The Monte Carlo value for pi.
+    pub pi: f64,
+    pub serial_correlation: f64,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Entropy = {:.6} bits per byte.", self.entropy)?;
+        writeln!(f)?;
+        writeln!(f, "Optimum compression would reduce the size")?;
+        writeln!(
+            f,
+            "of this {} byte file by {:.0} percent.",
+            self.bytes,
+            (8.0 - self.entropy) / 8.0 * 100.0
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Chi square distribution for {} samples is {:.2}, and randomly",
+            self.bytes, self.chi_square
+        )?;
+        writeln!(
+            f,
+            "would exceed this value {:.2} percent of the times.",
+            self.chi_square_probability * 100.0
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Arithmetic mean value of data bytes is {:.4} (127.5 = random).",
+            self.mean
+        )?;
+        writeln!(
+            f,
+            "Monte Carlo value for Pi is {:.9} (error {:.2} percent).",
+            self.pi,
+            (self.pi - std::f64::consts::PI).abs() / std::f64::consts::PI * 100.0
+        )?;
+        writeln!(
+            f,
+            "Serial correlation coefficient is {:.6} (totally uncorrelated = 0.0).",
+            self.serial_correlation
+        )
+    }
+}
+
+/// How to print the byte value histogram.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HistogramFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for HistogramFormat {
+    type Err = ParseHistogramFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(HistogramFormat::Text),
+            "csv" => Ok(HistogramFormat::Csv),
+            "json" => Ok(HistogramFormat::Json),
+            _ => Err(ParseHistogramFormatError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseHistogramFormatError(());
+
+impl fmt::Display for ParseHistogramFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid histogram format. See --help for a list of valid options."
+        )
+    }
+}
+
+/// The count of each byte value in the analyzed data, printed in the given format. The text
+/// format can include a bar chart, scaled so the most common value gets the widest bar.
+pub struct Histogram<'a> {
+    counts: &'a [u64; 256],
+    format: HistogramFormat,
+    bars: bool,
+}
+
+impl fmt::Display for Histogram<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            HistogramFormat::Text => {
+                let max = self.counts.iter().copied().max().unwrap_or(0).max(1);
+                let width = self.counts.iter().max().unwrap_or(&0).to_string().len();
+                for (value, &count) in self.counts.iter().enumerate() {
+                    write!(f, "0x{:02x} {:>width$}", value, count, width = width)?;
+                    if self.bars {
+                        let bar =
+                            (u128::from(count) * u128::from(BAR_WIDTH) / u128::from(max)) as usize;
+                        write!(f, " {}", "#".repeat(bar))?;
+                    }
+                    writeln!(f)?;
+                }
+                Ok(())
+            }
+            HistogramFormat::Csv => {
+                writeln!(f, "byte,count")?;
+                for (value, count) in self.counts.iter().enumerate() {
+                    writeln!(f, "{},{}", value, count)?;
+                }
+                Ok(())
+            }
+            HistogramFormat::Json => {
+                let counts: Vec<String> = self.counts.iter().map(u64::to_string).collect();
+                writeln!(f, "{{\"counts\":[{}]}}", counts.join(","))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_analysis() {
+    // Every byte value the same number of times: perfect entropy, chi-square and mean
+    let data: Vec<u8> = (0..=255).cycle().take(256 * 64).collect();
+    let mut analysis = Analysis::new();
+    // Split the data to exercise the state carried between updates
+    analysis.update(&data[..1001]);
+    analysis.update(&data[1001..]);
+    let report = analysis.report();
+    assert_eq!(report.bytes, 256 * 64);
+    assert!((report.entropy - 8.0).abs() < 1e-12);
+    assert_eq!(report.chi_square, 0.0);
+    assert_eq!(report.mean, 127.5);
+    assert!(report.serial_correlation > 0.9);
+
+    let csv = analysis.histogram(HistogramFormat::Csv, false).to_string();
+    assert_eq!(csv.lines().count(), 257);
+    assert_eq!(csv.lines().nth(256), Some("255,64"));
+
+    let mut constant = Analysis::new();
+    constant.update(&[7; 1000]);
+    let chart = constant.histogram(HistogramFormat::Text, true).to_string();
+    assert_eq!(
+        chart.lines().nth(7),
+        Some(&*format!("0x07 1000 {}", "#".repeat(64)))
+    );
+    assert_eq!(chart.lines().nth(8), Some("0x08    0 "));
+    let json = constant.histogram(HistogramFormat::Json, false).to_string();
+    assert!(json.starts_with("{\"counts\":[0,0,0,0,0,0,0,1000,0,"));
+    let report = constant.report();
+    assert_eq!(report.entropy, 0.0);
+    assert!(report.chi_square_probability < 1e-9);
+
+    let mut random = Analysis::new();
+    random
+        .read_from((&mut rand::rngs::OsRng as &mut dyn rand::RngCore).take(1 << 20))
+        .unwrap();
+    let report = random.report();
+    assert!(report.entropy > 7.99);
+    assert!((report.pi - std::f64::consts::PI).abs() < 0.05);
+    assert!(report.serial_correlation.abs() < 0.01);
+}
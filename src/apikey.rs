@@ -0,0 +1,100 @@
+//! The `apikey` subcommand. Tokens in the shape of the API keys of many services: a prefix telling
+//! what the key is for, the random part in base62, and optionally a checksum of the random part,
+//! so typos and truncated keys can be caught without a database lookup. Like the tokens of
+//! GitHub, which end in the CRC32 of the random part in six base62 digits.
+
+use crate::formatting;
+use crate::parsing::ParseError;
+use rand::RngCore;
+use std::fmt;
+
+/// The least entropy allowed, in bytes.
+pub const MIN_ENTROPY_LEN: usize = 16;
+
+/// The checksum at the end of the tokens.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Checksum {
+    None,
+    Crc32,
+}
+
+impl std::str::FromStr for Checksum {
+    type Err = ParseChecksumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Checksum::None),
+            "crc32" => Ok(Checksum::Crc32),
+            _ => Err(ParseChecksumError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseChecksumError(());
+
+impl fmt::Display for ParseChecksumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid checksum. See --help for a list of valid options."
+        )
+    }
+}
+
+/// Parses the prefix of the tokens. Only letters, digits and underscores are allowed,
+/// so the tokens can be selected with a double click and need no quoting.
+pub fn parse_prefix(s: &str) -> Result<String, ParseError> {
+    match s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+        true => Ok(s.to_owned()),
+        false => Err(ParseError::new(format!(
+            "\"{}\" is not a valid prefix. Use only letters, digits and '_'",
+            s
+        ))),
+    }
+}
+
+/// A random token with `entropy_len` random bytes.
+pub fn token(
+    prefix: &str,
+    entropy_len: usize,
+    checksum: Checksum,
+    rng: &mut dyn RngCore,
+) -> String {
+    let mut entropy = vec![0; entropy_len];
+    rng.fill_bytes(&mut entropy);
+    let random = formatting::base62(&entropy);
+    match checksum {
+        Checksum::None => format!("{}{}", prefix, random),
+        Checksum::Crc32 => {
+            let crc = formatting::crc32(0, random.as_bytes());
+            format!(
+                "{}{}{}",
+                prefix,
+                random,
+                formatting::base62(&crc.to_be_bytes())
+            )
+        }
+    }
+}
+
+#[test]
+fn test_token() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    let token = token("sk_test_", 24, Checksum::Crc32, &mut rng);
+    let rest = token.strip_prefix("sk_test_").unwrap();
+    // 24 bytes take 33 base62 digits, and the checksum 6
+    assert_eq!(rest.len(), 33 + 6);
+    assert!(rest.bytes().all(|b| b.is_ascii_alphanumeric()));
+    let (random, checksum) = rest.split_at(33);
+    assert_eq!(
+        checksum,
+        formatting::base62(&formatting::crc32(0, random.as_bytes()).to_be_bytes())
+    );
+
+    assert!(parse_prefix("ghp_").is_ok());
+    assert!(parse_prefix("a b").is_err());
+    assert!(parse_prefix("a-b").is_err());
+}
@@ -0,0 +1,180 @@
+//! The `audio` subcommand. White noise as an uncompressed PCM WAV file.
+//!
+//! Uniformly random bytes are uniformly random samples at every bit depth, so the generated
+//! data is written as is after the header.
+
+use crate::{sources, Algorithm};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// The sample formats WAV files can hold as plain PCM.
+const VALID_BITS: [u16; 4] = [8, 16, 24, 32];
+
+/// The size of the WAV header written before the samples.
+const HEADER_LEN: u32 = 44;
+
+/// The format of the generated audio.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AudioFormat {
+    /// Samples per second.
+    pub rate: u32,
+    /// Bits per sample.
+    pub bits: u16,
+    pub channels: u16,
+}
+
+impl AudioFormat {
+    /// Checks that the format is PCM a WAV header can describe.
+    pub fn validate(&self) -> Result<(), String> {
+        if !VALID_BITS.contains(&self.bits) {
+            return Err("The bits per sample must be 8, 16, 24 or 32".to_owned());
+        }
+        if self.rate == 0 || self.channels == 0 {
+            return Err("The sample rate and number of channels must be at least 1".to_owned());
+        }
+        // The frame size is a 16 bit field and the byte rate a 32 bit field of the header
+        let byte_rate = self
+            .channels
+            .checked_mul(self.bits / 8)
+            .and_then(|block_align| self.rate.checked_mul(u32::from(block_align)));
+        if byte_rate.is_none() {
+            return Err(format!(
+                "{} channels of {} bit samples at {} Hz don't fit in a WAV file",
+                self.channels, self.bits, self.rate
+            ));
+        }
+        Ok(())
+    }
+
+    /// The size of a frame, one sample of every channel, in bytes. The format must be valid.
+    fn block_align(&self) -> u16 {
+        self.channels * (self.bits / 8)
+    }
+
+    /// The length of the sample data for `frames` frames of audio, in bytes.
+    fn data_len(&self, frames: u64) -> Option<u64> {
+        frames.checked_mul(u64::from(self.block_align()))
+    }
+
+    /// The WAV file header for `frames` frames of audio. Fails if the data does not fit in a
+    /// WAV file.
+    fn header(&self, frames: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        let data_len = self
+            .data_len(frames)
+            .and_then(|len| u32::try_from(len).ok())
+            .filter(|len| len.checked_add(HEADER_LEN - 8).is_some())
+            .ok_or("The audio is too long for a WAV file, which must be below 4 GiB")?;
+        let byte_rate = self.rate * u32::from(self.block_align());
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(HEADER_LEN - 8 + data_len).to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        // The PCM format tag
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&self.channels.to_le_bytes());
+        header.extend_from_slice(&self.rate.to_le_bytes());
+        header.extend_from_slice(&byte_rate.to_le_bytes());
+        header.extend_from_slice(&self.block_align().to_le_bytes());
+        header.extend_from_slice(&self.bits.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_len.to_le_bytes());
+        Ok(header)
+    }
+}
+
+/// Writes `seconds` of white noise generated by `algorithm` to a WAV file at `out`.
+pub fn run(
+    algorithm: Algorithm,
+    format: AudioFormat,
+    seconds: f64,
+    out: &Path,
+) -> Result<(), Box<dyn Error>> {
+    format.validate()?;
+    if seconds.is_nan() || seconds < 0.0 {
+        return Err("The duration can not be negative".into());
+    }
+    let frames = (seconds * f64::from(format.rate)).round() as u64;
+    let header = format.header(frames)?;
+
+    let rng = sources::open_any(algorithm)?;
+    let output =
+        fs::File::create(out).map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+    let mut output = BufWriter::new(output);
+    let data_len = format.data_len(frames).expect("The header is valid");
+    write_wav(&mut output, &header, data_len, rng)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the header and then `data_len` bytes of sample data from `samples`. The samples are
+/// streamed, so durations of any length use little memory. Only failures to write are
+/// returned as `io::Error`s.
+fn write_wav(
+    mut writer: impl Write,
+    header: &[u8],
+    data_len: u64,
+    samples: impl Read,
+) -> Result<(), Box<dyn Error>> {
+    writer.write_all(header)?;
+    let mut samples = samples.take(data_len);
+    let mut buf = Zeroizing::new(vec![0u8; crate::BUFFER_SIZE]);
+    let mut copied = 0;
+    loop {
+        let len = match samples.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("Failed to read the samples: {}", e).into()),
+        };
+        writer.write_all(&buf[..len])?;
+        copied += len as u64;
+    }
+    if copied < data_len {
+        return Err("The generator ended before the audio was complete".into());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_wav() {
+    let format = AudioFormat {
+        rate: 48000,
+        bits: 16,
+        channels: 2,
+    };
+    let header = format.header(3).unwrap();
+    let expected: &[u8] = &[
+        b'R', b'I', b'F', b'F', 48, 0, 0, 0, b'W', b'A', b'V', b'E', // RIFF header
+        b'f', b'm', b't', b' ', 16, 0, 0, 0, 1, 0, 2, 0, // PCM, two channels
+        0x80, 0xbb, 0, 0, 0x00, 0xee, 0x02, 0, 4, 0, 16, 0, // Rates, alignment and bits
+        b'd', b'a', b't', b'a', 12, 0, 0, 0, // Data length
+    ];
+    assert_eq!(header, expected);
+
+    let mut wav = Vec::new();
+    write_wav(&mut wav, &header, 12, &[7; 100][..]).unwrap();
+    assert_eq!(wav.len(), 44 + 12);
+    assert!(write_wav(&mut wav, &header, 12, &[7; 11][..]).is_err());
+
+    assert!(format.header(1 << 30).is_err());
+
+    assert!(format.validate().is_ok());
+    let too_many_channels = AudioFormat {
+        channels: 40_000,
+        ..format
+    };
+    assert!(too_many_channels.validate().is_err());
+    let too_high_rate = AudioFormat {
+        rate: u32::MAX / 2,
+        ..format
+    };
+    assert!(too_high_rate.validate().is_err());
+    assert!(AudioFormat { bits: 12, ..format }.validate().is_err());
+}
@@ -0,0 +1,777 @@
+//! The `bench` subcommand. Measures how fast each algorithm generates data by running it for a
+//! fixed time, both on one thread and on several, and discarding the output. With a range of
+//! thread counts, every count in the range is measured to show how the algorithm scales.
+
+use crate::formatting;
+use crate::platform;
+use crate::{multithreaded, singlethreaded, Algorithm};
+use std::cmp::Ordering;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Seek, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The algorithms benchmarked when none are given. The other entropy sources depend on hardware
+/// that might not be present.
+pub const ALGORITHMS: &[Algorithm] = &[
+    Algorithm::Default,
+    Algorithm::Hc,
+    Algorithm::ChaCha8,
+    Algorithm::ChaCha12,
+    Algorithm::ChaCha20,
+    Algorithm::XorShift,
+    Algorithm::Pcg,
+    Algorithm::RomuTrio,
+    Algorithm::RomuDuoJr,
+    Algorithm::Lfsr,
+    Algorithm::LaggedFibonacci,
+    Algorithm::CtrDrbg,
+    Algorithm::HmacDrbg,
+    Algorithm::Os,
+];
+
+/// The file sink starts over from the beginning of the file after this many bytes.
+const FILE_SINK_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The knee point of a thread sweep is the fewest threads reaching this fraction of the best
+/// multithreaded throughput. More threads than that add little.
+const KNEE_FRACTION: f64 = 0.9;
+
+/// The throughput of one algorithm, in bytes per second.
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    pub algorithm: Algorithm,
+    pub single: f64,
+    /// The multithreaded throughput by thread count. Empty for the algorithms that always run
+    /// single threaded.
+    pub multi: Vec<(usize, f64)>,
+}
+
+impl Throughput {
+    /// The best of the single and multithreaded throughput.
+    fn best(&self) -> f64 {
+        self.multi
+            .iter()
+            .fold(self.single, |best, &(_, rate)| best.max(rate))
+    }
+
+    /// Returns the fewest threads that reach `KNEE_FRACTION` of the best multithreaded
+    /// throughput, or `None` if it was not measured.
+    pub fn knee(&self) -> Option<usize> {
+        let best = self.multi.iter().map(|&(_, rate)| rate).fold(0.0, f64::max);
+        self.multi
+            .iter()
+            .find(|&&(_, rate)| rate >= best * KNEE_FRACTION)
+            .map(|&(threads, _)| threads)
+    }
+}
+
+/// The throughput of one algorithm by buffer size, in bytes per second.
+#[derive(Debug, Clone)]
+pub struct BufferSweep {
+    pub algorithm: Algorithm,
+    pub rates: Vec<(usize, f64)>,
+}
+
+impl BufferSweep {
+    fn best(&self) -> f64 {
+        self.rates.iter().map(|&(_, rate)| rate).fold(0.0, f64::max)
+    }
+
+    /// Returns the buffer size with the highest throughput.
+    pub fn best_size(&self) -> Option<usize> {
+        let best = self.best();
+        self.rates
+            .iter()
+            .find(|&&(_, rate)| rate >= best)
+            .map(|&(buffer_size, _)| buffer_size)
+    }
+}
+
+/// Where the data goes in a buffer size sweep.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Sink {
+    /// Discards the data.
+    Null,
+    /// Writes a temporary file, without syncing it to storage.
+    File,
+    /// Writes to a pipe drained by another thread.
+    Pipe,
+}
+
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Sink::Null => "null",
+            Sink::File => "file",
+            Sink::Pipe => "pipe",
+        })
+    }
+}
+
+impl std::str::FromStr for Sink {
+    type Err = ParseSinkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "null" => Ok(Sink::Null),
+            "file" => Ok(Sink::File),
+            "pipe" => Ok(Sink::Pipe),
+            _ => Err(ParseSinkError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseSinkError(());
+
+impl fmt::Display for ParseSinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid sink. See --help for a list of valid options.")
+    }
+}
+
+/// An open sink. The temporary file is removed, and the pipe closed, when dropped.
+enum SinkOutput {
+    Null,
+    File(fs::File, PathBuf),
+    Pipe(Option<io::PipeWriter>, Option<thread::JoinHandle<()>>),
+}
+
+impl SinkOutput {
+    fn open(sink: Sink) -> io::Result<Self> {
+        Ok(match sink {
+            Sink::Null => SinkOutput::Null,
+            Sink::File => {
+                let path = env::temp_dir().join(format!("rng-bench-{}", std::process::id()));
+                SinkOutput::File(fs::File::create(&path)?, path)
+            }
+            Sink::Pipe => {
+                let (mut reader, writer) = io::pipe()?;
+                let drain = thread::spawn(move || {
+                    let _ = io::copy(&mut reader, &mut io::sink());
+                });
+                SinkOutput::Pipe(Some(writer), Some(drain))
+            }
+        })
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            SinkOutput::Null => Ok(()),
+            SinkOutput::File(file, _) => {
+                // Starts over instead of growing the file past what the page cache holds
+                if file.stream_position()? >= FILE_SINK_SIZE {
+                    file.rewind()?;
+                }
+                file.write_all(buf)
+            }
+            SinkOutput::Pipe(writer, _) => writer.as_mut().expect("Pipe is open").write_all(buf),
+        }
+    }
+}
+
+impl Drop for SinkOutput {
+    fn drop(&mut self) {
+        match self {
+            SinkOutput::Null => (),
+            SinkOutput::File(_, path) => {
+                let _ = fs::remove_file(path);
+            }
+            SinkOutput::Pipe(writer, drain) => {
+                // The draining thread reads until the write end is closed
+                *writer = None;
+                if let Some(drain) = drain.take() {
+                    let _ = drain.join();
+                }
+            }
+        }
+    }
+}
+
+/// Benchmarks every algorithm for `duration` on one thread and then on every thread count from
+/// `threads.0` to `threads.1`. Returns the results sorted by throughput, fastest first.
+pub fn run(
+    algorithms: &[Algorithm],
+    duration: Duration,
+    threads: (usize, usize),
+) -> Result<Vec<Throughput>, Box<dyn Error>> {
+    let (min_threads, max_threads) = threads;
+    if min_threads == 0 || min_threads > max_threads {
+        return Err("At least one thread is needed".into());
+    }
+    let mut results = Vec::with_capacity(algorithms.len());
+    for &algorithm in algorithms {
+        check_algorithm(algorithm)?;
+        eprintln!("Benchmarking {}", algorithm);
+        let single = measure_single(algorithm, duration, crate::BUFFER_SIZE, discard)?;
+        let mut multi = Vec::new();
+        if !algorithm.is_entropy_source() {
+            for threads in min_threads..=max_threads {
+                let rate =
+                    measure_multi(algorithm, duration, threads, crate::BUFFER_SIZE, discard)?;
+                multi.push((threads, rate));
+            }
+        }
+        results.push(Throughput {
+            algorithm,
+            single,
+            multi,
+        });
+    }
+    results.sort_by(|a, b| b.best().partial_cmp(&a.best()).unwrap_or(Ordering::Equal));
+    Ok(results)
+}
+
+/// Benchmarks every algorithm for `duration` with every buffer size from `buffer_sizes.0`,
+/// doubling up to `buffer_sizes.1`, writing the data to `sink`. Generates on `threads` threads,
+/// like rng itself would. Returns the results sorted by throughput, fastest first.
+pub fn sweep_buffer(
+    algorithms: &[Algorithm],
+    duration: Duration,
+    threads: usize,
+    buffer_sizes: (usize, usize),
+    sink: Sink,
+) -> Result<Vec<BufferSweep>, Box<dyn Error>> {
+    let (min_size, max_size) = buffer_sizes;
+    if threads == 0 || min_size == 0 || min_size > max_size {
+        return Err("At least one thread and one buffer size is needed".into());
+    }
+    let mut results = Vec::with_capacity(algorithms.len());
+    for &algorithm in algorithms {
+        check_algorithm(algorithm)?;
+        eprintln!("Benchmarking {}", algorithm);
+        let mut rates = Vec::new();
+        let mut buffer_size = min_size;
+        while buffer_size <= max_size {
+            let mut output = SinkOutput::open(sink)?;
+            let output_fn = |buf: &[u8]| output.write(buf);
+            let rate = if threads > 1 && !algorithm.is_entropy_source() {
+                measure_multi(algorithm, duration, threads, buffer_size, output_fn)?
+            } else {
+                measure_single(algorithm, duration, buffer_size, output_fn)?
+            };
+            rates.push((buffer_size, rate));
+            buffer_size *= 2;
+        }
+        results.push(BufferSweep { algorithm, rates });
+    }
+    results.sort_by(|a, b| b.best().partial_cmp(&a.best()).unwrap_or(Ordering::Equal));
+    Ok(results)
+}
+
+/// Fails for the algorithms that can't be benchmarked, as they need arguments or input.
+fn check_algorithm(algorithm: Algorithm) -> Result<(), Box<dyn Error>> {
+    match algorithm {
+        Algorithm::Plugin(_) | Algorithm::Stdin => {
+            Err(format!("{} can not be benchmarked", algorithm).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The output of the benchmarks not writing the data anywhere.
+fn discard(_: &[u8]) -> io::Result<()> {
+    Ok(())
+}
+
+/// Returns the bytes per second `algorithm` generates on the current thread, with every buffer
+/// passed to `output`.
+fn measure_single(
+    algorithm: Algorithm,
+    duration: Duration,
+    buffer_size: usize,
+    mut output: impl FnMut(&[u8]) -> io::Result<()>,
+) -> Result<f64, Box<dyn Error>> {
+    let mut meter = Meter::new(duration);
+    let mut write_fn = |buf: &[u8]| meter.add(output(buf).map(|()| buf.len()));
+    if algorithm.is_entropy_source() {
+        let source = crate::sources::open_any(algorithm)?;
+        singlethreaded::run_source(source, buffer_size, write_fn)?;
+    } else {
+        singlethreaded::run(algorithm, None, None, None, buffer_size, &mut write_fn);
+    }
+    Ok(meter.rate()?)
+}
+
+/// Returns the bytes per second `algorithm` generates on `threads` worker threads, with every
+/// buffer passed to `output`.
+fn measure_multi(
+    algorithm: Algorithm,
+    duration: Duration,
+    threads: usize,
+    buffer_size: usize,
+    mut output: impl FnMut(&[u8]) -> io::Result<()>,
+) -> Result<f64, Box<dyn Error>> {
+    let mut meter = Meter::new(duration);
+    let config = multithreaded::Config {
+        threads,
+        buffer_size,
+        queue_depth: None,
+        pin_cpus: None,
+        adaptive: false,
+        huge_pages: false,
+        mapping: None,
+        positioned: None,
+        verbose: false,
+    };
+    multithreaded::run(algorithm, &config, None, |bufs| {
+        let written = bufs
+            .iter()
+            .try_fold(0, |written, buf| output(buf).map(|()| written + buf.len()));
+        meter.add(written)
+    });
+    Ok(meter.rate()?)
+}
+
+/// Counts the bytes written until the benchmark duration has passed or a write fails.
+struct Meter {
+    start: Instant,
+    duration: Duration,
+    bytes: u64,
+    error: Option<io::Error>,
+}
+
+impl Meter {
+    fn new(duration: Duration) -> Self {
+        Meter {
+            start: Instant::now(),
+            duration,
+            bytes: 0,
+            error: None,
+        }
+    }
+
+    /// Counts the bytes of a write. Returns true when the benchmark is done.
+    fn add(&mut self, written: io::Result<usize>) -> bool {
+        match written {
+            Ok(bytes) => {
+                self.bytes += bytes as u64;
+                self.start.elapsed() >= self.duration
+            }
+            Err(e) => {
+                self.error = Some(e);
+                true
+            }
+        }
+    }
+
+    /// Returns the bytes per second, or the error that stopped the benchmark.
+    fn rate(self) -> io::Result<f64> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.bytes as f64 / self.start.elapsed().as_secs_f64()),
+        }
+    }
+}
+
+/// How to print the benchmark results.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(ParseFormatError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFormatError(());
+
+impl fmt::Display for ParseFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid format. See --help for a list of valid options.")
+    }
+}
+
+/// The machine the benchmarks ran on, included in the JSON and CSV results so results from
+/// different machines can be told apart.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    pub cpu_model: Option<String>,
+    pub cpus: usize,
+    pub physical_cpus: usize,
+}
+
+impl Machine {
+    pub fn current() -> Self {
+        Machine {
+            cpu_model: platform::cpu_model(),
+            cpus: num_cpus::get(),
+            physical_cpus: num_cpus::get_physical(),
+        }
+    }
+
+    fn json(&self) -> String {
+        format!(
+            "{{\"cpu_model\":{},\"cpus\":{},\"physical_cpus\":{},\"os\":\"{}\",\"arch\":\"{}\"}}",
+            self.cpu_model
+                .as_deref()
+                .map_or_else(|| "null".to_owned(), formatting::json_string),
+            self.cpus,
+            self.physical_cpus,
+            env::consts::OS,
+            env::consts::ARCH
+        )
+    }
+}
+
+/// The header of the CSV results. Both kinds of benchmark have the same columns, one row per
+/// measurement, so results can be concatenated.
+const CSV_HEADER: &str =
+    "cpu_model,cpus,physical_cpus,os,arch,algorithm,mode,threads,buffer_size,sink,bytes_per_second";
+
+/// One measurement as a row of the CSV results.
+struct CsvRow<'a> {
+    machine: &'a Machine,
+    algorithm: Algorithm,
+    threads: Option<usize>,
+    buffer_size: usize,
+    sink: Sink,
+    rate: f64,
+}
+
+impl fmt::Display for CsvRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{},{},{},{},{},{},{},{},{},{},{:.0}",
+            formatting::csv_field(self.machine.cpu_model.as_deref().unwrap_or("")),
+            self.machine.cpus,
+            self.machine.physical_cpus,
+            env::consts::OS,
+            env::consts::ARCH,
+            formatting::csv_field(&self.algorithm.to_string()),
+            if self.threads.is_some() {
+                "multi"
+            } else {
+                "single"
+            },
+            self.threads.unwrap_or(1),
+            self.buffer_size,
+            self.sink,
+            self.rate
+        )
+    }
+}
+
+/// The benchmark results in the given format. As text, a table with one algorithm per row and
+/// one column per thread count. A thread sweep also gets a column with the knee point.
+pub struct Table<'a> {
+    pub results: &'a [Throughput],
+    pub threads: (usize, usize),
+    pub format: Format,
+    pub machine: &'a Machine,
+}
+
+impl fmt::Display for Table<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            Format::Text => self.text(f),
+            Format::Json => self.json(f),
+            Format::Csv => {
+                writeln!(f, "{}", CSV_HEADER)?;
+                for result in self.results {
+                    let row = |threads, rate| CsvRow {
+                        machine: self.machine,
+                        algorithm: result.algorithm,
+                        threads,
+                        buffer_size: crate::BUFFER_SIZE,
+                        sink: Sink::Null,
+                        rate,
+                    };
+                    write!(f, "{}", row(None, result.single))?;
+                    for &(threads, rate) in &result.multi {
+                        write!(f, "{}", row(Some(threads), rate))?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Table<'_> {
+    fn text(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (min_threads, max_threads) = self.threads;
+        let sweep = min_threads < max_threads;
+        write!(f, "{:<12} {:>14}", "algorithm", "single")?;
+        for threads in min_threads..=max_threads {
+            match threads {
+                1 => write!(f, " {:>14}", "1 thread")?,
+                threads => write!(f, " {:>14}", format!("{} threads", threads))?,
+            }
+        }
+        if sweep {
+            write!(f, " {:>6}", "knee")?;
+        }
+        writeln!(f)?;
+        for result in self.results {
+            write!(
+                f,
+                "{:<12} {:>14}",
+                result.algorithm.to_string(),
+                format_rate(result.single)
+            )?;
+            for threads in min_threads..=max_threads {
+                let rate = result.multi.iter().find(|&&(n, _)| n == threads);
+                let rate = rate.map_or_else(|| "-".to_owned(), |&(_, rate)| format_rate(rate));
+                write!(f, " {:>14}", rate)?;
+            }
+            if sweep {
+                let knee = result
+                    .knee()
+                    .map_or_else(|| "-".to_owned(), |n| n.to_string());
+                write!(f, " {:>6}", knee)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    fn json(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let results: Vec<String> = self
+            .results
+            .iter()
+            .map(|result| {
+                let multi: Vec<String> = result
+                    .multi
+                    .iter()
+                    .map(|&(threads, rate)| {
+                        format!(
+                            "{{\"threads\":{},\"bytes_per_second\":{:.0}}}",
+                            threads, rate
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"algorithm\":{},\"single\":{:.0},\"multi\":[{}],\"knee\":{}}}",
+                    formatting::json_string(&result.algorithm.to_string()),
+                    result.single,
+                    multi.join(","),
+                    result
+                        .knee()
+                        .map_or_else(|| "null".to_owned(), |n| n.to_string())
+                )
+            })
+            .collect();
+        writeln!(
+            f,
+            "{{\"machine\":{},\"buffer_size\":{},\"results\":[{}]}}",
+            self.machine.json(),
+            crate::BUFFER_SIZE,
+            results.join(",")
+        )
+    }
+}
+
+/// The buffer size sweep results in the given format. As text, a table with one algorithm per
+/// row and one column per buffer size, followed by the buffer size with the highest throughput.
+pub struct SweepTable<'a> {
+    pub results: &'a [BufferSweep],
+    pub threads: usize,
+    pub sink: Sink,
+    pub format: Format,
+    pub machine: &'a Machine,
+}
+
+impl fmt::Display for SweepTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            Format::Text => self.text(f),
+            Format::Json => self.json(f),
+            Format::Csv => {
+                writeln!(f, "{}", CSV_HEADER)?;
+                for result in self.results {
+                    for &(buffer_size, rate) in &result.rates {
+                        let row = CsvRow {
+                            machine: self.machine,
+                            algorithm: result.algorithm,
+                            threads: self.multithreaded(result.algorithm),
+                            buffer_size,
+                            sink: self.sink,
+                            rate,
+                        };
+                        write!(f, "{}", row)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SweepTable<'_> {
+    /// The number of threads `algorithm` was generated on, or `None` if single threaded.
+    fn multithreaded(&self, algorithm: Algorithm) -> Option<usize> {
+        Some(self.threads).filter(|&threads| threads > 1 && !algorithm.is_entropy_source())
+    }
+
+    fn text(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<12}", "algorithm")?;
+        let sizes = self
+            .results
+            .first()
+            .map_or(&[][..], |result| &result.rates[..]);
+        for &(buffer_size, _) in sizes {
+            write!(f, " {:>14}", format_size(buffer_size))?;
+        }
+        writeln!(f, " {:>10}", "best")?;
+        for result in self.results {
+            write!(f, "{:<12}", result.algorithm.to_string())?;
+            for &(_, rate) in &result.rates {
+                write!(f, " {:>14}", format_rate(rate))?;
+            }
+            let best = result
+                .best_size()
+                .map_or_else(|| "-".to_owned(), format_size);
+            writeln!(f, " {:>10}", best)?;
+        }
+        Ok(())
+    }
+
+    fn json(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let results: Vec<String> =
+            self.results
+                .iter()
+                .map(|result| {
+                    let rates: Vec<String> = result
+                        .rates
+                        .iter()
+                        .map(|&(buffer_size, rate)| {
+                            format!(
+                                "{{\"buffer_size\":{},\"bytes_per_second\":{:.0}}}",
+                                buffer_size, rate
+                            )
+                        })
+                        .collect();
+                    format!(
+                    "{{\"algorithm\":{},\"threads\":{},\"rates\":[{}],\"best_buffer_size\":{}}}",
+                    formatting::json_string(&result.algorithm.to_string()),
+                    self.multithreaded(result.algorithm).unwrap_or(1),
+                    rates.join(","),
+                    result.best_size().map_or_else(|| "null".to_owned(), |n| n.to_string())
+                )
+                })
+                .collect();
+        writeln!(
+            f,
+            "{{\"machine\":{},\"sink\":\"{}\",\"results\":[{}]}}",
+            self.machine.json(),
+            self.sink,
+            results.join(",")
+        )
+    }
+}
+
+fn format_size(buffer_size: usize) -> String {
+    formatting::format_bytes_written(buffer_size as u64)
+}
+
+fn format_rate(bytes_per_second: f64) -> String {
+    format!(
+        "{}/s",
+        formatting::format_bytes_written(bytes_per_second as u64)
+    )
+}
+
+#[test]
+fn test_bench() {
+    let algorithms = [Algorithm::XorShift, Algorithm::ChaCha20, Algorithm::Os];
+    let results = run(&algorithms, Duration::from_millis(50), (1, 2)).unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|result| result.single > 0.0));
+    assert!(results
+        .windows(2)
+        .all(|pair| pair[0].best() >= pair[1].best()));
+    let os = results
+        .iter()
+        .find(|result| result.algorithm == Algorithm::Os)
+        .unwrap();
+    assert!(os.multi.is_empty());
+    assert!(os.knee().is_none());
+    let xorshift = results
+        .iter()
+        .find(|result| result.algorithm == Algorithm::XorShift)
+        .unwrap();
+    assert_eq!(xorshift.multi.len(), 2);
+}
+
+#[test]
+fn test_sweep_buffer() {
+    for sink in [Sink::Null, Sink::File, Sink::Pipe] {
+        let algorithms = [Algorithm::XorShift, Algorithm::Os];
+        let results = sweep_buffer(
+            &algorithms,
+            Duration::from_millis(20),
+            2,
+            (4096, 16384),
+            sink,
+        )
+        .unwrap();
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            let sizes: Vec<usize> = result.rates.iter().map(|&(size, _)| size).collect();
+            assert_eq!(sizes, [4096, 8192, 16384]);
+            assert!(result.rates.iter().all(|&(_, rate)| rate > 0.0));
+        }
+    }
+}
+
+#[test]
+fn test_formats() {
+    let machine = Machine {
+        cpu_model: Some("Fast \"CPU\", 3 GHz".to_owned()),
+        cpus: 8,
+        physical_cpus: 4,
+    };
+    let results = [Throughput {
+        algorithm: Algorithm::ChaCha8,
+        single: 1000.4,
+        multi: vec![(1, 900.0), (2, 1800.0)],
+    }];
+    let table = |format| {
+        Table {
+            results: &results,
+            threads: (1, 2),
+            format,
+            machine: &machine,
+        }
+        .to_string()
+    };
+    let csv = table(Format::Csv);
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0], CSV_HEADER);
+    assert!(rows[1].starts_with("\"Fast \"\"CPU\"\", 3 GHz\",8,4,"));
+    assert!(rows[1].ends_with(",chacha8,single,1,65536,null,1000"));
+    assert!(rows[3].ends_with(",chacha8,multi,2,65536,null,1800"));
+    let json = table(Format::Json);
+    assert!(json.starts_with("{\"machine\":{\"cpu_model\":\"Fast \\\"CPU\\\", 3 GHz\",\"cpus\":8,"));
+    assert!(json.contains("\"results\":[{\"algorithm\":\"chacha8\",\"single\":1000,\"multi\":[{\"threads\":1,\"bytes_per_second\":900},{\"threads\":2,\"bytes_per_second\":1800}],\"knee\":2}]"));
+}
+
+#[test]
+fn test_knee() {
+    let throughput = Throughput {
+        algorithm: Algorithm::ChaCha8,
+        single: 1.0,
+        multi: vec![(1, 1.0), (2, 1.9), (3, 2.7), (4, 2.9), (5, 2.95)],
+    };
+    assert_eq!(throughput.knee(), Some(3));
+}
@@ -0,0 +1,120 @@
+//! Recording the position in a seeded output stream with `--checkpoint-file`, so that an
+//! interrupted run can continue where it stopped with `--resume`.
+
+use crate::seed::Seed;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// How often a checkpoint is written during a run.
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Everything needed to continue a deterministic output stream. The seed and position fully
+/// determine the generator state for the algorithms that support --skip.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Checkpoint {
+    /// The name of the algorithm, as given on the command line.
+    pub algorithm: String,
+    pub seed: Seed,
+    pub stream_id: Option<u64>,
+    /// The position in the output stream of the algorithm to continue from, in bytes.
+    pub position: u128,
+    /// The number of bytes written to the output so far, over all resumed runs.
+    pub written: u64,
+}
+
+impl Checkpoint {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        contents.parse().map_err(|e: ParseCheckpointError| {
+            io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+        })
+    }
+
+    /// Writes the checkpoint to a temporary file next to `path` and renames it into place, so
+    /// an interruption never leaves a partially written checkpoint behind.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, self.to_string())?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "algorithm = {}", self.algorithm)?;
+        writeln!(f, "seed = {}", self.seed)?;
+        if let Some(stream_id) = self.stream_id {
+            writeln!(f, "stream-id = {}", stream_id)?;
+        }
+        writeln!(f, "position = {}", self.position)?;
+        writeln!(f, "written = {}", self.written)
+    }
+}
+
+impl std::str::FromStr for Checkpoint {
+    type Err = ParseCheckpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut algorithm = None;
+        let mut seed = None;
+        let mut stream_id = None;
+        let mut position = None;
+        let mut written = None;
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(ParseCheckpointError(format!("Invalid line: {}", line))),
+            };
+            let invalid = || ParseCheckpointError(format!("Invalid {}: {}", key, value));
+            match key {
+                "algorithm" => algorithm = Some(value.to_owned()),
+                "seed" => seed = Some(value.parse::<Seed>().map_err(|_| invalid())?),
+                "stream-id" => stream_id = Some(value.parse().map_err(|_| invalid())?),
+                "position" => position = Some(value.parse().map_err(|_| invalid())?),
+                "written" => written = Some(value.parse().map_err(|_| invalid())?),
+                _ => return Err(ParseCheckpointError(format!("Unknown key: {}", key))),
+            }
+        }
+        let missing = |key| ParseCheckpointError(format!("Missing {}", key));
+        Ok(Checkpoint {
+            algorithm: algorithm.ok_or_else(|| missing("algorithm"))?,
+            seed: seed.ok_or_else(|| missing("seed"))?,
+            stream_id,
+            position: position.ok_or_else(|| missing("position"))?,
+            written: written.ok_or_else(|| missing("written"))?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseCheckpointError(String);
+
+impl fmt::Display for ParseCheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid checkpoint file. {}", self.0)
+    }
+}
+
+#[test]
+fn test_checkpoint_roundtrip() {
+    for &seed in &[Seed::Number(42), Seed::from_hex("00ff10").unwrap()] {
+        let checkpoint = Checkpoint {
+            algorithm: "chacha20".to_owned(),
+            seed,
+            stream_id: Some(7),
+            position: (1 << 70) + 5,
+            written: 1 << 40,
+        };
+        assert_eq!(
+            checkpoint.to_string().parse::<Checkpoint>().unwrap(),
+            checkpoint
+        );
+    }
+    assert!("algorithm = pcg\nseed = --seed 1\n"
+        .parse::<Checkpoint>()
+        .is_err());
+}
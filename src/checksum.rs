@@ -0,0 +1,158 @@
+//! `--checksum`. Hashes the data as it is written, so it can be compared to a hash of what
+//! arrived at the other end of a pipe or network connection.
+
+use crate::parsing::{self, ParseError};
+use sha2::Digest;
+use std::fmt;
+
+/// The length of the digests of all the hash functions, in bytes.
+const DIGEST_LEN: usize = 32;
+
+/// The hash function used by `--checksum`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Algorithm {
+    Sha256,
+    Blake3,
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = ParseAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(Algorithm::Sha256),
+            "blake3" => Ok(Algorithm::Blake3),
+            _ => Err(ParseAlgorithmError(())),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::Sha256 => f.write_str("sha256"),
+            Algorithm::Blake3 => f.write_str("blake3"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseAlgorithmError(());
+
+impl fmt::Display for ParseAlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid checksum algorithm. See --help for a list of valid options."
+        )
+    }
+}
+
+/// A running hash of everything passed to `update`.
+pub enum Checksum {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Checksum {
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sha256 => Checksum::Sha256(sha2::Sha256::new()),
+            Algorithm::Blake3 => Checksum::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Checksum::Sha256(hasher) => hasher.update(data),
+            Checksum::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// The digest of all data so far, in hex, like `sha256sum` and `b3sum` print it.
+    pub fn finish(self) -> String {
+        match self {
+            Checksum::Sha256(hasher) => crate::formatting::hex(&hasher.finalize()),
+            Checksum::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Parses a digest for `--expect-checksum`, returning it in the lowercase hex `finish` returns.
+pub fn parse_digest(s: &str) -> Result<String, ParseError> {
+    let digest = parsing::parse_hex(s)?;
+    if digest.len() != DIGEST_LEN {
+        return Err(ParseError::new(format!(
+            "A checksum is {} hex digits, not {}",
+            2 * DIGEST_LEN,
+            2 * digest.len()
+        )));
+    }
+    Ok(crate::formatting::hex(&digest))
+}
+
+#[test]
+fn test_checksum() {
+    let digest = |algorithm| {
+        let mut checksum = Checksum::new(algorithm);
+        checksum.update(b"a");
+        checksum.update(b"bc");
+        checksum.finish()
+    };
+    assert_eq!(
+        digest(Algorithm::Sha256),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+        digest(Algorithm::Blake3),
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+
+    let expected = "0xBA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD";
+    assert_eq!(parse_digest(expected).unwrap(), digest(Algorithm::Sha256));
+    assert!(parse_digest("ba7816bf").is_err());
+}
+
+#[test]
+fn test_checksum_positioned() {
+    use rng_core::multithreaded::{self, Config};
+    use rng_core::{outputs::PositionedOutput, seed::Seed};
+    use std::fs;
+    use std::io::Write;
+
+    // With the pwrite backend the blocks are written out of order, but the digest must still
+    // be the one of the file they end up in
+    let len = 5 * rng_core::BUFFER_SIZE + 100;
+    let path = std::env::temp_dir().join(format!("rng-test-checksum-{}", std::process::id()));
+    let mut output = PositionedOutput::new(fs::File::create(&path).unwrap(), len as u64).unwrap();
+    let config = Config {
+        threads: 4,
+        buffer_size: rng_core::BUFFER_SIZE,
+        queue_depth: None,
+        pin_cpus: None,
+        adaptive: false,
+        huge_pages: false,
+        mapping: None,
+        positioned: Some(output.target()),
+        verbose: false,
+    };
+    let mut checksum = Checksum::new(Algorithm::Sha256);
+    let algorithm = rng_core::Algorithm::ChaCha8;
+    multithreaded::run_seeded(algorithm, Seed::Number(3), 0, &config, |bufs| {
+        for buf in bufs {
+            output.write_all(buf).unwrap();
+            checksum.update(buf);
+        }
+        false
+    });
+    drop(output);
+    let file = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(file.len(), len);
+    assert_eq!(
+        checksum.finish(),
+        crate::formatting::hex(&sha2::Sha256::digest(&file))
+    );
+}
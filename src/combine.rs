@@ -0,0 +1,152 @@
+//! Combining the output of multiple algorithms into one stream.
+
+use crate::rngs::EndOfStream;
+use crate::seed::Seed;
+use crate::Algorithm;
+use rand::RngCore;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CombineMethod {
+    Xor,
+}
+
+impl std::str::FromStr for CombineMethod {
+    type Err = ParseCombineMethodError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xor" => Ok(CombineMethod::Xor),
+            _ => Err(ParseCombineMethodError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseCombineMethodError(());
+
+impl fmt::Display for ParseCombineMethodError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid combine method. See --help for a list of valid options."
+        )
+    }
+}
+
+/// A generator producing the XOR of the output of all the generators it contains.
+pub struct XorRng {
+    rngs: Vec<Box<dyn RngCore>>,
+    scratch: Vec<u8>,
+}
+
+impl XorRng {
+    pub fn new(rngs: Vec<Box<dyn RngCore>>) -> Self {
+        assert!(!rngs.is_empty(), "Can't combine zero generators");
+        XorRng {
+            rngs,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Creates and combines new instances of the given user-space algorithms. Without a seed
+    /// they are seeded from the OS. With a seed, each generator gets a different seed derived
+    /// from it, so combining an algorithm with itself does not cancel out.
+    pub fn from_userspace(algorithms: &[Algorithm], seed: Option<Seed>) -> Self {
+        let rngs = algorithms
+            .iter()
+            .enumerate()
+            .map(|(i, &algorithm)| {
+                let seed = seed.map(|seed| match seed {
+                    Seed::Number(seed) => Seed::Number(component_seed(seed, i)),
+                    // --seed-hex can't be used with --combine, the seed width is ambiguous
+                    bytes @ Seed::Bytes { .. } => bytes,
+                });
+                new_userspace(algorithm, seed)
+            })
+            .collect();
+        Self::new(rngs)
+    }
+}
+
+impl RngCore for XorRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from the generator: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        let (first, rest) = self.rngs.split_first_mut().expect("Never empty");
+        // If a stream in the combination ends, the output is only complete up to where the
+        // shortest stream ended
+        let mut filled = EndOfStream::filled_len(first.try_fill_bytes(dest), dest.len())?;
+        self.scratch.resize(dest.len(), 0);
+        for rng in rest {
+            let result = rng.try_fill_bytes(&mut self.scratch);
+            filled = filled.min(EndOfStream::filled_len(result, dest.len())?);
+            for (byte, other) in dest.iter_mut().zip(&self.scratch) {
+                *byte ^= other;
+            }
+        }
+        match filled < dest.len() {
+            true => Err(rand::Error::new(EndOfStream { filled })),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Derives the seed for generator number `index` in a combination, using the SplitMix64
+/// output function.
+fn component_seed(seed: u64, index: usize) -> u64 {
+    let mut z = seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Creates a new boxed instance of a user-space algorithm, seeded from the OS unless a seed is
+/// given.
+pub fn new_userspace(algorithm: Algorithm, seed: Option<Seed>) -> Box<dyn RngCore> {
+    let constructor = algorithm
+        .constructor()
+        .unwrap_or_else(|| panic!("{:?} is not a user-space PRNG", algorithm));
+    (constructor.new)(seed)
+}
+
+#[test]
+fn test_xor() {
+    use rand::SeedableRng;
+
+    let mut combined = XorRng::from_userspace(
+        &[Algorithm::Pcg, Algorithm::ChaCha20],
+        Some(Seed::Number(1)),
+    );
+    let mut pcg = crate::PcgRng::seed_from_u64(component_seed(1, 0));
+    let mut chacha = rand_chacha::ChaCha20Rng::seed_from_u64(component_seed(1, 1));
+    let mut expected = [0u8; 100];
+    let mut other = [0u8; 100];
+    pcg.fill_bytes(&mut expected);
+    chacha.fill_bytes(&mut other);
+    expected.iter_mut().zip(&other).for_each(|(a, b)| *a ^= b);
+
+    let mut actual = [0u8; 100];
+    combined.fill_bytes(&mut actual);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_xor_with_itself_does_not_cancel() {
+    let mut combined =
+        XorRng::from_userspace(&[Algorithm::Pcg, Algorithm::Pcg], Some(Seed::Number(1)));
+    let mut buf = [0u8; 64];
+    combined.fill_bytes(&mut buf);
+    assert_ne!(buf, [0u8; 64]);
+}
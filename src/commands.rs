@@ -0,0 +1,799 @@
+//! Running the subcommands, everything `rng` does besides generating random data to the
+//! outputs.
+
+#[cfg(unix)]
+use crate::egd;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+use crate::fuse;
+use crate::{
+    analyze, apikey, audio, bench, combine, confirm_overwrite, corpus, device_test, fake,
+    formatting, geo, http, image, json, mac, nist, otp, platform, port, salt, seed, self_test,
+    serve, sleep, ula, unique, url, wipe, Algorithm, Command, ExitCode, TestSuite, ALGORITHMS,
+    MAX_BUFFER_SIZE, MIN_BUFFER_SIZE,
+};
+#[cfg(target_os = "linux")]
+use crate::{feed, rngs, sources};
+use rand::RngCore;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Runs a subcommand and exits with its exit code.
+pub fn run(command: &Command) -> ! {
+    let success = match command {
+        Command::SelfTest => self_test::run(),
+        Command::List => {
+            print!("{}", AlgorithmList);
+            true
+        }
+        Command::Analyze {
+            file,
+            generate,
+            bytes,
+            histogram,
+            format,
+            bars,
+        } => match analyze::run(file.as_deref(), *generate, *bytes) {
+            Ok(analysis) if *histogram => {
+                print!("{}", analysis.histogram(*format, *bars));
+                true
+            }
+            Ok(analysis) => {
+                print!("{}", analysis.report());
+                true
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                false
+            }
+        },
+        Command::Bench {
+            algorithms,
+            duration,
+            threads,
+            sweep_buffer,
+            sink,
+            format,
+        } => bench(
+            algorithms,
+            *duration,
+            *threads,
+            *sweep_buffer,
+            *sink,
+            *format,
+        ),
+        Command::Audio {
+            generate,
+            seconds,
+            rate,
+            bits,
+            channels,
+            out,
+        } => {
+            let format = audio::AudioFormat {
+                rate: *rate,
+                bits: *bits,
+                channels: *channels,
+            };
+            audio(
+                generate.unwrap_or(Algorithm::Default),
+                format,
+                *seconds,
+                out,
+            )
+        }
+        Command::Corpus {
+            dir,
+            files,
+            size,
+            distribution,
+            generate,
+            threads,
+        } => match corpus::run(
+            dir,
+            *files,
+            *size,
+            *distribution,
+            generate.unwrap_or(Algorithm::Default),
+            threads.unwrap_or_else(num_cpus::get),
+        ) {
+            Ok(total) => {
+                eprintln!("Wrote {} files, {} bytes in total", files, total);
+                true
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                exit_on_write_error(&*e);
+                false
+            }
+        },
+        Command::Wipe {
+            path,
+            passes,
+            generate,
+            final_zero,
+            verify,
+            yes_i_know,
+        } => wipe(path, *passes, generate, *final_zero, *verify, *yes_i_know),
+        Command::TestDevice {
+            path,
+            passes,
+            block_size,
+            threads,
+            yes_i_know,
+        } => {
+            let threads = threads.unwrap_or_else(num_cpus::get);
+            test_device(path, *passes, *block_size, threads, *yes_i_know)
+        }
+        Command::Serve {
+            listen,
+            generate,
+            metrics,
+        } => {
+            let algorithm = generate.unwrap_or(Algorithm::Default);
+            match serve::run(listen, algorithm, metrics.as_deref()) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    false
+                }
+            }
+        }
+        Command::ServeHttp { listen, generate } => {
+            match http::run(listen, generate.unwrap_or(Algorithm::Default)) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    false
+                }
+            }
+        }
+        Command::ServeEgd {
+            socket,
+            generate,
+            metrics,
+        } => serve_egd(
+            socket,
+            generate.unwrap_or(Algorithm::Default),
+            metrics.as_deref(),
+        ),
+        Command::Image {
+            file,
+            generate,
+            width,
+            height,
+            color,
+            out,
+        } => match image::run(file.as_deref(), *generate, *width, *height, *color, out) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Failed to render the image: {}", e);
+                exit_on_write_error(&*e);
+                false
+            }
+        },
+        Command::Mount {
+            path,
+            generate,
+            seed,
+        } => mount(path, generate.unwrap_or(Algorithm::Default), *seed),
+        Command::Unique {
+            count,
+            range,
+            seed,
+            skip,
+        } => unique(*count, *range, *seed, skip.unwrap_or(0)),
+        Command::Fake {
+            fields,
+            count,
+            locale,
+            format,
+            seed,
+        } => {
+            let mut rng = seeded_rng(*seed);
+            let result = fake::run(
+                *count,
+                fields,
+                *locale,
+                *format,
+                &mut *rng,
+                &mut io::stdout(),
+            );
+            print_result(result, "records")
+        }
+        Command::Url {
+            count,
+            scheme,
+            tld_list,
+            subdomains,
+            path_depth,
+            query_params,
+            seed,
+        } => {
+            check_subdomains(*subdomains);
+            let shape = url::UrlShape {
+                scheme: scheme.clone(),
+                max_subdomains: *subdomains,
+                max_path_depth: *path_depth,
+                max_query_params: *query_params,
+            };
+            let mut rng = seeded_rng(*seed);
+            let result = formatting::write_lines(*count, &mut io::stdout(), || {
+                url::url(&mut *rng, tld_list, &shape)
+            });
+            print_result(result, "URLs")
+        }
+        Command::Domain {
+            count,
+            tld_list,
+            subdomains,
+            seed,
+        } => {
+            check_subdomains(*subdomains);
+            let mut rng = seeded_rng(*seed);
+            let result = formatting::write_lines(*count, &mut io::stdout(), || {
+                url::domain(&mut *rng, tld_list, *subdomains)
+            });
+            print_result(result, "host names")
+        }
+        Command::Apikey {
+            prefix,
+            entropy_bytes,
+            checksum,
+            count,
+            seed,
+        } => {
+            if *entropy_bytes < apikey::MIN_ENTROPY_LEN {
+                eprintln!(
+                    "--entropy-bytes must be at least {}",
+                    apikey::MIN_ENTROPY_LEN
+                );
+                ExitCode::Usage.exit();
+            }
+            let mut rng = seeded_rng(*seed);
+            let result = formatting::write_lines(*count, &mut io::stdout(), || {
+                apikey::token(prefix, *entropy_bytes, *checksum, &mut *rng)
+            });
+            print_result(result, "tokens")
+        }
+        Command::Salt {
+            format,
+            count,
+            seed,
+        } => {
+            let mut rng = seeded_rng(*seed);
+            let result = formatting::write_lines(*count, &mut io::stdout(), || {
+                salt::salt(*format, &mut *rng)
+            });
+            print_result(result, "salts")
+        }
+        Command::Port {
+            range,
+            check,
+            protocol,
+        } => {
+            let check = match check {
+                true => Some(protocol.unwrap_or(port::Protocol::Both)),
+                false => None,
+            };
+            match port::pick(range.clone(), check, &mut rand::rngs::OsRng) {
+                Ok(port) => {
+                    println!("{}", port);
+                    true
+                }
+                Err(e) => {
+                    eprintln!("Failed to pick a port: {}", e);
+                    false
+                }
+            }
+        }
+        Command::Geo {
+            count,
+            within,
+            format,
+            seed,
+        } => {
+            let area = within.as_deref().map(read_area);
+            let mut rng = seeded_rng(*seed);
+            let result = geo::run(*count, area.as_ref(), *format, &mut *rng, &mut io::stdout());
+            print_result(result, "points")
+        }
+        Command::Mac {
+            count,
+            oui,
+            vendor,
+            seed,
+        } => {
+            let prefix = match oui {
+                Some(oui) => mac::Prefix::Oui(*oui),
+                None => mac::Prefix::Vendor(vendor.unwrap_or(mac::Vendor::RandomLaa)),
+            };
+            let mut rng = seeded_rng(*seed);
+            let result = formatting::write_lines(*count, &mut io::stdout(), || {
+                mac::address(prefix, &mut *rng)
+            });
+            print_result(result, "addresses")
+        }
+        Command::Ula { subnets } => ula(*subnets),
+        Command::Otp {
+            issuer,
+            account,
+            secret_bytes,
+            digits,
+            period,
+            seed,
+        } => otp(issuer, account, *secret_bytes, *digits, *period, *seed),
+        Command::Sleep {
+            min,
+            max,
+            distribution,
+            print,
+            seed,
+        } => {
+            if min > max {
+                eprintln!("--min can't be longer than --max");
+                ExitCode::Usage.exit();
+            }
+            let mut rng = combine::new_userspace(Algorithm::Default, seed.map(seed::Seed::Number));
+            let delay = sleep::choose(*min, *max, *distribution, &mut *rng);
+            if *print {
+                println!("{:.3}", delay.as_secs_f64());
+            }
+            std::thread::sleep(delay);
+            true
+        }
+        Command::FeedKernel {
+            source,
+            source_cmd,
+            device,
+            ratio,
+            rate,
+        } => feed_kernel(
+            *source,
+            source_cmd.as_deref(),
+            device.as_deref(),
+            *ratio,
+            *rate,
+        ),
+        Command::Test(TestSuite::Nist {
+            file,
+            generate,
+            bits,
+        }) => nist(file.as_deref(), *generate, *bits),
+    };
+    match (success, command) {
+        (true, _) => std::process::exit(0),
+        // The tests ran, and the data failed them
+        (false, Command::SelfTest | Command::Test(_)) => ExitCode::SelfTest.exit(),
+        (false, _) => ExitCode::Failure.exit(),
+    }
+}
+
+/// The generator of the subcommands making random values, seeded for reproducible output.
+fn seeded_rng(seed: Option<u64>) -> Box<dyn RngCore> {
+    combine::new_userspace(Algorithm::ChaCha20, seed.map(seed::Seed::Number))
+}
+
+/// Runs the `bench` subcommand. Returns whether it succeeded.
+fn bench(
+    algorithms: &[Algorithm],
+    duration: Duration,
+    threads: Option<(usize, usize)>,
+    sweep_buffer: Option<(u64, u64)>,
+    sink: Option<bench::Sink>,
+    format: bench::Format,
+) -> bool {
+    let algorithms = if algorithms.is_empty() {
+        bench::ALGORITHMS
+    } else {
+        algorithms
+    };
+    let (min, max) = match sweep_buffer {
+        Some(buffer_sizes) => buffer_sizes,
+        None => {
+            let threads = threads.unwrap_or_else(|| (num_cpus::get(), num_cpus::get()));
+            return match bench::run(algorithms, duration, threads) {
+                Ok(results) => {
+                    let table = bench::Table {
+                        results: &results,
+                        threads,
+                        format,
+                        machine: &bench::Machine::current(),
+                    };
+                    print!("{}", table);
+                    true
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    false
+                }
+            };
+        }
+    };
+    let threads = match threads {
+        Some((min, max)) if min != max => {
+            eprintln!("--sweep-buffer takes a single --threads count");
+            ExitCode::Usage.exit();
+        }
+        Some((threads, _)) => threads,
+        None => num_cpus::get(),
+    };
+    if min < MIN_BUFFER_SIZE
+        || max > MAX_BUFFER_SIZE
+        || !min.is_multiple_of(512)
+        || !max.is_multiple_of(512)
+    {
+        eprintln!("--sweep-buffer must be multiples of 512 bytes, from 4KiB to 256MiB");
+        ExitCode::Usage.exit();
+    }
+    let buffer_sizes = (min as usize, max as usize);
+    let sink = sink.unwrap_or(bench::Sink::Null);
+    match bench::sweep_buffer(algorithms, duration, threads, buffer_sizes, sink) {
+        Ok(results) => {
+            let table = bench::SweepTable {
+                results: &results,
+                threads,
+                sink,
+                format,
+                machine: &bench::Machine::current(),
+            };
+            print!("{}", table);
+            true
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Runs the `audio` subcommand. Returns whether it succeeded.
+fn audio(algorithm: Algorithm, format: audio::AudioFormat, seconds: f64, out: &Path) -> bool {
+    if let Err(e) = format.validate() {
+        eprintln!("{}", e);
+        ExitCode::Usage.exit();
+    }
+    match audio::run(algorithm, format, seconds, out) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to write the audio: {}", e);
+            exit_on_write_error(&*e);
+            false
+        }
+    }
+}
+
+/// Runs the `test-device` subcommand. Returns whether no bad blocks were found.
+fn test_device(
+    path: &Path,
+    passes: u32,
+    block_size: u64,
+    threads: usize,
+    yes_i_know: bool,
+) -> bool {
+    if !yes_i_know && !confirm_overwrite(path) {
+        eprintln!(
+            "Not testing {}. Pass --yes-i-know to skip the confirmation",
+            path.display()
+        );
+        ExitCode::Usage.exit();
+    }
+    let should_abort = platform::abort_handle();
+    let result = device_test::run(path, passes, block_size, threads, &should_abort);
+    if should_abort() {
+        ExitCode::Interrupted.exit();
+    }
+    match result {
+        Ok(no_bad_blocks) => no_bad_blocks,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Runs the `unique` subcommand. Returns whether it succeeded.
+fn unique(count: u64, range: Option<u64>, seed: Option<u64>, skip: u64) -> bool {
+    let range = range.map_or(1 << 64, u128::from);
+    if range == 0 || u128::from(count) + u128::from(skip) > range {
+        eprintln!("--count and --skip must fit in --range");
+        ExitCode::Usage.exit();
+    }
+    print_result(
+        unique::run(count, range, skip, seed, &mut io::stdout()),
+        "numbers",
+    )
+}
+
+/// Exits with a usage error if `url` and `domain` are asked for more subdomains than they make.
+fn check_subdomains(subdomains: u32) {
+    if subdomains > url::MAX_SUBDOMAINS {
+        eprintln!("--subdomains can be at most {}", url::MAX_SUBDOMAINS);
+        ExitCode::Usage.exit();
+    }
+}
+
+/// Reads the GeoJSON file of `geo --within`, exiting if it can't be read or parsed.
+fn read_area(path: &Path) -> geo::Area {
+    let geojson = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path.display(), e);
+        ExitCode::Failure.exit();
+    });
+    json::parse(&geojson)
+        .and_then(|json| geo::Area::from_geojson(&json))
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to parse {}: {}", path.display(), e);
+            ExitCode::Failure.exit();
+        })
+}
+
+/// Runs the `ula` subcommand. Returns whether it succeeded.
+fn ula(subnets: u32) -> bool {
+    if subnets > 1 << 16 {
+        eprintln!("A /48 prefix only has 65536 /64 subnets");
+        ExitCode::Usage.exit();
+    }
+    let eui64 = ula::eui64(&mut rand::rngs::OsRng);
+    let prefix = ula::prefix(ula::global_id(std::time::SystemTime::now(), eui64));
+    let mut lines = std::iter::once(format!("{}/48", prefix))
+        .chain((0..subnets).map(|id| format!("{}/64", ula::subnet(prefix, id as u16))));
+    let result = formatting::write_lines(u64::from(subnets) + 1, &mut io::stdout(), || {
+        lines.next().unwrap()
+    });
+    print_result(result, "prefix")
+}
+
+/// Runs the `otp` subcommand. Returns whether it succeeded.
+fn otp(
+    issuer: &str,
+    account: &str,
+    secret_bytes: usize,
+    digits: u32,
+    period: Duration,
+    seed: Option<u64>,
+) -> bool {
+    if secret_bytes < otp::MIN_SECRET_LEN {
+        eprintln!("--secret-bytes must be at least {}", otp::MIN_SECRET_LEN);
+        ExitCode::Usage.exit();
+    }
+    if period.as_secs() == 0 || period.subsec_nanos() != 0 {
+        eprintln!("--period must be a whole number of seconds");
+        ExitCode::Usage.exit();
+    }
+    let mut rng = seeded_rng(seed);
+    let secret = otp::secret(secret_bytes, &mut *rng);
+    let uri = otp::uri(&otp::Enrollment {
+        secret: &secret,
+        issuer,
+        account,
+        digits,
+        period: period.as_secs(),
+    });
+    println!("{}\n{}", secret, uri);
+    true
+}
+
+/// Runs the `test nist` subcommand. Returns whether all tests passed.
+fn nist(file: Option<&Path>, generate: Option<Algorithm>, bits: u64) -> bool {
+    match nist::run(file, generate, bits) {
+        Ok(results) => {
+            for result in &results {
+                println!("{}", result);
+            }
+            let passed = results.iter().filter(|result| result.passed()).count();
+            println!("{} of {} tests passed", passed, results.len());
+            passed == results.len()
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::Failure.exit();
+        }
+    }
+}
+
+/// All algorithms as a table, for `rng list`.
+struct AlgorithmList;
+
+impl fmt::Display for AlgorithmList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let row = |f: &mut fmt::Formatter, columns: [&str; 6]| {
+            writeln!(
+                f,
+                "{:<10} {:<18} {:>10} {:>10} {:<10} {}",
+                columns[0], columns[1], columns[2], columns[3], columns[4], columns[5]
+            )
+        };
+        row(
+            f,
+            [
+                "algorithm",
+                "class",
+                "state bits",
+                "seed bytes",
+                "speed",
+                "seekable",
+            ],
+        )?;
+        for info in ALGORITHMS {
+            let dash = || "-".to_owned();
+            row(
+                f,
+                [
+                    info.name,
+                    &info.class.to_string(),
+                    &info.state_bits.map_or_else(dash, |bits| bits.to_string()),
+                    &info
+                        .algorithm
+                        .seed_len()
+                        .map_or_else(dash, |len| len.to_string()),
+                    &info.speed.map_or_else(dash, |speed| speed.to_string()),
+                    if info.algorithm.is_seekable() {
+                        "yes"
+                    } else {
+                        "no"
+                    },
+                ],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the `wipe` subcommand. Returns whether it succeeded.
+fn wipe(
+    path: &Path,
+    passes: u32,
+    generate: &[Algorithm],
+    final_zero: bool,
+    verify: bool,
+    yes_i_know: bool,
+) -> bool {
+    if generate.len() > 1 && generate.len() != passes as usize {
+        eprintln!("--generate must be given once, or once per pass");
+        ExitCode::Usage.exit();
+    }
+    let mut pass_list: Vec<_> = (0..passes as usize)
+        .map(|i| {
+            let algorithm = match generate.len() {
+                0 => Algorithm::Default,
+                1 => generate[0],
+                _ => generate[i],
+            };
+            wipe::Pass::Random(algorithm)
+        })
+        .collect();
+    if final_zero {
+        pass_list.push(wipe::Pass::Zeros);
+    }
+    if !yes_i_know && !confirm_overwrite(path) {
+        eprintln!(
+            "Not wiping {}. Pass --yes-i-know to skip the confirmation",
+            path.display()
+        );
+        ExitCode::Usage.exit();
+    }
+    let should_abort = platform::abort_handle();
+    let result = wipe::run(path, &pass_list, verify, &should_abort);
+    if should_abort() {
+        ExitCode::Interrupted.exit();
+    }
+    match result {
+        Ok(true) => true,
+        // The file did not hold what was written
+        Ok(false) => ExitCode::SelfTest.exit(),
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Runs the `mount` subcommand. Returns whether it succeeded.
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+fn mount(path: &Path, algorithm: Algorithm, seed: Option<u64>) -> bool {
+    match fuse::run(path, algorithm, seed) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(all(feature = "fuse", target_os = "linux")))]
+fn mount(_path: &Path, _algorithm: Algorithm, _seed: Option<u64>) -> bool {
+    eprintln!("Mounting requires Linux and rng built with the \"fuse\" feature");
+    false
+}
+
+/// Handles the result of a subcommand printing generated values to stdout. Returns whether it
+/// succeeded. A reader closing stdout early, like `head` does, is not a failure.
+fn print_result(result: io::Result<()>, what: &str) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => true,
+        Err(e) => {
+            eprintln!("Failed to write the {}: {}", what, e);
+            ExitCode::for_write_error(&e).exit();
+        }
+    }
+}
+
+/// Exits with the exit code for a failed write if `e` is a failure to write the output.
+fn exit_on_write_error(e: &(dyn Error + 'static)) {
+    if let Some(e) = e.downcast_ref::<io::Error>() {
+        ExitCode::for_write_error(e).exit();
+    }
+}
+
+/// Runs the `serve-egd` subcommand. Returns whether it succeeded.
+#[cfg(unix)]
+fn serve_egd(socket: &Path, algorithm: Algorithm, metrics: Option<&str>) -> bool {
+    match egd::run(socket, algorithm, metrics) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn serve_egd(_socket: &Path, _algorithm: Algorithm, _metrics: Option<&str>) -> bool {
+    eprintln!("The EGD server is only supported on Unix");
+    false
+}
+
+/// Runs the `feed-kernel` subcommand. Returns whether it succeeded.
+#[cfg(target_os = "linux")]
+fn feed_kernel(
+    source: Algorithm,
+    source_cmd: Option<&str>,
+    device: Option<&Path>,
+    ratio: f64,
+    rate: Option<u64>,
+) -> bool {
+    let source: Box<dyn rand::RngCore> = match source_cmd {
+        Some(command) => match rngs::CommandRng::spawn(command) {
+            Ok(rng) => Box::new(rng),
+            Err(e) => {
+                eprintln!("Failed to run the source command: {}", e);
+                return false;
+            }
+        },
+        // Feeding the pool from itself or from a PRNG would credit entropy that isn't there
+        None if !source.is_entropy_source() || source == Algorithm::Os => {
+            eprintln!("{} can not feed the kernel. Use an entropy source", source);
+            return false;
+        }
+        None => match sources::open(source, device, rngs::OsSource::Default) {
+            Ok(rng) => rng,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        },
+    };
+    match feed::run(source, Path::new("/dev/random"), ratio, rate) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn feed_kernel(
+    _source: Algorithm,
+    _source_cmd: Option<&str>,
+    _device: Option<&Path>,
+    _ratio: f64,
+    _rate: Option<u64>,
+) -> bool {
+    eprintln!("Feeding the kernel entropy pool is only supported on Linux");
+    false
+}
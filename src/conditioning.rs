@@ -0,0 +1,290 @@
+//! Post-processing stages that condition the raw output of a generator before it is written.
+//! Mostly useful for entropy sources that don't deliver full entropy per output bit, such as
+//! hardware and jitter sources.
+
+use crate::rngs::EndOfStream;
+use rand::RngCore;
+use sha2::Digest;
+use std::fmt;
+
+/// The hash function used by `--whiten`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Whitening {
+    Sha256,
+    Blake3,
+}
+
+impl std::str::FromStr for Whitening {
+    type Err = ParseWhiteningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(Whitening::Sha256),
+            "blake3" => Ok(Whitening::Blake3),
+            _ => Err(ParseWhiteningError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseWhiteningError(());
+
+impl fmt::Display for ParseWhiteningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid whitening hash. See --help for a list of valid options."
+        )
+    }
+}
+
+/// The debiasing method used by `--debias`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Debiasing {
+    VonNeumann,
+}
+
+impl std::str::FromStr for Debiasing {
+    type Err = ParseDebiasingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "von-neumann" => Ok(Debiasing::VonNeumann),
+            _ => Err(ParseDebiasingError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseDebiasingError(());
+
+impl fmt::Display for ParseDebiasingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid debiasing method. See --help for a list of valid options."
+        )
+    }
+}
+
+/// The number of bytes of output produced per hash invocation. Both supported hashes have
+/// 256 bit outputs.
+const WHITENED_BLOCK: usize = 32;
+
+/// The number of input bytes consumed per output block. Compressing the input 2:1 means the
+/// output has full entropy as long as the input has at least half a bit of entropy per bit.
+const RAW_BLOCK: usize = 2 * WHITENED_BLOCK;
+
+/// A hash based randomness extractor. Every output block is the hash of a block counter and
+/// twice as many bytes of input from the wrapped generator.
+pub struct Whitener<R> {
+    rng: R,
+    hash: Whitening,
+    counter: u64,
+    raw: [u8; RAW_BLOCK],
+    /// Whitened bytes not yet returned, and how many of them have been consumed.
+    block: [u8; WHITENED_BLOCK],
+    block_pos: usize,
+}
+
+impl<R: RngCore> Whitener<R> {
+    pub fn new(rng: R, hash: Whitening) -> Self {
+        Whitener {
+            rng,
+            hash,
+            counter: 0,
+            raw: [0; RAW_BLOCK],
+            block: [0; WHITENED_BLOCK],
+            block_pos: WHITENED_BLOCK,
+        }
+    }
+
+    fn next_block(&mut self) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(&mut self.raw)?;
+        let counter = self.counter.to_le_bytes();
+        self.counter = self.counter.wrapping_add(1);
+        self.block = match self.hash {
+            Whitening::Sha256 => {
+                let mut hasher = sha2::Sha256::new();
+                hasher.update(counter);
+                hasher.update(self.raw);
+                hasher.finalize().into()
+            }
+            Whitening::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(&counter);
+                hasher.update(&self.raw);
+                hasher.finalize().into()
+            }
+        };
+        self.block_pos = 0;
+        Ok(())
+    }
+}
+
+impl<R: RngCore> RngCore for Whitener<R> {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from the generator: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        let mut filled = 0;
+        while filled < dest.len() {
+            if self.block_pos == WHITENED_BLOCK {
+                // A partial raw block at the end of a stream is dropped, it can't be hashed
+                self.next_block()
+                    .map_err(|e| EndOfStream::rebase(e, filled))?;
+            }
+            let n = (WHITENED_BLOCK - self.block_pos).min(dest.len() - filled);
+            dest[filled..filled + n]
+                .copy_from_slice(&self.block[self.block_pos..self.block_pos + n]);
+            self.block_pos += n;
+            filled += n;
+        }
+        Ok(())
+    }
+}
+
+/// The number of raw bytes read from the wrapped generator at a time by `VonNeumann`.
+const DEBIAS_RAW_BUFFER: usize = 4096;
+
+/// The von Neumann extractor. Splits the input into pairs of bits and outputs the first bit of
+/// every pair where the two bits differ, discarding pairs of equal bits. Removes any bias from
+/// input bits that are independent, at the cost of consuming at least two input bits per
+/// output bit, and four on average for unbiased input.
+pub struct VonNeumann<R> {
+    rng: R,
+    /// Raw bytes read from the wrapped generator, how many of them there are and how many of
+    /// them have been consumed.
+    raw: Box<[u8; DEBIAS_RAW_BUFFER]>,
+    raw_len: usize,
+    raw_pos: usize,
+    /// Output bits collected so far towards the next byte, and how many of them there are.
+    bits: u8,
+    num_bits: u32,
+}
+
+impl<R: RngCore> VonNeumann<R> {
+    pub fn new(rng: R) -> Self {
+        VonNeumann {
+            rng,
+            raw: Box::new([0; DEBIAS_RAW_BUFFER]),
+            raw_len: 0,
+            raw_pos: 0,
+            bits: 0,
+            num_bits: 0,
+        }
+    }
+}
+
+impl<R: RngCore> RngCore for VonNeumann<R> {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from the generator: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        let mut written = 0;
+        while written < dest.len() {
+            if self.raw_pos == self.raw_len {
+                // A short raw buffer means the wrapped stream ended. Only report the end once
+                // what was read before it has been debiased
+                if self.raw_len < DEBIAS_RAW_BUFFER && self.raw_pos > 0 {
+                    return Err(rand::Error::new(EndOfStream { filled: written }));
+                }
+                let result = self.rng.try_fill_bytes(&mut self.raw[..]);
+                self.raw_len = EndOfStream::filled_len(result, DEBIAS_RAW_BUFFER)?;
+                self.raw_pos = 0;
+                if self.raw_len == 0 {
+                    return Err(rand::Error::new(EndOfStream { filled: written }));
+                }
+            }
+            let byte = self.raw[self.raw_pos];
+            self.raw_pos += 1;
+            // Bit pairs from the most significant end of the byte. A byte gives at most four
+            // output bits, so it completes at most one output byte
+            for shift in [6, 4, 2, 0] {
+                let pair = (byte >> shift) & 0b11;
+                if pair == 0b01 || pair == 0b10 {
+                    self.bits = (self.bits << 1) | (pair >> 1);
+                    self.num_bits += 1;
+                    if self.num_bits == 8 {
+                        dest[written] = self.bits;
+                        written += 1;
+                        self.num_bits = 0;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generator outputting the same byte forever.
+#[cfg(test)]
+pub(crate) struct ConstRng(pub u8);
+
+#[cfg(test)]
+impl RngCore for ConstRng {
+    fn next_u32(&mut self) -> u32 {
+        u32::from_ne_bytes([self.0; 4])
+    }
+    fn next_u64(&mut self) -> u64 {
+        u64::from_ne_bytes([self.0; 8])
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.fill(self.0)
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_whitener_sha256() {
+    let mut whitener = Whitener::new(ConstRng(0), Whitening::Sha256);
+    let mut output = [0u8; 40];
+    whitener.fill_bytes(&mut output[..10]);
+    whitener.fill_bytes(&mut output[10..]);
+
+    let block = |counter: u64| {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(counter.to_le_bytes());
+        hasher.update([0u8; RAW_BLOCK]);
+        hasher.finalize()
+    };
+    assert_eq!(output[..32], block(0)[..]);
+    assert_eq!(output[32..], block(1)[..8]);
+}
+
+#[test]
+fn test_von_neumann() {
+    // The pairs 01, 10, 00, 11 give the output bits 0 and 1
+    let mut debiased = VonNeumann::new(ConstRng(0b01_10_00_11));
+    let mut output = [0u8; 5];
+    debiased.fill_bytes(&mut output);
+    assert_eq!(output, [0b0101_0101; 5]);
+
+    // 4 bits out per byte in
+    let mut debiased = VonNeumann::new(ConstRng(0b10_10_01_10));
+    debiased.fill_bytes(&mut output);
+    assert_eq!(output, [0b1101_1101; 5]);
+}
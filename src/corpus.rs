@@ -0,0 +1,194 @@
+//! The `corpus` subcommand. Creates a directory of files with random sizes and random contents,
+//! for testing backup, sync and deduplication software on realistic looking data sets.
+
+use crate::rngs::EndOfStream;
+use crate::{sources, Algorithm};
+use rand::RngCore;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use zeroize::Zeroizing;
+
+/// How the sizes of the files are distributed within the size range.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SizeDistribution {
+    /// Every size in the range is equally likely.
+    Uniform,
+    /// The logarithm of the size is uniformly distributed, so small files are much more common
+    /// than large ones, like on most real file systems.
+    LogUniform,
+}
+
+impl SizeDistribution {
+    /// Maps `unit`, in `[0, 1)`, to a size in the inclusive range `min..=max`.
+    fn size(self, unit: f64, min: u64, max: u64) -> u64 {
+        let size = match self {
+            SizeDistribution::Uniform => min as f64 + unit * ((max - min) as f64 + 1.0),
+            SizeDistribution::LogUniform => {
+                // Shifted by one so a range starting at zero is defined
+                let low = (min as f64 + 1.0).ln();
+                let high = (max as f64 + 2.0).ln();
+                (low + unit * (high - low)).exp() - 1.0
+            }
+        };
+        (size as u64).clamp(min, max)
+    }
+}
+
+impl FromStr for SizeDistribution {
+    type Err = ParseSizeDistributionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(SizeDistribution::Uniform),
+            "log-uniform" => Ok(SizeDistribution::LogUniform),
+            _ => Err(ParseSizeDistributionError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseSizeDistributionError(());
+
+impl fmt::Display for ParseSizeDistributionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid size distribution. See --help for a list of valid options."
+        )
+    }
+}
+
+/// Writes `files` files with sizes between `min_size` and `max_size` bytes into `dir`, using
+/// `threads` threads that each generate with their own instance of `algorithm`. Returns the
+/// total number of bytes written.
+pub fn run(
+    dir: &Path,
+    files: u64,
+    (min_size, max_size): (u64, u64),
+    distribution: SizeDistribution,
+    algorithm: Algorithm,
+    threads: usize,
+) -> Result<u64, Box<dyn Error>> {
+    if threads == 0 {
+        return Err("At least one thread is needed".into());
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    sources::open_any(algorithm)?;
+
+    // Zero padded names sort in creation order
+    let width = files.saturating_sub(1).to_string().len();
+    let next_file = AtomicU64::new(0);
+    let threads = threads
+        .min(usize::try_from(files).unwrap_or(usize::MAX))
+        .max(1);
+    let results = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| -> Result<u64, Box<dyn Error + Send + Sync>> {
+                    let mut rng = sources::open_any(algorithm).map_err(|e| e.to_string())?;
+                    let mut buf = Zeroizing::new(vec![0u8; crate::BUFFER_SIZE]);
+                    let mut written = 0;
+                    loop {
+                        let index = next_file.fetch_add(1, Ordering::Relaxed);
+                        if index >= files {
+                            return Ok(written);
+                        }
+                        let path = dir.join(format!("{:0width$}.bin", index, width = width));
+                        let result = random_unit(&mut *rng).and_then(|unit| {
+                            let size = distribution.size(unit, min_size, max_size);
+                            write_file(&path, size, &mut *rng, &mut buf)
+                        });
+                        match result {
+                            Ok(size) => written += size,
+                            Err(e) => {
+                                // Stop the other threads from starting on more files
+                                next_file.store(files, Ordering::Relaxed);
+                                let message = format!("Failed to write {}: {}", path.display(), e);
+                                // Failing to write keeps its kind, for the exit code
+                                return Err(match e.downcast_ref::<io::Error>() {
+                                    Some(e) => io::Error::new(e.kind(), message).into(),
+                                    None => message.into(),
+                                });
+                            }
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Corpus thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    let mut total = 0;
+    for result in results {
+        total += result.map_err(|e| e as Box<dyn Error>)?;
+    }
+    Ok(total)
+}
+
+/// A uniformly distributed number in `[0, 1)`. Fails instead of panicking if `rng` fails.
+fn random_unit(rng: &mut dyn RngCore) -> Result<f64, Box<dyn Error>> {
+    let mut bytes = [0u8; 8];
+    rng.try_fill_bytes(&mut bytes)?;
+    Ok((u64::from_ne_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64)
+}
+
+/// Writes `size` bytes from `rng` to a new file at `path`. Returns the number of bytes written.
+fn write_file(
+    path: &Path,
+    size: u64,
+    rng: &mut dyn RngCore,
+    buf: &mut [u8],
+) -> Result<u64, Box<dyn Error>> {
+    let mut file = File::create(path)?;
+    let mut left = size;
+    while left > 0 {
+        let len = buf.len().min(usize::try_from(left).unwrap_or(usize::MAX));
+        let filled = EndOfStream::filled_len(rng.try_fill_bytes(&mut buf[..len]), len)?;
+        file.write_all(&buf[..filled])?;
+        if filled < len {
+            return Err("The source ended".into());
+        }
+        left -= len as u64;
+    }
+    Ok(size)
+}
+
+#[test]
+fn test_corpus() {
+    let dir = std::env::temp_dir().join(format!("rng-test-corpus-{}", std::process::id()));
+    let total = run(
+        &dir,
+        20,
+        (10, 5000),
+        SizeDistribution::LogUniform,
+        Algorithm::ChaCha8,
+        3,
+    )
+    .unwrap();
+    let mut sizes = Vec::new();
+    for entry in fs::read_dir(&dir).unwrap() {
+        sizes.push(entry.unwrap().metadata().unwrap().len());
+    }
+    assert_eq!(sizes.len(), 20);
+    assert!(dir.join("00.bin").exists() && dir.join("19.bin").exists());
+    assert!(sizes.iter().all(|size| (10..=5000).contains(size)));
+    assert_eq!(sizes.iter().sum::<u64>(), total);
+    fs::remove_dir_all(&dir).unwrap();
+
+    for distribution in [SizeDistribution::Uniform, SizeDistribution::LogUniform] {
+        assert_eq!(distribution.size(0.0, 4, 9), 4);
+        assert_eq!(distribution.size(0.999_999, 4, 9), 9);
+        assert_eq!(distribution.size(0.5, 7, 7), 7);
+    }
+    assert_eq!(SizeDistribution::Uniform.size(0.5, 0, 99), 50);
+    assert!(SizeDistribution::LogUniform.size(0.5, 0, 99) < 50);
+}
@@ -0,0 +1,297 @@
+//! The `test-device` subcommand. A destructive storage media test like `badblocks -w`: every
+//! pass writes a random pattern over the whole device from several threads, reads it back and
+//! maps the blocks that could not be written, could not be read or did not hold the pattern.
+//!
+//! The pattern of a pass is the output of ChaCha8 with a fresh random seed. ChaCha can seek, so
+//! every block can be generated independently, both when writing and when reading it back.
+//! Every block is synced and evicted from the page cache after it is written, so errors are
+//! reported for the block they happened in, and reading it back reads the storage.
+
+use crate::formatting;
+use crate::platform;
+use crate::progress::Progress;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Block sizes must be a multiple of this, the sector size of most devices.
+const BLOCK_ALIGN: u64 = 512;
+
+/// How often the progress is checked while the threads work.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bad blocks, by their index, and what was wrong with them.
+type BadBlocks = BTreeMap<u64, String>;
+
+/// Tests all of `path` in `passes` passes of `block_size` bytes per block, using `threads`
+/// threads. Prints a report of the bad regions to stdout. Returns whether the device is free of
+/// bad blocks.
+pub fn run(
+    path: &Path,
+    passes: u32,
+    block_size: u64,
+    threads: usize,
+    should_abort: &(dyn Fn() -> bool + Sync),
+) -> Result<bool, Box<dyn Error>> {
+    if block_size == 0 || !block_size.is_multiple_of(BLOCK_ALIGN) {
+        return Err(format!("The block size must be a multiple of {} bytes", BLOCK_ALIGN).into());
+    }
+    if passes == 0 || threads == 0 {
+        return Err("At least one pass and one thread is needed".into());
+    }
+    let size = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|mut file| file.seek(SeekFrom::End(0)))
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    if size == 0 {
+        return Err(format!("{} is empty", path.display()).into());
+    }
+    let device = Device {
+        path,
+        size,
+        block_size,
+    };
+    eprintln!(
+        "Testing {} ({} bytes) of {} in {} blocks of {}",
+        formatting::format_bytes_written(size),
+        size,
+        path.display(),
+        device.blocks(),
+        formatting::format_bytes_written(block_size)
+    );
+
+    let bad = Mutex::new(BadBlocks::new());
+    for pass in 1..=passes {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        rand::rngs::OsRng.fill_bytes(&mut seed[..]);
+        let label = format!("Pass {}/{} writing", pass, passes);
+        device.run_phase(
+            &label,
+            threads,
+            &bad,
+            should_abort,
+            false,
+            |file, block, buf, _| {
+                device.pattern(&seed, block, buf);
+                file.write_all(buf)
+                    .map_err(|e| format!("Failed to write: {}", e))?;
+                // Sync every block by itself, so a failing write is reported for the block that
+                // failed instead of the one that happened to be synced. Then evict it, so it is
+                // read back from the storage
+                file.sync_data()
+                    .map_err(|e| format!("Failed to sync: {}", e))?;
+                let offset = block * device.block_size;
+                platform::drop_page_cache_range(file, offset, buf.len() as u64)
+                    .map_err(|e| format!("Failed to drop the cached data: {}", e))
+            },
+        )?;
+
+        let label = format!("Pass {}/{} reading", pass, passes);
+        device.run_phase(
+            &label,
+            threads,
+            &bad,
+            should_abort,
+            true,
+            |file, block, buf, expected| {
+                device.pattern(&seed, block, expected);
+                file.read_exact(buf)
+                    .map_err(|e| format!("Failed to read: {}", e))?;
+                match buf.iter().zip(&*expected).filter(|(a, b)| a != b).count() {
+                    0 => Ok(()),
+                    differing => Err(format!("{} bytes did not hold the pattern", differing)),
+                }
+            },
+        )?;
+    }
+
+    let bad = bad.into_inner().expect("A test thread panicked");
+    print_report(&device, passes, &bad);
+    Ok(bad.is_empty())
+}
+
+/// The device under test.
+struct Device<'a> {
+    path: &'a Path,
+    size: u64,
+    block_size: u64,
+}
+
+impl Device<'_> {
+    fn blocks(&self) -> u64 {
+        self.size.div_ceil(self.block_size)
+    }
+
+    /// The length of `block`. Only the last block can be shorter than the block size.
+    fn block_len(&self, block: u64) -> usize {
+        (self.size - block * self.block_size).min(self.block_size) as usize
+    }
+
+    /// Fills `buf` with the pattern of `block`.
+    fn pattern(&self, seed: &[u8; 32], block: u64, buf: &mut [u8]) {
+        let mut rng = ChaCha8Rng::from_seed(*seed);
+        // The offset is a multiple of the block alignment, so whole words are skipped
+        rng.set_word_pos(u128::from(block * self.block_size / 4));
+        rng.fill_bytes(buf);
+    }
+
+    /// Runs `test` on every block from `threads` threads, each with its own handle to the
+    /// device positioned at the start of the block and two buffers of the block length. Blocks
+    /// that fail are added to `bad`. With `skip_bad`, blocks already known to be bad are not
+    /// tested again.
+    fn run_phase(
+        &self,
+        label: &str,
+        threads: usize,
+        bad: &Mutex<BadBlocks>,
+        should_abort: &(dyn Fn() -> bool + Sync),
+        skip_bad: bool,
+        test: impl Fn(&mut File, u64, &mut [u8], &mut [u8]) -> Result<(), String> + Sync,
+    ) -> Result<(), Box<dyn Error>> {
+        let next_block = AtomicU64::new(0);
+        let done = AtomicU64::new(0);
+        let mut progress = Progress::new(label, self.size);
+        let results = thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| -> Result<(), String> {
+                        let mut file = fs::OpenOptions::new()
+                            .read(true)
+                            .write(true)
+                            .open(self.path)
+                            .map_err(|e| {
+                                format!("Failed to open {}: {}", self.path.display(), e)
+                            })?;
+                        let mut buf = Zeroizing::new(vec![0u8; self.block_size as usize]);
+                        let mut scratch = Zeroizing::new(vec![0u8; self.block_size as usize]);
+                        loop {
+                            let block = next_block.fetch_add(1, Ordering::Relaxed);
+                            if block >= self.blocks() || should_abort() {
+                                return Ok(());
+                            }
+                            let len = self.block_len(block);
+                            let known_bad = skip_bad && lock(bad).contains_key(&block);
+                            if !known_bad {
+                                let offset = block * self.block_size;
+                                let result = file
+                                    .seek(SeekFrom::Start(offset))
+                                    .map_err(|e| format!("Failed to seek: {}", e))
+                                    .and_then(|_| {
+                                        test(&mut file, block, &mut buf[..len], &mut scratch[..len])
+                                    });
+                                if let Err(reason) = result {
+                                    lock(bad).entry(block).or_insert(reason);
+                                }
+                            }
+                            done.fetch_add(len as u64, Ordering::Relaxed);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            while !workers.iter().all(|worker| worker.is_finished()) {
+                thread::sleep(POLL_INTERVAL);
+                progress.update(done.load(Ordering::Relaxed));
+            }
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("A test thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        for result in results {
+            result?;
+        }
+        if should_abort() {
+            return Err("Interrupted".into());
+        }
+        progress.finish();
+        Ok(())
+    }
+}
+
+fn lock(bad: &Mutex<BadBlocks>) -> std::sync::MutexGuard<'_, BadBlocks> {
+    bad.lock().expect("A test thread panicked")
+}
+
+/// Prints the bad regions, runs of consecutive bad blocks, with the problem of their first block.
+fn print_report(device: &Device, passes: u32, bad: &BadBlocks) {
+    println!(
+        "Tested {} ({} bytes) in {} passes of {} blocks",
+        device.path.display(),
+        device.size,
+        passes,
+        device.blocks()
+    );
+    if bad.is_empty() {
+        println!("No bad blocks found");
+        return;
+    }
+    let bad_bytes: u64 = bad
+        .keys()
+        .map(|&block| device.block_len(block) as u64)
+        .sum();
+    println!(
+        "Found {} bad blocks, {} ({} bytes):",
+        bad.len(),
+        formatting::format_bytes_written(bad_bytes),
+        bad_bytes
+    );
+    for (start, end, reason) in bad_regions(bad) {
+        let start_offset = start * device.block_size;
+        let end_offset = (end * device.block_size).min(device.size);
+        println!(
+            "  bytes {}..{} (blocks {}..{}): {}",
+            start_offset, end_offset, start, end, reason
+        );
+    }
+}
+
+/// Merges consecutive bad blocks into regions of blocks `start..end`.
+fn bad_regions(bad: &BadBlocks) -> Vec<(u64, u64, &str)> {
+    let mut regions: Vec<(u64, u64, &str)> = Vec::new();
+    for (&block, reason) in bad {
+        match regions.last_mut() {
+            Some((_, end, _)) if *end == block => *end += 1,
+            _ => regions.push((block, block + 1, reason)),
+        }
+    }
+    regions
+}
+
+#[test]
+fn test_device_test() {
+    let path = std::env::temp_dir().join(format!("rng-test-device-{}", std::process::id()));
+    fs::write(&path, vec![0u8; 1_000_000]).unwrap();
+    assert!(run(&path, 2, 64 * 1024, 3, &|| false).unwrap());
+    assert_eq!(fs::metadata(&path).unwrap().len(), 1_000_000);
+    assert!(run(&path, 1, 1000, 1, &|| false).is_err());
+    assert!(run(&path, 1, 4096, 1, &|| true).is_err());
+    fs::remove_file(&path).unwrap();
+
+    let device = Device {
+        path: &path,
+        size: 1_000_000,
+        block_size: 4096,
+    };
+    let mut whole = vec![0u8; 8192];
+    let mut second = vec![0u8; 4096];
+    device.pattern(&[1; 32], 0, &mut whole);
+    device.pattern(&[1; 32], 1, &mut second);
+    assert_eq!(whole[4096..], second[..]);
+    assert_eq!(device.block_len(244), 1_000_000 - 244 * 4096);
+
+    let bad: BadBlocks = [(1, "a"), (2, "b"), (5, "c")]
+        .iter()
+        .map(|&(block, reason)| (block, reason.to_owned()))
+        .collect();
+    assert_eq!(bad_regions(&bad), [(1, 3, "a"), (5, 6, "c")]);
+}
@@ -0,0 +1,162 @@
+//! The `serve-egd` subcommand. A server speaking the Entropy Gathering Daemon protocol on a Unix
+//! domain socket, for legacy software that fetches its entropy from EGD, such as older OpenSSL
+//! and GnuPG setups.
+//!
+//! Every request starts with a command byte:
+//!
+//! * `0x00` - Get the entropy level. Answered with the available bits as a big endian `u32`.
+//! * `0x01 n` - Read up to `n` bytes without blocking. Answered with a count byte and the data.
+//! * `0x02 n` - Read `n` bytes, blocking. Answered with the data.
+//! * `0x03 b1 b2 n data` - Write `n` bytes of entropy worth `b1 b2` bits. Accepted and ignored.
+//! * `0x04` - Get the process id. Answered with a length byte and the id as a string.
+
+use crate::metrics::{Metrics, Session};
+use crate::{sources, Algorithm};
+use rand::RngCore;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+/// The entropy level reported to clients. Data is generated on demand, so there is always
+/// plenty available.
+const ENTROPY_LEVEL_BITS: u32 = 8 * 4096;
+
+/// Listens on the Unix domain socket at `path`, or the socket passed by systemd socket
+/// activation, and serves clients until the process is stopped. A stale socket left by an
+/// earlier server is replaced. Only returns if setting up the server fails.
+/// Serves metrics on `metrics_address` if given.
+pub fn run(
+    path: &Path,
+    algorithm: Algorithm,
+    metrics_address: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    sources::open_any(algorithm)?;
+    let metrics = Metrics::without_self_check(algorithm);
+    if let Some(metrics_address) = metrics_address {
+        crate::http::run_metrics(metrics_address, metrics.clone())?;
+    }
+    if let Some(listener) = crate::platform::activated_unix_listener()? {
+        eprintln!("Listening on the socket passed by systemd");
+        serve(listener, algorithm, metrics);
+        return Ok(());
+    }
+    let listener = match UnixListener::bind(path) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse && UnixStream::connect(path).is_err() => {
+            fs::remove_file(path)?;
+            UnixListener::bind(path)
+        }
+        result => result,
+    }
+    .map_err(|e| format!("Failed to listen on {}: {}", path.display(), e))?;
+    eprintln!("Listening on {}", path.display());
+    serve(listener, algorithm, metrics);
+    Ok(())
+}
+
+/// Accepts clients forever. Every client is served by its own thread and generator instance.
+fn serve(listener: UnixListener, algorithm: Algorithm, metrics: Arc<Metrics>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("WARNING: Failed to accept a client: {}", e);
+                continue;
+            }
+        };
+        let session = metrics.session();
+        thread::spawn(move || {
+            if let Err(e) = serve_client(stream, algorithm, session) {
+                eprintln!("Failed to serve a client: {}", e);
+            }
+        });
+    }
+}
+
+/// Answers requests until the client disconnects.
+fn serve_client(
+    mut stream: UnixStream,
+    algorithm: Algorithm,
+    mut session: Session,
+) -> Result<(), Box<dyn Error>> {
+    let mut rng = sources::open_any(algorithm)?;
+    let mut command = [0u8; 1];
+    loop {
+        match stream.read(&mut command)? {
+            0 => return Ok(()),
+            _ => handle(&mut stream, command[0], &mut *rng, &mut session)?,
+        }
+    }
+}
+
+fn handle(
+    mut stream: impl Read + Write,
+    command: u8,
+    rng: &mut dyn RngCore,
+    session: &mut Session,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        0x00 => stream.write_all(&ENTROPY_LEVEL_BITS.to_be_bytes())?,
+        0x01 | 0x02 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            let mut reply = zeroize::Zeroizing::new(vec![0u8; 1 + usize::from(len[0])]);
+            rng.try_fill_bytes(&mut reply[1..])?;
+            session.served(&reply[1..]);
+            // The non-blocking read always has all the data, and prefixes it with its length
+            if command == 0x01 {
+                reply[0] = len[0];
+                stream.write_all(&reply)?;
+            } else {
+                stream.write_all(&reply[1..])?;
+            }
+        }
+        0x03 => {
+            let mut header = [0u8; 3];
+            stream.read_exact(&mut header)?;
+            let mut data = zeroize::Zeroizing::new(vec![0u8; usize::from(header[2])]);
+            stream.read_exact(&mut data)?;
+        }
+        0x04 => {
+            let pid = std::process::id().to_string();
+            stream.write_all(&[pid.len() as u8])?;
+            stream.write_all(pid.as_bytes())?;
+        }
+        command => return Err(format!("Unknown command 0x{:02x}", command).into()),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_serve_egd() {
+    let path = std::env::temp_dir().join(format!("rng-test-{}.egd", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let metrics = Metrics::without_self_check(Algorithm::ChaCha8);
+    thread::spawn(move || serve(listener, Algorithm::ChaCha8, metrics));
+    let mut stream = UnixStream::connect(&path).unwrap();
+
+    let mut level = [0u8; 4];
+    stream.write_all(&[0x00]).unwrap();
+    stream.read_exact(&mut level).unwrap();
+    assert_eq!(u32::from_be_bytes(level), ENTROPY_LEVEL_BITS);
+
+    let mut reply = [0u8; 17];
+    stream.write_all(&[0x01, 16]).unwrap();
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(reply[0], 16);
+    stream.write_all(&[0x02, 16]).unwrap();
+    stream.read_exact(&mut reply[1..]).unwrap();
+
+    // Written entropy is ignored, so the next reply is the process id
+    stream.write_all(&[0x03, 0, 16, 2, 7, 7, 0x04]).unwrap();
+    let mut len = [0u8; 1];
+    stream.read_exact(&mut len).unwrap();
+    let mut pid = vec![0u8; usize::from(len[0])];
+    stream.read_exact(&mut pid).unwrap();
+    assert_eq!(pid, std::process::id().to_string().as_bytes());
+    fs::remove_file(&path).unwrap();
+}
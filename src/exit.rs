@@ -0,0 +1,50 @@
+//! The exit codes of `rng`, so scripts can tell the kinds of failures apart. Listed in the
+//! README.
+
+use std::io;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ExitCode {
+    /// Any failure not covered by the other codes, such as an entropy source failing.
+    Failure = 1,
+    /// Invalid arguments, or arguments that can't be used together.
+    Usage = 2,
+    /// Opening or writing an output failed.
+    Output = 3,
+    /// An output ran out of space.
+    OutOfSpace = 4,
+    /// A self-test, health test, self-check or verification of the output failed.
+    SelfTest = 5,
+    /// Stopped by SIGINT or SIGTERM, or Ctrl+C on Windows, before finishing.
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    /// The exit code for a failure to write an output.
+    pub fn for_write_error(e: &io::Error) -> Self {
+        match is_out_of_space(e) {
+            true => ExitCode::OutOfSpace,
+            false => ExitCode::Output,
+        }
+    }
+
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+/// Returns true if `e` means the output has no space left for more data.
+pub fn is_out_of_space(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded | io::ErrorKind::FileTooLarge
+    )
+}
+
+#[test]
+fn test_for_write_error() {
+    let full = io::Error::from(io::ErrorKind::StorageFull);
+    assert_eq!(ExitCode::for_write_error(&full), ExitCode::OutOfSpace);
+    let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+    assert_eq!(ExitCode::for_write_error(&reset), ExitCode::Output);
+}
@@ -0,0 +1,374 @@
+//! The `fake` subcommand. Synthetic personal records for test fixtures, looking realistic but
+//! belonging to nobody. Email addresses use the example domains reserved by RFC 2606, and
+//! phone numbers the 555-01xx block reserved for fiction in North America.
+
+use crate::formatting;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use std::fmt;
+use std::io::{self, Write};
+
+/// A column of the generated records.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Field {
+    Name,
+    Email,
+    Phone,
+    Address,
+}
+
+impl std::str::FromStr for Field {
+    type Err = ParseFieldError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Field::Name),
+            "email" => Ok(Field::Email),
+            "phone" => Ok(Field::Phone),
+            "address" => Ok(Field::Address),
+            _ => Err(ParseFieldError(())),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Name => f.write_str("name"),
+            Field::Email => f.write_str("email"),
+            Field::Phone => f.write_str("phone"),
+            Field::Address => f.write_str("address"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFieldError(());
+
+impl fmt::Display for ParseFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid field. See --help for a list of valid options.")
+    }
+}
+
+/// The language and country the records look like they come from.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Locale {
+    En,
+}
+
+impl std::str::FromStr for Locale {
+    type Err = ParseLocaleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Locale::En),
+            _ => Err(ParseLocaleError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseLocaleError(());
+
+impl fmt::Display for ParseLocaleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid locale. See --help for a list of valid options.")
+    }
+}
+
+/// How the records are written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// A header line with the field names, then one line per record.
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(ParseFormatError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFormatError(());
+
+impl fmt::Display for ParseFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid format. See --help for a list of valid options.")
+    }
+}
+
+/// The words records of a locale are made of.
+struct Words {
+    first_names: &'static [&'static str],
+    last_names: &'static [&'static str],
+    streets: &'static [&'static str],
+    street_suffixes: &'static [&'static str],
+    /// Cities with their state and the first digits of their ZIP codes.
+    cities: &'static [(&'static str, &'static str, &'static str)],
+    area_codes: &'static [&'static str],
+}
+
+const EN: Words = Words {
+    first_names: &[
+        "James",
+        "Mary",
+        "Robert",
+        "Patricia",
+        "John",
+        "Jennifer",
+        "Michael",
+        "Linda",
+        "David",
+        "Elizabeth",
+        "William",
+        "Barbara",
+        "Richard",
+        "Susan",
+        "Joseph",
+        "Jessica",
+        "Thomas",
+        "Sarah",
+        "Charles",
+        "Karen",
+        "Daniel",
+        "Nancy",
+        "Matthew",
+        "Lisa",
+        "Anthony",
+        "Betty",
+        "Mark",
+        "Sandra",
+        "Steven",
+        "Ashley",
+        "Andrew",
+        "Emily",
+        "Joshua",
+        "Michelle",
+        "Kevin",
+        "Amanda",
+        "Brian",
+        "Melissa",
+        "George",
+        "Olivia",
+    ],
+    last_names: &[
+        "Smith",
+        "Johnson",
+        "Williams",
+        "Brown",
+        "Jones",
+        "Garcia",
+        "Miller",
+        "Davis",
+        "Rodriguez",
+        "Martinez",
+        "Hernandez",
+        "Lopez",
+        "Gonzalez",
+        "Wilson",
+        "Anderson",
+        "Thomas",
+        "Taylor",
+        "Moore",
+        "Jackson",
+        "Martin",
+        "Lee",
+        "Perez",
+        "Thompson",
+        "White",
+        "Harris",
+        "Sanchez",
+        "Clark",
+        "Ramirez",
+        "Lewis",
+        "Robinson",
+        "Walker",
+        "Young",
+        "Allen",
+        "King",
+        "Wright",
+        "Scott",
+        "Torres",
+        "Nguyen",
+        "Hill",
+        "Flores",
+    ],
+    streets: &[
+        "Oak",
+        "Maple",
+        "Cedar",
+        "Pine",
+        "Elm",
+        "Washington",
+        "Lake",
+        "Hill",
+        "Park",
+        "Main",
+        "Sunset",
+        "Lincoln",
+        "Jackson",
+        "Highland",
+        "River",
+        "Church",
+        "Spring",
+        "Meadow",
+        "Forest",
+        "Willow",
+    ],
+    street_suffixes: &[
+        "Street",
+        "Avenue",
+        "Road",
+        "Lane",
+        "Drive",
+        "Court",
+        "Boulevard",
+    ],
+    cities: &[
+        ("Springfield", "IL", "627"),
+        ("Portland", "OR", "972"),
+        ("Austin", "TX", "787"),
+        ("Columbus", "OH", "432"),
+        ("Madison", "WI", "537"),
+        ("Richmond", "VA", "232"),
+        ("Albany", "NY", "122"),
+        ("Denver", "CO", "802"),
+        ("Raleigh", "NC", "276"),
+        ("Sacramento", "CA", "958"),
+        ("Boise", "ID", "837"),
+        ("Tallahassee", "FL", "323"),
+    ],
+    area_codes: &[
+        "212", "303", "312", "415", "503", "512", "608", "614", "702", "804", "919", "916",
+    ],
+};
+
+/// Generates records with the chosen fields.
+pub struct Faker<'a> {
+    words: &'static Words,
+    fields: &'a [Field],
+    rng: &'a mut dyn RngCore,
+}
+
+impl<'a> Faker<'a> {
+    pub fn new(locale: Locale, fields: &'a [Field], rng: &'a mut dyn RngCore) -> Self {
+        let words = match locale {
+            Locale::En => &EN,
+        };
+        Faker { words, fields, rng }
+    }
+
+    /// The values of the next record, in the order of the fields. The email address is made
+    /// from the name of the same record.
+    pub fn record(&mut self) -> Vec<String> {
+        let first = *self.words.first_names.choose(self.rng).unwrap();
+        let last = *self.words.last_names.choose(self.rng).unwrap();
+        self.fields
+            .iter()
+            .map(|field| match field {
+                Field::Name => format!("{} {}", first, last),
+                Field::Email => {
+                    let domain = ["example.com", "example.net", "example.org"]
+                        .choose(self.rng)
+                        .unwrap();
+                    format!(
+                        "{}.{}{}@{}",
+                        first.to_lowercase(),
+                        last.to_lowercase(),
+                        self.rng.gen_range(1..100),
+                        domain
+                    )
+                }
+                Field::Phone => format!(
+                    "+1 {} 555-01{:02}",
+                    self.words.area_codes.choose(self.rng).unwrap(),
+                    self.rng.gen_range(0..100)
+                ),
+                Field::Address => {
+                    let (city, state, zip) = self.words.cities.choose(self.rng).unwrap();
+                    format!(
+                        "{} {} {}, {}, {} {}{:02}",
+                        self.rng.gen_range(1..10_000),
+                        self.words.streets.choose(self.rng).unwrap(),
+                        self.words.street_suffixes.choose(self.rng).unwrap(),
+                        city,
+                        state,
+                        zip,
+                        self.rng.gen_range(0..100)
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+/// Writes `count` records in `format`.
+pub fn run(
+    count: u64,
+    fields: &[Field],
+    locale: Locale,
+    format: Format,
+    rng: &mut dyn RngCore,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(out);
+    let mut faker = Faker::new(locale, fields, rng);
+    if format == Format::Csv {
+        let header: Vec<_> = fields.iter().map(Field::to_string).collect();
+        writeln!(out, "{}", header.join(","))?;
+    }
+    for _ in 0..count {
+        let values = faker.record();
+        let line: Vec<_> = match format {
+            Format::Csv => values.iter().map(|v| formatting::csv_field(v)).collect(),
+            Format::Json => fields
+                .iter()
+                .zip(&values)
+                .map(|(field, value)| format!("\"{}\":{}", field, formatting::json_string(value)))
+                .collect(),
+        };
+        match format {
+            Format::Csv => writeln!(out, "{}", line.join(","))?,
+            Format::Json => writeln!(out, "{{{}}}", line.join(","))?,
+        }
+    }
+    out.flush()
+}
+
+#[test]
+fn test_fake() {
+    use rand::SeedableRng;
+
+    let fields = [Field::Name, Field::Email, Field::Phone, Field::Address];
+    let generate = |format| {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+        let mut out = Vec::new();
+        run(3, &fields, Locale::En, format, &mut rng, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let csv = generate(Format::Csv);
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "name,email,phone,address");
+    // The address contains commas, so it is quoted
+    assert!(lines[1].ends_with('"'));
+    assert_eq!(csv, generate(Format::Csv));
+
+    let json = generate(Format::Json);
+    assert_eq!(json.lines().count(), 3);
+    for line in json.lines() {
+        assert!(line.starts_with("{\"name\":\""));
+        assert!(line.contains("@example."));
+        assert!(line.contains(" 555-01"));
+    }
+}
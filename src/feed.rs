@@ -0,0 +1,143 @@
+//! The `feed-kernel` subcommand. Mixes data from an entropy source into the Linux kernel entropy
+//! pool with the RNDADDENTROPY ioctl, like rngd does. For small systems with a hardware random
+//! number generator but no rngd.
+
+use crate::rngs::EndOfStream;
+use rand::RngCore;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// `_IOW('R', 0x03, int[2])`. The direction bits are encoded differently on some architectures.
+#[cfg(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+))]
+const RNDADDENTROPY: u32 = 0x8008_5203;
+#[cfg(not(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+)))]
+const RNDADDENTROPY: u32 = 0x4008_5203;
+
+/// The amount of data mixed into the pool per ioctl.
+const FEED_CHUNK: usize = 512;
+
+/// Without a rate, how long to wait for the kernel to ask for entropy before feeding anyway.
+/// Kernels since 5.18 only ask until their pool is initialized.
+const FEED_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Feeds the output of `source` into the entropy pool through `device`, crediting `ratio` bits
+/// of entropy per bit of data. Feeds `rate` bytes per second, or whenever the kernel asks for
+/// more entropy if `None`. Runs until the source ends or fails.
+pub fn run(
+    mut source: Box<dyn RngCore>,
+    device: &Path,
+    ratio: f64,
+    rate: Option<u64>,
+) -> Result<(), Box<dyn Error>> {
+    if ratio.is_nan() || !(0.0..=1.0).contains(&ratio) {
+        return Err("The entropy credit ratio must be between 0 and 1".into());
+    }
+    if rate == Some(0) {
+        return Err("The rate must be at least 1 byte per second".into());
+    }
+    let pool = OpenOptions::new()
+        .write(true)
+        .open(device)
+        .map_err(|e| format!("Failed to open {}: {}", device.display(), e))?;
+
+    let mut chunk = Zeroizing::new([0u8; FEED_CHUNK]);
+    let start = Instant::now();
+    let mut fed = 0u64;
+    loop {
+        match rate {
+            Some(rate) => {
+                let due = Duration::from_secs_f64(fed as f64 / rate as f64);
+                if let Some(wait) = due.checked_sub(start.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+            None => wait_for_demand(pool.as_raw_fd())?,
+        }
+        let len = match rate {
+            Some(rate) => FEED_CHUNK.min(usize::try_from(rate).unwrap_or(FEED_CHUNK)),
+            None => FEED_CHUNK,
+        };
+        let filled = EndOfStream::filled_len(source.try_fill_bytes(&mut chunk[..len]), len)
+            .map_err(|e| format!("Failed to read from the source: {}", e))?;
+        let credit = (filled as f64 * 8.0 * ratio) as i32;
+        add_entropy(pool.as_raw_fd(), &chunk[..filled], credit).map_err(|e| {
+            format!(
+                "Failed to add entropy to the pool through {}: {}",
+                device.display(),
+                e
+            )
+        })?;
+        fed += filled as u64;
+        if filled < len {
+            eprintln!("The source ended after feeding {} bytes", fed);
+            return Ok(());
+        }
+    }
+}
+
+/// Blocks until the kernel wants more entropy, or `FEED_INTERVAL` has passed.
+fn wait_for_demand(fd: RawFd) -> io::Result<()> {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    let timeout = FEED_INTERVAL.as_millis() as libc::c_int;
+    match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+        -1 => match io::Error::last_os_error() {
+            e if e.kind() == io::ErrorKind::Interrupted => Ok(()),
+            e => Err(e),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn add_entropy(fd: RawFd, data: &[u8], entropy_bits: i32) -> io::Result<()> {
+    let info = rand_pool_info(data, entropy_bits);
+    if unsafe { libc::ioctl(fd, RNDADDENTROPY as _, info.as_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Lays out `data` as a `struct rand_pool_info`: the entropy credit in bits and the length of
+/// the data in bytes, followed by the data.
+fn rand_pool_info(data: &[u8], entropy_bits: i32) -> Zeroizing<Vec<u32>> {
+    let mut info = Zeroizing::new(Vec::with_capacity(2 + data.len().div_ceil(4)));
+    info.push(entropy_bits as u32);
+    info.push(data.len() as u32);
+    for word in data.chunks(4) {
+        let mut bytes = [0u8; 4];
+        bytes[..word.len()].copy_from_slice(word);
+        info.push(u32::from_ne_bytes(bytes));
+    }
+    info
+}
+
+#[test]
+fn test_rand_pool_info() {
+    let info = rand_pool_info(&[1, 2, 3, 4, 5], 20);
+    assert_eq!(info.len(), 4);
+    assert_eq!(info[..2], [20, 5]);
+    assert_eq!(info[2], u32::from_ne_bytes([1, 2, 3, 4]));
+    assert_eq!(info[3], u32::from_ne_bytes([5, 0, 0, 0]));
+}
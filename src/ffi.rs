@@ -0,0 +1,189 @@
+//! A C API to the algorithms, for test harnesses not written in Rust. Built into the
+//! `rng_core` cdylib. The declarations are in `include/rng_core.h`:
+//!
+//! ```c
+//! // Creates a generator for `algorithm`, named like on the command line. Seeded with
+//! // `seed_len` bytes from `seed` like `--seed-hex`, or from the OS if `seed` is NULL.
+//! // Returns NULL on failure.
+//! rng_t *rng_new(const char *algorithm, const uint8_t *seed, size_t seed_len);
+//!
+//! // Like rng_new, but seeded with a number like `--seed`.
+//! rng_t *rng_new_u64(const char *algorithm, uint64_t seed);
+//!
+//! // Fills `dest` with `len` random bytes. Returns zero on success.
+//! int rng_fill_bytes(rng_t *rng, uint8_t *dest, size_t len);
+//!
+//! // Frees a generator created by rng_new or rng_new_u64.
+//! void rng_destroy(rng_t *rng);
+//! ```
+//!
+//! The output is the same stream `rng <algorithm>` writes with the same seed, no matter how it
+//! is split over `rng_fill_bytes` calls.
+
+use crate::seed::{Seed, MAX_SEED_LEN};
+use crate::{rngs, sources, Algorithm, BUFFER_SIZE};
+use rand::RngCore;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use zeroize::Zeroizing;
+
+/// A generator created through the C API. Generates `BUFFER_SIZE` bytes at a time, like the
+/// single threaded mode of the command line tool, so the output does not depend on the sizes
+/// requested.
+pub struct Rng {
+    rng: Box<dyn RngCore>,
+    buf: Zeroizing<Vec<u8>>,
+    /// The number of bytes of `buf` already handed out.
+    used: usize,
+}
+
+impl Rng {
+    fn new(algorithm: *const c_char, seed: Option<Seed>) -> Option<Self> {
+        if algorithm.is_null() {
+            return None;
+        }
+        // Safety: The caller passes a valid NUL terminated string
+        let algorithm = unsafe { CStr::from_ptr(algorithm) };
+        let algorithm = algorithm.to_str().ok()?.parse::<Algorithm>().ok()?;
+        if let (Some(len), Some(max_len)) = (seed.and_then(|s| s.len()), algorithm.seed_len()) {
+            if len > max_len {
+                return None;
+            }
+        }
+        let rng: Box<dyn RngCore> = match (algorithm, seed) {
+            (Algorithm::Plugin(path), seed) => {
+                Box::new(rngs::Plugin::load(path).ok()?.new_rng(seed).ok()?)
+            }
+            (algorithm, None) => sources::open_any(algorithm).ok()?,
+            (algorithm, Some(_)) if algorithm.is_entropy_source() => return None,
+            (algorithm, seed) => crate::combine::new_userspace(algorithm, seed),
+        };
+        Some(Rng {
+            rng,
+            buf: Zeroizing::new(vec![0u8; BUFFER_SIZE]),
+            used: BUFFER_SIZE,
+        })
+    }
+
+    fn fill_bytes(&mut self, mut dest: &mut [u8]) -> Result<(), rand::Error> {
+        while !dest.is_empty() {
+            if self.used == self.buf.len() {
+                self.rng.try_fill_bytes(&mut self.buf)?;
+                self.used = 0;
+            }
+            let len = dest.len().min(self.buf.len() - self.used);
+            dest[..len].copy_from_slice(&self.buf[self.used..self.used + len]);
+            self.used += len;
+            dest = &mut dest[len..];
+        }
+        Ok(())
+    }
+}
+
+fn into_raw(rng: Option<Rng>) -> *mut Rng {
+    rng.map_or(std::ptr::null_mut(), |rng| Box::into_raw(Box::new(rng)))
+}
+
+/// Creates a generator for the algorithm named `algorithm`. Seeded with the `seed_len` bytes at
+/// `seed`, like `--seed-hex`, or from the OS if `seed` is null. Returns null on failure.
+///
+/// # Safety
+///
+/// `algorithm` must be a NUL terminated string. `seed` must be null or point to `seed_len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rng_new(
+    algorithm: *const c_char,
+    seed: *const u8,
+    seed_len: usize,
+) -> *mut Rng {
+    let seed = if seed.is_null() {
+        None
+    } else if seed_len > MAX_SEED_LEN {
+        return std::ptr::null_mut();
+    } else {
+        let mut bytes = [0u8; MAX_SEED_LEN];
+        bytes[..seed_len].copy_from_slice(std::slice::from_raw_parts(seed, seed_len));
+        Some(Seed::Bytes {
+            bytes,
+            len: seed_len,
+        })
+    };
+    into_raw(Rng::new(algorithm, seed))
+}
+
+/// Like `rng_new`, but seeded with a number, like `--seed`.
+///
+/// # Safety
+///
+/// `algorithm` must be a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rng_new_u64(algorithm: *const c_char, seed: u64) -> *mut Rng {
+    into_raw(Rng::new(algorithm, Some(Seed::Number(seed))))
+}
+
+/// Fills `dest` with `len` random bytes. Returns zero on success, or -1 if the generator is an
+/// entropy source that failed.
+///
+/// # Safety
+///
+/// `rng` must come from `rng_new` or `rng_new_u64` and not be destroyed. `dest` must point to
+/// `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rng_fill_bytes(rng: *mut Rng, dest: *mut u8, len: usize) -> c_int {
+    if len == 0 {
+        return 0;
+    }
+    let dest = std::slice::from_raw_parts_mut(dest, len);
+    match (*rng).fill_bytes(dest) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Frees a generator. Does nothing if `rng` is null.
+///
+/// # Safety
+///
+/// `rng` must be null or come from `rng_new` or `rng_new_u64` and not already be destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn rng_destroy(rng: *mut Rng) {
+    if !rng.is_null() {
+        drop(Box::from_raw(rng));
+    }
+}
+
+#[test]
+fn test_ffi() {
+    use crate::Generator;
+
+    let mut expected = Vec::new();
+    Generator::builder()
+        .algorithm(Algorithm::ChaCha20)
+        .seed(Seed::Number(5))
+        .limit(3 * BUFFER_SIZE as u64)
+        .build()
+        .unwrap()
+        .generate(&mut expected)
+        .unwrap();
+
+    let mut data = vec![0u8; expected.len()];
+    unsafe {
+        let rng = rng_new_u64(b"chacha20\0".as_ptr().cast(), 5);
+        assert!(!rng.is_null());
+        // Uneven requests, crossing the buffer boundaries
+        for chunk in data.chunks_mut(1000 + 7) {
+            assert_eq!(rng_fill_bytes(rng, chunk.as_mut_ptr(), chunk.len()), 0);
+        }
+        rng_destroy(rng);
+
+        assert!(rng_new(b"chacha9\0".as_ptr().cast(), std::ptr::null(), 0).is_null());
+        assert!(rng_new_u64(b"os\0".as_ptr().cast(), 5).is_null());
+        let seed = [1u8; 33];
+        assert!(rng_new(b"chacha20\0".as_ptr().cast(), seed.as_ptr(), seed.len()).is_null());
+        let rng = rng_new(b"pcg\0".as_ptr().cast(), seed.as_ptr(), 16);
+        assert!(!rng.is_null());
+        rng_destroy(rng);
+    }
+    assert!(data == expected);
+}
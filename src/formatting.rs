@@ -1,18 +1,173 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by `--si`, for sizes and rates in powers of 1000 instead of 1024 everywhere.
+static SI_UNITS: AtomicBool = AtomicBool::new(false);
+
+/// Makes `format_bytes_written` use decimal (SI) prefixes from now on.
+pub fn use_si_units() {
+    SI_UNITS.store(true, Ordering::Relaxed);
+}
+
 /// Returns the number of bytes scaled down and with the correct prefix and with one digit after
-/// the decimal point. For example 1130 would return "1.1 KiB"
+/// the decimal point. For example 1130 would return "1.1 KiB", or "1.1 KB" after
+/// `use_si_units`.
 pub fn format_bytes_written(bytes: u64) -> String {
+    match SI_UNITS.load(Ordering::Relaxed) {
+        true => format_bytes_si(bytes),
+        false => format_bytes_binary(bytes),
+    }
+}
+
+/// Formats the number of bytes with binary prefixes, in powers of 1024.
+fn format_bytes_binary(bytes: u64) -> String {
     // All the binary unit prefixes needed (u64::MAX == 16 EiB)
     const PREFIXES: &[&str] = &["bytes", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    format_bytes(bytes, 1024.0, PREFIXES)
+}
+
+/// Formats the number of bytes with decimal prefixes, in powers of 1000, the way disk and
+/// network vendors quote sizes and speeds.
+fn format_bytes_si(bytes: u64) -> String {
+    // All the decimal unit prefixes needed (u64::MAX == 18.4 EB)
+    const PREFIXES: &[&str] = &["bytes", "KB", "MB", "GB", "TB", "PB", "EB"];
+    format_bytes(bytes, 1000.0, PREFIXES)
+}
+
+fn format_bytes(bytes: u64, base: f64, prefixes: &[&str]) -> String {
     let mut bytes = bytes as f64;
     let mut prefix_i = 0;
-    while bytes >= 1024.0 && prefix_i < PREFIXES.len() - 1 {
-        bytes /= 1024.0;
+    while bytes >= base && prefix_i < prefixes.len() - 1 {
+        bytes /= base;
         prefix_i += 1;
     }
     if prefix_i == 0 {
-        format!("{:.0} {}", bytes, PREFIXES[prefix_i])
+        format!("{:.0} {}", bytes, prefixes[prefix_i])
     } else {
-        format!("{:.1} {}", bytes, PREFIXES[prefix_i])
+        format!("{:.1} {}", bytes, prefixes[prefix_i])
+    }
+}
+
+/// Encodes bytes as lowercase hex.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The alphabet of standard base64, from RFC 4648.
+pub const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes as base64 with the bits in the order of RFC 4648 and the given alphabet, padded
+/// with "=" to a multiple of four characters if `padding` is set.
+pub fn base64(bytes: &[u8], alphabet: &[u8; 64], padding: bool) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(
+                    alphabet[(group >> (18 - 6 * i) & 0x3f) as usize],
+                ));
+            } else if padding {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Encodes bytes as base32 from RFC 4648, without the padding, as authenticator apps want
+/// their secrets.
+pub fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u16, 0);
+    for &byte in bytes {
+        buffer = buffer << 8 | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(char::from(ALPHABET[(buffer >> bits & 0x1f) as usize]));
+        }
+    }
+    if bits > 0 {
+        encoded.push(char::from(ALPHABET[(buffer << (5 - bits) & 0x1f) as usize]));
+    }
+    encoded
+}
+
+/// Encodes bytes as a big-endian number in base62, with the digits 0-9, A-Z and a-z. Padded with
+/// leading zeros to the length of the largest number of that many bytes, so encodings of equally
+/// long inputs are equally long.
+pub fn base62(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let len = (bytes.len() as f64 * 8.0 / 62f64.log2()).ceil() as usize;
+    let mut number = bytes.to_vec();
+    let mut digits = Vec::with_capacity(len);
+    for _ in 0..len {
+        // Long division of the number by 62, keeping the quotient and the remainder as a digit
+        let mut remainder = 0u32;
+        for byte in &mut number {
+            let value = remainder << 8 | u32::from(*byte);
+            *byte = (value / 62) as u8;
+            remainder = value % 62;
+        }
+        digits.push(ALPHABET[remainder as usize]);
+    }
+    digits
+        .iter()
+        .rev()
+        .map(|&digit| char::from(digit))
+        .collect()
+}
+
+/// Continues the CRC-32 checksum `crc` over `data`, the one of zlib, PNG and Ethernet. A new
+/// checksum starts from 0.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+/// Writes `count` lines made by `next`.
+pub fn write_lines(
+    count: u64,
+    out: &mut dyn Write,
+    mut next: impl FnMut() -> String,
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(out);
+    for _ in 0..count {
+        writeln!(out, "{}", next())?;
+    }
+    out.flush()
+}
+
+/// Quotes a string as JSON.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a CSV field if it contains a separator or quote.
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
     }
 }
 
@@ -32,3 +187,53 @@ fn test_format_bytes_written() {
 
     assert_eq!(format_bytes_written(u64::MAX), "16.0 EiB");
 }
+
+#[test]
+fn test_format_bytes_si() {
+    assert_eq!(format_bytes_si(999), "999 bytes");
+    assert_eq!(format_bytes_si(1000), "1.0 KB");
+    assert_eq!(format_bytes_si(1024), "1.0 KB");
+    assert_eq!(format_bytes_si(1_260_000), "1.3 MB");
+    assert_eq!(format_bytes_si(10_000_000_000), "10.0 GB");
+    assert_eq!(format_bytes_si(u64::MAX), "18.4 EB");
+}
+
+#[test]
+fn test_encodings() {
+    assert_eq!(hex(&[0x00, 0xff, 0x10]), "00ff10");
+    // The test vectors of RFC 4648
+    for (input, expected) in [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ] {
+        assert_eq!(base64(input.as_bytes(), BASE64_ALPHABET, true), expected);
+        let unpadded = expected.trim_end_matches('=');
+        assert_eq!(base64(input.as_bytes(), BASE64_ALPHABET, false), unpadded);
+    }
+    for (input, expected) in [
+        ("", ""),
+        ("f", "MY"),
+        ("fo", "MZXQ"),
+        ("foo", "MZXW6"),
+        ("foob", "MZXW6YQ"),
+        ("fooba", "MZXW6YTB"),
+        ("foobar", "MZXW6YTBOI"),
+    ] {
+        assert_eq!(base32(input.as_bytes()), expected);
+    }
+    assert_eq!(base62(&[]), "");
+    assert_eq!(base62(&[61]), "0z");
+    assert_eq!(base62(&[0xff; 4]), "4gfFC3");
+    assert_eq!(base62(&[0; 4]), "000000");
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+    assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf4_3926);
+}
@@ -0,0 +1,347 @@
+//! The `mount` subcommand. Serves a FUSE file that behaves like /dev/urandom, but with the data
+//! coming from any algorithm, optionally seeded. Speaks the FUSE kernel protocol directly over
+//! /dev/fuse, so mounting requires root. Only the requests a single read-only file needs are
+//! implemented, everything else is answered with ENOSYS.
+//!
+//! The file is mounted over a regular file, which FUSE supports when the root node is a file.
+//! It is opened in direct IO mode, so every read reaches the generator and the reported size of
+//! zero does not end the data, just like for a device.
+
+use crate::seed::Seed;
+use crate::seek::{BoxedSeek, Seek, MAX_STEP_BYTES};
+use crate::{sources, Algorithm};
+use rand::RngCore;
+use std::error::Error;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// The protocol version this implementation speaks. The kernel falls back to it if it is newer.
+const KERNEL_VERSION: u32 = 7;
+const KERNEL_MINOR_VERSION: u32 = 31;
+
+/// The largest write request the kernel is told to send. Writes are refused anyway.
+const MAX_WRITE: u32 = 64 * 1024;
+
+/// Room for the largest request the kernel can send, headers included.
+const REQUEST_BUFFER_SIZE: usize = MAX_WRITE as usize + 4096;
+
+/// The node id of the root of the mount, which is the random file itself.
+const ROOT_ID: u64 = 1;
+
+const IN_HEADER_LEN: usize = 40;
+const OUT_HEADER_LEN: usize = 16;
+
+const FUSE_GETATTR: u32 = 3;
+const FUSE_OPEN: u32 = 14;
+const FUSE_READ: u32 = 15;
+const FUSE_STATFS: u32 = 17;
+const FUSE_RELEASE: u32 = 18;
+const FUSE_FLUSH: u32 = 25;
+const FUSE_INIT: u32 = 26;
+const FUSE_ACCESS: u32 = 34;
+const FUSE_DESTROY: u32 = 38;
+/// Requests the kernel does not want a reply to.
+const NO_REPLY: [u32; 3] = [2, 36, 42];
+
+/// Makes the kernel pass every read to the file system instead of using the page cache.
+const FOPEN_DIRECT_IO: u32 = 1;
+
+/// Mounts the random file at `path` and serves reads until interrupted or unmounted. `path`
+/// is created if it does not exist. With a seed, user-space algorithms that can seek give the
+/// same data at the same offset every time, like a file. All other algorithms give the next
+/// data of one generator to every read.
+pub fn run(path: &Path, algorithm: Algorithm, seed: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let mut content = Content::new(algorithm, seed.map(Seed::Number))?;
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => {
+            return Err(format!(
+                "{} is a directory. The random file is mounted over a file, such as {}",
+                path.display(),
+                path.join("random").display()
+            )
+            .into())
+        }
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            File::create(path)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        }
+        Err(e) => return Err(format!("Failed to access {}: {}", path.display(), e).into()),
+    }
+
+    let device = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/fuse")
+        .map_err(|e| format!("Failed to open /dev/fuse: {}", e))?;
+    let target = CString::new(path.as_os_str().as_bytes())?;
+    mount(&target, &device).map_err(|e| format!("Failed to mount {}: {}", path.display(), e))?;
+    eprintln!("Mounted {}. Press Ctrl+C to unmount", path.display());
+
+    // Unmounting makes the kernel end the session, which ends the serve loop
+    let unmount_target = target.clone();
+    simple_signal::set_handler(
+        &[simple_signal::Signal::Term, simple_signal::Signal::Int],
+        move |_| unmount(&unmount_target),
+    );
+    let result = serve(device, &mut content);
+    unmount(&target);
+    result.map_err(|e| format!("The FUSE session failed: {}", e).into())
+}
+
+fn mount(target: &CString, device: &File) -> io::Result<()> {
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    // The root is a read only regular file. Anyone may read it, like /dev/urandom
+    let options = format!(
+        "fd={},rootmode=100000,user_id={},group_id={},allow_other,default_permissions",
+        device.as_raw_fd(),
+        uid,
+        gid
+    );
+    let options = CString::new(options).expect("The options contain no null bytes");
+    let result = unsafe {
+        libc::mount(
+            b"rng\0".as_ptr() as *const libc::c_char,
+            target.as_ptr(),
+            b"fuse.rng\0".as_ptr() as *const libc::c_char,
+            libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV,
+            options.as_ptr() as *const libc::c_void,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn unmount(target: &CString) {
+    unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) };
+}
+
+/// Answers requests from the kernel until the file system is unmounted.
+fn serve(mut device: File, content: &mut Content) -> io::Result<()> {
+    let mut request = vec![0u8; REQUEST_BUFFER_SIZE];
+    loop {
+        let len = match device.read(&mut request) {
+            Ok(len) => len,
+            // The file system was unmounted
+            Err(e) if e.raw_os_error() == Some(libc::ENODEV) => return Ok(()),
+            // The request was interrupted before it was read
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => continue,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if len < IN_HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Truncated request",
+            ));
+        }
+        let opcode = u32_at(&request, 4);
+        let unique = u64_at(&request, 8);
+        if NO_REPLY.contains(&opcode) {
+            continue;
+        }
+        let reply = handle(opcode, &request[IN_HEADER_LEN..len], content);
+        let result = match reply {
+            Ok(data) => send_reply(&mut device, unique, 0, &data),
+            Err(errno) => send_reply(&mut device, unique, errno, &[]),
+        };
+        match result {
+            // The request was interrupted and the kernel no longer waits for the reply
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => (),
+            result => result?,
+        }
+        if opcode == FUSE_DESTROY {
+            return Ok(());
+        }
+    }
+}
+
+/// Handles one request and returns the reply data, or an errno.
+fn handle(opcode: u32, arg: &[u8], content: &mut Content) -> Result<Zeroizing<Vec<u8>>, i32> {
+    let mut reply = Zeroizing::new(Vec::new());
+    match opcode {
+        FUSE_INIT => {
+            if arg.len() < 16 || u32_at(arg, 0) != KERNEL_VERSION {
+                return Err(libc::EPROTO);
+            }
+            let max_readahead = u32_at(arg, 8);
+            reply.extend_from_slice(&KERNEL_VERSION.to_ne_bytes());
+            reply.extend_from_slice(&KERNEL_MINOR_VERSION.to_ne_bytes());
+            reply.extend_from_slice(&max_readahead.to_ne_bytes());
+            // No optional features
+            reply.extend_from_slice(&0u32.to_ne_bytes());
+            // Max background requests and congestion threshold
+            reply.extend_from_slice(&[0; 4]);
+            reply.extend_from_slice(&MAX_WRITE.to_ne_bytes());
+            // Timestamp granularity in nanoseconds
+            reply.extend_from_slice(&1u32.to_ne_bytes());
+            reply.resize(64, 0);
+        }
+        FUSE_GETATTR => {
+            // How long the kernel may cache the attributes, in seconds and nanoseconds
+            reply.extend_from_slice(&1u64.to_ne_bytes());
+            reply.extend_from_slice(&[0; 8]);
+            reply.extend_from_slice(&attributes());
+        }
+        FUSE_OPEN => {
+            // File handle, open flags and padding
+            reply.extend_from_slice(&0u64.to_ne_bytes());
+            reply.extend_from_slice(&FOPEN_DIRECT_IO.to_ne_bytes());
+            reply.extend_from_slice(&[0; 4]);
+        }
+        FUSE_READ => {
+            if arg.len() < 20 {
+                return Err(libc::EINVAL);
+            }
+            let offset = u64_at(arg, 8);
+            let size = u32_at(arg, 16) as usize;
+            reply.resize(size, 0);
+            content.read(offset, &mut reply).map_err(|e| {
+                eprintln!("WARNING: Failed to generate data: {}", e);
+                libc::EIO
+            })?;
+        }
+        FUSE_STATFS => reply.resize(80, 0),
+        FUSE_RELEASE | FUSE_FLUSH | FUSE_ACCESS | FUSE_DESTROY => (),
+        _ => return Err(libc::ENOSYS),
+    }
+    Ok(reply)
+}
+
+/// The attributes of the random file. A world readable regular file of size zero.
+fn attributes() -> [u8; 88] {
+    let mut attr = [0u8; 88];
+    attr[..8].copy_from_slice(&ROOT_ID.to_ne_bytes());
+    // Size, blocks and timestamps are zero. Then mode, link count, owner and block size
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    attr[60..64].copy_from_slice(&(libc::S_IFREG | 0o444).to_ne_bytes());
+    attr[64..68].copy_from_slice(&1u32.to_ne_bytes());
+    attr[68..72].copy_from_slice(&uid.to_ne_bytes());
+    attr[72..76].copy_from_slice(&gid.to_ne_bytes());
+    attr[80..84].copy_from_slice(&(crate::BUFFER_SIZE as u32).to_ne_bytes());
+    attr
+}
+
+fn send_reply(device: &mut File, unique: u64, errno: i32, data: &[u8]) -> io::Result<()> {
+    let mut reply = Zeroizing::new(Vec::with_capacity(OUT_HEADER_LEN + data.len()));
+    reply.extend_from_slice(&((OUT_HEADER_LEN + data.len()) as u32).to_ne_bytes());
+    reply.extend_from_slice(&(-errno).to_ne_bytes());
+    reply.extend_from_slice(&unique.to_ne_bytes());
+    reply.extend_from_slice(data);
+    // Every reply must be written in a single write
+    let written = device.write(&reply)?;
+    if written != reply.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            "Partially written reply",
+        ));
+    }
+    Ok(())
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_ne_bytes(bytes)
+}
+
+fn u64_at(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_ne_bytes(bytes)
+}
+
+/// Where the data read from the file comes from.
+enum Content {
+    /// One generator, giving the next data to every read regardless of offset.
+    Stream(Box<dyn RngCore>),
+    /// A seeded generator recreated at the read offset when reads are not sequential, so every
+    /// offset always has the same data.
+    Positioned {
+        seed: Seed,
+        new_rng: fn(Seed) -> BoxedSeek,
+        rng: BoxedSeek,
+        position: u64,
+    },
+}
+
+impl Content {
+    fn new(algorithm: Algorithm, seed: Option<Seed>) -> Result<Self, Box<dyn Error>> {
+        let seed = match seed {
+            None => return Ok(Content::Stream(sources::open_any(algorithm)?)),
+            Some(_) if algorithm.is_entropy_source() => {
+                return Err(format!("{} can not be seeded", algorithm).into())
+            }
+            Some(seed) => seed,
+        };
+        if let Algorithm::Plugin(path) = algorithm {
+            let rng = crate::rngs::Plugin::load(path)?.new_rng(Some(seed))?;
+            return Ok(Content::Stream(Box::new(rng)));
+        }
+        let constructor = algorithm.constructor().expect("Not an entropy source");
+        match constructor.new_seekable {
+            Some(new_rng) => Ok(Content::Positioned {
+                seed,
+                new_rng,
+                rng: new_rng(seed),
+                position: 0,
+            }),
+            None => Ok(Content::Stream((constructor.new)(Some(seed)))),
+        }
+    }
+
+    fn read(&mut self, offset: u64, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            Content::Stream(rng) => rng.try_fill_bytes(dest),
+            Content::Positioned {
+                seed,
+                new_rng,
+                rng,
+                position,
+            } => {
+                // The generators consume whole steps of output, so the data is generated from
+                // the step boundary before the offset up to the one after the end of the read
+                let start = offset - offset % MAX_STEP_BYTES;
+                let skip = (offset - start) as usize;
+                let len = (skip + dest.len()).div_ceil(MAX_STEP_BYTES as usize);
+                let mut steps = Zeroizing::new(vec![0u8; len * MAX_STEP_BYTES as usize]);
+                if start != *position {
+                    *rng = new_rng(*seed);
+                    rng.seek_bytes(u128::from(start));
+                }
+                rng.fill_bytes(&mut steps);
+                dest.copy_from_slice(&steps[skip..skip + dest.len()]);
+                *position = start.wrapping_add(steps.len() as u64);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fuse_content() {
+    let mut content = Content::new(Algorithm::ChaCha20, Some(Seed::Number(3))).unwrap();
+    let mut sequential = [0u8; 100];
+    content.read(0, &mut sequential[..30]).unwrap();
+    content.read(30, &mut sequential[30..64]).unwrap();
+    content.read(64, &mut sequential[64..]).unwrap();
+    let mut expected = [0u8; 100];
+    Seed::Number(3)
+        .new_rng::<rand_chacha::ChaCha20Rng>()
+        .fill_bytes(&mut expected);
+    assert_eq!(sequential, expected);
+
+    // Reading an offset again gives the same data
+    let mut reread = [0u8; 7];
+    content.read(41, &mut reread).unwrap();
+    assert_eq!(reread, expected[41..48]);
+
+    assert_eq!(&attributes()[60..64], &0o100444u32.to_ne_bytes());
+}
@@ -0,0 +1,1652 @@
+//! Running `rng` without a subcommand: generating random data with the selected algorithm and
+//! writing it to the outputs.
+
+#[cfg(windows)]
+use crate::overlapped;
+#[cfg(target_os = "linux")]
+use crate::splice;
+use crate::{
+    checkpoint, checksum, combine, conditioning, confirm_overwrite, exit, formatting, health,
+    multithreaded, new_pcg_stream, outputs, parsing, platform, progress, rngs, seed, seek,
+    self_check, singlethreaded, sources, verify, Algorithm, ExitCode, Opt, MAX_BUFFER_SIZE,
+    MIN_BUFFER_SIZE,
+};
+use rand::RngCore;
+use rngs::Drbg;
+use seed::Seed;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Seek as _, Write};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+/// All outputs, written through one writer.
+type Outputs<'a> = outputs::Blocks<outputs::Tee<outputs::Retry<Output<'a>>>>;
+
+/// Generates the random data and writes it to the outputs, then exits if anything failed.
+pub fn run(mut opt: Opt) {
+    if opt.secure_memory {
+        if let Err(e) = platform::lock_memory() {
+            eprintln!("Failed to lock memory for --secure-memory: {}", e);
+            ExitCode::Failure.exit();
+        }
+    }
+    let plan = Plan::new(&mut opt);
+    let source = plan.new_source(&opt);
+    if opt.io_backend == outputs::IoBackend::Pwrite && (source.is_some() || plan.max_threads <= 1) {
+        eprintln!("The pwrite I/O backend requires multithreaded generation");
+        ExitCode::Usage.exit();
+    }
+    let device_limit = device_limit(&opt, plan.resumed_written);
+    let byte_limit = match (opt.bytes, device_limit) {
+        (Some(bytes), Some(device_limit)) => Some(bytes.min(device_limit)),
+        (bytes, device_limit) => bytes.or(device_limit),
+    };
+
+    // Prepare the writer (stdout/file) to write all data to
+    let should_abort = platform::abort_handle();
+    #[cfg(unix)]
+    if let Err(e) = platform::enable_pause_signal() {
+        eprintln!("WARNING: Failed to handle SIGUSR2 for pausing: {}", e);
+    }
+    let stdout = io::stdout();
+    // The temporary files of --atomic, and where they are renamed to once complete
+    let mut atomic_files = Vec::new();
+    let mut pipe_command = None;
+    let mut output = open_outputs(
+        &opt,
+        &plan,
+        &stdout,
+        &should_abort,
+        &mut pipe_command,
+        &mut atomic_files,
+    );
+    let mut written = write(&opt, &plan, source, &mut output, byte_limit, &should_abort);
+    // Closes the standard input of the --pipe-to command, if any, so it sees the end of the data
+    drop(output);
+
+    if let Some(e) = written.output_full.take() {
+        if !opt.fill {
+            eprintln!("Failed to write the output: {}", e);
+            ExitCode::OutOfSpace.exit();
+        }
+        written.bytes = fill(&opt, plan.resumed_written, written.bytes);
+    }
+    if let Some(e) = &written.output_error {
+        eprintln!("Failed to write the output: {}", e);
+    }
+    if device_limit.is_some() && device_limit == Some(written.bytes) && opt.bytes != device_limit {
+        eprintln!("Reached the end of the block device");
+    }
+    finish_atomic(&opt, &atomic_files, &written, &should_abort);
+    if written.output_error.is_some() {
+        ExitCode::Output.exit();
+    }
+    if opt.verify && !verify(&opt, &plan, written.bytes) {
+        ExitCode::SelfTest.exit();
+    }
+
+    // Print statistics about how much was written and in what time
+    if opt.verbose || pipe_command.is_some() {
+        print_statistics(&opt, &written);
+    }
+    if let Some(checksum) = written.checksum {
+        let digest = checksum.finish();
+        eprintln!("{}  -", digest);
+        if let Some(expected) = &opt.expect_checksum {
+            if digest != *expected {
+                eprintln!("The checksum does not match the expected {}", expected);
+                ExitCode::SelfTest.exit();
+            }
+        }
+    }
+    if let Some(mut child) = pipe_command {
+        let command = opt.pipe_to.as_deref().unwrap_or_default();
+        match child.wait() {
+            Ok(status) => {
+                eprintln!("The command exited with {}", status);
+                if !status.success() {
+                    std::process::exit(status.code().unwrap_or(1));
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to wait for {}: {}", command, e);
+                ExitCode::Failure.exit();
+            }
+        }
+    }
+    if written.self_check_failed {
+        ExitCode::SelfTest.exit();
+    }
+    if should_abort() {
+        ExitCode::Interrupted.exit();
+    }
+}
+
+/// The generator to run and how, resolved from the options.
+struct Plan {
+    algorithm: Algorithm,
+    /// The algorithms of --combine
+    combination: Option<Vec<Algorithm>>,
+    entropy_source: bool,
+    /// Conditioning stages work on a boxed generator in single threaded mode
+    conditioned: bool,
+    min_entropy: f64,
+    seed: Option<Seed>,
+    /// The position in the deterministic output stream to start writing from
+    skip: Option<u128>,
+    /// Bytes written to the output by the runs before a resumed run
+    resumed_written: u64,
+    plugin: Option<rngs::Plugin>,
+    personalization: Option<Vec<u8>>,
+    os_source: rngs::OsSource,
+    max_threads: usize,
+    buffer_size: usize,
+    block_size: Option<usize>,
+}
+
+impl Plan {
+    /// Validates the options and resolves them into a plan. Exits on invalid combinations.
+    /// Sets `opt.bytes` from --bs and --count.
+    fn new(opt: &mut Opt) -> Self {
+        let algorithm = opt.algorithm.unwrap_or(Algorithm::Default);
+        let mut seed = opt.seed.map(Seed::Number).or(opt.seed_hex);
+        if opt.expect_checksum.is_some() && seed.is_none() && opt.seed_passphrase.is_none() {
+            eprintln!("--expect-checksum requires a seed, or the output can't be known in advance");
+            ExitCode::Usage.exit();
+        }
+
+        let combination = opt.combine.map(|_| opt.algorithms.clone());
+        if let Some(algorithms) = &combination {
+            if opt.algorithm.is_some() {
+                eprintln!("<algorithm> can't be given together with --combine. Use --algorithms");
+                ExitCode::Usage.exit();
+            }
+            if algorithms.len() < 2 {
+                eprintln!("--combine needs at least two algorithms to combine");
+                ExitCode::Usage.exit();
+            }
+            if opt.skip.is_some() || opt.reseed_every.is_some() {
+                eprintln!("--skip and --reseed-every are not supported with --combine");
+                ExitCode::Usage.exit();
+            }
+        }
+        let algorithms = combination.clone().unwrap_or_else(|| vec![algorithm]);
+        let entropy_source =
+            opt.source_cmd.is_some() || algorithms.iter().any(|a| a.is_entropy_source());
+        let conditioned = opt.debias.is_some() || opt.whiten.is_some();
+
+        let (buffer_size, block_size) = check_output_options(opt);
+        if opt.health_tests.is_some() && !entropy_source {
+            eprintln!("--health-tests requires an entropy source");
+            ExitCode::Usage.exit();
+        }
+        let min_entropy = opt.min_entropy.unwrap_or(8.0);
+        if !(min_entropy > 0.0 && min_entropy <= 8.0) {
+            eprintln!("--min-entropy must be above 0 and at most 8 bits per byte");
+            ExitCode::Usage.exit();
+        }
+
+        if opt.seed_passphrase.is_some() && !entropy_source {
+            seed = Some(passphrase_seed(opt, algorithm));
+        }
+
+        if algorithms.iter().any(|a| a.is_statistically_poor()) {
+            eprintln!(
+                "WARNING: The selected algorithm is statistically poor and only intended for \
+                education. Do not use its output where the quality of the randomness matters."
+            );
+        }
+
+        let mut skip = start_position(opt, algorithm, seed.is_some());
+        let mut resumed_written = 0;
+        if opt.resume {
+            let checkpoint = read_checkpoint(opt, algorithm, &mut seed);
+            skip = Some(checkpoint.position);
+            resumed_written = checkpoint.written;
+        }
+
+        let plugin = load_plugin(opt, algorithm, &combination);
+
+        let personalization = opt.personalization.as_ref().map(|p| p.as_bytes().to_vec());
+        if personalization.is_some()
+            && (combination.is_some() || !algorithm.supports_personalization())
+        {
+            eprintln!("--personalization is only supported by DRBG algorithms such as 'ctr-drbg'");
+            ExitCode::Usage.exit();
+        }
+
+        let os_source = opt.os_source.unwrap_or(rngs::OsSource::Default);
+        if opt.os_source.is_some() && !algorithms.contains(&Algorithm::Os) {
+            eprintln!("WARNING: --os-source is ignored with algorithms other than 'os'");
+        }
+        let reads_device = algorithms.iter().any(|&a| match a {
+            Algorithm::Hwrng | Algorithm::Tpm => true,
+            Algorithm::Os => os_source == rngs::OsSource::Device,
+            _ => false,
+        });
+        if opt.device.is_some() && !reads_device {
+            eprintln!("WARNING: --device is ignored with algorithms not reading from a device");
+        }
+        if entropy_source && opt.reseed_every.is_some() {
+            eprintln!("WARNING: --reseed-every is ignored with entropy sources");
+        }
+        if entropy_source && (seed.is_some() || opt.seed_passphrase.is_some()) {
+            eprintln!("WARNING: seed is ignored when used with an entropy source");
+            seed = None;
+        }
+        if let (Some(len), Some(max_len)) = (seed.and_then(|s| s.len()), algorithm.seed_len()) {
+            if len > max_len {
+                eprintln!(
+                    "The seed is {} bytes, but the selected algorithm takes at most {} bytes",
+                    len, max_len
+                );
+                ExitCode::Usage.exit();
+            }
+        }
+        if let Some(print_seed) = &opt.print_seed {
+            match algorithm.seed_len() {
+                Some(len) if !entropy_source => {
+                    let seed = *seed.get_or_insert_with(|| Seed::from_entropy(len));
+                    print_seed_to(print_seed.as_deref(), seed);
+                }
+                _ => eprintln!("WARNING: --print-seed is ignored with entropy sources"),
+            }
+        }
+        check_seeded_options(opt, algorithm, seed.is_some(), entropy_source);
+
+        // Seeded output can only be generated in parallel by the algorithms that can seek to the
+        // position of every block. See `multithreaded::run_seeded`
+        let sequential_seed = seed.is_some() && !(algorithm.is_seekable() && combination.is_none());
+        let max_threads = max_threads(opt, sequential_seed, entropy_source, conditioned);
+
+        Plan {
+            algorithm,
+            combination,
+            entropy_source,
+            conditioned,
+            min_entropy,
+            seed,
+            skip,
+            resumed_written,
+            plugin,
+            personalization,
+            os_source,
+            max_threads,
+            buffer_size,
+            block_size,
+        }
+    }
+
+    /// The boxed generator for entropy sources, single threaded combinations and conditioned
+    /// output, or `None` when the algorithm runs directly. Called again to build the same
+    /// generator for --verify.
+    fn new_source(&self, opt: &Opt) -> Option<Box<dyn RngCore>> {
+        let mut source: Option<Box<dyn RngCore>> = match &self.combination {
+            Some(algorithms) if self.entropy_source => Some(Box::new(combine::XorRng::new(
+                algorithms
+                    .iter()
+                    .map(|&a| {
+                        if a.is_entropy_source() {
+                            self.open_source(opt, a)
+                        } else {
+                            combine::new_userspace(a, None)
+                        }
+                    })
+                    .collect(),
+            ))),
+            Some(algorithms) if self.max_threads <= 1 => Some(Box::new(
+                combine::XorRng::from_userspace(algorithms, self.seed),
+            )),
+            None if opt.source_cmd.is_some() => {
+                let command = opt.source_cmd.as_deref().unwrap_or_default();
+                match rngs::CommandRng::spawn(command) {
+                    Ok(rng) => Some(self.health_tested(opt, Box::new(rng))),
+                    Err(e) => {
+                        eprintln!("Failed to run the source command: {}", e);
+                        ExitCode::Failure.exit();
+                    }
+                }
+            }
+            None if self.entropy_source => Some(self.open_source(opt, self.algorithm)),
+            None if self.personalization.is_some() && self.max_threads <= 1 => {
+                let personalization = self.personalization.as_deref().unwrap_or_default();
+                Some(match self.algorithm {
+                    Algorithm::HmacDrbg => Box::new(rngs::HmacDrbg::with_personalization(
+                        self.seed,
+                        personalization,
+                    )),
+                    _ => Box::new(rngs::CtrDrbg::with_personalization(
+                        self.seed,
+                        personalization,
+                    )),
+                })
+            }
+            None if self.plugin.is_some() && self.max_threads <= 1 => {
+                let plugin = self.plugin.as_ref().expect("Plugin is loaded");
+                Some(Box::new(new_plugin_rng(plugin, self.seed)))
+            }
+            None if self.conditioned => Some(combine::new_userspace(self.algorithm, self.seed)),
+            _ => None,
+        };
+        if let Some(conditioning::Debiasing::VonNeumann) = opt.debias {
+            source = source.map(|s| Box::new(conditioning::VonNeumann::new(s)) as Box<_>);
+        }
+        if let Some(hash) = opt.whiten {
+            source = source.map(|s| Box::new(conditioning::Whitener::new(s, hash)) as Box<_>);
+        }
+        source
+    }
+
+    fn open_source(&self, opt: &Opt, algorithm: Algorithm) -> Box<dyn RngCore> {
+        let source = sources::open(algorithm, opt.device.as_deref(), self.os_source);
+        self.health_tested(
+            opt,
+            source.unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                ExitCode::Failure.exit();
+            }),
+        )
+    }
+
+    /// Health tests run on the raw output, before any conditioning.
+    fn health_tested(&self, opt: &Opt, source: Box<dyn RngCore>) -> Box<dyn RngCore> {
+        match opt.health_tests {
+            Some(action) => Box::new(health::HealthTested::new(source, action, self.min_entropy)),
+            None => source,
+        }
+    }
+}
+
+/// Validates the options of the outputs and I/O backends. Returns the buffer size and the
+/// --bs block size.
+fn check_output_options(opt: &mut Opt) -> (usize, Option<usize>) {
+    let udp_output = opt
+        .output
+        .iter()
+        .any(|target| matches!(target, outputs::OutputTarget::Udp(_)));
+    if (opt.packet_size.is_some() || opt.rate.is_some()) && !udp_output {
+        eprintln!("--packet-size and --rate require a UDP --output");
+        ExitCode::Usage.exit();
+    }
+    if opt
+        .packet_size
+        .is_some_and(|size| size == 0 || size > outputs::MAX_PACKET_SIZE as u64)
+    {
+        eprintln!(
+            "--packet-size must be between 1 and {} bytes",
+            outputs::MAX_PACKET_SIZE
+        );
+        ExitCode::Usage.exit();
+    }
+    if opt.rate == Some(0) {
+        eprintln!("--rate must be at least 1 byte per second");
+        ExitCode::Usage.exit();
+    }
+    let tcp_output = opt
+        .output
+        .iter()
+        .any(|target| matches!(target, outputs::OutputTarget::Tcp(_)));
+    if opt.reconnect.is_some() && !tcp_output {
+        eprintln!("--reconnect requires a TCP --output");
+        ExitCode::Usage.exit();
+    }
+    let fifo_output = opt
+        .output
+        .iter()
+        .any(|target| matches!(target, outputs::OutputTarget::Fifo(_)));
+    if opt.sandbox && fifo_output {
+        eprintln!("--sandbox can't be used with fifo outputs, they are reopened for every reader");
+        ExitCode::Usage.exit();
+    }
+    if opt.on_output_error.len() > 1 && opt.on_output_error.len() != opt.output.len() {
+        eprintln!("--on-output-error must be given once, or once per --output");
+        ExitCode::Usage.exit();
+    }
+    let file_output = opt
+        .output
+        .iter()
+        .any(|target| matches!(target, outputs::OutputTarget::File(_)));
+    if (opt.atomic || opt.preallocate) && !file_output {
+        eprintln!("--atomic and --preallocate require a file --output");
+        ExitCode::Usage.exit();
+    }
+    if (opt.fsync || opt.sync_every.is_some()) && !file_output {
+        eprintln!("--fsync and --sync-every require a file --output");
+        ExitCode::Usage.exit();
+    }
+    if opt.sync_every == Some(0) {
+        eprintln!("--sync-every must be more than zero");
+        ExitCode::Usage.exit();
+    }
+    if !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&opt.buffer_size)
+        || !opt.buffer_size.is_multiple_of(512)
+    {
+        eprintln!("--buffer-size must be a multiple of 512 bytes, from 4KiB to 256MiB");
+        ExitCode::Usage.exit();
+    }
+    let buffer_size = opt.buffer_size as usize;
+    if opt.huge_pages && !cfg!(target_os = "linux") {
+        eprintln!("--huge-pages is only supported on Linux");
+        ExitCode::Usage.exit();
+    }
+    if opt.queue_depth == Some(0) {
+        eprintln!("--queue-depth must be more than zero");
+        ExitCode::Usage.exit();
+    }
+    let block_size = match opt.bs.map(usize::try_from) {
+        None if opt.count.is_some() => Some(512),
+        None => None,
+        Some(Ok(bs)) if bs > 0 => Some(bs),
+        Some(_) => {
+            eprintln!("--bs must be more than zero and fit in memory");
+            ExitCode::Usage.exit();
+        }
+    };
+    if let Some(count) = opt.count {
+        let bytes = (block_size.unwrap_or_default() as u64).checked_mul(count);
+        opt.bytes = Some(bytes.unwrap_or_else(|| {
+            eprintln!("--bs multiplied by --count is too large");
+            ExitCode::Usage.exit();
+        }));
+    }
+    if opt.sparse && !file_output {
+        eprintln!("--sparse requires a file --output");
+        ExitCode::Usage.exit();
+    }
+    if !opt
+        .hole_ratio
+        .is_none_or(|ratio| (0.0..=1.0).contains(&ratio))
+    {
+        eprintln!("--hole-ratio must be between 0 and 1");
+        ExitCode::Usage.exit();
+    }
+    if opt.extent == Some(0) {
+        eprintln!("--extent must be more than zero");
+        ExitCode::Usage.exit();
+    }
+    check_io_backend(opt);
+    if opt.fill && (opt.output.len() != 1 || !file_output) {
+        eprintln!("--fill requires a single file --output");
+        ExitCode::Usage.exit();
+    }
+    (buffer_size, block_size)
+}
+
+/// Validates that the selected I/O backend is supported here and by the outputs.
+fn check_io_backend(opt: &Opt) {
+    if opt.io_backend == outputs::IoBackend::Splice {
+        if !cfg!(target_os = "linux") {
+            eprintln!("The splice I/O backend is only supported on Linux");
+            ExitCode::Usage.exit();
+        }
+        if opt.pipe_to.is_some()
+            || opt
+                .output
+                .iter()
+                .any(|target| *target != outputs::OutputTarget::Stdout)
+        {
+            eprintln!("The splice I/O backend only supports stdout");
+            ExitCode::Usage.exit();
+        }
+    }
+    if opt.io_backend == outputs::IoBackend::Overlapped {
+        if !cfg!(windows) {
+            eprintln!("The overlapped I/O backend is only supported on Windows");
+            ExitCode::Usage.exit();
+        }
+        if opt
+            .output
+            .iter()
+            .any(|target| !matches!(target, outputs::OutputTarget::File(_)))
+        {
+            eprintln!("The overlapped I/O backend only supports file outputs");
+            ExitCode::Usage.exit();
+        }
+        if opt.sparse {
+            eprintln!("The overlapped I/O backend does not support --sparse");
+            ExitCode::Usage.exit();
+        }
+    }
+    if opt.io_backend == outputs::IoBackend::Mmap && !cfg!(unix) {
+        eprintln!("The mmap I/O backend is only supported on Unix");
+        ExitCode::Usage.exit();
+    }
+    if matches!(
+        opt.io_backend,
+        outputs::IoBackend::Mmap | outputs::IoBackend::Pwrite
+    ) {
+        let single_file = match &opt.output[..] {
+            [outputs::OutputTarget::File(path)] => !platform::is_block_device(path),
+            _ => false,
+        };
+        if !single_file || opt.pipe_to.is_some() || opt.bytes.is_none() || opt.sparse {
+            eprintln!(
+                "The mmap and pwrite I/O backends require --bytes and a single file --output"
+            );
+            ExitCode::Usage.exit();
+        }
+    }
+    if opt.io_backend == outputs::IoBackend::Pwrite && opt.checkpoint_file.is_some() {
+        eprintln!("The pwrite I/O backend can't be combined with --checkpoint-file");
+        ExitCode::Usage.exit();
+    }
+}
+
+/// Derives the seed from --seed-passphrase.
+fn passphrase_seed(opt: &Opt, algorithm: Algorithm) -> Seed {
+    let source = opt.seed_passphrase.as_ref().expect("A passphrase is given");
+    let passphrase = source.read().unwrap_or_else(|e| {
+        eprintln!("Failed to read the seed passphrase: {}", e);
+        ExitCode::Failure.exit();
+    });
+    let params = opt.argon2_params.unwrap_or_default();
+    if opt.verbose {
+        eprintln!(
+            "Deriving the seed from the passphrase with Argon2id ({})",
+            params
+        );
+    }
+    let len = algorithm
+        .seed_len()
+        .expect("Only entropy sources lack a seed");
+    let derived = Seed::from_passphrase(passphrase.as_bytes(), params, len);
+    derived.unwrap_or_else(|e| {
+        eprintln!("Failed to derive the seed from the passphrase: {}", e);
+        ExitCode::Failure.exit();
+    })
+}
+
+/// The position given by --skip and --jumps.
+fn start_position(opt: &Opt, algorithm: Algorithm, seeded: bool) -> Option<u128> {
+    if let Some(skip) = opt.skip {
+        if !seeded {
+            eprintln!("--skip requires a seed. Unseeded output can't be reproduced anyway");
+            ExitCode::Usage.exit();
+        }
+        if !algorithm.is_seekable() {
+            eprintln!("The selected algorithm does not support --skip");
+            ExitCode::Usage.exit();
+        }
+        if opt.verbose {
+            eprintln!("Skipping {} bytes", skip);
+        }
+    }
+    if let Some(jumps) = opt.jumps {
+        if !seeded {
+            eprintln!("--jumps requires a seed. Unseeded output can't be reproduced anyway");
+            ExitCode::Usage.exit();
+        }
+        if !algorithm.is_seekable() {
+            eprintln!("The selected algorithm does not support --jumps");
+            ExitCode::Usage.exit();
+        }
+        if opt.verbose {
+            eprintln!("Starting at jump point {}", jumps);
+        }
+    }
+    match (opt.jumps, opt.skip) {
+        (None, None) => None,
+        (jumps, skip) => Some(
+            u128::from(jumps.unwrap_or(0)) * u128::from(seek::JUMP_BYTES)
+                + u128::from(skip.unwrap_or(0)),
+        ),
+    }
+}
+
+/// Reads the checkpoint to --resume from, and takes the seed from it if none is given.
+fn read_checkpoint(
+    opt: &Opt,
+    algorithm: Algorithm,
+    seed: &mut Option<Seed>,
+) -> checkpoint::Checkpoint {
+    let path = opt
+        .checkpoint_file
+        .as_deref()
+        .expect("--resume requires a checkpoint");
+    let checkpoint = checkpoint::Checkpoint::read(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read the checkpoint {}: {}", path.display(), e);
+        ExitCode::Failure.exit();
+    });
+    if checkpoint.algorithm != algorithm.to_string() {
+        eprintln!(
+            "The checkpoint is for the {} algorithm",
+            checkpoint.algorithm
+        );
+        ExitCode::Usage.exit();
+    }
+    if *seed.get_or_insert(checkpoint.seed) != checkpoint.seed {
+        eprintln!("The seed does not match the seed in the checkpoint");
+        ExitCode::Usage.exit();
+    }
+    if opt.stream_id != checkpoint.stream_id {
+        eprintln!("--stream-id does not match the stream id in the checkpoint");
+        ExitCode::Usage.exit();
+    }
+    if opt.verbose {
+        eprintln!("Resuming at position {}", checkpoint.position);
+    }
+    checkpoint
+}
+
+/// Loads the plugin if the algorithm is one.
+fn load_plugin(
+    opt: &Opt,
+    algorithm: Algorithm,
+    combination: &Option<Vec<Algorithm>>,
+) -> Option<rngs::Plugin> {
+    match algorithm {
+        _ if combination.is_some() => {
+            let algorithms = combination.iter().flatten();
+            if algorithms
+                .into_iter()
+                .any(|a| matches!(a, Algorithm::Plugin(_)))
+            {
+                eprintln!("Plugins can't be used with --combine");
+                ExitCode::Usage.exit();
+            }
+            None
+        }
+        Algorithm::Plugin(path) => {
+            if opt.reseed_every.is_some() {
+                eprintln!("--reseed-every is not supported with plugins");
+                ExitCode::Usage.exit();
+            }
+            Some(rngs::Plugin::load(path).unwrap_or_else(|e| {
+                eprintln!("Failed to load plugin: {}", e);
+                ExitCode::Failure.exit();
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn new_plugin_rng(plugin: &rngs::Plugin, seed: Option<Seed>) -> impl RngCore {
+    plugin.new_rng(seed).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        ExitCode::Failure.exit();
+    })
+}
+
+/// Prints the seed for --print-seed, to stderr or to the given file.
+fn print_seed_to(path: Option<&Path>, seed: Seed) {
+    match path {
+        None => eprintln!("Seed: {}", seed),
+        Some(path) => {
+            if let Err(e) = fs::write(path, format!("{}\n", seed)) {
+                eprintln!("Failed to write the seed to {}: {}", path.display(), e);
+                ExitCode::Failure.exit();
+            }
+        }
+    }
+}
+
+/// Validates the options that reproduce seeded output.
+fn check_seeded_options(opt: &Opt, algorithm: Algorithm, seeded: bool, entropy_source: bool) {
+    if opt.stream_id.is_some() {
+        if algorithm != Algorithm::Pcg {
+            eprintln!("--stream-id is only supported by the pcg algorithm");
+            ExitCode::Usage.exit();
+        }
+        if !seeded {
+            eprintln!("--stream-id requires a seed");
+            ExitCode::Usage.exit();
+        }
+    }
+    if opt.checkpoint_file.is_some() {
+        if !algorithm.is_seekable() {
+            eprintln!("The selected algorithm does not support --checkpoint-file");
+            ExitCode::Usage.exit();
+        }
+        if !seeded {
+            eprintln!("--checkpoint-file requires a seed. Use --print-seed to generate one");
+            ExitCode::Usage.exit();
+        }
+    }
+    if opt.verify && (!seeded || entropy_source) {
+        eprintln!("--verify requires a seed");
+        ExitCode::Usage.exit();
+    }
+}
+
+/// The number of threads to generate with. Warns if --max-threads is ignored.
+fn max_threads(opt: &Opt, sequential_seed: bool, entropy_source: bool, conditioned: bool) -> usize {
+    // WASI has no threads
+    let no_threads = cfg!(target_family = "wasm");
+    if !(sequential_seed || entropy_source || conditioned || no_threads) {
+        return opt.max_threads.unwrap_or_else(num_cpus::get);
+    }
+    if opt.max_threads.is_some() && sequential_seed {
+        eprintln!(
+            "WARNING: --max-threads is ignored when a seed is specified. \
+            Manually seeded randomness generation must be single threaded, \
+            except with the chacha and pcg algorithms."
+        );
+    }
+    if opt.max_threads.is_some() && entropy_source {
+        eprintln!("WARNING: --max-threads is ignored with entropy sources such as 'os'");
+    }
+    if opt.max_threads.is_some() && conditioned {
+        eprintln!("WARNING: --max-threads is ignored when conditioning the output");
+    }
+    if opt.max_threads.is_some() && no_threads {
+        eprintln!("WARNING: --max-threads is ignored on platforms without threads");
+    }
+    1
+}
+
+/// The bytes left on the block device outputs, if any. Writing to a block device destroys its
+/// contents, and must stop at the end of it. Asks for confirmation first.
+fn device_limit(opt: &Opt, resumed_written: u64) -> Option<u64> {
+    let mut device_limit: Option<u64> = None;
+    for target in &opt.output {
+        let path = match target {
+            outputs::OutputTarget::File(path) if platform::is_block_device(path) => path,
+            _ => continue,
+        };
+        if opt.atomic || opt.preallocate || opt.mode.is_some() || opt.secret || opt.sparse {
+            eprintln!(
+                "--atomic, --preallocate, --mode, --secret and --sparse can't be used with the \
+                 block device {}",
+                path.display()
+            );
+            ExitCode::Usage.exit();
+        }
+        let size = block_device_size(path).unwrap_or_else(|e| {
+            eprintln!("Failed to get the size of {}: {}", path.display(), e);
+            ExitCode::Failure.exit();
+        });
+        eprintln!(
+            "{} is a block device of {} ({} bytes)",
+            path.display(),
+            formatting::format_bytes_written(size),
+            size
+        );
+        if !opt.yes_i_know && !confirm_overwrite(path) {
+            eprintln!(
+                "Not writing to {}. Pass --yes-i-know to skip the confirmation",
+                path.display()
+            );
+            ExitCode::Usage.exit();
+        }
+        let left = size.saturating_sub(resumed_written);
+        device_limit = Some(device_limit.map_or(left, |limit| limit.min(left)));
+    }
+    device_limit
+}
+
+/// The size of the block device at `path`, in bytes.
+fn block_device_size(path: &Path) -> io::Result<u64> {
+    fs::File::open(path)?.seek(io::SeekFrom::End(0))
+}
+
+/// The temporary file that `path` is written to with --atomic. In the same directory, so it
+/// can be renamed into place, and hidden on Unix.
+fn atomic_temp_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// Opens all outputs, or stdout if none is given, and the --pipe-to command.
+fn open_outputs<'a>(
+    opt: &Opt,
+    plan: &Plan,
+    stdout: &'a io::Stdout,
+    should_abort: &(impl Fn() -> bool + Clone + 'static),
+    pipe_command: &mut Option<Child>,
+    atomic_files: &mut Vec<(PathBuf, PathBuf)>,
+) -> Outputs<'a> {
+    let retrying = |output, name: &str| {
+        let delay = opt.retry_delay.unwrap_or(Duration::from_secs(1));
+        let retries = opt.retry.unwrap_or(0);
+        let should_abort = Box::new(should_abort.clone());
+        outputs::Retry::new(output, name.to_owned(), retries, delay, should_abort)
+    };
+    let mut open = |target: &outputs::OutputTarget| {
+        let output = open_output(opt, target, plan.resumed_written, stdout, should_abort);
+        let (output, atomic_file) = output;
+        atomic_files.extend(atomic_file);
+        output
+    };
+    let mut tee = outputs::Tee::default();
+    if let Some(command) = &opt.pipe_to {
+        let mut child = rngs::shell(command)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to run {}: {}", command, e);
+                ExitCode::Output.exit();
+            });
+        let stdin = child.stdin.take().expect("stdin is piped");
+        *pipe_command = Some(child);
+        tee.push(
+            retrying(Output::Pipe(stdin), command),
+            command.clone(),
+            outputs::OutputErrorPolicy::Abort,
+        );
+    } else if opt.output.is_empty() {
+        tee.push(
+            retrying(open(&outputs::OutputTarget::Stdout), "stdout"),
+            "stdout".to_owned(),
+            outputs::OutputErrorPolicy::Abort,
+        );
+    }
+    for (i, target) in opt.output.iter().enumerate() {
+        let policy = match opt.on_output_error.len() {
+            0 => outputs::OutputErrorPolicy::Abort,
+            1 => opt.on_output_error[0],
+            _ => opt.on_output_error[i],
+        };
+        let name = target.to_string();
+        tee.push(retrying(open(target), &name), name, policy);
+    }
+    outputs::Blocks::new(tee, plan.block_size)
+}
+
+/// Opens one output. With --atomic, also returns the temporary file written to and the path
+/// it's renamed to once complete.
+fn open_output<'a>(
+    opt: &Opt,
+    target: &outputs::OutputTarget,
+    resumed_written: u64,
+    stdout: &'a io::Stdout,
+    should_abort: &(impl Fn() -> bool + Clone + 'static),
+) -> (Output<'a>, Option<(PathBuf, PathBuf)>) {
+    let mode = match opt.secret {
+        true => Some(0o600),
+        false => opt.mode,
+    };
+    // Mapping a file for writing requires it to be open for reading too
+    let mmap = opt.io_backend == outputs::IoBackend::Mmap;
+    let output = match target {
+        #[cfg(target_os = "linux")]
+        outputs::OutputTarget::Stdout if opt.io_backend == outputs::IoBackend::Splice => {
+            match splice::SpliceOutput::new(libc::STDOUT_FILENO) {
+                Ok(output) => Output::Splice(output),
+                Err(e) => {
+                    eprintln!("Can't splice to stdout: {}", e);
+                    ExitCode::Output.exit();
+                }
+            }
+        }
+        outputs::OutputTarget::Stdout => Output::Stdout(stdout.lock()),
+        outputs::OutputTarget::Null => Output::Null,
+        outputs::OutputTarget::File(path) if platform::is_block_device(path) => {
+            // Devices can't be created or truncated, only written from where a resumed run left off
+            let file = fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|mut file| {
+                    file.seek(io::SeekFrom::Start(resumed_written))?;
+                    Ok(file)
+                })
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to open {}: {}", path.display(), e);
+                    ExitCode::Output.exit();
+                });
+            plain_output(opt, file, path)
+        }
+        outputs::OutputTarget::File(path) if opt.resume => {
+            // Drop what was written after the checkpoint, it's generated again
+            let mut options = fs::OpenOptions::new();
+            options.write(true).read(mmap);
+            let file = platform::open_with_mode(options, path, mode)
+                .and_then(|mut file| {
+                    file.set_len(resumed_written)?;
+                    file.seek(io::SeekFrom::End(0))?;
+                    Ok(file)
+                })
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to open {}: {}", path.display(), e);
+                    ExitCode::Output.exit();
+                });
+            file_output(opt, resumed_written, file, path)
+        }
+        outputs::OutputTarget::File(path) if opt.atomic => {
+            let temp_path = atomic_temp_path(path);
+            let mut options = fs::OpenOptions::new();
+            options.write(true).read(mmap).create_new(true);
+            let file = platform::open_with_mode(options, &temp_path, mode).unwrap_or_else(|e| {
+                eprintln!("Failed to create {}: {}", temp_path.display(), e);
+                ExitCode::Output.exit();
+            });
+            let output = file_output(opt, resumed_written, file, &temp_path);
+            return (output, Some((temp_path, path.clone())));
+        }
+        outputs::OutputTarget::File(path) => {
+            let mut options = fs::OpenOptions::new();
+            options.write(true).read(mmap).create(true).truncate(true);
+            let file = platform::open_with_mode(options, path, mode).unwrap_or_else(|e| {
+                eprintln!("Failed to open {}: {}", path.display(), e);
+                ExitCode::Output.exit();
+            });
+            file_output(opt, resumed_written, file, path)
+        }
+        outputs::OutputTarget::Tcp(address) => {
+            let should_abort = Box::new(should_abort.clone());
+            let output = outputs::TcpOutput::connect(address.clone(), opt.reconnect, should_abort)
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to connect to {}: {}", address, e);
+                    ExitCode::Output.exit();
+                });
+            Output::Tcp(output)
+        }
+        outputs::OutputTarget::Udp(address) => {
+            let packet_size = opt.packet_size.unwrap_or(1200) as usize;
+            let output = outputs::UdpOutput::connect(address, packet_size, opt.rate)
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to connect to {}: {}", address, e);
+                    ExitCode::Output.exit();
+                });
+            Output::Udp(output)
+        }
+        outputs::OutputTarget::Unix(path) => {
+            let output = platform::connect_unix_socket(path).unwrap_or_else(|e| {
+                eprintln!("Failed to connect to {}: {}", path.display(), e);
+                ExitCode::Output.exit();
+            });
+            Output::Unix(output)
+        }
+        outputs::OutputTarget::Fifo(path) => {
+            let should_abort = Box::new(should_abort.clone());
+            let output =
+                outputs::FifoOutput::open(path.clone(), should_abort).unwrap_or_else(|e| {
+                    eprintln!("Failed to open {}: {}", path.display(), e);
+                    ExitCode::Output.exit();
+                });
+            Output::Fifo(output)
+        }
+        outputs::OutputTarget::NamedPipe(path) => {
+            let pipe = platform::open_named_pipe(path).unwrap_or_else(|e| {
+                eprintln!("Failed to open {}: {}", path.display(), e);
+                ExitCode::Output.exit();
+            });
+            plain_output(opt, pipe, path)
+        }
+    };
+    (output, None)
+}
+
+/// A regular file output, written with the chosen I/O backend, preallocated or sparse.
+fn file_output<'a>(opt: &Opt, resumed_written: u64, file: fs::File, path: &Path) -> Output<'a> {
+    if let (true, Some(bytes)) = (opt.preallocate, opt.bytes) {
+        if let Err(e) = platform::preallocate(&file, resumed_written + bytes) {
+            eprintln!("Failed to preallocate {}: {}", path.display(), e);
+            ExitCode::for_write_error(&e).exit();
+        }
+    }
+    if opt.io_backend == outputs::IoBackend::Pwrite {
+        let bytes = opt.bytes.expect("The pwrite backend requires --bytes");
+        match outputs::PositionedOutput::new(file, bytes) {
+            Ok(output) => return Output::Positioned(output),
+            Err(e) => {
+                eprintln!("Failed to prepare {}: {}", path.display(), e);
+                ExitCode::Output.exit();
+            }
+        }
+    }
+    #[cfg(unix)]
+    if opt.io_backend == outputs::IoBackend::Mmap {
+        let bytes = opt.bytes.expect("The mmap backend requires --bytes");
+        match outputs::MappedOutput::new(file, bytes) {
+            Ok(output) => return Output::Mapped(output),
+            Err(e) => {
+                eprintln!("Failed to map {}: {}", path.display(), e);
+                ExitCode::Output.exit();
+            }
+        }
+    }
+    if !opt.sparse {
+        return plain_output(opt, file, path);
+    }
+    let extent = opt.extent.unwrap_or(4 * 1024 * 1024);
+    let hole_ratio = opt.hole_ratio.unwrap_or(0.5);
+    match outputs::SparseOutput::new(file, extent, hole_ratio) {
+        Ok(output) => Output::Sparse(output),
+        Err(e) => {
+            eprintln!("Failed to make {} sparse: {}", path.display(), e);
+            ExitCode::Output.exit();
+        }
+    }
+}
+
+/// Plain files, devices and named pipes, written with the chosen I/O backend.
+fn plain_output<'a>(opt: &Opt, file: fs::File, path: &Path) -> Output<'a> {
+    #[cfg(windows)]
+    if opt.io_backend == outputs::IoBackend::Overlapped {
+        match overlapped::OverlappedOutput::new(file) {
+            Ok(output) => return Output::Overlapped(output),
+            Err(e) => {
+                eprintln!("Can't use overlapped I/O for {}: {}", path.display(), e);
+                ExitCode::Output.exit();
+            }
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = (opt, path);
+    Output::File(file)
+}
+
+/// What writing the output ended with.
+struct Written {
+    bytes: u64,
+    /// Set when an output ran out of space. Expected with --fill
+    output_full: Option<io::Error>,
+    /// Set when writing stopped because an output failed in any other way
+    output_error: Option<io::Error>,
+    self_check_failed: bool,
+    checksum: Option<checksum::Checksum>,
+    /// The time it took to generate the output, and to also sync it with --fsync
+    generated: Duration,
+    synced: Duration,
+}
+
+/// Generates the output and writes it until the limit, an error or an interruption.
+fn write(
+    opt: &Opt,
+    plan: &Plan,
+    source: Option<Box<dyn RngCore>>,
+    output: &mut Outputs,
+    byte_limit: Option<u64>,
+    should_abort: &impl Fn() -> bool,
+) -> Written {
+    let mut last_checkpoint = Instant::now();
+
+    let mut self_check = opt.self_check.map(self_check::SelfCheck::new);
+    let mut self_check_failed = false;
+    let mut checked = |buf: &[u8]| match self_check.as_mut().map(|check| check.update(buf)) {
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            self_check_failed = true;
+            false
+        }
+        _ => true,
+    };
+
+    let mut bytes_written: u64 = 0;
+    let mut meter = opt.meter.then(progress::Meter::new);
+    let mut stats_log = opt.stats_interval.map(|interval| {
+        let out: Box<dyn Write> = match &opt.stats_file {
+            Some(path) => {
+                let file = fs::OpenOptions::new().create(true).append(true).open(path);
+                Box::new(file.unwrap_or_else(|e| {
+                    eprintln!("Failed to open {}: {}", path.display(), e);
+                    ExitCode::Failure.exit();
+                }))
+            }
+            None => Box::new(io::stderr()),
+        };
+        progress::StatsLog::new(interval, out)
+    });
+    let stats_failed = |e: io::Error| eprintln!("WARNING: Failed to write the statistics: {}", e);
+    let mut json_progress = opt.progress_fd.map(|fd| {
+        let file = platform::open_fd(fd).unwrap_or_else(|e| {
+            eprintln!("Failed to open --progress-fd {}: {}", fd, e);
+            ExitCode::Usage.exit();
+        });
+        progress::JsonProgress::new(Box::new(file), byte_limit)
+    });
+    let mut checksum = opt.checksum.map(checksum::Checksum::new);
+    let mut output_error = None;
+    let mut synced_until = 0;
+    let config = thread_config(opt, plan, output);
+    // Writes a batch of buffers with one vectored write
+    let write_fn = |bufs: &[&[u8]]| {
+        if platform::is_paused() {
+            eprintln!("Paused, send SIGUSR2 again to resume");
+            platform::wait_while_paused(should_abort);
+            if !should_abort() {
+                eprintln!("Resumed");
+            }
+        }
+        let mut slices = Vec::with_capacity(bufs.len());
+        let mut len = 0;
+        for buf in bufs {
+            let buf = match byte_limit {
+                Some(limit) => {
+                    let left = usize::try_from(limit - bytes_written - len).unwrap_or(buf.len());
+                    &buf[..left.min(buf.len())]
+                }
+                None => &buf[..],
+            };
+            if !checked(buf) {
+                return true;
+            }
+            slices.push(io::IoSlice::new(buf));
+            len += buf.len() as u64;
+        }
+        if let Err(e) = outputs::write_all_vectored(&mut *output, &slices) {
+            output_error = Some(e);
+            return true;
+        }
+        if let Some(checksum) = &mut checksum {
+            slices.iter().for_each(|slice| checksum.update(slice));
+        }
+        bytes_written += len;
+        if let Some(meter) = &mut meter {
+            meter.update(bytes_written);
+        }
+        if let Some(Err(e)) = stats_log.as_mut().map(|log| log.update(bytes_written)) {
+            stats_failed(e);
+        }
+        if let Some(Err(e)) = json_progress.as_mut().map(|p| p.update(bytes_written)) {
+            stats_failed(e);
+        }
+        if opt.checkpoint_file.is_some()
+            && last_checkpoint.elapsed() >= checkpoint::CHECKPOINT_INTERVAL
+        {
+            // The checkpoint must not be ahead of the data that actually left the process
+            if output.flush().is_err() {
+                return true;
+            }
+            write_checkpoint(opt, plan, bytes_written);
+            last_checkpoint = Instant::now();
+        }
+        if let Some(sync_every) = opt.sync_every {
+            if bytes_written - synced_until >= sync_every {
+                if let Err(e) = output.get_mut().for_each(|o| o.get_mut().sync_data()) {
+                    eprintln!("Failed to sync the output: {}", e);
+                    return true;
+                }
+                synced_until = bytes_written;
+            }
+        }
+        should_abort() || byte_limit == Some(bytes_written)
+    };
+
+    if opt.sandbox {
+        if let Err(e) = platform::sandbox() {
+            eprintln!("Failed to set up --sandbox: {}", e);
+            ExitCode::Failure.exit();
+        }
+    }
+    let start = Instant::now();
+    run_generator(opt, plan, source, &config, write_fn);
+    let generated = start.elapsed();
+    if let Some(meter) = &meter {
+        meter.finish(bytes_written);
+    }
+    if let Some(Err(e)) = stats_log.as_mut().map(|log| log.finish(bytes_written)) {
+        stats_failed(e);
+    }
+    if let Some(Err(e)) = json_progress.as_mut().map(|p| p.finish(bytes_written)) {
+        stats_failed(e);
+    }
+    match output.flush() {
+        Ok(()) if opt.checkpoint_file.is_some() => {
+            write_checkpoint(opt, plan, bytes_written);
+        }
+        Ok(()) => (),
+        Err(e) => {
+            output_error.get_or_insert(e);
+        }
+    }
+    let output_full = output_error.take_if(|e| exit::is_out_of_space(e));
+    // A closed pipe means the reader has read enough, like with `rng | head`
+    let output_error = output_error.filter(|e| e.kind() != io::ErrorKind::BrokenPipe);
+    if opt.fsync && output_full.is_none() && output_error.is_none() {
+        if let Err(e) = output.get_mut().for_each(|o| o.get_mut().sync_all()) {
+            eprintln!("Failed to sync the output: {}", e);
+            ExitCode::for_write_error(&e).exit();
+        }
+    }
+    Written {
+        bytes: bytes_written,
+        output_full,
+        output_error,
+        self_check_failed,
+        checksum,
+        generated,
+        synced: start.elapsed(),
+    }
+}
+
+/// The threading of the generation. Pins the current thread, which does the writing, with
+/// --pin-threads.
+fn thread_config(opt: &Opt, plan: &Plan, output: &mut Outputs) -> multithreaded::Config {
+    // With the mmap backend, the worker threads generate straight into the mapped file
+    #[cfg(unix)]
+    let mapped_region = {
+        let mut region = None;
+        let _ = output.get_mut().for_each(|output| {
+            if let Output::Mapped(mapped) = output.get_mut() {
+                region = Some(mapped.unwritten());
+            }
+            Ok(())
+        });
+        region
+    };
+    #[cfg(not(unix))]
+    let mapped_region = None;
+    // With the pwrite backend, the worker threads write their blocks of the file themselves
+    let mut positioned_target = None;
+    let _ = output.get_mut().for_each(|output| {
+        if let Output::Positioned(positioned) = output.get_mut() {
+            positioned_target = Some(positioned.target());
+        }
+        Ok(())
+    });
+
+    let pin_cpus = match opt.pin_threads.clone() {
+        None => None,
+        Some(Some(parsing::CpuList(cpus))) => Some(cpus),
+        Some(None) => match platform::allowed_cpus() {
+            Ok(cpus) => Some(cpus),
+            Err(e) => {
+                eprintln!("Failed to get the CPUs to pin threads to: {}", e);
+                ExitCode::Failure.exit();
+            }
+        },
+    };
+    if let Some(cpus) = &pin_cpus {
+        if let Err(e) = platform::pin_thread(cpus[0]) {
+            eprintln!("Failed to pin the writing thread to CPU {}: {}", cpus[0], e);
+            ExitCode::Failure.exit();
+        }
+    }
+    multithreaded::Config {
+        threads: plan.max_threads,
+        buffer_size: plan.buffer_size,
+        queue_depth: opt.queue_depth,
+        pin_cpus,
+        adaptive: opt.adaptive,
+        huge_pages: opt.huge_pages,
+        mapping: mapped_region,
+        positioned: positioned_target,
+        verbose: opt.verbose,
+    }
+}
+
+/// Runs the planned generator, passing the output to `write_fn` until it returns true.
+fn run_generator<F: FnMut(&[&[u8]]) -> bool>(
+    opt: &Opt,
+    plan: &Plan,
+    source: Option<Box<dyn RngCore>>,
+    config: &multithreaded::Config,
+    mut write_fn: F,
+) {
+    let Plan {
+        algorithm,
+        seed,
+        skip,
+        buffer_size,
+        ..
+    } = *plan;
+    match (source, &plan.combination, plan.max_threads) {
+        (Some(source), _, _) => {
+            // Sources reading from a stream can end with a partial buffer. It is written like
+            // any other, so it is also limited, checked and hashed
+            let tail = singlethreaded::run_source(source, buffer_size, |buf| write_fn(&[buf]))
+                .unwrap_or_else(|e| source_failed(e));
+            if !tail.is_empty() {
+                write_fn(&[&tail]);
+            }
+        }
+        (None, Some(algorithms), _) => {
+            multithreaded::run_combined(algorithms.clone(), config, write_fn)
+        }
+        (None, None, _) if plan.personalization.is_some() => {
+            let personalization = plan.personalization.clone().unwrap_or_default();
+            multithreaded::run_personalized(algorithm, personalization, config, write_fn)
+        }
+        (None, None, _) if plan.plugin.is_some() => {
+            let plugin = plan.plugin.clone().expect("Plugin is loaded");
+            let new_rng = move || new_plugin_rng(&plugin, None);
+            multithreaded::run_with(new_rng, config, write_fn)
+        }
+        (None, None, max_threads) if opt.stream_id.is_some() => {
+            let seed = seed.expect("--stream-id requires a seed");
+            let stream_id = opt.stream_id.unwrap_or_default();
+            let new_rng = move || new_pcg_stream(seed, stream_id);
+            let skip = skip.unwrap_or(0);
+            if max_threads > 1 {
+                multithreaded::run_seeded_with(new_rng, skip, config, write_fn)
+            } else {
+                singlethreaded::run_seekable(new_rng(), skip, buffer_size, |buf| write_fn(&[buf]))
+            }
+        }
+        (None, None, max_threads) if max_threads > 1 && seed.is_some() => {
+            multithreaded::run_seeded(
+                algorithm,
+                seed.expect("Seed is given"),
+                skip.unwrap_or(0),
+                config,
+                write_fn,
+            )
+        }
+        (None, None, 0 | 1) => {
+            let reseed = opt.reseed_every;
+            singlethreaded::run(algorithm, seed, skip, reseed, buffer_size, |buf| {
+                write_fn(&[buf])
+            })
+        }
+        (None, None, _) => multithreaded::run(algorithm, config, opt.reseed_every, write_fn),
+    }
+}
+
+fn source_failed(e: rand::Error) -> ! {
+    eprintln!("Failed to read from the source: {}", e);
+    match e.inner().downcast_ref::<health::HealthTestFailure>() {
+        Some(_) => ExitCode::SelfTest.exit(),
+        None => ExitCode::Failure.exit(),
+    }
+}
+
+fn write_checkpoint(opt: &Opt, plan: &Plan, bytes_written: u64) {
+    let checkpoint = checkpoint::Checkpoint {
+        algorithm: plan.algorithm.to_string(),
+        seed: plan.seed.expect("Checkpoints require a seed"),
+        stream_id: opt.stream_id,
+        position: plan.skip.unwrap_or(0) + u128::from(bytes_written),
+        written: plan.resumed_written + bytes_written,
+    };
+    let path = opt
+        .checkpoint_file
+        .as_deref()
+        .expect("Checkpoints are enabled");
+    if let Err(e) = checkpoint.write(path) {
+        eprintln!("WARNING: Failed to write the checkpoint: {}", e);
+    }
+}
+
+/// Syncs the file that --fill filled. Returns the bytes written to it, counting the last
+/// buffer that only partially fit.
+fn fill(opt: &Opt, resumed_written: u64, mut bytes_written: u64) -> u64 {
+    let path = match &opt.output[..] {
+        [outputs::OutputTarget::File(path)] => path,
+        _ => unreachable!("--fill requires a single file output"),
+    };
+    // Only whole buffers are counted while writing, but the last one can have partially fit
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|mut file| {
+            if !platform::is_block_device(path) {
+                bytes_written = file.seek(io::SeekFrom::End(0))? - resumed_written;
+            }
+            file.sync_all()
+        });
+    if let Err(e) = result {
+        eprintln!("Failed to sync {}: {}", path.display(), e);
+        ExitCode::Output.exit();
+    }
+    eprintln!(
+        "Filled {} with {} ({} bytes)",
+        path.display(),
+        formatting::format_bytes_written(bytes_written),
+        bytes_written
+    );
+    bytes_written
+}
+
+/// Renames the --atomic temporary files into place if the output is complete, or removes them
+/// and exits if it's not.
+fn finish_atomic(
+    opt: &Opt,
+    atomic_files: &[(PathBuf, PathBuf)],
+    written: &Written,
+    should_abort: &impl Fn() -> bool,
+) {
+    if atomic_files.is_empty() {
+        return;
+    }
+    let complete = opt.bytes == Some(written.bytes);
+    for (temp_path, path) in atomic_files {
+        let result = match complete {
+            true => fs::OpenOptions::new()
+                .write(true)
+                .open(temp_path)
+                .and_then(|file| file.sync_all())
+                .and_then(|()| fs::rename(temp_path, path)),
+            false => fs::remove_file(temp_path),
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to finish {}: {}", path.display(), e);
+            ExitCode::Output.exit();
+        }
+    }
+    if !complete {
+        eprintln!(
+            "Stopped after {} of {} bytes. The incomplete output was removed",
+            written.bytes,
+            opt.bytes.unwrap_or_default()
+        );
+        match written.output_error {
+            Some(_) => ExitCode::Output.exit(),
+            None if should_abort() => ExitCode::Interrupted.exit(),
+            None => ExitCode::Failure.exit(),
+        }
+    }
+}
+
+/// Reads back the file outputs and compares them to the output generated again. Returns
+/// whether they all match.
+fn verify(opt: &Opt, plan: &Plan, bytes_written: u64) -> bool {
+    let mut verified = true;
+    for target in &opt.output {
+        let path = match target {
+            outputs::OutputTarget::File(path) => path,
+            _ => continue,
+        };
+        let verifier = verify::Verifier::open(path, plan.resumed_written, bytes_written);
+        let mut verifier = verifier.unwrap_or_else(|e| {
+            eprintln!("Failed to open {} to verify it: {}", path.display(), e);
+            ExitCode::Failure.exit();
+        });
+        // The verifier stops by itself after the bytes written. Not checking should_abort
+        // here, as it stays set when the generation was interrupted
+        let tail = match plan.new_source(opt) {
+            Some(source) => singlethreaded::run_source(source, plan.buffer_size, |buf: &[u8]| {
+                verifier.compare(buf)
+            })
+            .unwrap_or_else(|e| source_failed(e)),
+            None if opt.stream_id.is_some() => {
+                let seed = plan.seed.expect("--verify requires a seed");
+                let rng = new_pcg_stream(seed, opt.stream_id.unwrap_or_default());
+                let skip = plan.skip.unwrap_or(0);
+                singlethreaded::run_seekable(rng, skip, plan.buffer_size, |buf| {
+                    verifier.compare(buf)
+                });
+                zeroize::Zeroizing::new(Vec::new())
+            }
+            None => {
+                let (seed, skip) = (plan.seed, plan.skip);
+                singlethreaded::run(plan.algorithm, seed, skip, None, plan.buffer_size, |buf| {
+                    verifier.compare(buf)
+                });
+                zeroize::Zeroizing::new(Vec::new())
+            }
+        };
+        verifier.compare(&tail);
+        match verifier.finish() {
+            Ok(verify::Report {
+                first_mismatch: None,
+                ..
+            }) => eprintln!("Verified {}: {} bytes match", path.display(), bytes_written),
+            Ok(report) => {
+                eprintln!(
+                    "Verifying {} failed: {} of {} bytes differ, the first at offset {}",
+                    path.display(),
+                    report.mismatches,
+                    bytes_written,
+                    report.first_mismatch.unwrap_or_default()
+                );
+                verified = false;
+            }
+            Err(e) => {
+                eprintln!("Verifying {} failed: {}", path.display(), e);
+                verified = false;
+            }
+        }
+    }
+    verified
+}
+
+/// Prints how much was written, and how fast.
+fn print_statistics(opt: &Opt, written: &Written) {
+    let bytes_written = written.bytes;
+    let elapsed_seconds = written.generated.as_millis() as f64 / 1000.0;
+    let bytes_per_second = bytes_written as f64 / elapsed_seconds;
+    let null_output = opt.pipe_to.is_none()
+        && !opt.output.is_empty()
+        && opt
+            .output
+            .iter()
+            .all(|target| *target == outputs::OutputTarget::Null);
+    if null_output {
+        eprintln!(
+            "{} ({} bytes) generated in {:.1} seconds = {}/s, discarded by the synthetic null \
+             output without being written anywhere",
+            formatting::format_bytes_written(bytes_written),
+            bytes_written,
+            elapsed_seconds,
+            formatting::format_bytes_written(bytes_per_second as u64),
+        );
+    } else {
+        eprintln!(
+            "{} ({} bytes) written in {:.1} seconds = {}/s",
+            formatting::format_bytes_written(bytes_written),
+            bytes_written,
+            elapsed_seconds,
+            formatting::format_bytes_written(bytes_per_second as u64),
+        );
+    }
+    if opt.fsync {
+        let synced_seconds = written.synced.as_millis() as f64 / 1000.0;
+        eprintln!(
+            "Synced to storage after {:.1} seconds = {}/s",
+            synced_seconds,
+            formatting::format_bytes_written((bytes_written as f64 / synced_seconds) as u64),
+        );
+    }
+}
+
+enum Output<'a> {
+    Stdout(io::StdoutLock<'a>),
+    Null,
+    File(fs::File),
+    Pipe(std::process::ChildStdin),
+    Tcp(outputs::TcpOutput),
+    Udp(outputs::UdpOutput),
+    Unix(Box<dyn Write>),
+    Fifo(outputs::FifoOutput),
+    Sparse(outputs::SparseOutput),
+    #[cfg(target_os = "linux")]
+    Splice(splice::SpliceOutput),
+    #[cfg(windows)]
+    Overlapped(overlapped::OverlappedOutput),
+    #[cfg(unix)]
+    Mapped(outputs::MappedOutput),
+    Positioned(outputs::PositionedOutput),
+}
+
+impl Output<'_> {
+    /// Syncs the written data of file and device outputs to the storage. Does nothing for other
+    /// outputs.
+    fn sync_data(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(f) => f.sync_data(),
+            Output::Sparse(sparse) => sparse.file().sync_data(),
+            #[cfg(windows)]
+            Output::Overlapped(overlapped) => {
+                overlapped.flush()?;
+                overlapped.file().sync_data()
+            }
+            #[cfg(unix)]
+            Output::Mapped(mapped) => {
+                mapped.sync()?;
+                mapped.file().sync_data()
+            }
+            Output::Positioned(positioned) => positioned.file().sync_data(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `sync_data`, but also syncs the file metadata.
+    fn sync_all(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(f) => f.sync_all(),
+            Output::Sparse(sparse) => sparse.file().sync_all(),
+            #[cfg(windows)]
+            Output::Overlapped(overlapped) => {
+                overlapped.flush()?;
+                overlapped.file().sync_all()
+            }
+            #[cfg(unix)]
+            Output::Mapped(mapped) => {
+                mapped.sync()?;
+                mapped.file().sync_all()
+            }
+            Output::Positioned(positioned) => positioned.file().sync_all(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> Write for Output<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::Null => Ok(buf.len()),
+            Output::File(f) => f.write(buf),
+            Output::Pipe(stdin) => stdin.write(buf),
+            Output::Tcp(tcp) => tcp.write(buf),
+            Output::Udp(udp) => udp.write(buf),
+            Output::Unix(socket) => socket.write(buf),
+            Output::Fifo(fifo) => fifo.write(buf),
+            Output::Sparse(sparse) => sparse.write(buf),
+            #[cfg(target_os = "linux")]
+            Output::Splice(splice) => splice.write(buf),
+            #[cfg(windows)]
+            Output::Overlapped(overlapped) => overlapped.write(buf),
+            #[cfg(unix)]
+            Output::Mapped(mapped) => mapped.write(buf),
+            Output::Positioned(positioned) => positioned.write(buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write_vectored(bufs),
+            Output::Null => Ok(bufs.iter().map(|buf| buf.len()).sum()),
+            Output::File(f) => f.write_vectored(bufs),
+            Output::Pipe(stdin) => stdin.write_vectored(bufs),
+            Output::Tcp(tcp) => tcp.write_vectored(bufs),
+            Output::Udp(udp) => udp.write_vectored(bufs),
+            Output::Unix(socket) => socket.write_vectored(bufs),
+            Output::Fifo(fifo) => fifo.write_vectored(bufs),
+            Output::Sparse(sparse) => sparse.write_vectored(bufs),
+            #[cfg(target_os = "linux")]
+            Output::Splice(splice) => splice.write_vectored(bufs),
+            #[cfg(windows)]
+            Output::Overlapped(overlapped) => overlapped.write_vectored(bufs),
+            #[cfg(unix)]
+            Output::Mapped(mapped) => mapped.write_vectored(bufs),
+            Output::Positioned(positioned) => positioned.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::Null => Ok(()),
+            Output::File(f) => f.flush(),
+            Output::Pipe(stdin) => stdin.flush(),
+            Output::Tcp(tcp) => tcp.flush(),
+            Output::Udp(udp) => udp.flush(),
+            Output::Unix(socket) => socket.flush(),
+            Output::Fifo(fifo) => fifo.flush(),
+            Output::Sparse(sparse) => sparse.flush(),
+            #[cfg(target_os = "linux")]
+            Output::Splice(splice) => splice.flush(),
+            #[cfg(windows)]
+            Output::Overlapped(overlapped) => overlapped.flush(),
+            #[cfg(unix)]
+            Output::Mapped(mapped) => mapped.flush(),
+            Output::Positioned(positioned) => positioned.flush(),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! A builder style API for writing generated data to any `Write` sink, without the command line
+//! tool around it.
+
+use crate::rngs::{self, OsSource};
+use crate::seed::Seed;
+use crate::{multithreaded, singlethreaded, sources, Algorithm};
+use crate::{BUFFER_SIZE, MAX_BUFFER_SIZE, MIN_BUFFER_SIZE};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Selects the algorithm, seed, threads and amount of data to generate. Created with
+/// `Generator::builder`.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    algorithm: Algorithm,
+    seed: Option<Seed>,
+    threads: usize,
+    buffer_size: usize,
+    limit: Option<u64>,
+}
+
+impl Builder {
+    /// The algorithm to generate data with. `Algorithm::Default` unless set.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Seeds the algorithm, making the output reproducible. Seeded from the OS unless set.
+    pub fn seed(mut self, seed: Seed) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The number of worker threads. One unless set. Entropy sources, plugins and seeded
+    /// algorithms that can't seek always run on one thread, like with the command line tool.
+    /// So does everything on WebAssembly.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// The number of bytes generated at a time. `BUFFER_SIZE` unless set.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Stops after writing this many bytes. The output is endless unless set.
+    pub fn limit(mut self, bytes: u64) -> Self {
+        self.limit = Some(bytes);
+        self
+    }
+
+    /// Checks the selected options and creates the generator.
+    pub fn build(self) -> Result<Generator, BuildError> {
+        if self.threads == 0 {
+            return Err(BuildError("The number of threads must be at least one"));
+        }
+        let buffer_size = self.buffer_size as u64;
+        if !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&buffer_size) {
+            return Err(BuildError(
+                "The buffer size must be between 4 KiB and 256 MiB",
+            ));
+        }
+        if let Some(seed) = self.seed {
+            match (seed.len(), self.algorithm.seed_len()) {
+                (_, None) => return Err(BuildError("Entropy sources can't be seeded")),
+                (Some(len), Some(max_len)) if len > max_len => {
+                    return Err(BuildError("The seed is longer than the algorithm takes"))
+                }
+                _ => (),
+            }
+        }
+        let single_threaded = cfg!(target_family = "wasm")
+            || matches!(self.algorithm, Algorithm::Plugin(_))
+            || self.algorithm.is_entropy_source()
+            || (self.seed.is_some() && !self.algorithm.is_seekable());
+        Ok(Generator {
+            algorithm: self.algorithm,
+            seed: self.seed,
+            threads: if single_threaded { 1 } else { self.threads },
+            buffer_size: self.buffer_size,
+            limit: self.limit,
+        })
+    }
+}
+
+/// Writes generated data to a sink. See `Builder` for the options.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    algorithm: Algorithm,
+    seed: Option<Seed>,
+    threads: usize,
+    buffer_size: usize,
+    limit: Option<u64>,
+}
+
+impl Generator {
+    pub fn builder() -> Builder {
+        Builder {
+            algorithm: Algorithm::Default,
+            seed: None,
+            threads: 1,
+            buffer_size: BUFFER_SIZE,
+            limit: None,
+        }
+    }
+
+    /// Writes generated data to `sink` until the limit is reached or writing fails. Returns the
+    /// number of bytes written. Entropy sources that fail to open or read fail with
+    /// `ErrorKind::Other`.
+    pub fn generate(&self, mut sink: impl Write) -> io::Result<u64> {
+        let mut written = 0u64;
+        let mut error = None;
+        if self.limit == Some(0) {
+            return Ok(0);
+        }
+        // Returns true to stop generating
+        let mut write_fn = |bufs: &[&[u8]]| {
+            for buf in bufs {
+                let len = match self.limit {
+                    Some(limit) => (limit - written).min(buf.len() as u64) as usize,
+                    None => buf.len(),
+                };
+                if let Err(e) = sink.write_all(&buf[..len]) {
+                    error = Some(e);
+                    return true;
+                }
+                written += len as u64;
+                if Some(written) == self.limit {
+                    return true;
+                }
+            }
+            false
+        };
+
+        let source = match self.algorithm {
+            Algorithm::Plugin(path) => {
+                let plugin = rngs::Plugin::load(path).map_err(other)?;
+                Some(Box::new(plugin.new_rng(self.seed).map_err(other)?) as Box<_>)
+            }
+            algorithm if algorithm.is_entropy_source() => {
+                Some(sources::open(algorithm, None, OsSource::Default).map_err(other)?)
+            }
+            _ => None,
+        };
+        if let Some(source) = source {
+            let tail = singlethreaded::run_source(source, self.buffer_size, |buf| write_fn(&[buf]))
+                .map_err(other)?;
+            if !tail.is_empty() {
+                write_fn(&[&tail]);
+            }
+        } else if self.threads > 1 {
+            let config = multithreaded::Config {
+                threads: self.threads,
+                buffer_size: self.buffer_size,
+                queue_depth: None,
+                pin_cpus: None,
+                adaptive: false,
+                huge_pages: false,
+                mapping: None,
+                positioned: None,
+                verbose: false,
+            };
+            match self.seed {
+                Some(seed) => multithreaded::run_seeded(self.algorithm, seed, 0, &config, write_fn),
+                None => multithreaded::run(self.algorithm, &config, None, write_fn),
+            }
+        } else {
+            singlethreaded::run(
+                self.algorithm,
+                self.seed,
+                None,
+                None,
+                self.buffer_size,
+                |buf| write_fn(&[buf]),
+            );
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(written),
+        }
+    }
+}
+
+fn other(e: impl fmt::Display) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+/// An invalid combination of options given to `Builder`.
+#[derive(Debug)]
+pub struct BuildError(&'static str);
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Error for BuildError {}
+
+#[test]
+fn test_generator() {
+    let generate = |threads| {
+        let mut data = Vec::new();
+        let written = Generator::builder()
+            .algorithm(Algorithm::Pcg)
+            .seed(Seed::Number(7))
+            .threads(threads)
+            .buffer_size(8 * 1024)
+            .limit(100_000)
+            .build()
+            .unwrap()
+            .generate(&mut data)
+            .unwrap();
+        assert_eq!(written, 100_000);
+        data
+    };
+    let data = generate(1);
+    assert_eq!(data.len(), 100_000);
+    assert_eq!(generate(3), data);
+
+    let os = || Generator::builder().algorithm(Algorithm::Os);
+    assert!(os().seed(Seed::Number(7)).build().is_err());
+    assert!(os().threads(0).build().is_err());
+    assert!(os().buffer_size(1).build().is_err());
+    let mut data = Vec::new();
+    os().limit(10).build().unwrap().generate(&mut data).unwrap();
+    assert_eq!(data.len(), 10);
+}
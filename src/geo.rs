@@ -0,0 +1,326 @@
+//! The `geo` subcommand. Random points uniformly distributed over the surface of the earth, or
+//! over the polygons of a GeoJSON file, for synthetic geospatial datasets.
+//!
+//! Points inside polygons are found by rejection sampling. A polygon is picked with a
+//! probability proportional to the area of its bounding box, a uniform point is drawn from that
+//! box, and it is kept only if it is inside the polygon. The polygons of an area don't overlap,
+//! so every point of the area is equally likely, whatever the sizes of the boxes. As in GeoJSON,
+//! edges are straight lines in longitude and latitude, and polygons crossing the antimeridian
+//! must be split in two.
+
+use crate::json::Json;
+use crate::parsing::ParseError;
+use rand::{Rng, RngCore};
+use std::fmt;
+use std::io::{self, Write};
+
+/// A position as (longitude, latitude) in degrees, in the order of GeoJSON.
+type Position = (f64, f64);
+
+/// How the points are written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// A "latitude,longitude" header line, then one line per point.
+    Csv,
+    /// A GeoJSON FeatureCollection of Point features, one per line.
+    Geojson,
+}
+
+impl std::str::FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "geojson" => Ok(Format::Geojson),
+            _ => Err(ParseFormatError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFormatError(());
+
+impl fmt::Display for ParseFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid format. See --help for a list of valid options.")
+    }
+}
+
+/// A polygon with its bounding box.
+struct Polygon {
+    /// The exterior ring followed by the holes.
+    rings: Vec<Vec<Position>>,
+    min: Position,
+    max: Position,
+    /// The area of the bounding box on the sphere, up to a constant factor.
+    weight: f64,
+}
+
+impl Polygon {
+    fn new(rings: Vec<Vec<Position>>) -> Self {
+        let mut min = (f64::INFINITY, f64::INFINITY);
+        let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(lon, lat) in &rings[0] {
+            min = (min.0.min(lon), min.1.min(lat));
+            max = (max.0.max(lon), max.1.max(lat));
+        }
+        let weight = (max.0 - min.0) * (max.1.to_radians().sin() - min.1.to_radians().sin());
+        Polygon {
+            rings,
+            min,
+            max,
+            weight,
+        }
+    }
+
+    /// Whether the position is inside the exterior ring and outside the holes, by counting the
+    /// edges a ray from it crosses.
+    fn contains(&self, (lon, lat): Position) -> bool {
+        let mut inside = false;
+        for ring in &self.rings {
+            for edge in ring.windows(2) {
+                let ((lon1, lat1), (lon2, lat2)) = (edge[0], edge[1]);
+                if (lat1 > lat) != (lat2 > lat)
+                    && lon < lon1 + (lat - lat1) / (lat2 - lat1) * (lon2 - lon1)
+                {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+}
+
+/// The polygons of a GeoJSON file, to pick points inside.
+pub struct Area {
+    polygons: Vec<Polygon>,
+    total_weight: f64,
+}
+
+impl Area {
+    /// Reads the Polygon and MultiPolygon geometries of a GeoJSON document. It can be a single
+    /// geometry, a Feature, a FeatureCollection or a GeometryCollection.
+    pub fn from_geojson(json: &Json) -> Result<Self, ParseError> {
+        let mut polygons = Vec::new();
+        collect_polygons(json, &mut polygons)?;
+        if polygons.is_empty() {
+            return Err(ParseError::new("The GeoJSON has no polygons".to_owned()));
+        }
+        let total_weight = polygons.iter().map(|polygon| polygon.weight).sum();
+        Ok(Area {
+            polygons,
+            total_weight,
+        })
+    }
+
+    /// A uniformly distributed position inside the area.
+    fn sample(&self, rng: &mut dyn RngCore) -> Position {
+        loop {
+            let mut choice = rng.gen_range(0.0..self.total_weight);
+            let polygon = self
+                .polygons
+                .iter()
+                .find(|polygon| {
+                    choice -= polygon.weight;
+                    choice < 0.0
+                })
+                .unwrap_or_else(|| self.polygons.last().unwrap());
+            let position = in_box(polygon.min, polygon.max, rng);
+            if polygon.contains(position) {
+                return position;
+            }
+        }
+    }
+}
+
+fn invalid(message: &str) -> ParseError {
+    ParseError::new(format!("Invalid GeoJSON: {}", message))
+}
+
+fn collect_polygons(json: &Json, polygons: &mut Vec<Polygon>) -> Result<(), ParseError> {
+    let coordinates = || {
+        json.get("coordinates")
+            .and_then(Json::as_array)
+            .ok_or_else(|| invalid("a geometry has no coordinates"))
+    };
+    match json.get("type").and_then(Json::as_str) {
+        Some("FeatureCollection") => json
+            .get("features")
+            .and_then(Json::as_array)
+            .ok_or_else(|| invalid("a FeatureCollection has no features"))?
+            .iter()
+            .try_for_each(|feature| collect_polygons(feature, polygons)),
+        Some("Feature") => match json.get("geometry") {
+            Some(Json::Null) | None => Ok(()),
+            Some(geometry) => collect_polygons(geometry, polygons),
+        },
+        Some("GeometryCollection") => json
+            .get("geometries")
+            .and_then(Json::as_array)
+            .ok_or_else(|| invalid("a GeometryCollection has no geometries"))?
+            .iter()
+            .try_for_each(|geometry| collect_polygons(geometry, polygons)),
+        Some("Polygon") => {
+            polygons.push(polygon(coordinates()?)?);
+            Ok(())
+        }
+        Some("MultiPolygon") => coordinates()?.iter().try_for_each(|rings| {
+            let rings = rings
+                .as_array()
+                .ok_or_else(|| invalid("a polygon is not an array"))?;
+            polygons.push(polygon(rings)?);
+            Ok(())
+        }),
+        Some(other) => Err(ParseError::new(format!(
+            "Unsupported geometry type {}. Only Polygon and MultiPolygon are supported",
+            other
+        ))),
+        None => Err(invalid("an object has no type")),
+    }
+}
+
+fn polygon(rings: &[Json]) -> Result<Polygon, ParseError> {
+    let rings = rings.iter().map(ring).collect::<Result<Vec<_>, _>>()?;
+    if rings.is_empty() {
+        return Err(invalid("a polygon has no rings"));
+    }
+    if signed_area(&rings[0]) == 0.0 {
+        return Err(invalid("a polygon has no area"));
+    }
+    // Sampling would never find a point in a polygon whose holes cover all of it
+    let holes: f64 = rings[1..].iter().map(|hole| signed_area(hole).abs()).sum();
+    if signed_area(&rings[0]).abs() - holes <= 0.0 {
+        return Err(invalid("the holes of a polygon cover all of it"));
+    }
+    Ok(Polygon::new(rings))
+}
+
+fn ring(ring: &Json) -> Result<Vec<Position>, ParseError> {
+    let ring = ring
+        .as_array()
+        .ok_or_else(|| invalid("a ring is not an array"))?
+        .iter()
+        .map(|position| {
+            let position = position
+                .as_array()
+                .ok_or_else(|| invalid("a position is not an array"))?;
+            match position {
+                [lon, lat, ..] => match (lon.as_f64(), lat.as_f64()) {
+                    (Some(lon), Some(lat))
+                        if (-180.0..=180.0).contains(&lon) && (-90.0..=90.0).contains(&lat) =>
+                    {
+                        Ok((lon, lat))
+                    }
+                    _ => Err(invalid("a position is not a valid longitude and latitude")),
+                },
+                _ => Err(invalid("a position has fewer than two coordinates")),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if ring.len() < 4 || ring.first() != ring.last() {
+        return Err(invalid(
+            "a ring must have at least four positions, and end where it starts",
+        ));
+    }
+    Ok(ring)
+}
+
+/// The area enclosed by a closed ring, by the shoelace formula.
+fn signed_area(ring: &[Position]) -> f64 {
+    ring.windows(2)
+        .map(|edge| edge[0].0 * edge[1].1 - edge[1].0 * edge[0].1)
+        .sum::<f64>()
+        / 2.0
+}
+
+/// A position uniformly distributed over the surface of the sphere between `min` and `max`.
+/// Drawing the sine of the latitude uniformly makes up for the meridians converging towards
+/// the poles.
+fn in_box(min: Position, max: Position, rng: &mut dyn RngCore) -> Position {
+    let lon = rng.gen_range(min.0..=max.0);
+    let sin_lat = rng.gen_range(min.1.to_radians().sin()..=max.1.to_radians().sin());
+    (lon, sin_lat.asin().to_degrees())
+}
+
+/// Writes `count` random points inside `area`, or anywhere on earth if there is no area.
+pub fn run(
+    count: u64,
+    area: Option<&Area>,
+    format: Format,
+    rng: &mut dyn RngCore,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(out);
+    match format {
+        Format::Csv => writeln!(out, "latitude,longitude")?,
+        Format::Geojson => writeln!(out, "{{\"type\":\"FeatureCollection\",\"features\":[")?,
+    }
+    for i in 0..count {
+        let (lon, lat) = match area {
+            Some(area) => area.sample(rng),
+            None => in_box((-180.0, -90.0), (180.0, 90.0), rng),
+        };
+        match format {
+            Format::Csv => writeln!(out, "{:.6},{:.6}", lat, lon)?,
+            Format::Geojson => write!(
+                out,
+                "{}{{\"type\":\"Feature\",\"properties\":{{}},\"geometry\":\
+                 {{\"type\":\"Point\",\"coordinates\":[{:.6},{:.6}]}}}}",
+                if i == 0 { "" } else { ",\n" },
+                lon,
+                lat
+            )?,
+        }
+    }
+    if format == Format::Geojson {
+        writeln!(out, "\n]}}")?;
+    }
+    out.flush()
+}
+
+#[test]
+fn test_geo() {
+    use rand::SeedableRng;
+
+    // A square with a square hole in the middle, and a triangle far away
+    let geojson = r#"{"type": "FeatureCollection", "features": [
+        {"type": "Feature", "properties": {}, "geometry": {"type": "MultiPolygon", "coordinates": [
+            [[[0, 0], [4, 0], [4, 4], [0, 4], [0, 0]], [[1, 1], [3, 1], [3, 3], [1, 3], [1, 1]]],
+            [[[100, 0], [104, 0], [100, 4], [100, 0]]]
+        ]}},
+        {"type": "Feature", "properties": {}, "geometry": null}
+    ]}"#;
+    let area = Area::from_geojson(&crate::json::parse(geojson).unwrap()).unwrap();
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    let points: Vec<_> = (0..10_000).map(|_| area.sample(&mut rng)).collect();
+    let in_square = |&&(lon, lat): &&Position| {
+        (0.0..=4.0).contains(&lon)
+            && (0.0..=4.0).contains(&lat)
+            && !((1.0..3.0).contains(&lon) && (1.0..3.0).contains(&lat))
+    };
+    let in_triangle = |&&(lon, lat): &&Position| lon >= 100.0 && lat >= 0.0 && lon + lat <= 104.0;
+    assert!(points.iter().all(|p| in_square(&p) || in_triangle(&p)));
+    // The square with the hole has an area of 12, and the triangle 8
+    let share = points.iter().filter(in_square).count() as f64 / points.len() as f64;
+    assert!((share - 0.6).abs() < 0.02, "{}", share);
+
+    let parse = |s| Area::from_geojson(&crate::json::parse(s).unwrap());
+    assert!(parse(r#"{"type": "Point", "coordinates": [1, 2]}"#).is_err());
+    assert!(parse(r#"{"type": "Polygon", "coordinates": [[[0, 0], [1, 1], [0, 0]]]}"#).is_err());
+    assert!(parse(r#"{"type": "FeatureCollection", "features": []}"#).is_err());
+    let square = "[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]";
+    let holed = format!(
+        r#"{{"type": "Polygon", "coordinates": [{}, {}]}}"#,
+        square, square
+    );
+    assert!(parse(&holed).is_err());
+
+    let mut out = Vec::new();
+    run(2, None, Format::Geojson, &mut rng, &mut out).unwrap();
+    let out = crate::json::parse(std::str::from_utf8(&out).unwrap()).unwrap();
+    assert_eq!(
+        out.get("features").and_then(Json::as_array).unwrap().len(),
+        2
+    );
+}
@@ -0,0 +1,226 @@
+//! The continuous health tests of NIST SP 800-90B section 4.4, run on the raw output of entropy
+//! sources with `--health-tests`. Every output byte is treated as one noise sample.
+
+use crate::rngs::EndOfStream;
+use rand::RngCore;
+use std::fmt;
+
+/// What to do when a health test fails.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HealthAction {
+    Abort,
+    Warn,
+}
+
+impl std::str::FromStr for HealthAction {
+    type Err = ParseHealthActionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(HealthAction::Abort),
+            "warn" => Ok(HealthAction::Warn),
+            _ => Err(ParseHealthActionError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseHealthActionError(());
+
+impl fmt::Display for ParseHealthActionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid health test action. See --help for a list of valid options."
+        )
+    }
+}
+
+/// The false positive probability of the tests, as a power of two. SP 800-90B allows 2^-20 to
+/// 2^-40. The tests run over gigabytes of data, so the lowest rate is used.
+const ALPHA_LOG2: f64 = -40.0;
+
+/// The window size of the adaptive proportion test for non-binary samples.
+const APT_WINDOW: usize = 512;
+
+/// The repetition count test cutoff for a source with `min_entropy` bits of entropy per
+/// sample. A run of this many identical samples fails the test.
+pub fn repetition_count_cutoff(min_entropy: f64) -> usize {
+    1 + (-ALPHA_LOG2 / min_entropy).ceil() as usize
+}
+
+/// The adaptive proportion test cutoff for a source with `min_entropy` bits of entropy per
+/// sample. A window where the first sample occurs this many times fails the test.
+///
+/// This is one more than the smallest count the window exceeds with a probability of at most
+/// alpha, given the most likely sample has probability 2^-min_entropy.
+pub fn adaptive_proportion_cutoff(min_entropy: f64) -> usize {
+    let p = (-min_entropy).exp2();
+    let n = APT_WINDOW;
+    // The binomial probability mass function, computed in log space to not underflow
+    let mut ln_binomial = 0.0;
+    let ln_pmf: Vec<f64> = (0..=n)
+        .map(|k| {
+            if k > 0 {
+                ln_binomial += ((n - k + 1) as f64 / k as f64).ln();
+            }
+            ln_binomial + k as f64 * p.ln() + (n - k) as f64 * (1.0 - p).ln()
+        })
+        .collect();
+    let alpha = ALPHA_LOG2.exp2();
+    let mut tail = 0.0;
+    for k in (0..=n).rev() {
+        tail += ln_pmf[k].exp();
+        if tail > alpha {
+            return k + 1;
+        }
+    }
+    1
+}
+
+#[derive(Debug)]
+pub struct HealthTestFailure {
+    test: &'static str,
+    position: u64,
+}
+
+impl fmt::Display for HealthTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The {} health test failed at byte {} of the entropy source output",
+            self.test, self.position
+        )
+    }
+}
+
+impl std::error::Error for HealthTestFailure {}
+
+/// Wraps an entropy source and runs the repetition count test and the adaptive proportion test
+/// on everything read from it.
+pub struct HealthTested<R> {
+    rng: R,
+    action: HealthAction,
+    rct_cutoff: usize,
+    apt_cutoff: usize,
+    /// The number of samples tested so far.
+    position: u64,
+    /// The last sample and how many times in a row it has been seen.
+    rct_sample: u8,
+    rct_count: usize,
+    /// The first sample of the current window, how many times it has been seen in the window
+    /// and how many samples of the window have been tested.
+    apt_sample: u8,
+    apt_count: usize,
+    apt_len: usize,
+}
+
+impl<R: RngCore> HealthTested<R> {
+    pub fn new(rng: R, action: HealthAction, min_entropy: f64) -> Self {
+        HealthTested {
+            rng,
+            action,
+            rct_cutoff: repetition_count_cutoff(min_entropy),
+            apt_cutoff: adaptive_proportion_cutoff(min_entropy),
+            position: 0,
+            rct_sample: 0,
+            rct_count: 0,
+            apt_sample: 0,
+            apt_count: 0,
+            apt_len: APT_WINDOW,
+        }
+    }
+
+    /// Runs both tests on the given samples. Every failure is reported once, a stuck source
+    /// does not warn for every sample.
+    fn test(&mut self, samples: &[u8]) -> Result<(), HealthTestFailure> {
+        let mut failed = None;
+        for &sample in samples {
+            self.position += 1;
+            if self.rct_count > 0 && sample == self.rct_sample {
+                self.rct_count += 1;
+                if self.rct_count == self.rct_cutoff {
+                    failed = Some("repetition count");
+                }
+            } else {
+                self.rct_sample = sample;
+                self.rct_count = 1;
+            }
+
+            if self.apt_len == APT_WINDOW {
+                self.apt_sample = sample;
+                self.apt_count = 1;
+                self.apt_len = 1;
+            } else {
+                self.apt_len += 1;
+                if sample == self.apt_sample {
+                    self.apt_count += 1;
+                    if self.apt_count == self.apt_cutoff {
+                        failed = Some("adaptive proportion");
+                    }
+                }
+            }
+
+            if let Some(test) = failed.take() {
+                let failure = HealthTestFailure {
+                    test,
+                    position: self.position - 1,
+                };
+                match self.action {
+                    HealthAction::Abort => return Err(failure),
+                    HealthAction::Warn => eprintln!("WARNING: {}", failure),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: RngCore> RngCore for HealthTested<R> {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from the generator: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        let result = self.rng.try_fill_bytes(dest);
+        // Data read before the end of a stream is passed on, so it must be tested too
+        let filled = EndOfStream::filled_len(result, dest.len())?;
+        self.test(&dest[..filled]).map_err(rand::Error::new)?;
+        if filled < dest.len() {
+            return Err(rand::Error::new(EndOfStream { filled }));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_health_tests() {
+    assert_eq!(repetition_count_cutoff(8.0), 6);
+    assert_eq!(repetition_count_cutoff(1.0), 41);
+    // The cutoff grows as the entropy estimate shrinks, up to the window size
+    let cutoffs: Vec<_> = [8.0, 4.0, 1.0, 0.1]
+        .iter()
+        .map(|&h| adaptive_proportion_cutoff(h))
+        .collect();
+    assert!(cutoffs.windows(2).all(|w| w[0] < w[1]));
+    assert!(cutoffs[3] <= APT_WINDOW);
+
+    let mut buf = [0u8; 1 << 16];
+    let mut tested = HealthTested::new(rand::rngs::OsRng, HealthAction::Abort, 8.0);
+    for _ in 0..16 {
+        tested.try_fill_bytes(&mut buf).unwrap();
+    }
+
+    let mut stuck = HealthTested::new(crate::conditioning::ConstRng(7), HealthAction::Abort, 8.0);
+    let error = stuck.try_fill_bytes(&mut buf).unwrap_err();
+    assert!(error.to_string().contains("repetition count"));
+}
@@ -0,0 +1,330 @@
+//! The `serve-http` subcommand. A minimal HTTP/1.1 server handing out random data, for use as a
+//! randomness service in test labs. Every request gets a fresh connection-local generator and
+//! every response closes the connection, so no state is shared between clients.
+//!
+//! Endpoints:
+//!
+//! * `GET /bytes?n=<size>&format=<format>` - Exactly `n` random bytes.
+//! * `GET /stream?format=<format>` - Random data until the client disconnects.
+//! * `GET /metrics` - Prometheus metrics. See the `metrics` module.
+
+use crate::metrics::Metrics;
+use crate::{formatting, parsing, sources, Algorithm};
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The most bytes a single `/bytes` request can ask for.
+const MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// The most bytes of request line and headers read from a client.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// Clients that do not send a complete request within this time are disconnected.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The size of the pieces `/stream` generates and encodes. A multiple of three, so base64 can be
+/// encoded piece by piece without padding in between.
+const STREAM_CHUNK: usize = 48 * 1024;
+
+/// How the random bytes are encoded in the response body.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Format {
+    Raw,
+    Hex,
+    Base64,
+}
+
+impl Format {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "raw" => Some(Format::Raw),
+            "hex" => Some(Format::Hex),
+            "base64" => Some(Format::Base64),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Raw => "application/octet-stream",
+            Format::Hex | Format::Base64 => "text/plain",
+        }
+    }
+
+    fn encode(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Format::Raw => bytes.to_vec(),
+            Format::Hex => formatting::hex(bytes).into_bytes(),
+            Format::Base64 => {
+                formatting::base64(bytes, formatting::BASE64_ALPHABET, true).into_bytes()
+            }
+        }
+    }
+}
+
+/// Listens on `address`, or the socket passed by systemd socket activation, and serves
+/// requests until the process is stopped. An address without a host, such as ":8080", listens
+/// on all interfaces. Only returns if setting up the server fails.
+pub fn run(address: &str, algorithm: Algorithm) -> Result<(), Box<dyn Error>> {
+    sources::open_any(algorithm)?;
+    let listener = match crate::platform::activated_tcp_listener()? {
+        Some(listener) => listener,
+        None => {
+            let address = match address.strip_prefix(':') {
+                Some(port) => format!("0.0.0.0:{}", port),
+                None => address.to_owned(),
+            };
+            TcpListener::bind(&address)
+                .map_err(|e| format!("Failed to listen on {}: {}", address, e))?
+        }
+    };
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+    serve(listener, algorithm, Metrics::new(algorithm));
+    Ok(())
+}
+
+/// Serves the metrics of another serve mode at `/metrics` on `address`, from a background
+/// thread. Returns once listening.
+pub fn run_metrics(address: &str, metrics: Arc<Metrics>) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(address)
+        .map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    eprintln!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(Result::ok) {
+            let metrics = metrics.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle_metrics(stream, &metrics) {
+                    eprintln!("WARNING: Failed to serve a request: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve(listener: TcpListener, algorithm: Algorithm, metrics: Arc<Metrics>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("WARNING: Failed to accept a client: {}", e);
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, algorithm, &metrics) {
+                eprintln!("WARNING: Failed to serve a request: {}", e);
+            }
+        });
+    }
+}
+
+/// Reads one request and writes the response.
+fn handle(
+    mut stream: TcpStream,
+    algorithm: Algorithm,
+    metrics: &Arc<Metrics>,
+) -> Result<(), Box<dyn Error>> {
+    let target = match read_get_target(&mut stream)? {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let mut n = None;
+    let mut format = Format::Raw;
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        match key {
+            "n" => match parsing::parse_size(value) {
+                Ok(value) if value <= MAX_BYTES => n = Some(value),
+                _ => {
+                    let message = format!("n must be a size of at most {} bytes", MAX_BYTES);
+                    return respond_error(&mut stream, "400 Bad Request", &message);
+                }
+            },
+            "format" => match Format::parse(value) {
+                Some(value) => format = value,
+                None => {
+                    let message = "format must be one of raw, hex or base64";
+                    return respond_error(&mut stream, "400 Bad Request", message);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    if path == "/metrics" {
+        return respond_metrics(&mut stream, metrics);
+    }
+    let mut rng = sources::open_any(algorithm)?;
+    match path {
+        "/bytes" => {
+            let mut session = metrics.session();
+            let mut bytes = zeroize::Zeroizing::new(vec![0u8; n.unwrap_or(32) as usize]);
+            rng.try_fill_bytes(&mut bytes)?;
+            session.served(&bytes);
+            let body = zeroize::Zeroizing::new(format.encode(&bytes));
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+                 Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+                format.content_type(),
+                body.len()
+            )?;
+            stream.write_all(&body)?;
+        }
+        "/stream" => {
+            let mut session = metrics.session();
+            // Without a content length the body lasts until the connection closes
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\n\
+                 Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+                format.content_type()
+            )?;
+            let mut chunk = zeroize::Zeroizing::new([0u8; STREAM_CHUNK]);
+            loop {
+                rng.try_fill_bytes(&mut chunk[..])?;
+                let body = zeroize::Zeroizing::new(format.encode(&chunk[..]));
+                if stream.write_all(&body).is_err() {
+                    // The client disconnecting is how a stream ends
+                    return Ok(());
+                }
+                session.served(&chunk[..]);
+            }
+        }
+        _ => return respond_error(&mut stream, "404 Not Found", "Unknown endpoint"),
+    }
+    Ok(())
+}
+
+/// Answers a request to the server of `run_metrics`.
+fn handle_metrics(mut stream: TcpStream, metrics: &Metrics) -> Result<(), Box<dyn Error>> {
+    match read_get_target(&mut stream)? {
+        Some(target) if target.split('?').next() == Some("/metrics") => {
+            respond_metrics(&mut stream, metrics)
+        }
+        Some(_) => respond_error(&mut stream, "404 Not Found", "Unknown endpoint"),
+        None => Ok(()),
+    }
+}
+
+fn respond_metrics(stream: &mut TcpStream, metrics: &Metrics) -> Result<(), Box<dyn Error>> {
+    let body = metrics.render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+/// Reads a request and returns its target. Returns `None` after responding with an error if the
+/// request is invalid or not a GET request.
+fn read_get_target(stream: &mut TcpStream) -> Result<Option<String>, Box<dyn Error>> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = match read_request(stream)? {
+        Some(request) => request,
+        None => {
+            respond_error(stream, "400 Bad Request", "Invalid request")?;
+            return Ok(None);
+        }
+    };
+    let mut words = request.split(' ');
+    match (words.next(), words.next()) {
+        (Some("GET"), Some(target)) => Ok(Some(target.to_owned())),
+        (Some(_), Some(_)) => {
+            respond_error(stream, "405 Method Not Allowed", "Only GET is supported")?;
+            Ok(None)
+        }
+        _ => {
+            respond_error(stream, "400 Bad Request", "Invalid request")?;
+            Ok(None)
+        }
+    }
+}
+
+/// Reads the request line and headers, and returns the request line. Returns `None` if the
+/// request is too long or not valid UTF-8.
+fn read_request(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|end| end == b"\r\n\r\n") {
+        let n = stream.read(&mut buf)?;
+        if n == 0 || request.len() + n > MAX_REQUEST_LEN {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = match String::from_utf8(request) {
+        Ok(request) => request,
+        Err(_) => return Ok(None),
+    };
+    Ok(request.lines().next().map(str::to_owned))
+}
+
+fn respond_error(
+    stream: &mut TcpStream,
+    status: &str,
+    message: &str,
+) -> Result<(), Box<dyn Error>> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}\n",
+        status,
+        message.len() + 1,
+        message
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_serve_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let metrics = Metrics::new(Algorithm::ChaCha8);
+    let server_metrics = metrics.clone();
+    thread::spawn(move || serve(listener, Algorithm::ChaCha8, server_metrics));
+    let get = |request: &str| {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = get("GET /bytes?n=16&format=hex HTTP/1.1\r\nHost: test\r\n\r\n");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Content-Length: 32\r\n"));
+    assert_eq!(body.len(), 32);
+    assert!(body.bytes().all(|b| b.is_ascii_hexdigit()));
+
+    let response = get("GET /bytes?n=10&format=base64 HTTP/1.1\r\n\r\n");
+    assert!(response.ends_with("=="));
+    assert!(get("GET /bytes?n=1TiB HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 400"));
+    assert!(get("GET /nothing HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+    assert!(get("POST /bytes HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405"));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET /stream HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = vec![0u8; 1 << 20];
+    stream.read_exact(&mut response).unwrap();
+
+    let response = get("GET /metrics HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("rng_bytes_served_total{algorithm=\"chacha8\"} "));
+    assert!(metrics
+        .render()
+        .contains("rng_connections_total{algorithm=\"chacha8\"} 3\n"));
+}
@@ -0,0 +1,237 @@
+//! The `image` subcommand. Renders random data as a PNG image, one byte per gray pixel or three
+//! bytes per color pixel, for inspecting the data for patterns visually.
+//!
+//! Random data does not compress, so the image data is stored in uncompressed deflate blocks.
+//! That keeps the encoder small and lets it stream images of any size.
+
+use crate::{analyze, formatting, Algorithm};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The largest amount of data in one uncompressed deflate block.
+const MAX_STORED_BLOCK: usize = 65_535;
+
+/// How bytes are mapped to pixels.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ColorType {
+    Gray,
+    Rgb,
+}
+
+impl ColorType {
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            ColorType::Gray => 1,
+            ColorType::Rgb => 3,
+        }
+    }
+
+    /// The color type value in the PNG header.
+    fn png_color_type(self) -> u8 {
+        match self {
+            ColorType::Gray => 0,
+            ColorType::Rgb => 2,
+        }
+    }
+}
+
+impl std::str::FromStr for ColorType {
+    type Err = ParseColorTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gray" => Ok(ColorType::Gray),
+            "rgb" => Ok(ColorType::Rgb),
+            _ => Err(ParseColorTypeError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseColorTypeError(());
+
+impl fmt::Display for ParseColorTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid color type. See --help for a list of valid options."
+        )
+    }
+}
+
+/// Renders the data in `file`, or the output of the `generate` algorithm, as a PNG image and
+/// writes it to `out`. Generates with the default algorithm if neither is given.
+pub fn run(
+    file: Option<&Path>,
+    generate: Option<Algorithm>,
+    width: u32,
+    height: u32,
+    color: ColorType,
+    out: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if width == 0 || height == 0 {
+        return Err("The image width and height must be at least 1".into());
+    }
+    let generate = match (file, generate) {
+        (None, None) => Some(Algorithm::Default),
+        (_, generate) => generate,
+    };
+    let input = analyze::open_input(file, generate)?;
+    let output =
+        fs::File::create(out).map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+    let mut output = BufWriter::new(output);
+    write_png(&mut output, width, height, color, input)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes a PNG image with the pixel data read from `pixels`, row by row. Only failures to
+/// write are returned as `io::Error`s.
+pub fn write_png(
+    mut writer: impl Write,
+    width: u32,
+    height: u32,
+    color: ColorType,
+    mut pixels: impl Read,
+) -> Result<(), Box<dyn Error>> {
+    writer.write_all(&PNG_SIGNATURE)?;
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, the color type, deflate compression, adaptive filtering and no interlacing
+    header.extend_from_slice(&[8, color.png_color_type(), 0, 0, 0]);
+    write_chunk(&mut writer, b"IHDR", &header)?;
+
+    let mut image_data = ImageDataWriter::new(&mut writer);
+    let mut row = vec![0u8; width as usize * color.bytes_per_pixel()];
+    for _ in 0..height {
+        pixels.read_exact(&mut row).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                format!("Not enough data for a {}x{} image", width, height)
+            }
+            _ => format!("Failed to read the pixel data: {}", e),
+        })?;
+        // Every row starts with its filter type. Random data gains nothing from filtering
+        image_data.write_all(&[0])?;
+        image_data.write_all(&row)?;
+    }
+    image_data.finish()?;
+
+    write_chunk(&mut writer, b"IEND", &[])?;
+    Ok(())
+}
+
+fn write_chunk(mut writer: impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len()).expect("Chunks are small");
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = formatting::crc32(formatting::crc32(0, kind), data);
+    writer.write_all(&crc.to_be_bytes())
+}
+
+/// Writes a zlib stream of uncompressed deflate blocks, one block per IDAT chunk.
+struct ImageDataWriter<W: Write> {
+    writer: W,
+    block: Vec<u8>,
+    adler: u32,
+    started: bool,
+}
+
+impl<W: Write> ImageDataWriter<W> {
+    fn new(writer: W) -> Self {
+        ImageDataWriter {
+            writer,
+            block: Vec::with_capacity(MAX_STORED_BLOCK),
+            adler: 1,
+            started: false,
+        }
+    }
+
+    fn write_block(&mut self, last: bool) -> io::Result<()> {
+        let mut chunk = Vec::with_capacity(self.block.len() + 11);
+        if !self.started {
+            // The zlib header. Deflate with a 32 KiB window and no preset dictionary
+            chunk.extend_from_slice(&[0x78, 0x01]);
+            self.started = true;
+        }
+        let len = self.block.len() as u16;
+        chunk.push(u8::from(last));
+        chunk.extend_from_slice(&len.to_le_bytes());
+        chunk.extend_from_slice(&(!len).to_le_bytes());
+        chunk.extend_from_slice(&self.block);
+        if last {
+            chunk.extend_from_slice(&self.adler.to_be_bytes());
+        }
+        self.block.clear();
+        write_chunk(&mut self.writer, b"IDAT", &chunk)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.write_block(true)
+    }
+}
+
+impl<W: Write> Write for ImageDataWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.block.len() == MAX_STORED_BLOCK {
+            self.write_block(false)?;
+        }
+        let len = buf.len().min(MAX_STORED_BLOCK - self.block.len());
+        self.block.extend_from_slice(&buf[..len]);
+        self.adler = adler32(self.adler, &buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Continues the Adler-32 checksum `adler` over `data`.
+fn adler32(adler: u32, data: &[u8]) -> u32 {
+    const MOD: u32 = 65_521;
+    let (mut a, mut b) = (adler & 0xffff, adler >> 16);
+    for &byte in data {
+        a = (a + u32::from(byte)) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
+
+#[test]
+fn test_png() {
+    assert_eq!(adler32(1, b"Wikipedia"), 0x11e6_0398);
+
+    let mut png = Vec::new();
+    write_png(&mut png, 2, 1, ColorType::Rgb, &[1, 2, 3, 4, 5, 6][..]).unwrap();
+    let expected: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, // Signature
+        0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52, // IHDR
+        0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, //
+        0x7b, 0x40, 0xe8, 0xdd, // IHDR CRC
+        0x00, 0x00, 0x00, 0x12, 0x49, 0x44, 0x41, 0x54, // IDAT
+        0x78, 0x01, 0x01, 0x07, 0x00, 0xf8, 0xff, // zlib header and stored block header
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, // Filter type and pixels
+        0x00, 0x3f, 0x00, 0x16, // Adler-32
+        0x68, 0x41, 0x5f, 0x8d, // IDAT CRC
+        0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82, // IEND
+    ];
+    assert_eq!(png, expected);
+
+    // Large enough to span several deflate blocks
+    let data = vec![7; 300 * 300];
+    let mut png = Vec::new();
+    write_png(&mut png, 300, 300, ColorType::Gray, &data[..]).unwrap();
+    let idat_chunks = png.windows(4).filter(|kind| kind == b"IDAT").count();
+    assert_eq!(idat_chunks, 2);
+
+    let mut png = Vec::new();
+    assert!(write_png(&mut png, 300, 301, ColorType::Gray, &data[..]).is_err());
+}
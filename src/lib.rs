@@ -0,0 +1,1509 @@
+//! The generation, threading and output plumbing behind the `rng` command line tool. The
+//! `Generator` builder writes the output of any of the algorithms to a `Write` sink. The `rng`
+//! binary is a thin command line frontend on top of this library. The `ffi` module exposes the
+//! algorithms to C.
+//!
+//! ```
+//! use rng_core::seed::Seed;
+//! use rng_core::{Algorithm, Generator};
+//!
+//! let mut data = Vec::new();
+//! Generator::builder()
+//!     .algorithm(Algorithm::ChaCha20)
+//!     .seed(Seed::Number(1234))
+//!     .threads(2)
+//!     .limit(4096)
+//!     .build()
+//!     .unwrap()
+//!     .generate(&mut data)
+//!     .unwrap();
+//! assert_eq!(data.len(), 4096);
+//! ```
+
+use rand::{RngCore, SeedableRng};
+use reseed::{ReseedInterval, ReseedingRng};
+use seed::Seed;
+use seek::{BoxedSeek, Seek};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+pub mod combine;
+pub mod exit;
+pub mod ffi;
+mod generator;
+pub mod outputs;
+#[cfg(windows)]
+pub mod overlapped;
+pub mod parsing;
+pub mod platform;
+pub mod reseed;
+pub mod rngs;
+pub mod seed;
+pub mod seek;
+pub mod sources;
+#[cfg(target_os = "linux")]
+pub mod splice;
+
+pub use generator::{BuildError, Builder, Generator};
+
+/// The number of bytes to handle in each generate-write iteration, unless --buffer-size is given.
+pub const BUFFER_SIZE: usize = 64 * 1024;
+pub const MIN_BUFFER_SIZE: u64 = 4 * 1024;
+pub const MAX_BUFFER_SIZE: u64 = 256 * 1024 * 1024;
+
+/// A heap allocated output buffer, or one mapped from huge pages. Cleared when dropped, so
+/// generated data does not linger in freed memory.
+enum Buffer {
+    Heap(Box<[u8]>),
+    #[cfg(target_os = "linux")]
+    HugePages(platform::Pages),
+}
+
+impl Buffer {
+    fn new(len: usize) -> Self {
+        Buffer::Heap(vec![0u8; len].into_boxed_slice())
+    }
+
+    /// Allocates a buffer from huge pages if `huge_pages` is set and they can be mapped.
+    fn allocate(len: usize, huge_pages: bool) -> Self {
+        #[cfg(target_os = "linux")]
+        if huge_pages {
+            if let Ok(pages) = platform::map_huge_pages(len) {
+                return Buffer::HugePages(pages);
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = huge_pages;
+        Buffer::new(len)
+    }
+}
+
+impl std::ops::Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Buffer::Heap(buf) => buf,
+            #[cfg(target_os = "linux")]
+            Buffer::HugePages(pages) => pages,
+        }
+    }
+}
+
+impl std::ops::DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Buffer::Heap(buf) => buf,
+            #[cfg(target_os = "linux")]
+            Buffer::HugePages(pages) => pages,
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        (**self).zeroize();
+    }
+}
+
+// We select PCG algorithm depending on platform. In order to get the best performance possible.
+// This code is copied from the implementation of `SmallRng` in the `rand` crate.
+// `SmallRng` does not guarantee it will always stick to PCG, otherwise we could use that wrapper
+// directly.
+#[cfg(all(not(target_os = "emscripten"), target_pointer_width = "64"))]
+pub type PcgRng = rand_pcg::Pcg64Mcg;
+#[cfg(not(all(not(target_os = "emscripten"), target_pointer_width = "64")))]
+pub type PcgRng = rand_pcg::Pcg32;
+
+// `Pcg64Mcg` is a multiplicative generator and has no stream parameter. So --stream-id uses the
+// regular 128 bit LCG based PCG instead on platforms where `PcgRng` is `Pcg64Mcg`.
+#[cfg(all(not(target_os = "emscripten"), target_pointer_width = "64"))]
+pub type PcgStreamRng = rand_pcg::Pcg64;
+#[cfg(not(all(not(target_os = "emscripten"), target_pointer_width = "64")))]
+pub type PcgStreamRng = rand_pcg::Pcg32;
+
+/// Creates the PCG generator for stream `stream_id` of the given seed. All streams of a seed start
+/// from the same state, but use different LCG increments and thus produce unrelated sequences.
+pub fn new_pcg_stream(seed: seed::Seed, stream_id: u64) -> PcgStreamRng {
+    let mut state = [0u8; 16];
+    seed.fill_bytes(&mut state);
+    #[cfg(all(not(target_os = "emscripten"), target_pointer_width = "64"))]
+    let rng = PcgStreamRng::new(u128::from_le_bytes(state), u128::from(stream_id));
+    #[cfg(not(all(not(target_os = "emscripten"), target_pointer_width = "64")))]
+    let rng = {
+        let mut state64 = [0u8; 8];
+        state64.copy_from_slice(&state[..8]);
+        PcgStreamRng::new(u64::from_le_bytes(state64), stream_id)
+    };
+    rng
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Algorithm {
+    /// A generator from a plugin library at the given path. See `rngs::Plugin`.
+    Plugin(&'static Path),
+    Default,
+    Hc,
+    ChaCha8,
+    ChaCha12,
+    ChaCha20,
+    XorShift,
+    Pcg,
+    RomuTrio,
+    RomuDuoJr,
+    Lfsr,
+    LaggedFibonacci,
+    CtrDrbg,
+    HmacDrbg,
+    Os,
+    Rdrand,
+    Rdseed,
+    Hwrng,
+    Tpm,
+    Jitter,
+    Stdin,
+}
+
+/// How suitable an algorithm is for security sensitive uses.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SecurityClass {
+    /// A cryptographically secure PRNG.
+    Csprng,
+    /// A fast generator with good statistical quality, but predictable from its output.
+    NonCryptographic,
+    /// An educational generator with known poor statistical quality.
+    Poor,
+    /// Reads randomness from an external entropy source rather than computing it.
+    EntropySource,
+}
+
+impl fmt::Display for SecurityClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SecurityClass::Csprng => "CSPRNG",
+            SecurityClass::NonCryptographic => "non-cryptographic",
+            SecurityClass::Poor => "statistically poor",
+            SecurityClass::EntropySource => "entropy source",
+        })
+    }
+}
+
+/// How fast an algorithm is relative to the others, from `rng bench` on a typical x86_64 CPU.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum SpeedClass {
+    /// Several GiB/s per thread.
+    VeryFast,
+    /// More than a GiB/s per thread.
+    Fast,
+    /// Hundreds of MiB/s.
+    Medium,
+    /// Less than that.
+    Slow,
+}
+
+impl fmt::Display for SpeedClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SpeedClass::VeryFast => "very fast",
+            SpeedClass::Fast => "fast",
+            SpeedClass::Medium => "medium",
+            SpeedClass::Slow => "slow",
+        })
+    }
+}
+
+/// What is known about an algorithm. See `ALGORITHMS`.
+pub struct AlgorithmInfo {
+    pub algorithm: Algorithm,
+    /// The command line argument selecting the algorithm.
+    pub name: &'static str,
+    /// Other arguments also selecting it.
+    pub aliases: &'static [&'static str],
+    pub class: SecurityClass,
+    /// The size of the generator state in bits, or `None` for entropy sources.
+    pub state_bits: Option<u32>,
+    /// `None` when it depends on something outside of rng, like for stdin.
+    pub speed: Option<SpeedClass>,
+    /// How to create the generator, or `None` for entropy sources.
+    pub constructor: Option<Constructor>,
+    /// Whether the algorithm takes a personalization string when instantiated.
+    pub personalization: bool,
+}
+
+/// Creates the generators of an algorithm that computes its output, so the code running an
+/// algorithm chosen at run time looks it up in `ALGORITHMS` instead of matching on it.
+#[derive(Copy, Clone)]
+pub struct Constructor {
+    /// The width of the seed in bytes.
+    pub seed_len: usize,
+    /// A new instance seeded with the seed, or from the OS without one.
+    pub new: fn(Option<Seed>) -> Box<dyn RngCore>,
+    /// Like `new`, but reseeding itself from the OS at the interval.
+    pub new_reseeding: fn(Option<Seed>, ReseedInterval) -> Box<dyn RngCore>,
+    /// A new seeded instance that can fast-forward its output stream, if the algorithm can.
+    /// See the `seek` module.
+    pub new_seekable: Option<fn(Seed) -> BoxedSeek>,
+}
+
+impl Constructor {
+    const fn userspace<R: SeedableRng + RngCore + 'static>() -> Self {
+        fn new<R: SeedableRng + RngCore + 'static>(seed: Option<Seed>) -> Box<dyn RngCore> {
+            match seed {
+                None => Box::new(R::from_entropy()),
+                Some(seed) => Box::new(seed.new_rng::<R>()),
+            }
+        }
+        fn new_reseeding<R: SeedableRng + RngCore + 'static>(
+            seed: Option<Seed>,
+            interval: ReseedInterval,
+        ) -> Box<dyn RngCore> {
+            let rng = match seed {
+                None => R::from_entropy(),
+                Some(seed) => seed.new_rng::<R>(),
+            };
+            Box::new(ReseedingRng::new(rng, interval))
+        }
+        Constructor {
+            seed_len: std::mem::size_of::<R::Seed>(),
+            new: new::<R>,
+            new_reseeding: new_reseeding::<R>,
+            new_seekable: None,
+        }
+    }
+
+    const fn seekable<R: SeedableRng + RngCore + Seek + 'static>() -> Self {
+        fn new_seekable<R: SeedableRng + RngCore + Seek + 'static>(seed: Seed) -> BoxedSeek {
+            BoxedSeek(Box::new(seed.new_rng::<R>()))
+        }
+        Constructor {
+            new_seekable: Some(new_seekable::<R>),
+            ..Self::userspace::<R>()
+        }
+    }
+}
+
+/// All algorithms except plugins, in the order `rng list` shows them. Parsing, formatting, the
+/// properties of `Algorithm` and the creation of the generators are all looked up here. The
+/// default algorithm is listed, but is only selected by not naming an algorithm.
+pub const ALGORITHMS: &[AlgorithmInfo] = &[
+    AlgorithmInfo {
+        algorithm: Algorithm::Default,
+        name: "default",
+        aliases: &[],
+        class: SecurityClass::Csprng,
+        state_bits: Some(384),
+        speed: Some(SpeedClass::Fast),
+        constructor: Some(Constructor::userspace::<rand::rngs::StdRng>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Hc,
+        name: "hc",
+        aliases: &[],
+        class: SecurityClass::Csprng,
+        state_bits: Some(32768),
+        speed: Some(SpeedClass::Fast),
+        constructor: Some(Constructor::userspace::<rand_hc::Hc128Rng>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::ChaCha8,
+        name: "chacha8",
+        aliases: &[],
+        class: SecurityClass::Csprng,
+        state_bits: Some(384),
+        speed: Some(SpeedClass::Fast),
+        constructor: Some(Constructor::seekable::<rand_chacha::ChaCha8Rng>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::ChaCha12,
+        name: "chacha12",
+        aliases: &[],
+        class: SecurityClass::Csprng,
+        state_bits: Some(384),
+        speed: Some(SpeedClass::Fast),
+        constructor: Some(Constructor::seekable::<rand_chacha::ChaCha12Rng>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::ChaCha20,
+        name: "chacha20",
+        aliases: &["chacha"],
+        class: SecurityClass::Csprng,
+        state_bits: Some(384),
+        speed: Some(SpeedClass::Fast),
+        constructor: Some(Constructor::seekable::<rand_chacha::ChaCha20Rng>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::XorShift,
+        name: "xorshift",
+        aliases: &[],
+        class: SecurityClass::NonCryptographic,
+        state_bits: Some(128),
+        speed: Some(SpeedClass::Fast),
+        constructor: Some(Constructor::userspace::<rand_xorshift::XorShiftRng>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Pcg,
+        name: "pcg",
+        aliases: &[],
+        class: SecurityClass::NonCryptographic,
+        state_bits: Some(128),
+        speed: Some(SpeedClass::VeryFast),
+        constructor: Some(Constructor::seekable::<PcgRng>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::RomuTrio,
+        name: "romutrio",
+        aliases: &[],
+        class: SecurityClass::NonCryptographic,
+        state_bits: Some(192),
+        speed: Some(SpeedClass::VeryFast),
+        constructor: Some(Constructor::userspace::<rngs::RomuTrio>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::RomuDuoJr,
+        name: "romuduojr",
+        aliases: &[],
+        class: SecurityClass::NonCryptographic,
+        state_bits: Some(128),
+        speed: Some(SpeedClass::VeryFast),
+        constructor: Some(Constructor::userspace::<rngs::RomuDuoJr>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Lfsr,
+        name: "lfsr",
+        aliases: &[],
+        class: SecurityClass::Poor,
+        state_bits: Some(64),
+        speed: Some(SpeedClass::Slow),
+        constructor: Some(Constructor::userspace::<rngs::Lfsr64>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::LaggedFibonacci,
+        name: "lfib",
+        aliases: &[],
+        class: SecurityClass::Poor,
+        state_bits: Some(55 * 64),
+        speed: Some(SpeedClass::Fast),
+        constructor: Some(Constructor::userspace::<rngs::LaggedFibonacci>()),
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::CtrDrbg,
+        name: "ctr-drbg",
+        aliases: &[],
+        class: SecurityClass::Csprng,
+        state_bits: Some(384),
+        speed: Some(SpeedClass::Medium),
+        constructor: Some(Constructor::userspace::<rngs::CtrDrbg>()),
+        personalization: true,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::HmacDrbg,
+        name: "hmac-drbg",
+        aliases: &[],
+        class: SecurityClass::Csprng,
+        state_bits: Some(512),
+        speed: Some(SpeedClass::Medium),
+        constructor: Some(Constructor::userspace::<rngs::HmacDrbg>()),
+        personalization: true,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Os,
+        name: "os",
+        aliases: &[],
+        class: SecurityClass::EntropySource,
+        state_bits: None,
+        speed: Some(SpeedClass::Medium),
+        constructor: None,
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Rdrand,
+        name: "rdrand",
+        aliases: &[],
+        class: SecurityClass::EntropySource,
+        state_bits: None,
+        speed: Some(SpeedClass::Medium),
+        constructor: None,
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Rdseed,
+        name: "rdseed",
+        aliases: &[],
+        class: SecurityClass::EntropySource,
+        state_bits: None,
+        speed: Some(SpeedClass::Slow),
+        constructor: None,
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Hwrng,
+        name: "hwrng",
+        aliases: &[],
+        class: SecurityClass::EntropySource,
+        state_bits: None,
+        speed: Some(SpeedClass::Slow),
+        constructor: None,
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Tpm,
+        name: "tpm",
+        aliases: &[],
+        class: SecurityClass::EntropySource,
+        state_bits: None,
+        speed: Some(SpeedClass::Slow),
+        constructor: None,
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Jitter,
+        name: "jitter",
+        aliases: &[],
+        class: SecurityClass::EntropySource,
+        state_bits: None,
+        speed: Some(SpeedClass::Slow),
+        constructor: None,
+        personalization: false,
+    },
+    AlgorithmInfo {
+        algorithm: Algorithm::Stdin,
+        name: "stdin",
+        aliases: &[],
+        class: SecurityClass::EntropySource,
+        state_bits: None,
+        speed: None,
+        constructor: None,
+        personalization: false,
+    },
+];
+
+impl std::str::FromStr for Algorithm {
+    type Err = ParseAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Arguments are only parsed once, so leaking the path to keep `Algorithm: Copy` is fine
+        if let Some(path) = s.strip_prefix("plugin:") {
+            return Ok(Algorithm::Plugin(Box::leak(
+                PathBuf::from(path).into_boxed_path(),
+            )));
+        }
+        // The default algorithm is only selected by not giving one
+        ALGORITHMS
+            .iter()
+            .filter(|info| info.algorithm != Algorithm::Default)
+            .find(|info| info.name == s || info.aliases.contains(&s))
+            .map(|info| info.algorithm)
+            .ok_or(ParseAlgorithmError(()))
+    }
+}
+
+/// Formats the algorithm as the command line argument selecting it.
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.info() {
+            Some(info) => f.write_str(info.name),
+            None => match self {
+                Algorithm::Plugin(path) => write!(f, "plugin:{}", path.display()),
+                _ => unreachable!("All other algorithms are in ALGORITHMS"),
+            },
+        }
+    }
+}
+
+impl Algorithm {
+    /// Returns what is known about the algorithm, or `None` for plugins.
+    pub fn info(self) -> Option<&'static AlgorithmInfo> {
+        ALGORITHMS.iter().find(|info| info.algorithm == self)
+    }
+
+    /// Returns true for the educational generators with known poor statistical quality.
+    pub fn is_statistically_poor(self) -> bool {
+        self.info()
+            .is_some_and(|info| info.class == SecurityClass::Poor)
+    }
+
+    /// Returns true for the algorithms that read randomness from an external entropy source
+    /// rather than computing it. These can't be seeded and always run single threaded.
+    pub fn is_entropy_source(self) -> bool {
+        self.info()
+            .is_some_and(|info| info.class == SecurityClass::EntropySource)
+    }
+
+    /// Returns true for the algorithms that take a personalization string when instantiated.
+    pub fn supports_personalization(self) -> bool {
+        self.info().is_some_and(|info| info.personalization)
+    }
+
+    /// Returns the width of the algorithm's seed in bytes, or `None` for entropy sources.
+    pub fn seed_len(self) -> Option<usize> {
+        match self {
+            Algorithm::Plugin(_) => Some(rngs::PLUGIN_SEED_LEN),
+            _ => self.constructor().map(|constructor| constructor.seed_len),
+        }
+    }
+
+    /// Returns true if the algorithm can fast-forward its output stream. See the `seek` module.
+    pub fn is_seekable(self) -> bool {
+        self.constructor()
+            .is_some_and(|constructor| constructor.new_seekable.is_some())
+    }
+
+    /// Returns how to create the generator, or `None` for entropy sources and plugins.
+    pub fn constructor(self) -> Option<Constructor> {
+        self.info().and_then(|info| info.constructor)
+    }
+}
+
+#[test]
+fn test_algorithm_registry() {
+    for info in ALGORITHMS {
+        if info.algorithm != Algorithm::Default {
+            assert_eq!(info.name.parse::<Algorithm>().unwrap(), info.algorithm);
+        }
+        assert_eq!(info.algorithm.to_string(), info.name);
+        for alias in info.aliases {
+            assert_eq!(alias.parse::<Algorithm>().unwrap(), info.algorithm);
+        }
+        // Only the generators computing their output have a state and a seed
+        let entropy_source = info.class == SecurityClass::EntropySource;
+        assert_eq!(info.state_bits.is_none(), entropy_source);
+        assert_eq!(info.algorithm.seed_len().is_none(), entropy_source);
+    }
+    assert_eq!(Algorithm::ChaCha8.seed_len(), Some(32));
+    assert_eq!(Algorithm::Pcg.seed_len(), Some(std::mem::size_of::<u128>()));
+    // The constructors create the generator of the algorithm
+    let constructor = Algorithm::XorShift.constructor().unwrap();
+    let mut expected = rand_xorshift::XorShiftRng::seed_from_u64(9);
+    assert_eq!(
+        (constructor.new)(Some(seed::Seed::Number(9))).next_u64(),
+        expected.next_u64()
+    );
+    assert!(constructor.new_seekable.is_none());
+    assert!(Algorithm::ChaCha20.is_seekable());
+    let plugin = "plugin:/lib/rng.so".parse::<Algorithm>().unwrap();
+    assert_eq!(plugin, Algorithm::Plugin(Path::new("/lib/rng.so")));
+    assert_eq!(plugin.to_string(), "plugin:/lib/rng.so");
+    assert!("chacha9".parse::<Algorithm>().is_err());
+    assert!("default".parse::<Algorithm>().is_err());
+}
+
+#[derive(Debug)]
+pub struct ParseAlgorithmError(());
+
+impl fmt::Display for ParseAlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid algorithm. See --help for a list of valid options."
+        )
+    }
+}
+
+pub mod multithreaded {
+    use super::{Algorithm, Buffer};
+    use crate::outputs::{MappedRegion, PositionedTarget};
+    use crate::reseed::ReseedInterval;
+    use crate::rngs::Drbg;
+    use crate::seed::Seed;
+    use crate::seek::Seek;
+    use crossbeam_channel::{Receiver, Sender};
+    use rand::RngCore;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use zeroize::Zeroizing;
+
+    /// A buffer and the index of the block of output it holds.
+    type Block = (u64, Buffer);
+
+    /// With `--adaptive`, a worker thread is stopped after the writer has found the queue full
+    /// this many times in a row.
+    const ADAPTIVE_PATIENCE: u32 = 16;
+
+    /// How the generation is split over threads.
+    #[derive(Debug, Clone)]
+    pub struct Config {
+        /// The maximum number of worker threads.
+        pub threads: usize,
+        /// The size of the buffers the workers fill.
+        pub buffer_size: usize,
+        /// How many filled buffers can wait for the writer. The default depends on the mode.
+        pub queue_depth: Option<usize>,
+        /// The CPUs to pin the threads to. The writing thread has the first one.
+        pub pin_cpus: Option<Vec<usize>>,
+        /// Stop worker threads when the writer can't keep up with them.
+        pub adaptive: bool,
+        /// Allocate the buffers from huge pages.
+        pub huge_pages: bool,
+        /// Generate directly into this part of a memory mapped output instead of into buffers.
+        pub mapping: Option<MappedRegion>,
+        /// Have the worker threads write to their own parts of this file themselves.
+        pub positioned: Option<PositionedTarget>,
+        pub verbose: bool,
+    }
+
+    /// Pins the calling worker thread, numbered from one, to its CPU, if pinning is enabled.
+    fn pin_worker(cpus: &Option<Vec<usize>>, worker: usize) {
+        if let Some(cpus) = cpus {
+            let cpu = cpus[worker % cpus.len()];
+            if let Err(e) = crate::platform::pin_thread(cpu) {
+                eprintln!(
+                    "Failed to pin worker thread {} to CPU {}: {}",
+                    worker, cpu, e
+                );
+            }
+        }
+    }
+
+    pub fn run<F: FnMut(&[&[u8]]) -> bool>(
+        algorithm: Algorithm,
+        config: &Config,
+        reseed: Option<ReseedInterval>,
+        write_fn: F,
+    ) {
+        let constructor = algorithm
+            .constructor()
+            .unwrap_or_else(|| panic!("{:?} does not support multithreaded mode", algorithm));
+        match reseed {
+            None => run_with(move || (constructor.new)(None), config, write_fn),
+            Some(interval) => {
+                let new_rng = move || (constructor.new_reseeding)(None, interval);
+                run_with(new_rng, config, write_fn)
+            }
+        }
+    }
+
+    /// Runs the XOR combination of the given user-space algorithms. See `combine::XorRng`.
+    pub fn run_combined<F: FnMut(&[&[u8]]) -> bool>(
+        algorithms: Vec<Algorithm>,
+        config: &Config,
+        write_fn: F,
+    ) {
+        let new_rng = move || crate::combine::XorRng::from_userspace(&algorithms, None);
+        run_with(new_rng, config, write_fn);
+    }
+
+    /// Runs a seeded algorithm that supports seeking on multiple threads, with output identical
+    /// to the single threaded mode. The output stream is split into buffer sized blocks, each
+    /// generated by a new generator instance seeked to the position of the block. The blocks
+    /// are written in order, so the output does not depend on the number of threads.
+    pub fn run_seeded<F: FnMut(&[&[u8]]) -> bool>(
+        algorithm: Algorithm,
+        seed: Seed,
+        skip: u128,
+        config: &Config,
+        write_fn: F,
+    ) {
+        let new_seekable = algorithm
+            .constructor()
+            .and_then(|constructor| constructor.new_seekable)
+            .unwrap_or_else(|| panic!("{:?} does not support seeking", algorithm));
+        run_seeded_with(move || new_seekable(seed), skip, config, write_fn);
+    }
+
+    /// Like `run_seeded`, but with the generator for every block created by `new_rng`. It must
+    /// create identical generators at the start of the output stream every time.
+    pub fn run_seeded_with<R, N, F>(new_rng: N, skip: u128, config: &Config, mut write_fn: F)
+    where
+        R: RngCore + Seek,
+        N: Fn() -> R + Clone + Send + 'static,
+        F: FnMut(&[&[u8]]) -> bool,
+    {
+        if config.mapping.is_some() || config.positioned.is_some() {
+            let buffer_size = config.buffer_size;
+            let new_fill = move || {
+                let new_rng = new_rng.clone();
+                move |index: u64, block: &mut [u8]| {
+                    let position = skip + u128::from(index) * buffer_size as u128;
+                    let mut rng = new_rng();
+                    match rng.seek_bytes(position) {
+                        0 => rng.fill_bytes(block),
+                        offset => {
+                            let mut scratch =
+                                Zeroizing::new(vec![0u8; block.len() + R::STEP_BYTES as usize]);
+                            rng.fill_bytes(&mut scratch);
+                            block.copy_from_slice(&scratch[offset..][..block.len()]);
+                        }
+                    }
+                }
+            };
+            return run_direct(new_fill, config, write_fn);
+        }
+        // Blocks are passed around together with their index in the output stream. A fixed
+        // number of buffers circulate between the writer and the workers, which bounds how far
+        // ahead of the writer the workers can get.
+        let window = config.queue_depth.unwrap_or(2 * config.threads);
+        let buffer_size = config.buffer_size;
+        let (job_sender, job_receiver) = crossbeam_channel::bounded::<Block>(window);
+        let (done_sender, done_receiver) = crossbeam_channel::bounded::<Block>(window);
+        for index in 0..window as u64 {
+            let buf = Buffer::allocate(buffer_size, config.huge_pages);
+            job_sender.send((index, buf)).expect("Fits in the channel");
+        }
+        let threads: Vec<_> = (0..config.threads)
+            .map(|i| {
+                if config.verbose {
+                    eprintln!("Spawning worker thread {}", i + 1);
+                }
+                let job_receiver = job_receiver.clone();
+                let done_sender = done_sender.clone();
+                let new_rng = new_rng.clone();
+                let pin_cpus = config.pin_cpus.clone();
+                thread::spawn(move || {
+                    pin_worker(&pin_cpus, i + 1);
+                    let mut scratch =
+                        Zeroizing::new(vec![0u8; buffer_size + R::STEP_BYTES as usize]);
+                    for (index, mut buf) in job_receiver {
+                        let position = skip + u128::from(index) * buffer_size as u128;
+                        let mut rng = new_rng();
+                        match rng.seek_bytes(position) {
+                            0 => rng.fill_bytes(&mut buf),
+                            offset => {
+                                rng.fill_bytes(&mut scratch);
+                                buf.copy_from_slice(&scratch[offset..][..buffer_size]);
+                            }
+                        }
+                        if done_sender.send((index, buf)).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(done_sender);
+
+        // Blocks that are done but can't be written until the blocks before them are
+        let mut pending = BTreeMap::new();
+        let mut next_write = 0;
+        let mut next_job = window as u64;
+        let mut batch = Vec::with_capacity(window);
+        for (index, buf) in &done_receiver {
+            pending.insert(index, buf);
+            while let Some(buf) = pending.remove(&next_write) {
+                batch.push(buf);
+                next_write += 1;
+            }
+            if batch.is_empty() {
+                continue;
+            }
+            let bufs: Vec<&[u8]> = batch.iter().map(|buf| &**buf).collect();
+            if write_fn(&bufs) {
+                break;
+            }
+            for buf in batch.drain(..) {
+                let _ = job_sender.send((next_job, buf));
+                next_job += 1;
+            }
+        }
+        drop(job_sender);
+        drop(done_receiver);
+        for thread in threads {
+            thread.join().expect("Worker threads don't panic");
+        }
+    }
+
+    /// Runs a DRBG instantiated with the given personalization string in every worker thread.
+    pub fn run_personalized<F: FnMut(&[&[u8]]) -> bool>(
+        algorithm: Algorithm,
+        personalization: Vec<u8>,
+        config: &Config,
+        write_fn: F,
+    ) {
+        let run_fn = match algorithm {
+            Algorithm::CtrDrbg => run_drbg::<crate::rngs::CtrDrbg, F>,
+            Algorithm::HmacDrbg => run_drbg::<crate::rngs::HmacDrbg, F>,
+            _ => panic!("{:?} does not take a personalization string", algorithm),
+        };
+        run_fn(personalization, config, write_fn);
+    }
+
+    fn run_drbg<R: Drbg + RngCore + 'static, F: FnMut(&[&[u8]]) -> bool>(
+        personalization: Vec<u8>,
+        config: &Config,
+        write_fn: F,
+    ) {
+        let new_rng = move || R::with_personalization(None, &personalization);
+        run_with(new_rng, config, write_fn);
+    }
+
+    /// Decides when `--adaptive` stops a worker thread, from how often the writer finds the queue
+    /// full.
+    #[derive(Debug, Default)]
+    struct Scaler {
+        /// How many times in a row the queue was full.
+        full_queue_count: u32,
+    }
+
+    impl Scaler {
+        /// Records whether the queue was full when the writer came for the next buffer. Returns
+        /// true if one of the `running` workers should stop. The last worker never stops.
+        fn should_stop_worker(&mut self, queue_full: bool, running: usize) -> bool {
+            self.full_queue_count = match queue_full {
+                true => self.full_queue_count + 1,
+                false => 0,
+            };
+            if self.full_queue_count >= ADAPTIVE_PATIENCE && running > 1 {
+                self.full_queue_count = 0;
+                return true;
+            }
+            false
+        }
+    }
+
+    /// Runs the multithreaded generation where each worker thread creates its own generator
+    /// instance by calling `new_rng`. `write_fn` is given all buffers that are ready at once, so
+    /// they can be written with a single vectored write.
+    pub fn run_with<R, N, F>(new_rng: N, config: &Config, mut write_fn: F)
+    where
+        R: RngCore,
+        N: Fn() -> R + Clone + Send + 'static,
+        F: FnMut(&[&[u8]]) -> bool,
+    {
+        if config.mapping.is_some() || config.positioned.is_some() {
+            let new_fill = move || {
+                let mut rng = new_rng();
+                move |_: u64, block: &mut [u8]| rng.fill_bytes(block)
+            };
+            return run_direct(new_fill, config, write_fn);
+        }
+        let queue_depth = config.queue_depth.unwrap_or(config.threads);
+        let (sender, receiver) = crossbeam_channel::bounded(queue_depth);
+        let (buf_return_sender, buf_return_receiver) =
+            crossbeam_channel::bounded(config.queue_depth.unwrap_or(config.threads.max(8)));
+        let mut threads: Vec<thread::JoinHandle<()>> = Vec::with_capacity(config.threads);
+        let mut batch = Vec::with_capacity(queue_depth + 1);
+        // The number of worker threads that should stop. The first workers to see it stop.
+        let retire = Arc::new(AtomicUsize::new(0));
+        let mut scaler = Scaler::default();
+        loop {
+            if config.adaptive {
+                threads.retain(|thread| !thread.is_finished());
+                let running = threads.len().saturating_sub(retire.load(Ordering::Relaxed));
+                if scaler.should_stop_worker(receiver.is_full(), running) {
+                    if config.verbose {
+                        eprintln!("Stopping a worker thread, {} left", running - 1);
+                    }
+                    retire.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            let buf = receiver.try_recv().unwrap_or_else(|_| {
+                if config.adaptive {
+                    // The writer is waiting, so no more workers should stop
+                    retire.store(0, Ordering::Relaxed);
+                }
+                add_worker_thread(
+                    &new_rng,
+                    &mut threads,
+                    config,
+                    &retire,
+                    &sender,
+                    &receiver,
+                    &buf_return_receiver,
+                )
+            });
+            // Write all buffers that are ready together
+            batch.push(buf);
+            batch.extend(receiver.try_iter().take(queue_depth));
+            let bufs: Vec<&[u8]> = batch.iter().map(|buf| &**buf).collect();
+            if write_fn(&bufs) {
+                break;
+            }
+            for buf in batch.drain(..) {
+                let _ = buf_return_sender.try_send(buf);
+            }
+        }
+        drop(receiver);
+        for thread in threads {
+            thread.join().expect("Worker threads don't panic");
+        }
+    }
+
+    /// Runs the generation where the worker threads put the data in the output themselves, with
+    /// `config.mapping` or `config.positioned`. `new_fill` creates a function for every worker
+    /// that fills a block with the data for the block index it is given.
+    fn run_direct<N, G, F>(new_fill: N, config: &Config, write_fn: F)
+    where
+        N: Fn() -> G + Clone + Send + 'static,
+        G: FnMut(u64, &mut [u8]),
+        F: FnMut(&[&[u8]]) -> bool,
+    {
+        match (config.mapping, &config.positioned) {
+            (Some(region), _) => run_mapped(new_fill, region, config, write_fn),
+            (None, Some(target)) => run_positioned(new_fill, target.clone(), config, write_fn),
+            (None, None) => unreachable!("Direct output is not enabled"),
+        }
+    }
+
+    /// Has the worker threads generate the blocks of `target` and write them at their offsets
+    /// in the file, so the writing is spread over the threads. The threads take buffer sized
+    /// blocks in turn and fill them with a function created by `new_fill`. `write_fn` is given
+    /// the blocks after they are written, in order. A failed write is handed to the output
+    /// through `PositionedTarget::fail`, and reported when the block is given to `write_fn`.
+    fn run_positioned<N, G, F>(
+        new_fill: N,
+        target: PositionedTarget,
+        config: &Config,
+        mut write_fn: F,
+    ) where
+        N: Fn() -> G + Clone + Send + 'static,
+        G: FnMut(u64, &mut [u8]),
+        F: FnMut(&[&[u8]]) -> bool,
+    {
+        let buffer_size = config.buffer_size as u64;
+        let blocks = target.len.div_ceil(buffer_size);
+        let queue_depth = config.queue_depth.unwrap_or(config.threads);
+        // Blocks finished out of order wait for the ones before them in their buffers. Limiting
+        // the buffers keeps the workers from getting far ahead of the slowest one
+        let max_buffers = config.threads + queue_depth;
+        let buffers = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = crossbeam_channel::bounded(queue_depth);
+        let (buf_return_sender, buf_return_receiver) = crossbeam_channel::bounded(max_buffers);
+        let next_block = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let threads: Vec<_> = (0..config.threads)
+            .map(|i| {
+                if config.verbose {
+                    eprintln!("Spawning worker thread {}", i + 1);
+                }
+                let new_fill = new_fill.clone();
+                let target = target.clone();
+                let next_block = next_block.clone();
+                let stop = stop.clone();
+                let sender = sender.clone();
+                let buffers = buffers.clone();
+                let buf_return_receiver: Receiver<Buffer> = buf_return_receiver.clone();
+                let pin_cpus = config.pin_cpus.clone();
+                let huge_pages = config.huge_pages;
+                thread::spawn(move || {
+                    pin_worker(&pin_cpus, i + 1);
+                    let mut fill = new_fill();
+                    loop {
+                        crate::platform::wait_while_paused(|| stop.load(Ordering::Relaxed));
+                        if stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        // The buffer is taken before the block, so the first unfinished block
+                        // always has one
+                        let buf = match buf_return_receiver.try_recv() {
+                            Ok(buf) => Ok(buf),
+                            Err(_) if buffers.fetch_add(1, Ordering::Relaxed) < max_buffers => {
+                                Ok(Buffer::allocate(buffer_size as usize, huge_pages))
+                            }
+                            Err(_) => buf_return_receiver.recv(),
+                        };
+                        let Ok(mut buf) = buf else {
+                            break;
+                        };
+                        let index = next_block.fetch_add(1, Ordering::Relaxed);
+                        if index >= blocks {
+                            break;
+                        }
+                        let offset = index * buffer_size;
+                        let len = buffer_size.min(target.len - offset) as usize;
+                        fill(index, &mut buf[..len]);
+                        let result = crate::platform::write_all_at(
+                            &target.file,
+                            &buf[..len],
+                            target.start + offset,
+                        );
+                        if sender.send((index, result, buf, len)).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        // Blocks that are written but can't be passed on until the blocks before them are
+        let mut done = BTreeMap::new();
+        let mut next = 0;
+        'receive: for (index, result, buf, len) in &receiver {
+            done.insert(index, (result, buf, len));
+            while let Some((result, buf, len)) = done.remove(&next) {
+                next += 1;
+                if let Err(e) = result {
+                    stop.store(true, Ordering::Relaxed);
+                    target.fail(e);
+                }
+                if write_fn(&[&buf[..len]]) {
+                    break 'receive;
+                }
+                let _ = buf_return_sender.try_send(buf);
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+        drop(receiver);
+        drop(buf_return_sender);
+        for thread in threads {
+            thread.join().expect("Worker threads don't panic");
+        }
+    }
+
+    /// Generates the output directly into `region` of a memory mapped output. The region is
+    /// split into buffer sized blocks, which the worker threads take in turn and fill with a
+    /// function created by `new_fill`. It is given the index of the block. `write_fn` is given
+    /// the finished blocks in order, already in place in the output.
+    fn run_mapped<N, G, F>(new_fill: N, region: MappedRegion, config: &Config, mut write_fn: F)
+    where
+        N: Fn() -> G + Clone + Send + 'static,
+        G: FnMut(u64, &mut [u8]),
+        F: FnMut(&[&[u8]]) -> bool,
+    {
+        let buffer_size = config.buffer_size;
+        let blocks = region.len.div_ceil(buffer_size) as u64;
+        let block_range = move |index: u64| {
+            let start = index as usize * buffer_size;
+            start..(start + buffer_size).min(region.len)
+        };
+        let next_block = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (done_sender, done_receiver) = crossbeam_channel::unbounded();
+        let threads: Vec<_> = (0..config.threads)
+            .map(|i| {
+                if config.verbose {
+                    eprintln!("Spawning worker thread {}", i + 1);
+                }
+                let new_fill = new_fill.clone();
+                let next_block = next_block.clone();
+                let stop = stop.clone();
+                let done_sender = done_sender.clone();
+                let pin_cpus = config.pin_cpus.clone();
+                thread::spawn(move || {
+                    pin_worker(&pin_cpus, i + 1);
+                    let mut fill = new_fill();
+                    loop {
+                        crate::platform::wait_while_paused(|| stop.load(Ordering::Relaxed));
+                        if stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let index = next_block.fetch_add(1, Ordering::Relaxed);
+                        if index >= blocks {
+                            break;
+                        }
+                        // Safety: Every block is taken by one worker, and only read by the
+                        // writer after the worker is done with it
+                        fill(index, unsafe { region.slice_mut(block_range(index)) });
+                        if done_sender.send(index).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(done_sender);
+
+        // Blocks that are done but can't be written until the blocks before them are
+        let mut done = BTreeSet::new();
+        let mut next_write = 0;
+        for index in &done_receiver {
+            done.insert(index);
+            let mut bufs = Vec::new();
+            while done.remove(&next_write) {
+                // Safety: The worker sent the index after it was done with the block
+                bufs.push(unsafe { region.slice(block_range(next_write)) });
+                next_write += 1;
+            }
+            if !bufs.is_empty() && write_fn(&bufs) {
+                break;
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+        drop(done_receiver);
+        for thread in threads {
+            thread.join().expect("Worker threads don't panic");
+        }
+    }
+
+    /// Spawn another worker thread producing random data.
+    /// This is cold since it will only happen a few times at the very start of the run.
+    #[cold]
+    #[inline(never)]
+    fn add_worker_thread<R: RngCore, N: Fn() -> R + Clone + Send + 'static>(
+        new_rng: &N,
+        threads: &mut Vec<thread::JoinHandle<()>>,
+        config: &Config,
+        retire: &Arc<AtomicUsize>,
+        sender: &Sender<Buffer>,
+        receiver: &Receiver<Buffer>,
+        buf_return_receiver: &Receiver<Buffer>,
+    ) -> Buffer {
+        if threads.len() < config.threads {
+            let buffer_size = config.buffer_size;
+            let worker = threads.len() + 1;
+            let huge_pages = config.huge_pages;
+            let pin_cpus = config.pin_cpus.clone();
+            let retire = retire.clone();
+            let sender = sender.clone();
+            let buf_return_receiver = buf_return_receiver.clone();
+            let new_rng = new_rng.clone();
+            threads.push(thread::spawn(move || {
+                pin_worker(&pin_cpus, worker);
+                let mut rng = new_rng();
+                loop {
+                    // Try to get a buffer from the writer thread, or allocate a new one
+                    let mut buf = buf_return_receiver
+                        .try_recv()
+                        .unwrap_or_else(|_| Buffer::allocate(buffer_size, huge_pages));
+                    rng.fill_bytes(&mut buf);
+                    if sender.send(buf).is_err() {
+                        break;
+                    }
+                    let stop = retire
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+                    if stop.is_ok() {
+                        break;
+                    }
+                }
+            }));
+            if config.verbose {
+                eprintln!("Spawning worker thread {}", threads.len());
+            }
+        }
+        receiver.recv().expect("The channel can't be closed here")
+    }
+
+    #[test]
+    fn test_scaler() {
+        let mut scaler = Scaler::default();
+        for _ in 1..ADAPTIVE_PATIENCE {
+            assert!(!scaler.should_stop_worker(true, 4));
+        }
+        assert!(scaler.should_stop_worker(true, 4));
+        // The count starts over after stopping a worker, and when the queue is not full
+        for _ in 1..ADAPTIVE_PATIENCE {
+            assert!(!scaler.should_stop_worker(true, 3));
+        }
+        assert!(!scaler.should_stop_worker(false, 3));
+        for _ in 1..ADAPTIVE_PATIENCE {
+            assert!(!scaler.should_stop_worker(true, 3));
+        }
+        assert!(scaler.should_stop_worker(true, 3));
+        // The last worker keeps running
+        for _ in 0..2 * ADAPTIVE_PATIENCE {
+            assert!(!scaler.should_stop_worker(true, 1));
+        }
+    }
+
+    #[test]
+    fn test_run_seeded() {
+        let seed = Seed::Number(42);
+        let len = 5 * crate::BUFFER_SIZE;
+        for &algorithm in &[Algorithm::ChaCha8, Algorithm::Pcg] {
+            for &skip in &[None, Some(5)] {
+                let mut reference = Vec::new();
+                crate::singlethreaded::run(
+                    algorithm,
+                    Some(seed),
+                    skip,
+                    None,
+                    crate::BUFFER_SIZE,
+                    |buf| {
+                        reference.extend_from_slice(buf);
+                        reference.len() >= len
+                    },
+                );
+                for &threads in &[2, 3] {
+                    let mut output = Vec::new();
+                    let config = Config {
+                        threads,
+                        buffer_size: crate::BUFFER_SIZE,
+                        queue_depth: None,
+                        pin_cpus: None,
+                        adaptive: false,
+                        huge_pages: false,
+                        mapping: None,
+                        positioned: None,
+                        verbose: false,
+                    };
+                    run_seeded(algorithm, seed, skip.unwrap_or(0), &config, |bufs| {
+                        let left = (len - output.len()) / crate::BUFFER_SIZE;
+                        for buf in bufs.iter().take(left) {
+                            output.extend_from_slice(buf);
+                        }
+                        output.len() >= len
+                    });
+                    assert!(output == reference);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_positioned() {
+        let seed = Seed::Number(7);
+        let len = 5 * crate::BUFFER_SIZE + 100;
+        let mut reference = Vec::new();
+        crate::singlethreaded::run(
+            Algorithm::ChaCha8,
+            Some(seed),
+            None,
+            None,
+            crate::BUFFER_SIZE,
+            |buf| {
+                reference.extend_from_slice(buf);
+                reference.len() >= len
+            },
+        );
+        reference.truncate(len);
+
+        let path = std::env::temp_dir().join(format!("rng-test-pwrite-{}", std::process::id()));
+        let mut output = crate::outputs::PositionedOutput::new(
+            std::fs::File::create(&path).unwrap(),
+            len as u64,
+        )
+        .unwrap();
+        let config = Config {
+            threads: 4,
+            buffer_size: crate::BUFFER_SIZE,
+            queue_depth: None,
+            pin_cpus: None,
+            adaptive: false,
+            huge_pages: false,
+            mapping: None,
+            positioned: Some(output.target()),
+            verbose: false,
+        };
+        // The blocks are passed on in the order of the stream
+        let mut passed = Vec::new();
+        run_seeded(Algorithm::ChaCha8, seed, 0, &config, |bufs| {
+            for buf in bufs {
+                std::io::Write::write_all(&mut output, buf).unwrap();
+                passed.extend_from_slice(buf);
+            }
+            false
+        });
+        assert!(passed == reference);
+        drop(output);
+        assert!(std::fs::read(&path).unwrap() == reference);
+
+        // Stopping early cuts the file after the blocks passed on
+        let mut output = crate::outputs::PositionedOutput::new(
+            std::fs::File::create(&path).unwrap(),
+            len as u64,
+        )
+        .unwrap();
+        let config = Config {
+            positioned: Some(output.target()),
+            ..config
+        };
+        run_seeded(Algorithm::ChaCha8, seed, 0, &config, |bufs| {
+            for buf in bufs {
+                std::io::Write::write_all(&mut output, buf).unwrap();
+            }
+            true
+        });
+        drop(output);
+        assert!(std::fs::read(&path).unwrap() == reference[..crate::BUFFER_SIZE]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_pcg_streams() {
+        let len = 3 * crate::BUFFER_SIZE;
+        let collect = |stream_id, max_threads| {
+            let new_rng = move || crate::new_pcg_stream(Seed::Number(42), stream_id);
+            let mut output = Vec::new();
+            let mut write_fn = |bufs: &[&[u8]]| {
+                for buf in bufs.iter().take((len - output.len()) / crate::BUFFER_SIZE) {
+                    output.extend_from_slice(buf);
+                }
+                output.len() >= len
+            };
+            match max_threads {
+                1 => crate::singlethreaded::run_seekable(new_rng(), 3, crate::BUFFER_SIZE, |buf| {
+                    write_fn(&[buf])
+                }),
+                threads => {
+                    let config = Config {
+                        threads,
+                        buffer_size: crate::BUFFER_SIZE,
+                        queue_depth: Some(1),
+                        pin_cpus: None,
+                        adaptive: false,
+                        huge_pages: false,
+                        mapping: None,
+                        positioned: None,
+                        verbose: false,
+                    };
+                    run_seeded_with(new_rng, 3, &config, write_fn)
+                }
+            }
+            output
+        };
+        let stream0 = collect(0, 1);
+        assert!(stream0 == collect(0, 3));
+        assert!(stream0 != collect(1, 1));
+        assert!(collect(1, 1) == collect(1, 2));
+    }
+}
+
+pub mod singlethreaded {
+    use crate::reseed::{ReseedInterval, ReseedingRng};
+    use crate::rngs::EndOfStream;
+    use crate::seed::Seed;
+    use crate::seek::Seek;
+    use crate::Algorithm;
+    use rand::{RngCore, SeedableRng};
+    use zeroize::Zeroizing;
+
+    pub fn run<F: FnMut(&[u8]) -> bool>(
+        algorithm: Algorithm,
+        seed: Option<Seed>,
+        skip: Option<u128>,
+        reseed: Option<ReseedInterval>,
+        buffer_size: usize,
+        write_fn: F,
+    ) {
+        if let (Some(seed), Some(skip)) = (seed, skip) {
+            let new_seekable = algorithm
+                .constructor()
+                .and_then(|constructor| constructor.new_seekable)
+                .unwrap_or_else(|| panic!("{:?} does not support seeking", algorithm));
+            return run_seekable(new_seekable(seed), skip, buffer_size, write_fn);
+        }
+        let constructor = algorithm
+            .constructor()
+            .unwrap_or_else(|| panic!("{:?} is not a user-space PRNG", algorithm));
+        match reseed {
+            None => generate_to_stdout((constructor.new)(seed), buffer_size, write_fn),
+            Some(interval) => {
+                let rng = (constructor.new_reseeding)(seed, interval);
+                generate_to_stdout(rng, buffer_size, write_fn)
+            }
+        }
+    }
+
+    pub fn run_userspace<R: SeedableRng + RngCore, F: FnMut(&[u8]) -> bool>(
+        seed: Option<Seed>,
+        reseed: Option<ReseedInterval>,
+        buffer_size: usize,
+        write_fn: F,
+    ) {
+        let rng = match seed {
+            None => R::from_entropy(),
+            Some(seed) => seed.new_rng::<R>(),
+        };
+        match reseed {
+            None => generate_to_stdout(rng, buffer_size, write_fn),
+            Some(interval) => {
+                generate_to_stdout(ReseedingRng::new(rng, interval), buffer_size, write_fn)
+            }
+        }
+    }
+
+    /// Fast-forwards the given generator `skip` bytes and writes its output.
+    pub fn run_seekable<F: FnMut(&[u8]) -> bool>(
+        mut rng: impl RngCore + Seek,
+        skip: u128,
+        buffer_size: usize,
+        write_fn: F,
+    ) {
+        match rng.seek_bytes(skip) {
+            0 => generate_to_stdout(rng, buffer_size, write_fn),
+            offset => generate_with_offset(rng, offset, buffer_size, write_fn),
+        }
+    }
+
+    /// Writes the output of an entropy source. See the `sources` module. Returns the data read
+    /// after the last full buffer if the source is a stream that ended, or the error if reading
+    /// from the source failed.
+    pub fn run_source<F: FnMut(&[u8]) -> bool>(
+        mut source: Box<dyn RngCore>,
+        buffer_size: usize,
+        mut write_fn: F,
+    ) -> Result<Zeroizing<Vec<u8>>, rand::Error> {
+        let mut buf = Zeroizing::new(vec![0u8; buffer_size]);
+        loop {
+            if let Err(e) = source.try_fill_bytes(&mut buf) {
+                return match EndOfStream::filled(&e) {
+                    Some(filled) => Ok(Zeroizing::new(buf[..filled].to_vec())),
+                    None => Err(e),
+                };
+            }
+            if write_fn(&buf) {
+                return Ok(Zeroizing::new(Vec::new()));
+            }
+        }
+    }
+
+    /// Given a random number generator, writes the output of it to stdout forever, or until there
+    /// is an error writing to stdout. Usually because the pipe has closed.
+    fn generate_to_stdout<F: FnMut(&[u8]) -> bool>(
+        mut rng: impl RngCore,
+        buffer_size: usize,
+        mut write_fn: F,
+    ) {
+        let mut buf = Zeroizing::new(vec![0u8; buffer_size]);
+        loop {
+            rng.fill_bytes(&mut buf);
+            if write_fn(&buf) {
+                break;
+            }
+        }
+    }
+
+    /// Like `generate_to_stdout`, but drops the first `offset` bytes of the generated stream.
+    /// Used when seeking ends in the middle of a generator step.
+    fn generate_with_offset<F: FnMut(&[u8]) -> bool>(
+        mut rng: impl RngCore,
+        offset: usize,
+        buffer_size: usize,
+        mut write_fn: F,
+    ) {
+        let mut buf = Zeroizing::new(vec![0u8; buffer_size]);
+        let mut next = Zeroizing::new(vec![0u8; buffer_size]);
+        rng.fill_bytes(&mut next);
+        loop {
+            std::mem::swap(&mut *buf, &mut *next);
+            rng.fill_bytes(&mut next);
+            buf.copy_within(offset.., 0);
+            buf[buffer_size - offset..].copy_from_slice(&next[..offset]);
+            if write_fn(&buf) {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_skip() {
+        fn collect(algorithm: Algorithm, skip: Option<u128>) -> Vec<u8> {
+            let mut output = Vec::new();
+            run(
+                algorithm,
+                Some(Seed::Number(42)),
+                skip,
+                None,
+                crate::BUFFER_SIZE,
+                |buf| {
+                    output.extend_from_slice(buf);
+                    output.len() >= 3 * crate::BUFFER_SIZE
+                },
+            );
+            output
+        }
+        for &algorithm in &[Algorithm::ChaCha8, Algorithm::ChaCha20, Algorithm::Pcg] {
+            let reference = collect(algorithm, None);
+            for &skip in &[0, 1, 4, 7, 8, 13, crate::BUFFER_SIZE + 3] {
+                let skipped = collect(algorithm, Some(skip as u128));
+                assert_eq!(
+                    skipped[..crate::BUFFER_SIZE],
+                    reference[skip..skip + crate::BUFFER_SIZE]
+                );
+            }
+        }
+    }
+}
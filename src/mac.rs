@@ -0,0 +1,124 @@
+//! The `mac` subcommand. Random MAC addresses, either locally administered or under the OUI of
+//! a virtualization platform, as some tooling checks that guests have their platform's prefix.
+
+use crate::parsing::ParseError;
+use rand::RngCore;
+use std::fmt;
+
+/// The first three bytes of a MAC address, identifying the organization that assigned it.
+pub type Oui = [u8; 3];
+
+/// Parses an OUI such as "52:54:00". It must be a unicast address, as MAC addresses of network
+/// interfaces are.
+pub fn parse_oui(s: &str) -> Result<Oui, ParseError> {
+    let invalid = || ParseError::new(format!("\"{}\" is not an OUI such as 52:54:00", s));
+    let mut oui = [0; 3];
+    let mut parts = s.split([':', '-']);
+    for byte in &mut oui {
+        let part = parts
+            .next()
+            .filter(|part| part.len() == 2)
+            .ok_or_else(invalid)?;
+        *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    if parts.next().is_some() || oui[0] & 0x01 != 0 {
+        return Err(invalid());
+    }
+    Ok(oui)
+}
+
+/// Platforms with their own MAC address prefix.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Vendor {
+    Qemu,
+    Vmware,
+    Virtualbox,
+    Xen,
+    HyperV,
+    /// Not a vendor, but any locally administered unicast address.
+    RandomLaa,
+}
+
+impl std::str::FromStr for Vendor {
+    type Err = ParseVendorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qemu" => Ok(Vendor::Qemu),
+            "vmware" => Ok(Vendor::Vmware),
+            "virtualbox" => Ok(Vendor::Virtualbox),
+            "xen" => Ok(Vendor::Xen),
+            "hyper-v" => Ok(Vendor::HyperV),
+            "random-laa" => Ok(Vendor::RandomLaa),
+            _ => Err(ParseVendorError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseVendorError(());
+
+impl fmt::Display for ParseVendorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid vendor. See --help for a list of valid options.")
+    }
+}
+
+/// Where the addresses come from.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Prefix {
+    Oui(Oui),
+    Vendor(Vendor),
+}
+
+/// A random MAC address with `prefix`.
+pub fn address(prefix: Prefix, rng: &mut dyn RngCore) -> String {
+    let mut mac = [0; 6];
+    rng.fill_bytes(&mut mac);
+    let oui = match prefix {
+        Prefix::Oui(oui) => Some(oui),
+        Prefix::Vendor(Vendor::Qemu) => Some([0x52, 0x54, 0x00]),
+        Prefix::Vendor(Vendor::Vmware) => {
+            // VMware leaves 00:50:56:00:00:00 to 00:50:56:3f:ff:ff for manually set addresses
+            mac[3] &= 0x3f;
+            Some([0x00, 0x50, 0x56])
+        }
+        Prefix::Vendor(Vendor::Virtualbox) => Some([0x08, 0x00, 0x27]),
+        Prefix::Vendor(Vendor::Xen) => Some([0x00, 0x16, 0x3e]),
+        Prefix::Vendor(Vendor::HyperV) => Some([0x00, 0x15, 0x5d]),
+        Prefix::Vendor(Vendor::RandomLaa) => {
+            // Set the locally administered bit and clear the multicast bit
+            mac[0] = (mac[0] | 0x02) & !0x01;
+            None
+        }
+    };
+    if let Some(oui) = oui {
+        mac[..3].copy_from_slice(&oui);
+    }
+    let bytes: Vec<_> = mac.iter().map(|byte| format!("{:02x}", byte)).collect();
+    bytes.join(":")
+}
+
+#[test]
+fn test_address() {
+    use rand::SeedableRng;
+
+    assert_eq!(parse_oui("52:54:00").unwrap(), [0x52, 0x54, 0x00]);
+    assert_eq!(parse_oui("00-50-56").unwrap(), [0x00, 0x50, 0x56]);
+    assert!(parse_oui("01:00:5e").is_err());
+    assert!(parse_oui("52:54").is_err());
+    assert!(parse_oui("52:54:00:01").is_err());
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    for _ in 0..100 {
+        assert!(address(Prefix::Vendor(Vendor::Qemu), &mut rng).starts_with("52:54:00:"));
+        let vmware = address(Prefix::Vendor(Vendor::Vmware), &mut rng);
+        assert!(vmware.starts_with("00:50:56:"));
+        assert!(u8::from_str_radix(&vmware[9..11], 16).unwrap() <= 0x3f);
+        let laa = address(Prefix::Vendor(Vendor::RandomLaa), &mut rng);
+        assert_eq!(u8::from_str_radix(&laa[..2], 16).unwrap() & 0x03, 0x02);
+        let oui = address(Prefix::Oui([0xaa, 0xbb, 0xcc]), &mut rng);
+        assert!(oui.starts_with("aa:bb:cc:"));
+        assert_eq!(oui.len(), 17);
+    }
+}
@@ -1,24 +1,59 @@
-use std::fmt;
-use std::fs;
-use std::io::{self, Write};
-use std::path::PathBuf;
-use std::time::Instant;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use structopt::StructOpt;
 
+mod analyze;
+mod apikey;
+mod audio;
+mod bench;
+mod checkpoint;
+mod checksum;
+mod commands;
+mod conditioning;
+mod corpus;
+mod device_test;
+#[cfg(unix)]
+mod egd;
+mod fake;
+#[cfg(target_os = "linux")]
+mod feed;
 mod formatting;
-mod platform;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+mod fuse;
+mod generate;
+mod geo;
+mod health;
+mod http;
+mod image;
+mod json;
+mod mac;
+mod metrics;
+mod nist;
+mod otp;
+mod port;
+mod progress;
+mod salt;
+mod self_check;
+mod self_test;
+mod serve;
+mod sleep;
+mod stats;
+mod ula;
+mod unique;
+mod url;
+mod verify;
+mod wipe;
 
-/// The number of bytes to handle in each generate-write iteration.
-const BUFFER_SIZE: usize = 64 * 1024;
-
-// We select PCG algorithm depending on platform. In order to get the best performance possible.
-// This code is copied from the implementation of `SmallRng` in the `rand` crate.
-// `SmallRng` does not guarantee it will always stick to PCG, otherwise we could use that wrapper
-// directly.
-#[cfg(all(not(target_os = "emscripten"), target_pointer_width = "64"))]
-type PcgRng = rand_pcg::Pcg64Mcg;
-#[cfg(not(all(not(target_os = "emscripten"), target_pointer_width = "64")))]
-type PcgRng = rand_pcg::Pcg32;
+use rng_core::exit::{self, ExitCode};
+#[cfg(windows)]
+use rng_core::overlapped;
+#[cfg(target_os = "linux")]
+use rng_core::splice;
+use rng_core::{
+    combine, multithreaded, new_pcg_stream, outputs, parsing, platform, reseed, rngs, seed, seek,
+    singlethreaded, sources, Algorithm, ALGORITHMS, BUFFER_SIZE, MAX_BUFFER_SIZE, MIN_BUFFER_SIZE,
+};
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -51,29 +86,233 @@ struct Opt {
     /// * hc - A cryptographically secure random number generator that uses the HC-128 algorithm.
     ///
     /// * chacha[8,12,20] - A cryptographically secure random number generator that uses the ChaCha
-    /// algorithm. Uses 8, 12 or 20 rounds. Defaults to 20 rounds if the number is not specified.
+    ///   algorithm. Uses 8, 12 or 20 rounds. Defaults to 20 rounds if the number is not specified.
     ///
     /// * xorshift - This algorithm is NOT suitable for cryptographic purposes, but is fast.
     ///
     /// * pcg - This algorithm is NOT considered cryptographically secure. But it has good
-    /// statistical quality and is usually the fastest algorithm in this tool.
+    ///   statistical quality and is usually the fastest algorithm in this tool.
+    ///
+    /// * romutrio, romuduojr - The Romu family of nonlinear generators. NOT suitable for
+    ///   cryptographic purposes, but extremely fast. RomuTrio is the general purpose variant,
+    ///   RomuDuoJr trades some statistical capacity for even more speed.
+    ///
+    /// * lfsr, lfib - A 64 bit linear-feedback shift register and an additive lagged Fibonacci
+    ///   generator. These are statistically POOR generators and are only included for educational
+    ///   purposes, as examples of generators that fail randomness tests.
+    ///
+    /// * ctr-drbg, hmac-drbg - CTR_DRBG and HMAC_DRBG from NIST SP 800-90A, using AES-256 with
+    ///   the derivation function and HMAC-SHA-256 respectively. For when a NIST specified
+    ///   deterministic random bit generator is required. Support a personalization string via
+    ///   --personalization.
     ///
     /// * os - A random number generator that retrieves randomness from the operating system.
-    /// Usually cryptograhically secure, but depends on the OS. Usually much slower than the
-    /// user-space PRNGs. The --seed argument can't be used with this algorithm, as the operating
-    /// system is in control of providing the data.
+    ///   Usually cryptograhically secure, but depends on the OS. Usually much slower than the
+    ///   user-space PRNGs. The --seed argument can't be used with this algorithm, as the operating
+    ///   system is in control of providing the data.
+    ///
+    /// * rdrand, rdseed - Randomness directly from the CPU's hardware random number generator,
+    ///   via the RDRAND or RDSEED instructions. Only available on x86_64 CPUs that support them.
+    ///   RDSEED gives direct access to the conditioned entropy source and is much slower than
+    ///   RDRAND. Just like "os", these can't be seeded.
+    ///
+    /// * hwrng - Reads raw bytes from a hardware true random number generator device. Defaults
+    ///   to /dev/hwrng, a different device can be given with --device. Can't be seeded.
+    ///
+    /// * tpm - Fetches randomness from a TPM 2.0 chip with the TPM2_GetRandom command. Defaults
+    ///   to the /dev/tpmrm0 device, a different device can be given with --device. Slow, and
+    ///   can't be seeded.
+    ///
+    /// * jitter - An entropy source harvesting timing variations in the CPU's execution of a
+    ///   memory access workload, similar to the jitterentropy library. Very slow, but works
+    ///   without support from the OS or special hardware. Can't be seeded.
+    ///
+    /// * plugin:<path> - A custom generator loaded from the shared library at <path>. See the
+    ///   project documentation for the small C interface the library must implement. Runs
+    ///   multithreaded and can be seeded, just like the built in user-space generators.
+    ///
+    /// * stdin - Reads raw bytes from standard input and writes them out again, after
+    ///   --debias and --whiten if given. Turns this tool into a filter for conditioning the
+    ///   output of external hardware generators. Output ends when stdin does.
     algorithm: Option<Algorithm>,
 
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    /// Runs all the algorithms given to --algorithms and combines their output with the given
+    /// method into a single stream, instead of running a single algorithm.
+    ///
+    /// Possible values are:
+    ///
+    /// * xor - XOR the output of all the algorithms together. The result is at least as
+    ///   unpredictable as the strongest algorithm in the combination.
+    ///
+    /// If any of the algorithms is an entropy source, such as "os", the combination can't be
+    /// seeded and runs single threaded. If a seed is given, each algorithm is seeded with a
+    /// different seed derived from it.
+    #[structopt(long, value_name = "method", requires = "algorithms")]
+    combine: Option<combine::CombineMethod>,
+
+    /// Comma separated list of the algorithms to combine with --combine. Takes the same values
+    /// as <algorithm>.
+    #[structopt(long, value_name = "list", use_delimiter = true, requires = "combine")]
+    algorithms: Vec<Algorithm>,
+
     /// Seeds the random number generator algorithm with a given 64 bit unsigned integer.
     /// This makes the output of the program identical for each run with the same algorithm and
     /// same seed.
     /// If this argument is not given, the PRNG will be seeded from the operating system.
     /// Specifying a seed is NOT recommended for cryptographic use.
     ///
-    /// Only single threaded operation is possible when a seed is specified.
+    /// Seeded runs of chacha8, chacha12, chacha20 and pcg use multiple threads, and produce the
+    /// same output regardless of the number of threads. Other algorithms run single threaded
+    /// when seeded.
     #[structopt(long)]
     seed: Option<u64>,
 
+    /// Seeds the random number generator algorithm with the given hex encoded bytes instead of
+    /// a 64 bit integer. Allows giving the full seed of the algorithm, such as a 256 bit ChaCha
+    /// or HC-128 key, to reproduce a stream from an externally supplied key. Shorter seeds are
+    /// padded with zero bytes.
+    ///
+    /// Behaves like --seed in all other respects. Can't be used with --combine.
+    #[structopt(
+        long,
+        value_name = "hex",
+        parse(try_from_str = seed::Seed::from_hex),
+        conflicts_with_all = &["seed", "combine"]
+    )]
+    seed_hex: Option<seed::Seed>,
+
+    /// Derives the seed from a passphrase with the Argon2id key derivation function, giving
+    /// reproducible output without having to store a raw seed. The seed fills the full seed
+    /// width of the algorithm.
+    ///
+    /// Possible values are:
+    ///
+    /// * prompt - Asks for the passphrase on the terminal, without echoing it.
+    ///
+    /// * env:<name> - Reads the passphrase from the environment variable <name>.
+    ///
+    /// * file:<path> - Reads the passphrase from the first line of the file at <path>.
+    ///
+    /// Behaves like --seed in all other respects. Can't be used with --combine.
+    #[structopt(
+        long,
+        value_name = "source",
+        conflicts_with_all = &["seed", "seed-hex", "combine"]
+    )]
+    seed_passphrase: Option<seed::PassphraseSource>,
+
+    /// The Argon2id cost parameters used by --seed-passphrase, as comma separated key=value
+    /// pairs. The keys are m (memory size, such as "64MiB"), t (iterations) and p (parallelism).
+    /// Parameters not given keep their defaults, which are "m=64MiB,t=3,p=4". Different
+    /// parameters give a different seed.
+    #[structopt(long, value_name = "params", requires = "seed-passphrase")]
+    argon2_params: Option<seed::Argon2Params>,
+
+    /// Fast-forwards the deterministic output stream this many bytes before starting to output
+    /// data. Requires --seed. The data written is identical to the tail of what the same
+    /// algorithm and seed would have produced without --skip.
+    ///
+    /// Only available for algorithms that can jump forward without generating the skipped data:
+    /// chacha8, chacha12, chacha20 and pcg.
+    #[structopt(long, value_name = "bytes")]
+    skip: Option<u64>,
+
+    /// Starts the deterministic output stream at jump point N. Jump points are 2^50 bytes
+    /// (1 PiB) apart, so parallel jobs k = 0, 1, 2... given the same seed and --jumps k each
+    /// produce a disjoint substream of the single seeded stream, as long as no job writes more
+    /// than 1 PiB. --skip then skips within the substream. Requires --seed.
+    ///
+    /// Available for the same algorithms as --skip. The number of disjoint substreams is
+    /// limited by the period of the algorithm: 2^20 for chacha and 2^16 for pcg on 32 bit
+    /// platforms. On 64 bit platforms all jump points of pcg are disjoint.
+    #[structopt(long, value_name = "N", conflicts_with = "combine")]
+    jumps: Option<u64>,
+
+    /// Periodically records the position in the seeded output stream to this file, together
+    /// with the algorithm and seed. An interrupted run can then be continued exactly where it
+    /// stopped with --resume. The file contains the seed, so protect it like the seed itself.
+    ///
+    /// Only available for the algorithms that support --skip. The checkpoint is written every
+    /// 10 seconds and when the run ends.
+    #[structopt(
+        long,
+        value_name = "path",
+        conflicts_with_all = &["combine", "debias", "whiten"]
+    )]
+    checkpoint_file: Option<PathBuf>,
+
+    /// Continues the run recorded in --checkpoint-file. The seed is taken from the checkpoint
+    /// unless given. When writing to a file with --output, the file is truncated to the data
+    /// written up until the checkpoint and appended to, instead of being overwritten.
+    #[structopt(
+        long,
+        requires = "checkpoint-file",
+        conflicts_with_all = &["skip", "jumps", "print-seed"]
+    )]
+    resume: bool,
+
+    /// Selects one of the independent output streams of the pcg algorithm. Runs with the same
+    /// seed and different stream ids give unrelated output, so deterministic work can be sharded
+    /// across many machines with a single seed. Requires a seed.
+    ///
+    /// The streams are implemented with the PCG increment parameter. The multiplicative PCG
+    /// normally used on 64 bit platforms has no such parameter, so the 128 bit PCG XSL RR is used
+    /// there instead. The output thus differs from the output without --stream-id. Only stream
+    /// ids below 2^63 are unique on 32 bit platforms.
+    #[structopt(
+        long,
+        value_name = "N",
+        conflicts_with_all = &["combine", "debias", "whiten"]
+    )]
+    stream_id: Option<u64>,
+
+    /// Prints the seed used, as the argument that reproduces the run. Without a seed argument
+    /// a full width seed is generated from OS entropy, instead of letting the generator seed
+    /// itself, so that an interesting run can be reproduced later. The run is then seeded, so
+    /// see --seed for how that affects multithreading.
+    ///
+    /// The seed is printed to stderr, or written to a file with --print-seed=<path>.
+    #[structopt(
+        long,
+        value_name = "path",
+        min_values = 0,
+        require_equals = true,
+        conflicts_with = "combine"
+    )]
+    print_seed: Option<Option<PathBuf>>,
+
+    /// Periodically replaces the generator with a new instance seeded with fresh entropy from
+    /// the operating system. Gives forward security for very long running streams: a
+    /// compromised generator state does not reveal output from before the last reseed.
+    ///
+    /// The interval is either an amount of generated data, such as "1GiB", or a duration, such
+    /// as "60s" or "10m". In multithreaded mode every worker thread reseeds independently.
+    /// Can't be used with --seed, since the output would no longer be reproducible.
+    #[structopt(long, value_name = "interval", conflicts_with_all = &["seed", "seed-hex", "seed-passphrase", "print-seed"])]
+    reseed_every: Option<reseed::ReseedInterval>,
+
+    /// A personalization string mixed into the instantiation of DRBG algorithms, such as
+    /// "ctr-drbg". Makes the output differ between instances that would otherwise be seeded
+    /// identically.
+    #[structopt(long, value_name = "string", conflicts_with = "reseed-every")]
+    personalization: Option<String>,
+
+    /// Runs the given command with the system shell and uses its standard output as the source
+    /// of randomness, instead of one of the algorithms. Useful for post-processing the output of
+    /// other generators with --debias and --whiten, or for benchmarking them.
+    ///
+    /// The command is treated as an entropy source: it can't be seeded and runs single threaded.
+    /// Output ends when the command exits.
+    #[structopt(
+        long,
+        value_name = "command",
+        conflicts_with_all = &["algorithm", "combine", "skip", "jumps", "personalization"]
+    )]
+    source_cmd: Option<String>,
+
     /// Sets an upper limit on the number of worker threads to spawn for generating the random data.
     /// If not specified, the number of available hardware threads is used as the max number of
     /// worker threads.
@@ -87,8 +326,9 @@ struct Opt {
     /// but where output speed is limited by the performance of a single core.
     ///
     /// If a seed is specified, max threads will be ignored and the tool will work in single
-    /// threaded mode. The same holds for the 'os' algorithm as no speed improvement is
-    /// gained from trying to extract randomness from the OS in parallel.
+    /// threaded mode, except for the algorithms that support deterministic multithreading. See
+    /// --seed. The same holds for the 'os', 'rdrand' and 'rdseed' algorithms as no speed
+    /// improvement is gained from trying to extract randomness from the OS or CPU in parallel.
     #[structopt(long, short = "t")]
     max_threads: Option<usize>,
 
@@ -96,284 +336,1210 @@ struct Opt {
     #[structopt(long, short)]
     verbose: bool,
 
-    /// Writes to <output> instead of stdout.
-    #[structopt(long, short)]
-    output: Option<PathBuf>,
-}
+    /// Shows a live meter of the bytes written and the current and average throughput on
+    /// stderr, like pv, without the extra copy of the data a pipe through pv costs. Only shown
+    /// when stderr is a terminal.
+    #[structopt(long)]
+    meter: bool,
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum Algorithm {
-    Default,
-    Hc,
-    ChaCha8,
-    ChaCha12,
-    ChaCha20,
-    XorShift,
-    Pcg,
-    Os,
-}
+    /// Reports sizes and rates in decimal units (KB, MB, GB, powers of 1000), as disk and
+    /// network vendors quote them, instead of binary units (KiB, MiB, GiB, powers of 1024).
+    /// Applies to all statistics and progress output, also of the subcommands.
+    #[structopt(long)]
+    si: bool,
 
-impl std::str::FromStr for Algorithm {
-    type Err = ParseAlgorithmError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "hc" => Ok(Algorithm::Hc),
-            "chacha" | "chacha20" => Ok(Algorithm::ChaCha20),
-            "chacha8" => Ok(Algorithm::ChaCha8),
-            "chacha12" => Ok(Algorithm::ChaCha12),
-            "xorshift" => Ok(Algorithm::XorShift),
-            "pcg" => Ok(Algorithm::Pcg),
-            "os" => Ok(Algorithm::Os),
-            _ => Err(ParseAlgorithmError(())),
-        }
-    }
-}
+    /// Writes a line of statistics to stderr at this interval, such as "10s": the bytes written
+    /// so far and the throughput since the previous line. A last line is written when the run
+    /// ends. For long running soak tests.
+    #[structopt(long, value_name = "duration", parse(try_from_str = parsing::parse_duration))]
+    stats_interval: Option<Duration>,
 
-#[derive(Debug)]
-struct ParseAlgorithmError(());
+    /// Writes progress records as newline-delimited JSON to this inherited file descriptor, such
+    /// as 3, for programs wrapping rng. Every record is a JSON object with "bytes" written,
+    /// "total" bytes if known, "elapsed" seconds, the average "rate" in bytes per second, the
+    /// estimated seconds left as "eta" if the total is known, and "done". Written about once a
+    /// second, and when the run ends with "done" true. Descriptors 0 to 2, the standard streams,
+    /// are not allowed. Unix only.
+    #[structopt(long, value_name = "fd")]
+    progress_fd: Option<i32>,
 
-impl fmt::Display for ParseAlgorithmError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Invalid algorithm. See --help for a list of valid options."
-        )
-    }
-}
+    /// Appends the --stats-interval lines to this file instead of writing them to stderr.
+    #[structopt(
+        long,
+        value_name = "path",
+        parse(from_os_str),
+        requires = "stats-interval"
+    )]
+    stats_file: Option<PathBuf>,
 
-fn main() {
-    let opt = Opt::from_args();
-    let algorithm = opt.algorithm.unwrap_or(Algorithm::Default);
-    let seed = opt.seed;
-
-    let max_threads = if seed.is_some() || algorithm == Algorithm::Os {
-        if opt.max_threads.is_some() && seed.is_some() {
-            eprintln!(
-                "WARNING: --max-threads is ignored when a seed is specified. \
-                Manually seeded randomness generation must be single threaded."
-            );
-        }
-        if opt.max_threads.is_some() && algorithm == Algorithm::Os {
-            eprintln!("WARNING: --max-threads is ignored with the 'os' PRNG");
-        }
-        1
-    } else {
-        opt.max_threads.unwrap_or_else(num_cpus::get)
-    };
-
-    // Prepare the writer (stdout/file) to write all data to
-    let stdout = io::stdout();
-    let mut output = match opt.output {
-        None => Output::Stdout(stdout.lock()),
-        Some(path) => {
-            let file = fs::File::create(&path).unwrap_or_else(|e| {
-                eprintln!("Failed to open output file: {}", e);
-                std::process::exit(1);
-            });
-            Output::File(file)
-        }
-    };
+    /// Removes bias from the generated data before writing it, or before whitening it when
+    /// combined with --whiten. Intended for raw entropy sources such as "hwrng" and "jitter".
+    ///
+    /// Possible values are:
+    ///
+    /// * von-neumann - The von Neumann extractor. Removes the bias of independent input bits, but
+    ///   outputs at most half as much data as it consumes, and a quarter for unbiased input.
+    ///
+    /// Debiasing always runs single threaded.
+    #[structopt(long, value_name = "method", conflicts_with_all = &["skip", "jumps", "reseed-every"])]
+    debias: Option<conditioning::Debiasing>,
 
-    let mut bytes_written: u64 = 0;
-    let should_abort = platform::abort_handle();
-    let write_fn = |buf: &[u8; BUFFER_SIZE]| {
-        if output.write_all(&*buf).is_err() {
-            return true;
-        }
-        bytes_written += crate::BUFFER_SIZE as u64;
-        should_abort()
-    };
-
-    let start = Instant::now();
-    // Start generating the data and writing it
-    match max_threads {
-        0 | 1 => singlethreaded::run(algorithm, seed, write_fn),
-        max_threads => multithreaded::run(algorithm, max_threads, write_fn, opt.verbose),
-    }
-    if let Err(e) = output.flush() {
-        eprintln!("Failed to flush output: {}", e);
-    }
+    /// Passes the generated data through a hash based randomness extractor before writing it.
+    /// Every 32 bytes of output is the hash of 64 bytes of generator output. Useful for entropy
+    /// sources that don't deliver full entropy, such as "hwrng" and "jitter".
+    ///
+    /// Possible values are: sha256, blake3.
+    ///
+    /// Whitening always runs single threaded.
+    #[structopt(long, value_name = "hash", conflicts_with_all = &["skip", "jumps", "reseed-every"])]
+    whiten: Option<conditioning::Whitening>,
 
-    // Print statistics about how much was written and in what time
-    if opt.verbose {
-        let elapsed_seconds = start.elapsed().as_millis() as f64 / 1000.0;
-        let bytes_per_second = bytes_written as f64 / elapsed_seconds;
-        eprintln!(
-            "{} ({} bytes) written in {:.1} seconds = {}/s",
-            formatting::format_bytes_written(bytes_written),
-            bytes_written,
-            elapsed_seconds,
-            formatting::format_bytes_written(bytes_per_second as u64),
-        );
-    }
-}
+    /// Runs the continuous health tests of NIST SP 800-90B, the repetition count test and the
+    /// adaptive proportion test, on the raw output of entropy sources such as "os", "hwrng" and
+    /// "jitter". Detects a source that got stuck or lost most of its entropy.
+    ///
+    /// Possible values are:
+    ///
+    /// * abort - Stops with an error at the first failure.
+    ///
+    /// * warn - Prints a warning for every failure and keeps going.
+    ///
+    /// For a source delivering the entropy given by --min-entropy, the tests have a false
+    /// positive probability of 2^-40 per byte.
+    #[structopt(long, value_name = "action")]
+    health_tests: Option<health::HealthAction>,
 
-mod multithreaded {
-    use super::Algorithm;
-    use crossbeam_channel::{Receiver, Sender};
-    use rand::{RngCore, SeedableRng};
-    use std::thread;
-
-    pub(crate) fn run<F: FnMut(&[u8; crate::BUFFER_SIZE]) -> bool>(
-        algorithm: Algorithm,
-        max_threads: usize,
-        write_fn: F,
-        verbose: bool,
-    ) {
-        let run_fn = match algorithm {
-            Algorithm::Default => run_internal::<rand::rngs::StdRng, F>,
-            Algorithm::Hc => run_internal::<rand_hc::Hc128Rng, F>,
-            Algorithm::ChaCha8 => run_internal::<rand_chacha::ChaCha8Rng, F>,
-            Algorithm::ChaCha12 => run_internal::<rand_chacha::ChaCha12Rng, F>,
-            Algorithm::ChaCha20 => run_internal::<rand_chacha::ChaCha20Rng, F>,
-            Algorithm::XorShift => run_internal::<rand_xorshift::XorShiftRng, F>,
-            Algorithm::Pcg => run_internal::<crate::PcgRng, F>,
-            Algorithm::Os => panic!("OS PRNG does not support multithreaded mode"),
-        };
-        run_fn(max_threads, verbose, write_fn);
-    }
+    /// The assessed min-entropy of the entropy source, in bits per byte. Sets the cutoffs of
+    /// --health-tests. The default of 8 suits sources that output conditioned data, such as
+    /// "os". Raw noise sources must be given their assessed entropy, or the tests will fail on
+    /// healthy data.
+    #[structopt(long, value_name = "bits", requires = "health-tests")]
+    min_entropy: Option<f64>,
 
-    fn run_internal<R: SeedableRng + RngCore, F: FnMut(&[u8; crate::BUFFER_SIZE]) -> bool>(
-        max_threads: usize,
-        verbose: bool,
-        mut write_fn: F,
-    ) {
-        let (sender, receiver) = crossbeam_channel::bounded(max_threads);
-        let (buf_return_sender, buf_return_receiver) =
-            crossbeam_channel::bounded(max_threads.max(8));
-        let mut threads = Vec::with_capacity(max_threads);
-        loop {
-            let buf = receiver.try_recv().unwrap_or_else(|_| {
-                add_worker_thread::<R>(
-                    &mut threads,
-                    max_threads,
-                    &sender,
-                    &receiver,
-                    &buf_return_receiver,
-                    verbose,
-                )
-            });
-            if write_fn(&*buf) {
-                break;
-            }
-            let _ = buf_return_sender.try_send(buf);
-        }
-        drop(receiver);
-        for thread in threads {
-            thread.join().expect("Worker threads don't panic");
-        }
-    }
+    /// Runs a chi-square test on the byte values and a monobit test on the bits of the last
+    /// 1 MiB of output, every 64 KiB, before the data is written. Catches a badly broken
+    /// generator or entropy source early. The tests only fail for data far from random, so they
+    /// are not a replacement for the `analyze` and `test` subcommands.
+    ///
+    /// Possible values are:
+    ///
+    /// * abort - Stops with an error before writing the data failing a test.
+    ///
+    /// * warn - Prints a warning for every failure and keeps going.
+    #[structopt(long, value_name = "action")]
+    self_check: Option<health::HealthAction>,
 
-    /// Spawn another worker thread producing random data.
-    /// This is cold since it will only happen a few times at the very start of the run.
-    #[cold]
-    #[inline(never)]
-    fn add_worker_thread<R: SeedableRng + RngCore>(
-        threads: &mut Vec<thread::JoinHandle<()>>,
-        max_threads: usize,
-        sender: &Sender<Box<[u8; crate::BUFFER_SIZE]>>,
-        receiver: &Receiver<Box<[u8; crate::BUFFER_SIZE]>>,
-        buf_return_receiver: &Receiver<Box<[u8; crate::BUFFER_SIZE]>>,
-        verbose: bool,
-    ) -> Box<[u8; crate::BUFFER_SIZE]> {
-        if threads.len() < max_threads {
-            let sender = sender.clone();
-            let buf_return_receiver = buf_return_receiver.clone();
-            threads.push(thread::spawn(move || {
-                let mut rng = R::from_entropy();
-                loop {
-                    // Try to get a buffer from the writer thread, or allocate a new one
-                    let mut buf = buf_return_receiver
-                        .try_recv()
-                        .unwrap_or_else(|_| Box::new([0u8; crate::BUFFER_SIZE]));
-                    rng.fill_bytes(&mut *buf);
-                    if sender.send(buf).is_err() {
-                        break;
-                    }
-                }
-            }));
-            if verbose {
-                eprintln!("Spawning worker thread {}", threads.len());
-            }
-        }
-        receiver.recv().expect("The channel can't be closed here")
-    }
+    /// Locks all memory of the process into RAM, so that generated data and generator state are
+    /// never written to swap, and disables core dumps. For generating key material on shared
+    /// machines. Unix only. Might require raising the locked memory limit, see `ulimit -l`.
+    ///
+    /// Output buffers are always cleared when freed, also without this option. So is the state
+    /// of the ctr-drbg and hmac-drbg algorithms.
+    #[structopt(long)]
+    secure_memory: bool,
+
+    /// Runs with this nice value, from -20 (highest priority) to 19 (lowest), so a long fill
+    /// job doesn't slow down interactive work on the same machine. Raising the priority usually
+    /// requires root. On Windows, the closest priority class is used.
+    #[structopt(long, value_name = "N", allow_hyphen_values = true)]
+    nice: Option<i32>,
+
+    /// The I/O scheduling class, "idle" or "best-effort". With idle, the disk is only used
+    /// when no other process needs it. Linux and Windows only. On Windows, idle puts the process
+    /// in background mode, which lowers its CPU priority too.
+    #[structopt(long, value_name = "class")]
+    io_class: Option<platform::IoClass>,
+
+    /// Confines rng to the few system calls it needs to keep generating and writing, once the
+    /// outputs are open, so an exploited bug can do little else. rng often runs as root against
+    /// block devices. Uses seccomp on Linux and pledge and unveil on OpenBSD. Can't be combined
+    /// with options that open files or connect after the start, such as --fill, or with fifo
+    /// outputs.
+    #[structopt(
+        long,
+        conflicts_with_all = &["atomic", "checkpoint-file", "fill", "reconnect", "verify"]
+    )]
+    sandbox: bool,
+
+    /// The device to read from for the "hwrng" and "tpm" algorithms, and for the "os" algorithm
+    /// with --os-source device.
+    #[structopt(long, value_name = "path")]
+    device: Option<PathBuf>,
+
+    /// Selects which operating system interface the "os" algorithm fetches randomness from.
+    /// By default the interface recommended for the platform is used.
+    ///
+    /// Possible values are:
+    ///
+    /// * default - The interface recommended for the platform.
+    ///
+    /// * getrandom - getrandom(2) without flags. Blocks until the kernel entropy pool has been
+    ///   initialized. Linux only.
+    ///
+    /// * getrandom-nonblock - getrandom(2) with GRND_NONBLOCK. Fails instead of blocking if the
+    ///   kernel entropy pool has not been initialized yet. Linux only.
+    ///
+    /// * getrandom-random - getrandom(2) with GRND_RANDOM. Uses the blocking /dev/random pool
+    ///   on kernels that have one. Linux only.
+    ///
+    /// * device - Reads from a device file. /dev/urandom unless another path is given with
+    ///   --device, for example /dev/random. Unix only.
+    ///
+    /// * cng - BCryptGenRandom from the CNG API. Windows only.
+    ///
+    /// * rtlgenrandom - The legacy RtlGenRandom function. Windows only.
+    #[structopt(long, value_name = "source")]
+    os_source: Option<rngs::OsSource>,
+
+    /// Stops after writing this many bytes, such as "1GiB". Without it, data is written until
+    /// the output is closed or rng is interrupted.
+    #[structopt(long, value_name = "size", parse(try_from_str = parsing::parse_size))]
+    bytes: Option<u64>,
+
+    /// The number of bytes generated and written at a time, such as "1MiB". Larger buffers mean
+    /// fewer system calls, smaller ones less memory and latency. A multiple of 512 bytes, from
+    /// 4KiB to 256MiB. Multithreaded generation keeps a few buffers per thread.
+    #[structopt(
+        long,
+        value_name = "size",
+        default_value = "64KiB",
+        parse(try_from_str = parsing::parse_size)
+    )]
+    buffer_size: u64,
+
+    /// How many generated buffers can wait to be written in multithreaded mode. Defaults to the
+    /// number of threads, or twice that for seeded multithreading.
+    ///
+    /// A deeper queue lets the worker threads keep generating while the output is stalled, which
+    /// helps with bursty outputs such as network sockets. Each queued buffer holds --buffer-size
+    /// bytes, so the memory use grows with the depth, and a deeper queue does not help an output
+    /// that is slower than the generation on average.
+    #[structopt(long, value_name = "buffers")]
+    queue_depth: Option<usize>,
+
+    /// Pins the threads to CPUs, such as "--pin-threads=0-3,8". The writing thread runs on the
+    /// first CPU in the list and the worker threads on the following ones, wrapping around if
+    /// there are more threads than CPUs. Without a list, all CPUs the process may run on are used.
+    ///
+    /// Gives more stable throughput for benchmarking, especially on NUMA systems and CPUs with
+    /// cores of different speeds. Supported on Linux and Windows.
+    #[structopt(long, value_name = "cpus")]
+    pin_threads: Option<Option<parsing::CpuList>>,
+
+    /// Stops worker threads while the output can't keep up with them, and starts them again when
+    /// it can. Saves CPU time and memory when writing to a slow output in multithreaded mode.
+    /// Seeded multithreading always runs all its threads.
+    #[structopt(long)]
+    adaptive: bool,
+
+    /// Allocates the buffers of the worker threads from huge pages, which reduces TLB misses at
+    /// high rates. Uses the reserved huge pages of the system if there are free ones, or otherwise
+    /// transparent huge pages. Reserved huge pages are 2MiB, so use a --buffer-size that is a
+    /// multiple of that. The buffers are placed on the NUMA node of the worker thread that first
+    /// fills them, see --pin-threads. Linux only.
+    #[structopt(long)]
+    huge_pages: bool,
+
+    /// Writes to the outputs in blocks of this size, such as "1M", like the bs operand of dd.
+    /// Only the last block can be shorter.
+    #[structopt(long, value_name = "size", parse(try_from_str = parsing::parse_size))]
+    bs: Option<u64>,
+
+    /// Stops after writing this many blocks of --bs, like the count operand of dd. Without --bs,
+    /// blocks are 512 bytes, also like dd.
+    #[structopt(long, value_name = "N", conflicts_with = "bytes")]
+    count: Option<u64>,
+
+    /// Writes to <output> instead of stdout. A file path, "-" for stdout, "tcp://host:port" to
+    /// connect to a TCP server and stream the data to it, "udp://host:port" to send the data as
+    /// UDP datagrams, "unix:/path/to.sock" to connect to a Unix domain stream socket (Unix only),
+    /// "fifo:/path/to/fifo" to stream to a FIFO (Unix only), "\\.\pipe\name" to connect to a
+    /// named pipe (Windows only), or "null" to discard the data without any system calls, for
+    /// measuring the generation speed alone. Use "./null" for a file named null. A FIFO or named
+    /// pipe that does not exist is created, and rng waits for a client to connect to it. When the
+    /// reader of a FIFO goes away, rng waits for the next one and continues.
+    ///
+    /// Can be given several times to write the same data to all the outputs, such as keeping a
+    /// copy on disk of exactly what was streamed to a device. See --on-output-error.
+    #[structopt(
+        long,
+        short,
+        number_of_values = 1,
+        parse(from_os_str = outputs::OutputTarget::from_os_str)
+    )]
+    output: Vec<outputs::OutputTarget>,
+
+    /// Reads file outputs back after writing them, and compares them to the same seeded stream
+    /// generated again. Reports the number of differing bytes and the offset of the first one,
+    /// and exits with an error if any differ. For testing storage media for data integrity.
+    /// Requires a seed.
+    #[structopt(long, requires = "output")]
+    verify: bool,
+
+    /// Hashes all data as it is written and prints the digest when done, in the format of
+    /// sha256sum and b3sum. For checking that what arrived at the other end of a pipe or network
+    /// connection matches what was generated.
+    ///
+    /// Possible values are: sha256, blake3.
+    #[structopt(long, value_name = "hash")]
+    checksum: Option<checksum::Algorithm>,
+
+    /// Exits with an error if the --checksum digest of the output is not this one, given in
+    /// hex. With a seed and --bytes, checks that a deployment generates the same stream as where
+    /// the digest was taken, before trusting it for reproducible datasets.
+    #[structopt(
+        long,
+        value_name = "hex",
+        requires_all = &["checksum", "bytes"],
+        parse(try_from_str = checksum::parse_digest)
+    )]
+    expect_checksum: Option<String>,
+
+    /// Writes file outputs to a temporary file in the same directory, and renames it into place
+    /// only once all of --bytes has been written. Consumers never see a half-written file. If
+    /// generation stops early, the temporary file is removed and rng exits with an error.
+    #[structopt(long, requires_all = &["bytes", "output"], conflicts_with = "resume")]
+    atomic: bool,
+
+    /// Writes to block device outputs without asking for confirmation first. Everything on the
+    /// device is overwritten.
+    #[structopt(long, requires = "output")]
+    yes_i_know: bool,
+
+    /// Reserves the disk space for all of --bytes in file outputs before writing. Running out of
+    /// space is then reported right away instead of after writing most of a huge file, and the
+    /// file is not fragmented. Linux and Windows only.
+    #[structopt(long, requires_all = &["bytes", "output"])]
+    preallocate: bool,
+
+    /// Writes until the file or device output is full, for filling a disk with random data.
+    /// Running out of space then stops generation cleanly: the output is synced, the number of
+    /// bytes that actually fit is printed, and rng exits successfully. Without it, a full output
+    /// is an error. Requires a single file --output.
+    #[structopt(long, requires = "output", conflicts_with = "atomic")]
+    fill: bool,
+
+    /// Syncs file and device outputs to the storage before exiting, so everything written is
+    /// durable. With --verbose, the throughput including the sync is reported as well.
+    #[structopt(long, requires = "output")]
+    fsync: bool,
+
+    /// Syncs file and device outputs to the storage every time this much has been written, such
+    /// as "1GiB". Keeps the page cache from absorbing a large part of the output, so the
+    /// throughput is that of the storage.
+    #[structopt(
+        long,
+        value_name = "size",
+        requires = "output",
+        parse(try_from_str = parsing::parse_size)
+    )]
+    sync_every: Option<u64>,
+
+    /// Writes file outputs as sparse files. The data is split into extents, and a share of them,
+    /// spread evenly over the file, are left as holes instead of written. The data extents hold
+    /// the same data at the same offsets as without --sparse. For testing backup tools, file
+    /// systems and copy utilities.
+    #[structopt(long, requires = "output", conflicts_with = "verify")]
+    sparse: bool,
+
+    /// The share of the extents of --sparse that are holes, from 0 to 1. Defaults to 0.5.
+    #[structopt(long, value_name = "ratio", requires = "sparse")]
+    hole_ratio: Option<f64>,
+
+    /// The size of the extents of --sparse, such as "64KiB". Holes are only created for whole
+    /// blocks of the file system, so it should be a multiple of the block size. Defaults to
+    /// 4MiB.
+    #[structopt(
+        long,
+        value_name = "size",
+        requires = "sparse",
+        parse(try_from_str = parsing::parse_size)
+    )]
+    extent: Option<u64>,
+
+    /// The permissions of file outputs, in octal, such as "600". New files are created with
+    /// them, so they are never readable by others, and existing files get them before anything
+    /// is written. Unix only.
+    #[structopt(
+        long,
+        value_name = "mode",
+        requires = "output",
+        parse(try_from_str = parsing::parse_mode)
+    )]
+    mode: Option<u32>,
+
+    /// Shorthand for --mode 600, for files holding key material.
+    #[structopt(long, requires = "output", conflicts_with = "mode")]
+    secret: bool,
+
+    /// What to do when writing to an --output fails. Given once it applies to all outputs,
+    /// given once per --output it applies to each output in order.
+    ///
+    /// Possible values are:
+    ///
+    /// * abort - Stop generating. The default.
+    ///
+    /// * drop - Stop writing to the failed output, and keep writing to the others. Generation
+    ///   stops when all outputs have failed.
+    #[structopt(long, value_name = "policy", number_of_values = 1, requires = "output")]
+    on_output_error: Vec<outputs::OutputErrorPolicy>,
+
+    /// How the data is handed to the operating system.
+    ///
+    /// Possible values are:
+    ///
+    /// * write - Plain write calls. The default.
+    ///
+    /// * splice - When stdout is a pipe, gives the data to the pipe with vmsplice instead of
+    ///   having the kernel copy it. Saves CPU time with the fast algorithms. The reader must copy
+    ///   the data out of the pipe: one moving it on with splice, such as pv, can see later data
+    ///   instead. Linux only.
+    ///
+    /// * overlapped - Keeps several writes in flight to file outputs with overlapped I/O, so
+    ///   the disk never waits for the next write. Not supported for named pipes, and can't be
+    ///   combined with --sparse. Windows only.
+    ///
+    /// * mmap - Maps a single file --output into memory, and has the worker threads generate
+    ///   the data straight into it, without write calls or copying. Requires --bytes. Unix
+    ///   only.
+    ///
+    /// * pwrite - Has the worker threads write the blocks they generate straight to their
+    ///   offsets in a single file --output, so no single thread does all the writing. Seeded
+    ///   output is identical to that of the other backends. When stopped early, the file is cut
+    ///   after the data written in order. Requires --bytes and multithreaded generation, and
+    ///   can't be combined with --checkpoint-file.
+    #[structopt(long, value_name = "backend", default_value = "write")]
+    io_backend: outputs::IoBackend,
+
+    /// Reconnects to the server of a network --output when connecting or writing fails, after
+    /// waiting this long, such as "1s". Retries until it succeeds. Writing blocks while
+    /// disconnected, and the write that failed is sent again on the new connection. Data the lost
+    /// connection had accepted but not yet delivered can't be recovered. Without this option,
+    /// generation stops when the connection is lost.
+    #[structopt(
+        long,
+        value_name = "delay",
+        requires = "output",
+        parse(try_from_str = parsing::parse_duration)
+    )]
+    reconnect: Option<Duration>,
+
+    /// Retries a write that failed with a transient error up to this many times in a row before
+    /// giving up on the output. Transient errors are timeouts, full non-blocking sockets, busy
+    /// devices and I/O errors such as those of a network filesystem having a blip. Other errors
+    /// are not retried.
+    #[structopt(long, value_name = "N")]
+    retry: Option<u32>,
+
+    /// How long to wait before each retry of --retry, such as "500ms". Defaults to 1s.
+    #[structopt(
+        long,
+        value_name = "delay",
+        requires = "retry",
+        parse(try_from_str = parsing::parse_duration)
+    )]
+    retry_delay: Option<Duration>,
+
+    /// The payload size of the datagrams sent to a UDP --output, such as "1200" or "8KiB". At
+    /// most 65507 bytes. Every datagram has this size, except the last one when the output ends
+    /// in the middle of a packet, which is shorter.
+    #[structopt(
+        long,
+        value_name = "size",
+        requires = "output",
+        parse(try_from_str = parsing::parse_size)
+    )]
+    packet_size: Option<u64>,
+
+    /// Limits the data sent to a UDP --output to this many bytes per second, such as "10MiB".
+    /// Makes rng a simple traffic generator.
+    #[structopt(
+        long,
+        value_name = "size",
+        requires = "output",
+        parse(try_from_str = parsing::parse_size)
+    )]
+    rate: Option<u64>,
+
+    /// Runs the given command with the system shell and writes to its standard input instead
+    /// of stdout. For feeding test suites such as PractRand ("RNG_test stdin64") or dieharder
+    /// ("dieharder -a -g 200"). The output of the command is passed through.
+    ///
+    /// Generation stops when the command exits. Then statistics about the written data are
+    /// printed, and rng exits with the exit code of the command.
+    #[structopt(long, value_name = "command", conflicts_with = "output")]
+    pipe_to: Option<String>,
 }
 
-mod singlethreaded {
-    use crate::Algorithm;
-    use rand::{RngCore, SeedableRng};
+// Subcommands doing something else than generating random data.
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Verifies that all deterministic algorithms produce their known output streams on this
+    /// platform, that --skip and seeded multithreading give identical output, and that the
+    /// operating system entropy source works. Exits with a non-zero exit code on any failure.
+    SelfTest,
+
+    /// Reports statistics showing how random some data looks, like the `ent` program: entropy
+    /// per byte, chi-square test, arithmetic mean, Monte Carlo value for pi and serial
+    /// correlation.
+    Analyze {
+        /// The file to analyze. Reads from stdin if not given.
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+
+        /// Analyzes the output of this algorithm instead of reading data. Requires --bytes.
+        #[structopt(
+            long,
+            value_name = "algorithm",
+            conflicts_with = "file",
+            requires = "bytes"
+        )]
+        generate: Option<Algorithm>,
+
+        /// Analyzes at most this many bytes, such as "100MiB".
+        #[structopt(long, value_name = "size", parse(try_from_str = parsing::parse_size))]
+        bytes: Option<u64>,
+
+        /// Prints the number of times each byte value occurs instead of the statistics.
+        #[structopt(long)]
+        histogram: bool,
+
+        /// The format of the histogram. One of "text", "csv" or "json".
+        #[structopt(
+            long,
+            value_name = "format",
+            default_value = "text",
+            requires = "histogram"
+        )]
+        format: analyze::HistogramFormat,
+
+        /// Draws a bar chart next to the counts in the text histogram.
+        #[structopt(long, requires = "histogram")]
+        bars: bool,
+    },
+
+    /// Lists all algorithms with how suitable they are for security sensitive uses, the size of
+    /// their state and seed, how fast they are relative to each other and whether they can seek
+    /// with --skip and multithread seeded generation.
+    List,
+
+    /// Measures how fast each algorithm generates data, on one thread and on several. Runs
+    /// every algorithm for a fixed time, discarding its output, and prints the throughput of
+    /// each, fastest first.
+    Bench {
+        /// The algorithms to benchmark. Benchmarks all user-space algorithms and "os" if none
+        /// are given.
+        #[structopt(value_name = "algorithm")]
+        algorithms: Vec<Algorithm>,
+
+        /// How long to run every algorithm for, in each mode.
+        #[structopt(
+            long,
+            value_name = "duration",
+            default_value = "2s",
+            parse(try_from_str = parsing::parse_duration)
+        )]
+        duration: Duration,
+
+        /// The number of threads in the multithreaded mode, or a range of thread counts to
+        /// measure each of, such as "1..16". A range also reports the knee point, the fewest
+        /// threads reaching 90% of the best throughput, as a guide for --max-threads. Defaults
+        /// to the number of CPUs.
+        #[structopt(
+            long,
+            value_name = "N",
+            parse(try_from_str = parsing::parse_thread_range)
+        )]
+        threads: Option<(usize, usize)>,
+
+        /// Measures the throughput of every buffer size in this range instead, such as
+        /// "4KiB..4MiB", starting from the smallest and doubling. The data is written to --sink
+        /// and generated like rng would generate it, on --threads threads. Prints the buffer
+        /// size with the highest throughput, as a guide for --buffer-size.
+        #[structopt(long, value_name = "range", parse(try_from_str = parsing::parse_size_range))]
+        sweep_buffer: Option<(u64, u64)>,
+
+        /// Where the data goes in a buffer size sweep. One of "null", "file" or "pipe". "file"
+        /// writes a temporary file without syncing it to storage, and "pipe" writes to a pipe
+        /// another thread reads from. Defaults to "null".
+        #[structopt(long, value_name = "sink", requires = "sweep-buffer")]
+        sink: Option<bench::Sink>,
+
+        /// The format of the results. One of "text", "json" or "csv". JSON and CSV include the
+        /// CPU model and the number of CPUs, and give the throughput in bytes per second.
+        #[structopt(long, value_name = "format", default_value = "text")]
+        format: bench::Format,
+    },
+
+    /// Writes white noise to a PCM WAV file, for testing audio equipment and signal
+    /// processing.
+    Audio {
+        /// The algorithm generating the samples. Uses the default algorithm if not given.
+        #[structopt(long, value_name = "algorithm")]
+        generate: Option<Algorithm>,
+
+        /// The duration of the audio, in seconds.
+        #[structopt(long, default_value = "10")]
+        seconds: f64,
+
+        /// The sample rate, in samples per second.
+        #[structopt(long, default_value = "48000")]
+        rate: u32,
+
+        /// The number of bits per sample. One of 8, 16, 24 or 32.
+        #[structopt(long, default_value = "16")]
+        bits: u16,
+
+        /// The number of channels.
+        #[structopt(long, default_value = "1")]
+        channels: u16,
+
+        /// The file to write the WAV audio to.
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+    },
+
+    /// Creates a directory of files with random sizes and random contents, for testing backup,
+    /// sync and deduplication software. The files are named by their number and written by
+    /// several threads at once.
+    Corpus {
+        /// The directory to create the files in. Created if it does not exist.
+        #[structopt(long, parse(from_os_str))]
+        dir: PathBuf,
+
+        /// The number of files to create.
+        #[structopt(long)]
+        files: u64,
+
+        /// The range of file sizes, such as "4KiB..2MiB", or a single size for equally sized
+        /// files.
+        #[structopt(long, value_name = "range", parse(try_from_str = parsing::parse_size_range))]
+        size: (u64, u64),
+
+        /// How the file sizes are distributed within the range. One of "uniform" or
+        /// "log-uniform". With "log-uniform" small files are more common than large ones.
+        #[structopt(long, value_name = "distribution", default_value = "uniform")]
+        distribution: corpus::SizeDistribution,
+
+        /// The algorithm generating the contents. Uses the default algorithm if not given.
+        #[structopt(long, value_name = "algorithm")]
+        generate: Option<Algorithm>,
+
+        /// The number of threads writing files. Defaults to the number of CPUs.
+        #[structopt(long, value_name = "N")]
+        threads: Option<usize>,
+    },
+
+    /// Overwrites all of a block device or file with random data in one or more passes, such as
+    /// before disposing of a disk. Syncs after every pass and reports the progress. Asks for
+    /// confirmation first, unless --yes-i-know is given.
+    Wipe {
+        /// The block device or file to wipe, such as "/dev/sdb".
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// The number of random passes.
+        #[structopt(long, value_name = "N", default_value = "1")]
+        passes: u32,
+
+        /// The algorithm of the random passes. Given once it applies to all passes, given once
+        /// per pass it applies to each pass in order. Uses the default algorithm if not given.
+        #[structopt(long, value_name = "algorithm", number_of_values = 1)]
+        generate: Vec<Algorithm>,
+
+        /// Overwrites everything with zeros in a last pass after the random passes.
+        #[structopt(long)]
+        final_zero: bool,
 
-    pub(crate) fn run<F: FnMut(&[u8; crate::BUFFER_SIZE]) -> bool>(
-        algorithm: Algorithm,
+        /// Reads everything back after the last pass and checks that it holds what was written.
+        /// Random data is then generated from a seed, so it can be generated again to compare.
+        #[structopt(long)]
+        verify: bool,
+
+        /// Wipes without asking for confirmation first.
+        #[structopt(long)]
+        yes_i_know: bool,
+    },
+
+    /// Tests a storage device for bad blocks, like "badblocks -w". Every pass writes a random
+    /// pattern over the whole device from several threads and reads it back. Prints the regions
+    /// that could not be written, could not be read or did not hold the pattern, and exits with
+    /// an error if there are any. Destroys all data on the device, so asks for confirmation
+    /// first unless --yes-i-know is given.
+    TestDevice {
+        /// The block device or file to test, such as "/dev/sdb".
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// The number of passes, each with a new pattern.
+        #[structopt(long, value_name = "N", default_value = "1")]
+        passes: u32,
+
+        /// The size of the blocks that are tested and reported as bad, such as "64KiB". A
+        /// multiple of 512 bytes.
+        #[structopt(
+            long,
+            value_name = "size",
+            default_value = "1MiB",
+            parse(try_from_str = parsing::parse_size)
+        )]
+        block_size: u64,
+
+        /// The number of threads testing blocks. Defaults to the number of CPUs.
+        #[structopt(long, value_name = "N")]
+        threads: Option<usize>,
+
+        /// Tests without asking for confirmation first.
+        #[structopt(long)]
+        yes_i_know: bool,
+    },
+
+    /// Runs a TCP server streaming random data to every client that connects, each from its own
+    /// thread and generator instance. For network throughput testing. Supports systemd socket
+    /// activation, then the passed socket is used instead of --listen.
+    Serve {
+        /// The address to listen on.
+        #[structopt(long, value_name = "address", default_value = "127.0.0.1:7777")]
+        listen: String,
+
+        /// The algorithm generating the data. Uses the default algorithm if not given.
+        #[structopt(long, value_name = "algorithm")]
+        generate: Option<Algorithm>,
+
+        /// Serves Prometheus metrics at /metrics on this address, such as "127.0.0.1:9100".
+        #[structopt(long, value_name = "address")]
+        metrics: Option<String>,
+    },
+
+    /// Runs an HTTP server handing out random data. "GET /bytes?n=1024&format=hex" returns the
+    /// given number of bytes, 32 by default and at most 64 MiB. "GET /stream" returns random
+    /// data until the client disconnects. The format is one of "raw" (default), "hex" or
+    /// "base64". "GET /metrics" returns Prometheus metrics. Supports systemd socket activation,
+    /// then the passed socket is used instead of --listen.
+    ServeHttp {
+        /// The address to listen on. Listens on all interfaces if only a port is given, such as
+        /// ":8080".
+        #[structopt(long, value_name = "address", default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// The algorithm generating the data. Uses the default algorithm if not given.
+        #[structopt(long, value_name = "algorithm")]
+        generate: Option<Algorithm>,
+    },
+
+    /// Runs a server speaking the Entropy Gathering Daemon protocol on a Unix domain socket, for
+    /// software that fetches entropy from EGD. Every client gets its own generator instance.
+    /// Supports systemd socket activation, then the passed socket is used instead of --socket.
+    /// Unix only.
+    ServeEgd {
+        /// The path of the socket to listen on, such as "/var/run/egd-pool".
+        #[structopt(long, value_name = "path", parse(from_os_str))]
+        socket: PathBuf,
+
+        /// The algorithm generating the data. Uses the default algorithm if not given.
+        #[structopt(long, value_name = "algorithm")]
+        generate: Option<Algorithm>,
+
+        /// Serves Prometheus metrics at /metrics on this address, such as "127.0.0.1:9100".
+        #[structopt(long, value_name = "address")]
+        metrics: Option<String>,
+    },
+
+    /// Runs statistical test suites on some data.
+    Test(TestSuite),
+
+    /// Renders random data as a PNG image, for spotting patterns visually. Every byte is one
+    /// gray pixel, or every three bytes one color pixel with --color rgb. Renders the output of
+    /// the default algorithm unless a file or --generate is given.
+    Image {
+        /// The file to render.
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+
+        /// Renders the output of this algorithm.
+        #[structopt(long, value_name = "algorithm", conflicts_with = "file")]
+        generate: Option<Algorithm>,
+
+        /// The width of the image, in pixels.
+        #[structopt(long, default_value = "1024")]
+        width: u32,
+
+        /// The height of the image, in pixels.
+        #[structopt(long, default_value = "1024")]
+        height: u32,
+
+        /// How bytes are mapped to pixels. One of "gray" or "rgb".
+        #[structopt(long, value_name = "type", default_value = "gray")]
+        color: image::ColorType,
+
+        /// The file to write the PNG image to.
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+    },
+
+    /// Mounts a file that behaves like /dev/urandom but is backed by the given algorithm, until
+    /// interrupted. With --seed, seekable algorithms return the same data at the same offset
+    /// every time, making the file a reproducible randomness device for tests. Requires Linux,
+    /// root and rng built with the "fuse" feature.
+    Mount {
+        /// The file to mount over. Created if it does not exist.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// The algorithm generating the data. Uses the default algorithm if not given.
+        #[structopt(long, value_name = "algorithm")]
+        generate: Option<Algorithm>,
+
+        /// Seeds the algorithm with this number.
+        #[structopt(long, value_name = "N")]
         seed: Option<u64>,
-        write_fn: F,
-    ) {
-        let run_fn = match algorithm {
-            Algorithm::Default => run_userspace::<rand::rngs::StdRng, F>,
-            Algorithm::Hc => run_userspace::<rand_hc::Hc128Rng, F>,
-            Algorithm::ChaCha8 => run_userspace::<rand_chacha::ChaCha8Rng, F>,
-            Algorithm::ChaCha12 => run_userspace::<rand_chacha::ChaCha12Rng, F>,
-            Algorithm::ChaCha20 => run_userspace::<rand_chacha::ChaCha20Rng, F>,
-            Algorithm::XorShift => run_userspace::<rand_xorshift::XorShiftRng, F>,
-            Algorithm::Pcg => run_userspace::<crate::PcgRng, F>,
-            Algorithm::Os => run_os,
-        };
-        run_fn(seed, write_fn);
-    }
+    },
+
+    /// Mixes data from an entropy source into the kernel entropy pool with the RNDADDENTROPY
+    /// ioctl, like rngd. Without --rate, data is fed whenever the kernel asks for more entropy,
+    /// and at least once a minute. Runs until interrupted or the source ends. Linux only, and
+    /// requires root.
+    FeedKernel {
+        /// The entropy source to feed from, such as "hwrng", "jitter", "rdseed" or "tpm".
+        #[structopt(long, value_name = "algorithm", default_value = "hwrng")]
+        source: Algorithm,
+
+        /// Feeds the standard output of this command, run with the system shell, instead.
+        #[structopt(long, value_name = "command")]
+        source_cmd: Option<String>,
 
-    pub fn run_userspace<R: SeedableRng + RngCore, F: FnMut(&[u8; crate::BUFFER_SIZE]) -> bool>(
+        /// The device to read from for the "hwrng" and "tpm" sources.
+        #[structopt(long, value_name = "path", parse(from_os_str))]
+        device: Option<PathBuf>,
+
+        /// The bits of entropy credited per bit of data fed, between 0 and 1. Lower it for
+        /// sources of unknown quality.
+        #[structopt(long, default_value = "1")]
+        ratio: f64,
+
+        /// Feeds this many bytes per second, such as "4KiB".
+        #[structopt(long, value_name = "size", parse(try_from_str = parsing::parse_size))]
+        rate: Option<u64>,
+    },
+
+    /// Sleeps for a random duration between --min and --max, then exits. For adding jitter to
+    /// cron jobs and retry scripts, so they don't all run at the same moment.
+    Sleep {
+        /// The shortest delay, such as "500ms" or "1m".
+        #[structopt(
+            long,
+            value_name = "delay",
+            default_value = "0s",
+            parse(try_from_str = parsing::parse_duration)
+        )]
+        min: Duration,
+
+        /// The longest delay.
+        #[structopt(long, value_name = "delay", parse(try_from_str = parsing::parse_duration))]
+        max: Duration,
+
+        /// How the delay is distributed between --min and --max.
+        ///
+        /// Possible values are:
+        ///
+        /// * uniform - Every delay is equally likely.
+        ///
+        /// * exp - Short delays are more likely. An exponential distribution starting at --min,
+        ///   with a third of the range as its mean, cut off at --max.
+        #[structopt(long, value_name = "distribution", default_value = "uniform")]
+        distribution: sleep::Distribution,
+
+        /// Prints the chosen delay in seconds before sleeping.
+        #[structopt(long)]
+        print: bool,
+
+        /// Seeds the choice with this number, so the same delay is chosen every time. Such as
+        /// a hash of the host name, to spread the hosts of a fleet out but keep each one at a
+        /// fixed time.
+        #[structopt(long, value_name = "N")]
         seed: Option<u64>,
-        write_fn: F,
-    ) {
-        let rng = match seed {
-            None => R::from_entropy(),
-            Some(seed) => R::seed_from_u64(seed),
-        };
-        generate_to_stdout(rng, write_fn)
-    }
+    },
 
-    fn run_os<F: FnMut(&[u8; crate::BUFFER_SIZE]) -> bool>(seed: Option<u64>, write_fn: F) {
-        if seed.is_some() {
-            eprintln!("WARNING: seed is ignored when used with the OS PRNG");
-        }
-        generate_to_stdout(rand::rngs::OsRng, write_fn)
-    }
+    /// Prints unique random numbers below --range, one per line, without keeping track of the
+    /// numbers already printed. Works for ranges of any size up to 2^64, so it scales to
+    /// billions of synthetic IDs without a table for removing duplicates.
+    Unique {
+        /// The number of numbers to print.
+        #[structopt(long, value_name = "N")]
+        count: u64,
 
-    /// Given a random number generator, writes the output of it to stdout forever, or until there
-    /// is an error writing to stdout. Usually because the pipe has closed.
-    fn generate_to_stdout<F: FnMut(&[u8; crate::BUFFER_SIZE]) -> bool>(
-        mut rng: impl RngCore,
-        mut write_fn: F,
-    ) {
-        let mut buf = [0u8; crate::BUFFER_SIZE];
-        loop {
-            rng.fill_bytes(&mut buf);
-            if write_fn(&buf) {
-                break;
-            }
-        }
-    }
+        /// The numbers are below this. All 64 bit numbers can come up if not given.
+        #[structopt(long, value_name = "N")]
+        range: Option<u64>,
+
+        /// Seeds the order of the numbers. With the same seed and range, the same numbers are
+        /// printed in the same order every time.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+
+        /// Skips this many numbers of the seeded order first. For printing more numbers with
+        /// the same seed later, without repeating any printed before, by skipping as many as
+        /// were printed.
+        #[structopt(long, value_name = "N", requires = "seed")]
+        skip: Option<u64>,
+    },
+
+    /// Prints synthetic personal records for test fixtures. They look realistic, but belong to
+    /// nobody: email addresses are at the example domains reserved for documentation, and phone
+    /// numbers in the 555-01xx block reserved for fiction.
+    Fake {
+        /// The fields of every record, in order. Possible values are: name, email, phone,
+        /// address.
+        #[structopt(
+            long,
+            value_name = "list",
+            use_delimiter = true,
+            default_value = "name,email,phone,address"
+        )]
+        fields: Vec<fake::Field>,
+
+        /// The number of records to print.
+        #[structopt(long, value_name = "N", default_value = "10")]
+        count: u64,
+
+        /// The language and country the records look like they come from. Possible values
+        /// are: en.
+        #[structopt(long, value_name = "locale", default_value = "en")]
+        locale: fake::Locale,
+
+        /// "csv" prints a header line with the field names and then one line per record.
+        /// "json" prints one JSON object per line.
+        #[structopt(long, value_name = "format", default_value = "csv")]
+        format: fake::Format,
+
+        /// Seeds the records, so the same ones are printed every time.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Prints random but valid URLs, one per line, for testing crawlers, proxies and log
+    /// pipelines. The host names are under the reserved "test" and "example" top-level domains
+    /// unless --tld-list is given, so they don't point to real hosts.
+    Url {
+        /// The number of URLs to print.
+        #[structopt(long, value_name = "N", default_value = "10")]
+        count: u64,
+
+        /// The scheme of the URLs.
+        #[structopt(
+            long,
+            value_name = "scheme",
+            default_value = "https",
+            parse(try_from_str = url::parse_scheme)
+        )]
+        scheme: String,
+
+        /// The top-level domains to pick from, such as "com,net,org".
+        #[structopt(
+            long,
+            value_name = "list",
+            use_delimiter = true,
+            default_value = url::DEFAULT_TLDS,
+            parse(try_from_str = url::parse_tld)
+        )]
+        tld_list: Vec<String>,
+
+        /// The most labels in front of the registered name of the host, such as "www" in
+        /// "www.name.test". At most 8.
+        #[structopt(long, value_name = "N", default_value = "1")]
+        subdomains: u32,
+
+        /// The most segments in the path.
+        #[structopt(long, value_name = "N", default_value = "3")]
+        path_depth: u32,
+
+        /// The most parameters in the query string.
+        #[structopt(long, value_name = "N", default_value = "2")]
+        query_params: u32,
+
+        /// Seeds the URLs, so the same ones are printed every time.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Prints random but valid host names, one per line. Under the reserved "test" and
+    /// "example" top-level domains unless --tld-list is given, so they don't point to real
+    /// hosts.
+    Domain {
+        /// The number of host names to print.
+        #[structopt(long, value_name = "N", default_value = "10")]
+        count: u64,
+
+        /// The top-level domains to pick from, such as "com,net,org".
+        #[structopt(
+            long,
+            value_name = "list",
+            use_delimiter = true,
+            default_value = url::DEFAULT_TLDS,
+            parse(try_from_str = url::parse_tld)
+        )]
+        tld_list: Vec<String>,
+
+        /// The most labels in front of the registered name, such as "www" in "www.name.test".
+        /// At most 8.
+        #[structopt(long, value_name = "N", default_value = "1")]
+        subdomains: u32,
+
+        /// Seeds the host names, so the same ones are printed every time.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Prints random tokens in the shape of the API keys of many services: a prefix, the random
+    /// part in base62 and optionally a checksum. For realistic credentials in tests and
+    /// documentation.
+    Apikey {
+        /// The start of every token, telling what it is for. Letters, digits and '_'.
+        #[structopt(
+            long,
+            value_name = "prefix",
+            default_value = "",
+            parse(try_from_str = apikey::parse_prefix)
+        )]
+        prefix: String,
+
+        /// The number of random bytes in every token. At least 16.
+        #[structopt(long, value_name = "N", default_value = "24")]
+        entropy_bytes: usize,
+
+        /// The checksum at the end of the tokens. Possible values are:
+        ///
+        /// * none
+        ///
+        /// * crc32 - The CRC32 of the random part as six base62 digits, like GitHub tokens.
+        #[structopt(long, value_name = "checksum", default_value = "none")]
+        checksum: apikey::Checksum,
+
+        /// The number of tokens to print.
+        #[structopt(long, value_name = "N", default_value = "1")]
+        count: u64,
+
+        /// Seeds the tokens, so the same ones are printed every time. Only for tests, as anyone
+        /// knowing the seed knows the tokens.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Prints random salts in the encoding each password hashing scheme expects, for scripts
+    /// building /etc/shadow entries or htpasswd files.
+    Salt {
+        /// The scheme the salts are for. Possible values are:
+        ///
+        /// * bcrypt - 16 bytes as the 22 characters that go after "$2b$<cost>$".
+        ///
+        /// * sha512crypt - 16 characters of the crypt(3) alphabet, the longest salt sha512crypt
+        ///   uses. Goes after "$6$" in /etc/shadow, or to `openssl passwd -6 -salt`.
+        ///
+        /// * raw:N - N bytes in hex, for schemes taking any bytes, like Argon2 and PBKDF2. At
+        ///   most 1024.
+        #[structopt(long, value_name = "format")]
+        format: salt::Format,
+
+        /// The number of salts to print.
+        #[structopt(long, value_name = "N", default_value = "1")]
+        count: u64,
+
+        /// Seeds the salts, so the same ones are printed every time.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Prints a random port number, for test services that each need their own port. Picking
+    /// from a large range avoids the collisions of fixed or sequential ports.
+    Port {
+        /// The ports to pick from, both included.
+        #[structopt(
+            long,
+            value_name = "min-max",
+            default_value = "1024-65535",
+            parse(try_from_str = port::parse_port_range)
+        )]
+        range: std::ops::RangeInclusive<u16>,
+
+        /// Only prints a port that can be bound on all interfaces right now. Tries up to 100
+        /// random ports. Another program can still take the port before the test service binds
+        /// it.
+        #[structopt(long)]
+        check: bool,
+
+        /// The protocols --check binds the port for: tcp, udp or both. Defaults to both.
+        #[structopt(long, value_name = "protocol", requires = "check")]
+        protocol: Option<port::Protocol>,
+    },
+
+    /// Prints random points, uniformly distributed over the surface of the earth or inside the
+    /// polygons of a GeoJSON file, for synthetic geospatial datasets.
+    Geo {
+        /// The number of points to print.
+        #[structopt(long, value_name = "N", default_value = "10")]
+        count: u64,
+
+        /// Only prints points inside the Polygon and MultiPolygon geometries of this GeoJSON
+        /// file. Edges are straight lines in longitude and latitude, as in GeoJSON, and polygons
+        /// crossing the antimeridian must be split in two.
+        #[structopt(long, value_name = "path", parse(from_os_str))]
+        within: Option<PathBuf>,
+
+        /// "csv" prints a "latitude,longitude" header line and then one line per point.
+        /// "geojson" prints a FeatureCollection of Point features.
+        #[structopt(long, value_name = "format", default_value = "csv")]
+        format: geo::Format,
+
+        /// Seeds the points, so the same ones are printed every time.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Prints random MAC addresses, one per line. Locally administered unicast addresses unless
+    /// --oui or --vendor is given.
+    Mac {
+        /// The number of addresses to print.
+        #[structopt(long, value_name = "N", default_value = "1")]
+        count: u64,
+
+        /// The first three bytes of the addresses, such as 52:54:00.
+        #[structopt(long, value_name = "oui", parse(try_from_str = mac::parse_oui))]
+        oui: Option<mac::Oui>,
+
+        /// Gives the addresses the prefix a virtualization platform expects for its guests.
+        /// Possible values are: qemu, vmware, virtualbox, xen, hyper-v, random-laa. "random-laa"
+        /// is any locally administered unicast address, the default.
+        #[structopt(long, value_name = "vendor", conflicts_with = "oui")]
+        vendor: Option<mac::Vendor>,
+
+        /// Seeds the addresses, so the same ones are printed every time.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Prints a unique local IPv6 /48 prefix (RFC 4193) on the first line, and the first /64
+    /// subnets of it on the following lines. The global ID of the prefix is made with the
+    /// algorithm from the RFC, from the current time and the MAC address of a network interface.
+    Ula {
+        /// The number of /64 subnets to print, at most 65536.
+        #[structopt(long, value_name = "N", default_value = "4")]
+        subnets: u32,
+    },
+
+    /// Prints a random TOTP secret in base32 on the first line, and an otpauth:// URI for
+    /// enrolling it in an authenticator app on the second. For setting up two-factor
+    /// authentication for test users. Turn the URI into a QR code with for example `qrencode`.
+    Otp {
+        /// The service the secret is for, shown by the authenticator app.
+        #[structopt(long, value_name = "name", parse(try_from_str = otp::parse_issuer))]
+        issuer: String,
+
+        /// The user the secret is for, usually a user name or email address.
+        #[structopt(long, value_name = "name")]
+        account: String,
+
+        /// The length of the secret in bytes. At least 16.
+        #[structopt(long, value_name = "N", default_value = "20")]
+        secret_bytes: usize,
+
+        /// The number of digits of the codes, 6 or 8.
+        #[structopt(
+            long,
+            value_name = "N",
+            default_value = "6",
+            parse(try_from_str = otp::parse_digits)
+        )]
+        digits: u32,
+
+        /// How long each code is valid.
+        #[structopt(
+            long,
+            value_name = "duration",
+            default_value = "30s",
+            parse(try_from_str = parsing::parse_duration)
+        )]
+        period: Duration,
+
+        /// Seeds the secret, so the same one is printed every time. Only for tests, as anyone
+        /// knowing the seed knows the secret.
+        #[structopt(long, value_name = "N")]
+        seed: Option<u64>,
+    },
 }
 
-enum Output<'a> {
-    Stdout(io::StdoutLock<'a>),
-    File(fs::File),
+#[derive(Debug, StructOpt)]
+enum TestSuite {
+    /// Runs the frequency, block frequency, runs, longest run of ones and approximate entropy
+    /// tests from NIST SP 800-22 and prints the p-value of each. A test fails if its p-value is
+    /// below 0.01. Exits with a non-zero exit code if any test fails.
+    Nist {
+        /// The file to test. Reads from stdin if not given.
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+
+        /// Tests the output of this algorithm instead of reading data.
+        #[structopt(long, value_name = "algorithm", conflicts_with = "file")]
+        generate: Option<Algorithm>,
+
+        /// The number of bits to test. The block lengths of the tests are chosen for sequences
+        /// of at least a million bits.
+        #[structopt(long, value_name = "N", default_value = "1000000")]
+        bits: u64,
+    },
 }
 
-impl<'a> Write for Output<'a> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self {
-            Output::Stdout(stdout) => stdout.write(buf),
-            Output::File(f) => f.write(buf),
+/// Asks on the terminal whether to overwrite the block device at `path`. Returns false without
+/// asking if stdin is not a terminal.
+fn confirm_overwrite(path: &Path) -> bool {
+    use std::io::{BufRead, IsTerminal};
+
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!(
+        "Everything on {} will be overwritten. Type \"yes\" to continue: ",
+        path.display()
+    );
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).is_ok() && answer.trim() == "yes"
+}
+
+/// Applies --nice and --io-class to the process.
+fn set_priorities(opt: &Opt) {
+    if let Some(nice) = opt.nice {
+        if !(-20..=19).contains(&nice) {
+            eprintln!("--nice must be between -20 and 19");
+            ExitCode::Usage.exit();
+        }
+        if let Err(e) = platform::set_nice(nice) {
+            eprintln!("Failed to set the nice value: {}", e);
+            ExitCode::Failure.exit();
+        }
+    }
+    if let Some(class) = opt.io_class {
+        if let Err(e) = platform::set_io_class(class) {
+            eprintln!("Failed to set the I/O class: {}", e);
+            ExitCode::Failure.exit();
         }
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        match self {
-            Output::Stdout(stdout) => stdout.flush(),
-            Output::File(f) => f.flush(),
+fn main() {
+    let opt = Opt::from_iter_safe(std::env::args_os()).unwrap_or_else(|e| {
+        // --help and --version are reported as errors too, but printed to stdout
+        if !e.use_stderr() {
+            e.exit();
         }
+        eprintln!("{}", e.message);
+        ExitCode::Usage.exit();
+    });
+    if opt.si {
+        formatting::use_si_units();
+    }
+    // Before any threads are spawned, so they inherit the priorities
+    set_priorities(&opt);
+    match &opt.command {
+        Some(command) => commands::run(command),
+        None => generate::run(opt),
     }
 }
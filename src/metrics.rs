@@ -0,0 +1,182 @@
+//! Metrics of the serve modes, in the Prometheus text exposition format. `serve-http` serves
+//! them at `/metrics`, the other serve modes on a separate address given with `--metrics`.
+//!
+//! The data served in large pieces is also run through the inline self-check of `--self-check`,
+//! and failures are reported as the health status. See the `self_check` module. `serve-egd`
+//! only hands out a few bytes at a time, so it reports no health status.
+
+use crate::health::HealthAction;
+use crate::self_check::SelfCheck;
+use crate::Algorithm;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// The smallest piece of served data that is self-checked. A window of smaller pieces holds too
+/// little data for the tests to be meaningful.
+const MIN_SELF_CHECK_LEN: usize = 4096;
+
+/// Counters shared by all connections of a server.
+pub struct Metrics {
+    algorithm: Algorithm,
+    start: Instant,
+    bytes_served: AtomicU64,
+    connections: AtomicU64,
+    active_connections: AtomicU64,
+    /// Whether the served data is self-checked, and the self-check metrics are reported.
+    self_checked: bool,
+    self_check_failures: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new(algorithm: Algorithm) -> Arc<Self> {
+        Self::with_self_check(algorithm, true)
+    }
+
+    /// Metrics of a server handing out data in pieces too small to be self-checked.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub fn without_self_check(algorithm: Algorithm) -> Arc<Self> {
+        Self::with_self_check(algorithm, false)
+    }
+
+    fn with_self_check(algorithm: Algorithm, self_checked: bool) -> Arc<Self> {
+        Arc::new(Metrics {
+            algorithm,
+            start: Instant::now(),
+            bytes_served: AtomicU64::new(0),
+            connections: AtomicU64::new(0),
+            active_connections: AtomicU64::new(0),
+            self_checked,
+            self_check_failures: AtomicU64::new(0),
+        })
+    }
+
+    /// Starts tracking a new connection. It is active until the session is dropped.
+    pub fn session(self: &Arc<Self>) -> Session {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        Session {
+            metrics: self.clone(),
+            self_check: SelfCheck::new(HealthAction::Abort),
+        }
+    }
+
+    /// Formats the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let bytes_served = self.bytes_served.load(Ordering::Relaxed);
+        let failures = self.self_check_failures.load(Ordering::Relaxed);
+        let throughput = bytes_served as f64 / self.start.elapsed().as_secs_f64();
+        let algorithm = self
+            .algorithm
+            .to_string()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: &dyn fmt::Display| {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+            writeln!(out, "{}{{algorithm=\"{}\"}} {}", name, algorithm, value).unwrap();
+        };
+        metric(
+            "rng_bytes_served_total",
+            "counter",
+            "Random bytes served to clients.",
+            &bytes_served,
+        );
+        metric(
+            "rng_throughput_bytes_per_second",
+            "gauge",
+            "Average rate of random bytes served since the server started.",
+            &throughput,
+        );
+        metric(
+            "rng_connections_total",
+            "counter",
+            "Clients served.",
+            &self.connections.load(Ordering::Relaxed),
+        );
+        metric(
+            "rng_active_connections",
+            "gauge",
+            "Clients currently connected.",
+            &self.active_connections.load(Ordering::Relaxed),
+        );
+        if !self.self_checked {
+            return out;
+        }
+        metric(
+            "rng_self_check_failures_total",
+            "counter",
+            "Self-check failures on the served data.",
+            &failures,
+        );
+        metric(
+            "rng_healthy",
+            "gauge",
+            "1 if the served data never failed the self-check, otherwise 0.",
+            &u8::from(failures == 0),
+        );
+        out
+    }
+}
+
+/// One connection of a server.
+pub struct Session {
+    metrics: Arc<Metrics>,
+    self_check: SelfCheck,
+}
+
+impl Session {
+    /// Records `data` as served and runs the self-check on it if it is large enough. A failure
+    /// is counted and warned about, but serving continues.
+    pub fn served(&mut self, data: &[u8]) {
+        let metrics = &self.metrics;
+        metrics
+            .bytes_served
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        if !metrics.self_checked || data.len() < MIN_SELF_CHECK_LEN {
+            return;
+        }
+        if let Err(failure) = self.self_check.update(data) {
+            metrics.self_check_failures.fetch_add(1, Ordering::Relaxed);
+            eprintln!("WARNING: {}", failure);
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.metrics
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_metrics() {
+    let metrics = Metrics::new(Algorithm::ChaCha8);
+    let mut session = metrics.session();
+    session.served(&[1; 100]);
+    let rendered = metrics.render();
+    assert!(rendered.contains("rng_bytes_served_total{algorithm=\"chacha8\"} 100\n"));
+    assert!(rendered.contains("rng_active_connections{algorithm=\"chacha8\"} 1\n"));
+    assert!(rendered.contains("# TYPE rng_healthy gauge\n"));
+
+    // A window of constant data fails the self-check
+    for _ in 0..16 {
+        session.served(&[1; 4096]);
+    }
+    drop(session);
+    let rendered = metrics.render();
+    assert!(rendered.contains("rng_active_connections{algorithm=\"chacha8\"} 0\n"));
+    assert!(rendered.contains("rng_healthy{algorithm=\"chacha8\"} 0\n"));
+    assert!(rendered.contains("rng_connections_total{algorithm=\"chacha8\"} 1\n"));
+
+    let metrics = Metrics::without_self_check(Algorithm::ChaCha8);
+    metrics.session().served(&[1; 4096]);
+    let rendered = metrics.render();
+    assert!(rendered.contains("rng_bytes_served_total{algorithm=\"chacha8\"} 4096\n"));
+    assert!(!rendered.contains("rng_healthy"));
+}
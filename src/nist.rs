@@ -0,0 +1,234 @@
+//! The `test nist` subcommand. A subset of the statistical tests from NIST SP 800-22 rev. 1a,
+//! computed as described in section 2 of the publication.
+
+use crate::{analyze, stats, Algorithm};
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+/// The significance level. A sequence with a smaller p-value fails a test.
+pub const ALPHA: f64 = 0.01;
+
+/// The block length of the block frequency test. SP 800-22 recommends at least 20.
+const BLOCK_FREQUENCY_LENGTH: usize = 128;
+
+/// The block length of the approximate entropy test. SP 800-22 recommends less than
+/// log2(n) - 5, so this suits sequences of a million bits and more.
+const APPROXIMATE_ENTROPY_LENGTH: usize = 10;
+
+/// The fewest bits the tests can run on, given by the smallest longest run test variant.
+pub const MIN_BITS: u64 = 128;
+
+/// Runs all tests on the first `bits` bits of `file`, of stdin if no file is given, or of the
+/// output of the `generate` algorithm.
+pub fn run(
+    file: Option<&Path>,
+    generate: Option<Algorithm>,
+    bits: u64,
+) -> Result<Vec<TestResult>, Box<dyn Error>> {
+    let mut data = Vec::new();
+    analyze::open_input(file, generate)?
+        .take(bits.div_ceil(8))
+        .read_to_end(&mut data)?;
+    let available = data.len() as u64 * 8;
+    if available < MIN_BITS {
+        return Err(format!("The tests need at least {} bits of data", MIN_BITS).into());
+    }
+    let epsilon = to_bits(&data, bits.min(available) as usize);
+    Ok(run_tests(&epsilon))
+}
+
+/// Expands bytes to one bit per element, most significant bit first, keeping `len` bits.
+fn to_bits(data: &[u8], len: usize) -> Vec<u8> {
+    data.iter()
+        .flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+        .take(len)
+        .collect()
+}
+
+pub fn run_tests(epsilon: &[u8]) -> Vec<TestResult> {
+    vec![
+        TestResult::new("frequency", frequency(epsilon)),
+        TestResult::new(
+            "block frequency",
+            block_frequency(epsilon, BLOCK_FREQUENCY_LENGTH),
+        ),
+        TestResult::new("runs", runs(epsilon)),
+        TestResult::new("longest run of ones", longest_run(epsilon)),
+        TestResult::new(
+            "approximate entropy",
+            approximate_entropy(epsilon, APPROXIMATE_ENTROPY_LENGTH),
+        ),
+    ]
+}
+
+/// The frequency (monobit) test, section 2.1.
+fn frequency(epsilon: &[u8]) -> f64 {
+    let n = epsilon.len() as f64;
+    let sum: i64 = epsilon.iter().map(|&bit| 2 * i64::from(bit) - 1).sum();
+    let s_obs = (sum as f64).abs() / n.sqrt();
+    stats::erfc(s_obs / 2f64.sqrt())
+}
+
+/// The frequency test within a block, section 2.2.
+fn block_frequency(epsilon: &[u8], block_len: usize) -> f64 {
+    let blocks = epsilon.len() / block_len;
+    let chi_square: f64 = epsilon
+        .chunks_exact(block_len)
+        .map(|block| {
+            let ones = block.iter().filter(|&&bit| bit == 1).count();
+            let pi = ones as f64 / block_len as f64 - 0.5;
+            pi * pi
+        })
+        .sum::<f64>()
+        * 4.0
+        * block_len as f64;
+    stats::igamc(blocks as f64 / 2.0, chi_square / 2.0)
+}
+
+/// The runs test, section 2.3.
+fn runs(epsilon: &[u8]) -> f64 {
+    let n = epsilon.len() as f64;
+    let pi = epsilon.iter().filter(|&&bit| bit == 1).count() as f64 / n;
+    // The frequency prerequisite. The test is not applicable, and fails, if it is not met
+    if (pi - 0.5).abs() >= 2.0 / n.sqrt() {
+        return 0.0;
+    }
+    let v_obs = 1 + epsilon.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    let product = pi * (1.0 - pi);
+    stats::erfc((v_obs as f64 - 2.0 * n * product).abs() / (2.0 * (2.0 * n).sqrt() * product))
+}
+
+/// The test for the longest run of ones in a block, section 2.4. The block length and the
+/// class probabilities depend on the sequence length, as given in section 2.4.2 and 3.4.
+fn longest_run(epsilon: &[u8]) -> f64 {
+    let (block_len, min_class, probabilities): (usize, usize, &[f64]) = match epsilon.len() {
+        n if n >= 750_000 => (
+            10_000,
+            10,
+            &[0.0882, 0.2092, 0.2483, 0.1933, 0.1208, 0.0675, 0.0727],
+        ),
+        n if n >= 6272 => (128, 4, &[0.1174, 0.2430, 0.2493, 0.1752, 0.1027, 0.1124]),
+        _ => (8, 1, &[0.2148, 0.3672, 0.2305, 0.1875]),
+    };
+    let mut classes = vec![0u64; probabilities.len()];
+    for block in epsilon.chunks_exact(block_len) {
+        let longest = block
+            .split(|&bit| bit == 0)
+            .map(|run| run.len())
+            .max()
+            .unwrap_or(0);
+        let class = longest.clamp(min_class, min_class + classes.len() - 1) - min_class;
+        classes[class] += 1;
+    }
+    let blocks = (epsilon.len() / block_len) as f64;
+    let chi_square: f64 = classes
+        .iter()
+        .zip(probabilities)
+        .map(|(&observed, &p)| {
+            let expected = blocks * p;
+            (observed as f64 - expected).powi(2) / expected
+        })
+        .sum();
+    stats::igamc((probabilities.len() - 1) as f64 / 2.0, chi_square / 2.0)
+}
+
+/// The approximate entropy test, section 2.12.
+fn approximate_entropy(epsilon: &[u8], block_len: usize) -> f64 {
+    let n = epsilon.len() as f64;
+    let ap_en = phi(epsilon, block_len) - phi(epsilon, block_len + 1);
+    let chi_square = 2.0 * n * (2f64.ln() - ap_en);
+    stats::igamc((1u64 << (block_len - 1)) as f64, chi_square / 2.0)
+}
+
+/// The sum of C_i ln(C_i) over the frequencies C_i of every overlapping `m` bit pattern, with
+/// the sequence wrapped around at the end.
+fn phi(epsilon: &[u8], m: usize) -> f64 {
+    let n = epsilon.len();
+    let mask = (1usize << m) - 1;
+    let mut counts = vec![0u64; 1 << m];
+    let mut pattern = epsilon[..m - 1]
+        .iter()
+        .fold(0, |pattern, &bit| pattern << 1 | usize::from(bit));
+    for i in 0..n {
+        pattern = (pattern << 1 | usize::from(epsilon[(i + m - 1) % n])) & mask;
+        counts[pattern] += 1;
+    }
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let c = count as f64 / n as f64;
+            c * c.ln()
+        })
+        .sum()
+}
+
+/// The outcome of one test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: &'static str,
+    pub p_value: f64,
+}
+
+impl TestResult {
+    fn new(name: &'static str, p_value: f64) -> Self {
+        TestResult { name, p_value }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.p_value >= ALPHA
+    }
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<22} {:.6}  {}",
+            self.name,
+            self.p_value,
+            if self.passed() { "PASS" } else { "FAIL" }
+        )
+    }
+}
+
+#[cfg(test)]
+fn parse_bits(s: &str) -> Vec<u8> {
+    s.bytes().map(|c| c - b'0').collect()
+}
+
+#[test]
+fn test_nist_examples() {
+    // The examples with n = 100 and n = 128 from section 2 of SP 800-22
+    let epsilon = parse_bits(
+        "11001001000011111101101010100010001000010110100011\
+         00001000110100110001001100011001100010100010111000",
+    );
+    let assert_p = |actual: f64, expected: f64| {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    };
+    assert_p(frequency(&epsilon), 0.109_599);
+    assert_p(block_frequency(&epsilon, 10), 0.706_438);
+    assert_p(runs(&epsilon), 0.500_798);
+    assert_p(approximate_entropy(&epsilon, 2), 0.235_301);
+
+    let epsilon = parse_bits(
+        "11001100000101010110110001001100111000000000001001\
+         00110101010001000100111101011010000000110101111100\
+         1100111001101101100010110010",
+    );
+    assert_p(longest_run(&epsilon), 0.180_598);
+
+    let data: Vec<u8> = (0..1 << 17).map(|_| rand::random()).collect();
+    let results = run_tests(&to_bits(&data, 1_000_000));
+    assert_eq!(results.len(), 5);
+    assert!(results.iter().all(|result| result.p_value > 1e-6));
+    assert!(run_tests(&[1; 1000]).iter().all(|result| !result.passed()));
+}
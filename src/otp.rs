@@ -0,0 +1,107 @@
+//! The `otp` subcommand. Random TOTP secrets with an otpauth:// URI for provisioning them, in the
+//! key URI format most authenticator apps read from QR codes.
+
+use crate::formatting;
+use crate::parsing::ParseError;
+use rand::RngCore;
+
+/// The shortest secret allowed, in bytes. RFC 4226 requires at least 128 bits.
+pub const MIN_SECRET_LEN: usize = 16;
+
+/// Parses the issuer of the secret. It can't contain a colon, which separates the issuer from
+/// the account in the label of the URI.
+pub fn parse_issuer(s: &str) -> Result<String, ParseError> {
+    match s {
+        "" => Err(ParseError::new("The issuer can't be empty".to_owned())),
+        s if s.contains(':') => Err(ParseError::new(
+            "The issuer can't contain a colon".to_owned(),
+        )),
+        s => Ok(s.to_owned()),
+    }
+}
+
+/// Parses the number of digits of the codes. Authenticator apps support 6 and 8.
+pub fn parse_digits(s: &str) -> Result<u32, ParseError> {
+    match s {
+        "6" => Ok(6),
+        "8" => Ok(8),
+        _ => Err(ParseError::new(
+            "The codes can have 6 or 8 digits".to_owned(),
+        )),
+    }
+}
+
+/// A random secret of `len` bytes, encoded as base32.
+pub fn secret(len: usize, rng: &mut dyn RngCore) -> String {
+    let mut secret = vec![0; len];
+    rng.fill_bytes(&mut secret);
+    formatting::base32(&secret)
+}
+
+/// What the URI tells the authenticator app.
+#[derive(Debug)]
+pub struct Enrollment<'a> {
+    /// The base32 secret.
+    pub secret: &'a str,
+    /// The service the secret is for.
+    pub issuer: &'a str,
+    /// The user the secret is for, usually a user name or email address.
+    pub account: &'a str,
+    pub digits: u32,
+    /// How long each code is valid, in seconds.
+    pub period: u64,
+}
+
+/// The otpauth:// URI of the enrollment. The algorithm is always SHA1, as many apps ignore
+/// anything else.
+pub fn uri(enrollment: &Enrollment) -> String {
+    let issuer = percent_encode(enrollment.issuer);
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer,
+        percent_encode(enrollment.account),
+        enrollment.secret,
+        issuer,
+        enrollment.digits,
+        enrollment.period
+    )
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[test]
+fn test_otp() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    let secret = secret(20, &mut rng);
+    assert_eq!(secret.len(), 32);
+    assert!(secret
+        .bytes()
+        .all(|b| b.is_ascii_uppercase() || (b'2'..=b'7').contains(&b)));
+
+    let enrollment = Enrollment {
+        secret: "JBSWY3DPEHPK3PXP",
+        issuer: "ACME Co",
+        account: "user@example.com",
+        digits: 6,
+        period: 30,
+    };
+    assert_eq!(
+        uri(&enrollment),
+        "otpauth://totp/ACME%20Co:user%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co\
+         &algorithm=SHA1&digits=6&period=30"
+    );
+    assert!(parse_issuer("a:b").is_err());
+    assert!(parse_digits("7").is_err());
+}
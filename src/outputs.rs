@@ -0,0 +1,1257 @@
+//! Where the generated data is written. `--output` takes a file path, or an address of a
+//! network service or a FIFO to stream the data to. It can be given several times to write the
+//! same data to several outputs.
+
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{self, IoSlice, Seek, SeekFrom, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// The largest payload of a UDP datagram over IPv4.
+pub const MAX_PACKET_SIZE: usize = 65_507;
+
+/// How often `Retry` checks if it should stop while waiting to try again.
+const RETRY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The destination given with `--output`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OutputTarget {
+    /// Standard output, from "-".
+    Stdout,
+    /// Discards the data without writing it anywhere, from "null". For measuring how fast the
+    /// data is generated.
+    Null,
+    File(PathBuf),
+    /// A TCP server to connect to, from "tcp://host:port".
+    Tcp(String),
+    /// An address to send UDP datagrams to, from "udp://host:port".
+    Udp(String),
+    /// A Unix domain stream socket to connect to, from "unix:/path/to.sock".
+    Unix(PathBuf),
+    /// A Windows named pipe to connect to or create, from "\\.\pipe\name".
+    NamedPipe(PathBuf),
+    /// A FIFO to create if needed and stream to, from "fifo:/path/to/fifo".
+    Fifo(PathBuf),
+}
+
+impl OutputTarget {
+    /// Parses a target. Everything without a known scheme prefix is a file path.
+    pub fn from_os_str(s: &OsStr) -> Self {
+        match s.to_str() {
+            Some("-") => OutputTarget::Stdout,
+            Some("null") => OutputTarget::Null,
+            Some(s) if s.starts_with("tcp://") => OutputTarget::Tcp(s["tcp://".len()..].to_owned()),
+            Some(s) if s.starts_with("udp://") => OutputTarget::Udp(s["udp://".len()..].to_owned()),
+            Some(s) if s.starts_with("unix:") => {
+                OutputTarget::Unix(PathBuf::from(&s["unix:".len()..]))
+            }
+            Some(s) if s.starts_with("fifo:") => {
+                OutputTarget::Fifo(PathBuf::from(&s["fifo:".len()..]))
+            }
+            Some(s) if cfg!(windows) && is_named_pipe(s) => OutputTarget::NamedPipe(s.into()),
+            _ => OutputTarget::File(PathBuf::from(s)),
+        }
+    }
+}
+
+/// Formats the target like it was given on the command line.
+impl fmt::Display for OutputTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputTarget::Stdout => write!(f, "stdout"),
+            OutputTarget::Null => write!(f, "null"),
+            OutputTarget::File(path) | OutputTarget::NamedPipe(path) => path.display().fmt(f),
+            OutputTarget::Tcp(address) => write!(f, "tcp://{}", address),
+            OutputTarget::Udp(address) => write!(f, "udp://{}", address),
+            OutputTarget::Unix(path) => write!(f, "unix:{}", path.display()),
+            OutputTarget::Fifo(path) => write!(f, "fifo:{}", path.display()),
+        }
+    }
+}
+
+/// What to do when writing to an output fails.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OutputErrorPolicy {
+    /// Stop generating.
+    Abort,
+    /// Stop writing to the failed output and keep writing to the others.
+    Drop,
+}
+
+impl std::str::FromStr for OutputErrorPolicy {
+    type Err = ParseOutputErrorPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(OutputErrorPolicy::Abort),
+            "drop" => Ok(OutputErrorPolicy::Drop),
+            _ => Err(ParseOutputErrorPolicyError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseOutputErrorPolicyError(());
+
+impl fmt::Display for ParseOutputErrorPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid output error policy. See --help for a list of valid options."
+        )
+    }
+}
+
+/// How data is handed to the operating system.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IoBackend {
+    /// Plain write calls.
+    Write,
+    /// vmsplice into a stdout pipe. Linux only.
+    Splice,
+    /// Several overlapped writes in flight to files. Windows only.
+    Overlapped,
+    /// Generating directly into a memory mapped file. Unix only.
+    Mmap,
+    /// Worker threads writing to their own parts of a file at once.
+    Pwrite,
+}
+
+impl std::str::FromStr for IoBackend {
+    type Err = ParseIoBackendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "write" => Ok(IoBackend::Write),
+            "splice" => Ok(IoBackend::Splice),
+            "overlapped" => Ok(IoBackend::Overlapped),
+            "mmap" => Ok(IoBackend::Mmap),
+            "pwrite" => Ok(IoBackend::Pwrite),
+            _ => Err(ParseIoBackendError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseIoBackendError(());
+
+impl fmt::Display for ParseIoBackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid I/O backend. See --help for a list of valid options."
+        )
+    }
+}
+
+/// Writes the same data to several outputs, each with its own error policy. Fails when an
+/// output with the abort policy fails, or when no outputs remain.
+pub struct Tee<W> {
+    outputs: Vec<TeeOutput<W>>,
+}
+
+struct TeeOutput<W> {
+    /// `None` after the output failed and was dropped.
+    writer: Option<W>,
+    name: String,
+    policy: OutputErrorPolicy,
+}
+
+impl<W> Default for Tee<W> {
+    fn default() -> Self {
+        Tee {
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Tee<W> {
+    pub fn push(&mut self, writer: W, name: String, policy: OutputErrorPolicy) {
+        self.outputs.push(TeeOutput {
+            writer: Some(writer),
+            name,
+            policy,
+        });
+    }
+
+    /// Calls `f` on every remaining output and applies the error policy of the ones failing.
+    pub fn for_each(&mut self, mut f: impl FnMut(&mut W) -> io::Result<()>) -> io::Result<()> {
+        for output in &mut self.outputs {
+            let writer = match &mut output.writer {
+                Some(writer) => writer,
+                None => continue,
+            };
+            if let Err(e) = f(writer) {
+                match output.policy {
+                    OutputErrorPolicy::Abort => return Err(e),
+                    OutputErrorPolicy::Drop => {
+                        eprintln!("WARNING: Stopped writing to {}: {}", output.name, e);
+                        output.writer = None;
+                    }
+                }
+            }
+        }
+        match self.outputs.iter().any(|output| output.writer.is_some()) {
+            true => Ok(()),
+            false => Err(io::Error::other("Writing to all outputs failed")),
+        }
+    }
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.for_each(|writer| writer.write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.for_each(|writer| write_all_vectored(writer, bufs))?;
+        Ok(bufs.iter().map(|buf| buf.len()).sum())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.for_each(|writer| writer.flush())
+    }
+}
+
+/// Writes to `inner` in blocks of exactly `block_size` bytes, like dd with `bs`. Data is held
+/// back until a whole block is available. Flushing writes out what is held back as a shorter
+/// block. Without a block size, everything is passed straight through.
+pub struct Blocks<W> {
+    inner: W,
+    block_size: Option<usize>,
+    buf: Zeroizing<Vec<u8>>,
+}
+
+impl<W: Write> Blocks<W> {
+    pub fn new(inner: W, block_size: Option<usize>) -> Self {
+        Blocks {
+            inner,
+            block_size,
+            buf: Zeroizing::new(Vec::with_capacity(block_size.unwrap_or(0))),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for Blocks<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let block_size = match self.block_size {
+            Some(block_size) => block_size,
+            None => return self.inner.write(buf),
+        };
+        if self.buf.is_empty() && buf.len() >= block_size {
+            // Whole blocks can be written without copying them
+            let len = buf.len() - buf.len() % block_size;
+            for block in buf[..len].chunks(block_size) {
+                self.inner.write_all(block)?;
+            }
+            return Ok(len);
+        }
+        let len = buf.len().min(block_size - self.buf.len());
+        self.buf.extend_from_slice(&buf[..len]);
+        if self.buf.len() == block_size {
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(len)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        match self.block_size {
+            Some(_) => {
+                let buf = bufs
+                    .iter()
+                    .find(|buf| !buf.is_empty())
+                    .map_or(&[][..], |buf| buf);
+                self.write(buf)
+            }
+            None => self.inner.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        self.inner.flush()
+    }
+}
+
+/// Retries writes failing with transient errors, for `--retry`. Gives up after `retries` failed
+/// attempts in a row, waiting `delay` before each new attempt. Gives up at once when
+/// `should_abort` returns true, also while waiting.
+pub struct Retry<W> {
+    inner: W,
+    name: String,
+    retries: u32,
+    delay: Duration,
+    should_abort: Box<dyn Fn() -> bool>,
+}
+
+impl<W: Write> Retry<W> {
+    pub fn new(
+        inner: W,
+        name: String,
+        retries: u32,
+        delay: Duration,
+        should_abort: Box<dyn Fn() -> bool>,
+    ) -> Self {
+        Retry {
+            inner,
+            name,
+            retries,
+            delay,
+            should_abort,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    fn retry<T>(&mut self, mut f: impl FnMut(&mut W) -> io::Result<T>) -> io::Result<T> {
+        let mut attempt = 0;
+        loop {
+            match f(&mut self.inner) {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    eprintln!(
+                        "WARNING: Writing to {} failed, retrying ({} of {}): {}",
+                        self.name, attempt, self.retries, e
+                    );
+                    if !self.wait() {
+                        return Err(e);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sleeps for the delay between attempts. Returns false without waiting the rest of it if
+    /// the program is told to stop.
+    fn wait(&self) -> bool {
+        let deadline = Instant::now() + self.delay;
+        loop {
+            if (self.should_abort)() {
+                return false;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return true;
+            }
+            thread::sleep(left.min(RETRY_POLL_INTERVAL));
+        }
+    }
+}
+
+impl<W: Write> Write for Retry<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.retry(|inner| inner.write(buf))
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.retry(|inner| inner.write_vectored(bufs))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.retry(|inner| inner.flush())
+    }
+}
+
+/// Returns true for the errors that can go away by themselves, so a write failing with them is
+/// worth trying again. EIO is included for the momentary failures of network filesystems.
+pub fn is_transient(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(libc::EIO) {
+        return true;
+    }
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::StaleNetworkFileHandle
+    )
+}
+
+/// Writes all of `bufs` to `writer` with as few vectored writes as it allows.
+pub fn write_all_vectored(writer: &mut impl Write, bufs: &[IoSlice<'_>]) -> io::Result<()> {
+    let mut bufs = bufs.to_vec();
+    let mut bufs = &mut bufs[..];
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(written) => IoSlice::advance_slices(&mut bufs, written),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// If `path` is in the namespace of Windows named pipes.
+fn is_named_pipe(path: &str) -> bool {
+    path.get(..9)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(r"\\.\pipe\"))
+}
+
+/// Streams data to a TCP server. Optionally reconnects when the connection fails, both when
+/// first connecting and when writing. A write that fails waits for the new connection and is
+/// then sent again, so only data already sent on the lost connection can go missing.
+pub struct TcpOutput {
+    address: String,
+    stream: Option<TcpStream>,
+    /// How long to wait before trying to connect again, or `None` to not reconnect.
+    reconnect_delay: Option<Duration>,
+    should_abort: Box<dyn Fn() -> bool>,
+}
+
+impl TcpOutput {
+    pub fn connect(
+        address: String,
+        reconnect_delay: Option<Duration>,
+        should_abort: Box<dyn Fn() -> bool>,
+    ) -> io::Result<Self> {
+        let mut output = TcpOutput {
+            address,
+            stream: None,
+            reconnect_delay,
+            should_abort,
+        };
+        output.reconnect()?;
+        Ok(output)
+    }
+
+    /// Connects to the server. Keeps trying until it succeeds if reconnecting is enabled, or
+    /// until the program is told to stop.
+    fn reconnect(&mut self) -> io::Result<()> {
+        let mut warned = false;
+        loop {
+            match TcpStream::connect(&self.address) {
+                Ok(stream) => {
+                    // Data is written in large buffers, there is nothing to gain from delaying
+                    stream.set_nodelay(true)?;
+                    self.stream = Some(stream);
+                    return Ok(());
+                }
+                Err(e) => match self.reconnect_delay {
+                    Some(delay) if !(self.should_abort)() => {
+                        if !warned {
+                            eprintln!(
+                                "WARNING: Failed to connect to {}: {}. Retrying",
+                                self.address, e
+                            );
+                            warned = true;
+                        }
+                        thread::sleep(delay);
+                    }
+                    _ => return Err(e),
+                },
+            }
+        }
+    }
+}
+
+impl Write for TcpOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let stream = match &mut self.stream {
+                Some(stream) => stream,
+                None => {
+                    self.reconnect()?;
+                    continue;
+                }
+            };
+            match stream.write(buf) {
+                Err(e)
+                    if e.kind() != io::ErrorKind::Interrupted && self.reconnect_delay.is_some() =>
+                {
+                    eprintln!(
+                        "WARNING: Lost the connection to {}: {}. Reconnecting",
+                        self.address, e
+                    );
+                    self.stream = None;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes a sparse file. The data is split into extents, and a `hole_ratio` share of them,
+/// spread evenly, are left as holes instead of written. The data meant for the holes is dropped,
+/// so the data extents hold the same data at the same offsets as a dense file would.
+pub struct SparseOutput {
+    file: fs::File,
+    extent: u64,
+    hole_ratio: f64,
+    /// The offset in the file the next data belongs at.
+    position: u64,
+}
+
+impl SparseOutput {
+    /// Writes to `file` from its current position.
+    pub fn new(mut file: fs::File, extent: u64, hole_ratio: f64) -> io::Result<Self> {
+        crate::platform::set_sparse(&file)?;
+        let position = file.stream_position()?;
+        Ok(SparseOutput {
+            file,
+            extent,
+            hole_ratio,
+            position,
+        })
+    }
+
+    pub fn file(&self) -> &fs::File {
+        &self.file
+    }
+
+    /// If the extent with index `extent` is a hole. Exactly every `1 / hole_ratio`th extent,
+    /// rounded, is one.
+    fn is_hole(&self, extent: u64) -> bool {
+        ((extent + 1) as f64 * self.hole_ratio).floor() > (extent as f64 * self.hole_ratio).floor()
+    }
+}
+
+impl Write for SparseOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let left_in_extent = self.extent - self.position % self.extent;
+        let len = buf
+            .len()
+            .min(usize::try_from(left_in_extent).unwrap_or(usize::MAX));
+        if self.is_hole(self.position / self.extent) {
+            // Seeking past the end leaves a hole, punching one also covers existing data
+            crate::platform::punch_hole(&self.file, self.position, len as u64)?;
+            self.position += len as u64;
+            self.file.seek(SeekFrom::Start(self.position))?;
+            Ok(len)
+        } else {
+            let written = self.file.write(&buf[..len])?;
+            self.position += written as u64;
+            Ok(written)
+        }
+    }
+
+    /// Extends the file over a trailing hole, which seeking alone does not.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.file.metadata()?.len() < self.position {
+            self.file.set_len(self.position)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a file through a memory mapping instead of with write calls. Data that is written from
+/// where it already is in the mapping, because it was generated there, is not copied. The file is
+/// cut to the written length when the output is dropped.
+#[cfg(unix)]
+pub struct MappedOutput {
+    file: fs::File,
+    /// Only accessed through pointers, as worker threads write to the unwritten part.
+    pages: crate::platform::Pages,
+    len: usize,
+    position: usize,
+}
+
+#[cfg(unix)]
+impl MappedOutput {
+    /// Grows `file`, which must be open for reading and writing, to fit `len` bytes after its
+    /// current position and maps it. Writing starts from the current position. The space is
+    /// reserved up front where possible, as running out of it while writing to the mapping
+    /// would crash the process.
+    pub fn new(mut file: fs::File, len: u64) -> io::Result<Self> {
+        let position = file.stream_position()?;
+        if let Err(e) = crate::platform::preallocate(&file, position + len) {
+            if e.kind() == io::ErrorKind::StorageFull {
+                return Err(e);
+            }
+        }
+        file.set_len(position + len)?;
+        let len = usize::try_from(position + len).map_err(io::Error::other)?;
+        let pages = crate::platform::map_file(&file, len)?;
+        Ok(MappedOutput {
+            file,
+            pages,
+            len,
+            position: position as usize,
+        })
+    }
+
+    pub fn file(&self) -> &fs::File {
+        &self.file
+    }
+
+    /// Writes the mapped data to the file.
+    pub fn sync(&self) -> io::Result<()> {
+        self.pages.sync()
+    }
+
+    /// The part of the mapping that is not written yet, for generating data into.
+    pub fn unwritten(&mut self) -> MappedRegion {
+        MappedRegion {
+            ptr: self.pages.as_mut_ptr().wrapping_add(self.position),
+            len: self.len - self.position,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Write for MappedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.len - self.position < buf.len() {
+            return Err(io::Error::other("Wrote past the end of the mapped file"));
+        }
+        let target = self.pages.as_mut_ptr().wrapping_add(self.position);
+        if !std::ptr::eq(target, buf.as_ptr()) {
+            // Safety: The target is within the mapping, and `copy` allows `buf` to overlap it
+            unsafe { std::ptr::copy(buf.as_ptr(), target, buf.len()) };
+        }
+        self.position += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedOutput {
+    fn drop(&mut self) {
+        // Only the mapping reaches past the new end, and it is not used again
+        let _ = self.file.set_len(self.position as u64);
+    }
+}
+
+/// A file that the worker threads write to themselves, each block at its offset. Writing to this
+/// output only counts the data as written, as the data given to it has already been written by
+/// a worker. Writing fails if a worker failed to write the data. The file is truncated to the
+/// data counted as written when the output is dropped, so stopping early leaves no unwritten
+/// part at the end.
+pub struct PositionedOutput {
+    target: PositionedTarget,
+    written: u64,
+}
+
+impl PositionedOutput {
+    /// Grows `file` to fit `len` bytes after its current position, where the workers start
+    /// writing. The space is reserved up front where possible.
+    pub fn new(mut file: fs::File, len: u64) -> io::Result<Self> {
+        let start = file.stream_position()?;
+        if let Err(e) = crate::platform::preallocate(&file, start + len) {
+            if e.kind() == io::ErrorKind::StorageFull {
+                return Err(e);
+            }
+        }
+        file.set_len(start + len)?;
+        Ok(PositionedOutput {
+            target: PositionedTarget {
+                file: std::sync::Arc::new(file),
+                start,
+                len,
+                error: Default::default(),
+            },
+            written: 0,
+        })
+    }
+
+    pub fn file(&self) -> &fs::File {
+        &self.target.file
+    }
+
+    /// Where the worker threads are to write the data.
+    pub fn target(&self) -> PositionedTarget {
+        self.target.clone()
+    }
+}
+
+impl Write for PositionedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(e) = self.target.error.lock().expect("Not poisoned").take() {
+            return Err(e);
+        }
+        if self.target.len - self.written < buf.len() as u64 {
+            return Err(io::Error::other("Wrote past the end of the output"));
+        }
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PositionedOutput {
+    fn drop(&mut self) {
+        if self.written < self.target.len {
+            let _ = self.target.file.set_len(self.target.start + self.written);
+        }
+    }
+}
+
+/// The part of the file of a `PositionedOutput` to write, from `start` and `len` bytes on.
+#[derive(Debug, Clone)]
+pub struct PositionedTarget {
+    pub file: std::sync::Arc<fs::File>,
+    pub start: u64,
+    pub len: u64,
+    /// A write error of a worker, for the output to return.
+    error: std::sync::Arc<std::sync::Mutex<Option<io::Error>>>,
+}
+
+impl PositionedTarget {
+    /// Makes the next write to the output fail with `error`.
+    pub fn fail(&self, error: io::Error) {
+        *self.error.lock().expect("Not poisoned") = Some(error);
+    }
+}
+
+/// A part of a `MappedOutput` that worker threads generate data directly into.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(not(unix), allow(dead_code))]
+pub struct MappedRegion {
+    ptr: *mut u8,
+    pub len: usize,
+}
+
+// Threads only access the region through `slice` and `slice_mut`, whose callers must make sure
+// they don't overlap
+unsafe impl Send for MappedRegion {}
+unsafe impl Sync for MappedRegion {}
+
+impl MappedRegion {
+    /// # Safety
+    ///
+    /// The range must be within the region, and not be written at the same time.
+    pub unsafe fn slice(&self, range: std::ops::Range<usize>) -> &[u8] {
+        std::slice::from_raw_parts(self.ptr.add(range.start), range.len())
+    }
+
+    /// # Safety
+    ///
+    /// The range must be within the region, and not be accessed by anything else at the same
+    /// time.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn slice_mut(&self, range: std::ops::Range<usize>) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.ptr.add(range.start), range.len())
+    }
+}
+
+/// Streams data to a FIFO. When the reader closes its end, waits for the next reader to open it
+/// and continues writing to that one, so several consumers can be fed one after another. Data
+/// is never dropped, the generation is paused while no reader is attached.
+pub struct FifoOutput {
+    path: PathBuf,
+    file: Option<fs::File>,
+    should_abort: Box<dyn Fn() -> bool>,
+}
+
+impl FifoOutput {
+    /// Creates the FIFO at `path` if needed and waits for the first reader.
+    pub fn open(path: PathBuf, should_abort: Box<dyn Fn() -> bool>) -> io::Result<Self> {
+        let file = crate::platform::open_fifo(&path, &*should_abort)?;
+        Ok(FifoOutput {
+            path,
+            file: Some(file),
+            should_abort,
+        })
+    }
+}
+
+impl Write for FifoOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let file = match &mut self.file {
+                Some(file) => file,
+                None => {
+                    let file = crate::platform::open_fifo(&self.path, &*self.should_abort)?;
+                    self.file = Some(file);
+                    continue;
+                }
+            };
+            match file.write(buf) {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                    eprintln!(
+                        "WARNING: The reader of {} went away. Waiting for a new reader",
+                        self.path.display()
+                    );
+                    self.file = None;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Sends the data as UDP datagrams of a fixed size, optionally limited to a number of bytes per
+/// second.
+pub struct UdpOutput {
+    socket: UdpSocket,
+    packet: Vec<u8>,
+    packet_size: usize,
+    /// The maximum number of bytes per second, when the packets were first sent and how many
+    /// bytes have been sent since.
+    rate: Option<u64>,
+    start: Option<Instant>,
+    sent: u64,
+}
+
+impl UdpOutput {
+    pub fn connect(address: &str, packet_size: usize, rate: Option<u64>) -> io::Result<Self> {
+        let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "The address did not resolve")
+        })?;
+        let local = if address.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(address)?;
+        Ok(UdpOutput {
+            socket,
+            packet: Vec::with_capacity(packet_size),
+            packet_size,
+            rate,
+            start: None,
+            sent: 0,
+        })
+    }
+
+    fn send_packet(&mut self) -> io::Result<()> {
+        if let Some(rate) = self.rate {
+            // Wait until sending the packet does not take the average above the rate
+            let start = *self.start.get_or_insert_with(Instant::now);
+            let due = Duration::from_secs_f64(self.sent as f64 / rate as f64);
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        match self.socket.send(&self.packet) {
+            // The ICMP error of an earlier datagram. A traffic generator does not care if
+            // anything is listening
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => (),
+            Err(e) => return Err(e),
+            Ok(_) => (),
+        }
+        self.sent += self.packet.len() as u64;
+        self.packet.clear();
+        Ok(())
+    }
+}
+
+impl Write for UdpOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.packet_size - self.packet.len());
+        self.packet.extend_from_slice(&buf[..len]);
+        if self.packet.len() == self.packet_size {
+            self.send_packet()?;
+        }
+        Ok(len)
+    }
+
+    /// Does nothing. Data not filling a whole packet is kept until more is written, so flushing
+    /// in the middle of the output never sends a short datagram.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for UdpOutput {
+    /// Sends what remains when the output ends, as a last datagram shorter than the packet
+    /// size. Errors are ignored, as nothing can be done about them here.
+    fn drop(&mut self) {
+        if !self.packet.is_empty() {
+            let _ = self.send_packet();
+        }
+    }
+}
+
+#[test]
+fn test_tcp_output() {
+    use std::io::Read;
+
+    assert_eq!(
+        OutputTarget::from_os_str(OsStr::new("tcp://127.0.0.1:1")),
+        OutputTarget::Tcp("127.0.0.1:1".to_owned())
+    );
+    assert_eq!(
+        OutputTarget::from_os_str(OsStr::new("tcp.bin")),
+        OutputTarget::File(PathBuf::from("tcp.bin"))
+    );
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let reader = thread::spawn(move || {
+        let mut received = Vec::new();
+        listener
+            .accept()
+            .unwrap()
+            .0
+            .read_to_end(&mut received)
+            .unwrap();
+        received
+    });
+    let mut output = TcpOutput::connect(address.clone(), None, Box::new(|| false)).unwrap();
+    output.write_all(&[7; 100_000]).unwrap();
+    drop(output);
+    assert_eq!(reader.join().unwrap(), vec![7; 100_000]);
+
+    // Nothing listens on the address anymore
+    assert!(TcpOutput::connect(address.clone(), None, Box::new(|| false)).is_err());
+    let aborted = TcpOutput::connect(address, Some(Duration::from_millis(1)), Box::new(|| true));
+    assert!(aborted.is_err());
+}
+
+#[test]
+fn test_udp_output() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = receiver.local_addr().unwrap().to_string();
+    assert_eq!(
+        OutputTarget::from_os_str(OsStr::new(&format!("udp://{}", address))),
+        OutputTarget::Udp(address.clone())
+    );
+
+    let mut output = UdpOutput::connect(&address, 100, Some(10_000)).unwrap();
+    let start = Instant::now();
+    output.write_all(&[7; 250]).unwrap();
+    output.flush().unwrap();
+    let mut buf = [0u8; 1000];
+    let sizes: Vec<usize> = (0..2).map(|_| receiver.recv(&mut buf).unwrap()).collect();
+    assert_eq!(sizes, [100, 100]);
+    // Flushing keeps the remainder, so every packet but the last is full
+    receiver.set_nonblocking(true).unwrap();
+    assert!(receiver.recv(&mut buf).is_err());
+    receiver.set_nonblocking(false).unwrap();
+    output.write_all(&[7; 60]).unwrap();
+    assert_eq!(receiver.recv(&mut buf).unwrap(), 100);
+    drop(output);
+    assert_eq!(receiver.recv(&mut buf).unwrap(), 10);
+    // The last packet is due after 300 bytes at 10000 bytes per second
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[test]
+fn test_sparse_output() {
+    let path = std::env::temp_dir().join(format!("rng-test-sparse-{}", std::process::id()));
+    let data: Vec<u8> = (1..=255).cycle().take(10_000).collect();
+    let file = fs::File::create(&path).unwrap();
+    let mut output = SparseOutput::new(file, 1000, 0.5).unwrap();
+    output.write_all(&data).unwrap();
+    output.flush().unwrap();
+
+    let written = fs::read(&path).unwrap();
+    assert_eq!(written.len(), 10_000);
+    for (extent, (written, data)) in written.chunks(1000).zip(data.chunks(1000)).enumerate() {
+        match extent % 2 {
+            0 => assert_eq!(written, data),
+            _ => assert!(written.iter().all(|&b| b == 0)),
+        }
+    }
+
+    let output = SparseOutput::new(fs::File::create(&path).unwrap(), 1, 0.25).unwrap();
+    let holes: Vec<bool> = (0..8).map(|extent| output.is_hole(extent)).collect();
+    assert_eq!(
+        holes,
+        [false, false, false, true, false, false, false, true]
+    );
+    drop(output);
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_mapped_output() {
+    let path = std::env::temp_dir().join(format!("rng-test-mapped-{}", std::process::id()));
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    let mut output = MappedOutput::new(file, 1000).unwrap();
+    output.write_all(b"copied").unwrap();
+    // Data generated in place is only taken as written
+    let region = output.unwritten();
+    assert_eq!(region.len, 994);
+    let in_place = unsafe { region.slice_mut(0..4) };
+    in_place.copy_from_slice(b"here");
+    output.write_all(in_place).unwrap();
+    assert!(output.write_all(&[1; 991]).is_err());
+    drop(output);
+
+    assert_eq!(fs::read(&path).unwrap(), b"copiedhere");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_blocks() {
+    /// Records the size of every write.
+    #[derive(Default)]
+    struct Writes(Vec<usize>);
+
+    impl Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut blocks = Blocks::new(Writes::default(), Some(100));
+    blocks.write_all(&[0; 250]).unwrap();
+    blocks.write_all(&[0; 30]).unwrap();
+    blocks.write_all(&[0; 30]).unwrap();
+    blocks.flush().unwrap();
+    assert_eq!(blocks.get_mut().0, [100, 100, 100, 10]);
+
+    let mut blocks = Blocks::new(Writes::default(), None);
+    blocks.write_all(&[0; 250]).unwrap();
+    blocks.flush().unwrap();
+    assert_eq!(blocks.get_mut().0, [250]);
+
+    // The default vectored write is one write per buffer
+    let bufs = [
+        IoSlice::new(&[0; 100]),
+        IoSlice::new(&[]),
+        IoSlice::new(&[0; 50]),
+    ];
+    let mut blocks = Blocks::new(Writes::default(), Some(60));
+    write_all_vectored(&mut blocks, &bufs).unwrap();
+    blocks.flush().unwrap();
+    assert_eq!(blocks.get_mut().0, [60, 60, 30]);
+}
+
+#[test]
+fn test_retry() {
+    /// Fails with `WouldBlock` the given number of times before every successful write.
+    struct Flaky {
+        failures: u32,
+        left: u32,
+        written: Vec<u8>,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.left > 0 {
+                self.left -= 1;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.left = self.failures;
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let flaky = |failures| Flaky {
+        failures,
+        left: failures,
+        written: Vec::new(),
+    };
+    let never = || Box::new(|| false);
+    let mut retry = Retry::new(flaky(2), "flaky".to_owned(), 2, Duration::ZERO, never());
+    retry.write_all(b"abc").unwrap();
+    retry.write_all(b"def").unwrap();
+    assert_eq!(retry.get_mut().written, b"abcdef");
+
+    let mut retry = Retry::new(flaky(3), "flaky".to_owned(), 2, Duration::ZERO, never());
+    let e = retry.write_all(b"abc").unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+
+    // Stopping the program cuts the wait for the next attempt short
+    let delay = Duration::from_secs(60);
+    let mut retry = Retry::new(flaky(1), "flaky".to_owned(), 2, delay, Box::new(|| true));
+    let start = Instant::now();
+    let e = retry.write_all(b"abc").unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    assert!(start.elapsed() < delay);
+
+    // Errors that won't go away are not retried
+    assert!(!is_transient(&io::ErrorKind::StorageFull.into()));
+    assert!(!is_transient(&io::ErrorKind::BrokenPipe.into()));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix_output() {
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+
+    let path = std::env::temp_dir().join(format!("rng-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let target = OutputTarget::from_os_str(OsStr::new(&format!("unix:{}", path.display())));
+    assert_eq!(target, OutputTarget::Unix(path.clone()));
+
+    let mut output = crate::platform::connect_unix_socket(&path).unwrap();
+    output.write_all(b"random").unwrap();
+    drop(output);
+    let mut received = Vec::new();
+    listener
+        .accept()
+        .unwrap()
+        .0
+        .read_to_end(&mut received)
+        .unwrap();
+    assert_eq!(received, b"random");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fifo_output() {
+    use std::io::Read;
+
+    let path = std::env::temp_dir().join(format!("rng-test-{}.fifo", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let target = OutputTarget::from_os_str(OsStr::new(&format!("fifo:{}", path.display())));
+    assert_eq!(target, OutputTarget::Fifo(path.clone()));
+
+    // Every reader gets the data written while it is attached
+    let (closed_tx, closed_rx) = std::sync::mpsc::channel();
+    let reader_path = path.clone();
+    let readers = thread::spawn(move || {
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            // Wait for the FIFO to be created
+            while !reader_path.exists() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let mut reader = fs::File::open(&reader_path).unwrap();
+            let mut buf = [0u8; 6];
+            reader.read_exact(&mut buf).unwrap();
+            received.extend_from_slice(&buf);
+            drop(reader);
+            closed_tx.send(()).unwrap();
+        }
+        received
+    });
+    let mut output = FifoOutput::open(path.clone(), Box::new(|| false)).unwrap();
+    output.write_all(b"random").unwrap();
+    closed_rx.recv().unwrap();
+    output.write_all(b"second").unwrap();
+    assert_eq!(readers.join().unwrap(), b"randomsecond");
+    fs::remove_file(&path).unwrap();
+
+    fs::write(&path, b"").unwrap();
+    assert!(FifoOutput::open(path.clone(), Box::new(|| false)).is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(windows)]
+#[test]
+fn test_named_pipe_output() {
+    use std::io::Read;
+
+    let path = format!(r"\\.\pipe\rng-test-{}", std::process::id());
+    let target = OutputTarget::from_os_str(OsStr::new(&path));
+    assert_eq!(target, OutputTarget::NamedPipe(PathBuf::from(&path)));
+
+    let server_path = path.clone();
+    let server = thread::spawn(move || {
+        let mut pipe = crate::platform::open_named_pipe(server_path.as_ref()).unwrap();
+        pipe.write_all(b"random").unwrap();
+    });
+    let mut client = loop {
+        match std::fs::File::open(&path) {
+            Ok(client) => break client,
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    server.join().unwrap();
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).ok();
+    assert_eq!(received, b"random");
+}
+
+#[test]
+fn test_tee() {
+    /// Accepts `capacity` bytes, then fails.
+    struct Limited {
+        written: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written.len() + buf.len() > self.capacity {
+                return Err(io::Error::other("Full"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    assert_eq!(
+        OutputTarget::from_os_str(OsStr::new("-")),
+        OutputTarget::Stdout
+    );
+    assert_eq!(
+        OutputTarget::from_os_str(OsStr::new("null")),
+        OutputTarget::Null
+    );
+    assert_eq!(
+        OutputTarget::from_os_str(OsStr::new("./null")),
+        OutputTarget::File(PathBuf::from("./null"))
+    );
+    let limited = |capacity| Limited {
+        written: Vec::new(),
+        capacity,
+    };
+
+    let mut tee = Tee::default();
+    tee.push(limited(4), "small".to_owned(), OutputErrorPolicy::Drop);
+    tee.push(limited(8), "large".to_owned(), OutputErrorPolicy::Drop);
+    tee.write_all(&[1, 2, 3, 4]).unwrap();
+    // The small output is dropped, the large one keeps going until it is full too
+    tee.write_all(&[5, 6, 7, 8]).unwrap();
+    assert!(tee.write_all(&[9]).is_err());
+    assert!(tee.outputs[0].writer.is_none());
+
+    let mut tee = Tee::default();
+    tee.push(limited(4), "small".to_owned(), OutputErrorPolicy::Abort);
+    tee.push(limited(8), "large".to_owned(), OutputErrorPolicy::Drop);
+    tee.write_all(&[1, 2, 3, 4]).unwrap();
+    assert!(tee.write_all(&[5, 6, 7, 8]).is_err());
+}
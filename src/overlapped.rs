@@ -0,0 +1,238 @@
+//! `--io-backend overlapped`. Writes files with overlapped I/O on Windows. A few writes are kept
+//! in flight, so the device always has the next data queued instead of waiting for rng to make
+//! the next write call. Only files opened by path are supported, as the handle is opened again
+//! with `ReOpenFile`, which fails for pipes.
+//!
+//! Each write is copied into a buffer owned by its slot, since the data must stay in place until
+//! the write completes. A failed write is reported by a later call to `write` or `flush`.
+
+use std::ffi::c_void;
+use std::fs;
+use std::io::{self, Write};
+use std::os::windows::io::{AsRawHandle, FromRawHandle};
+use zeroize::Zeroizing;
+
+/// The number of writes that can be in flight at once.
+const SLOTS: usize = 4;
+/// The largest write to issue, as the length of a write is 32 bits.
+const MAX_WRITE: usize = 1 << 30;
+
+const GENERIC_WRITE: u32 = 0x4000_0000;
+const FILE_SHARE_READ: u32 = 0x0000_0001;
+const FILE_SHARE_WRITE: u32 = 0x0000_0002;
+const FILE_FLAG_OVERLAPPED: u32 = 0x4000_0000;
+const ERROR_IO_PENDING: i32 = 997;
+const INVALID_HANDLE_VALUE: *mut c_void = -1isize as *mut c_void;
+
+/// The `OVERLAPPED` struct, with the offset as its two halves.
+#[repr(C)]
+struct Overlapped {
+    internal: usize,
+    internal_high: usize,
+    offset: u32,
+    offset_high: u32,
+    event: *mut c_void,
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn ReOpenFile(file: *mut c_void, access: u32, share_mode: u32, flags: u32) -> *mut c_void;
+    fn CreateEventW(
+        security_attributes: *mut c_void,
+        manual_reset: i32,
+        initial_state: i32,
+        name: *const u16,
+    ) -> *mut c_void;
+    fn CloseHandle(handle: *mut c_void) -> i32;
+    fn WriteFile(
+        file: *mut c_void,
+        buffer: *const u8,
+        len: u32,
+        written: *mut u32,
+        overlapped: *mut Overlapped,
+    ) -> i32;
+    fn GetOverlappedResult(
+        file: *mut c_void,
+        overlapped: *mut Overlapped,
+        transferred: *mut u32,
+        wait: i32,
+    ) -> i32;
+}
+
+/// A write that can be in flight. The `OVERLAPPED` is boxed so it does not move while the write
+/// is pending.
+struct Slot {
+    overlapped: Box<Overlapped>,
+    buf: Zeroizing<Vec<u8>>,
+    pending: bool,
+}
+
+pub struct OverlappedOutput {
+    file: fs::File,
+    slots: Vec<Slot>,
+    /// The slot to use for the next write.
+    next: usize,
+    /// The file offset of the next write. Ignored by pipes.
+    position: u64,
+}
+
+// The slots are only accessed through `&mut self`
+unsafe impl Send for OverlappedOutput {}
+
+impl OverlappedOutput {
+    /// Opens `file` again for overlapped I/O, writing from its current position. `file` is
+    /// closed. Fails for handles that were not opened by path, and for pipes.
+    pub fn new(mut file: fs::File) -> io::Result<Self> {
+        let position = io::Seek::stream_position(&mut file).unwrap_or(0);
+        // Safety: The handle is valid for the lifetime of `file`
+        let handle = unsafe {
+            ReOpenFile(
+                file.as_raw_handle(),
+                GENERIC_WRITE,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                FILE_FLAG_OVERLAPPED,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: The handle is valid and owned by nothing else. The file closes it.
+        let file = unsafe { fs::File::from_raw_handle(handle) };
+        // Created before the events, so dropping it closes them if creating one fails
+        let mut output = OverlappedOutput {
+            file,
+            slots: Vec::with_capacity(SLOTS),
+            next: 0,
+            position,
+        };
+        for _ in 0..SLOTS {
+            // A manual reset event, as GetOverlappedResult expects
+            let event = unsafe { CreateEventW(std::ptr::null_mut(), 1, 0, std::ptr::null()) };
+            if event.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            output.slots.push(Slot {
+                overlapped: Box::new(Overlapped {
+                    internal: 0,
+                    internal_high: 0,
+                    offset: 0,
+                    offset_high: 0,
+                    event,
+                }),
+                buf: Zeroizing::new(Vec::new()),
+                pending: false,
+            });
+        }
+        Ok(output)
+    }
+
+    /// The file being written. Call `flush` first for it to hold all written data.
+    pub fn file(&self) -> &fs::File {
+        &self.file
+    }
+
+    /// Waits for the write of the slot at `index`, if any, and checks that it completed.
+    fn complete(&mut self, index: usize) -> io::Result<()> {
+        let handle = self.file.as_raw_handle();
+        let slot = &mut self.slots[index];
+        if !slot.pending {
+            return Ok(());
+        }
+        slot.pending = false;
+        let mut written = 0u32;
+        // Safety: The OVERLAPPED is the one the pending write was started with
+        let result = unsafe { GetOverlappedResult(handle, &mut *slot.overlapped, &mut written, 1) };
+        if result == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if written as usize != slot.buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "Overlapped write was cut short",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Write for OverlappedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let index = self.next;
+        self.complete(index)?;
+        let handle = self.file.as_raw_handle();
+        let len = buf.len().min(MAX_WRITE);
+        let slot = &mut self.slots[index];
+        slot.buf.clear();
+        slot.buf.extend_from_slice(&buf[..len]);
+        slot.overlapped.internal = 0;
+        slot.overlapped.internal_high = 0;
+        slot.overlapped.offset = self.position as u32;
+        slot.overlapped.offset_high = (self.position >> 32) as u32;
+        // Safety: The buffer and OVERLAPPED are owned by the slot and stay in place until the
+        // write is completed in `complete`.
+        let result = unsafe {
+            WriteFile(
+                handle,
+                slot.buf.as_ptr(),
+                len as u32,
+                std::ptr::null_mut(),
+                &mut *slot.overlapped,
+            )
+        };
+        if result == 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(ERROR_IO_PENDING) {
+                return Err(e);
+            }
+        }
+        // Writes completing at once also signal their event and are checked the same way
+        slot.pending = true;
+        self.position += len as u64;
+        self.next = (index + 1) % SLOTS;
+        Ok(len)
+    }
+
+    /// Waits for all writes in flight.
+    fn flush(&mut self) -> io::Result<()> {
+        // In the order they were started, so the first error is reported
+        for i in 0..SLOTS {
+            self.complete((self.next + i) % SLOTS)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OverlappedOutput {
+    fn drop(&mut self) {
+        // The buffers can't be freed while the writes still use them
+        for i in 0..self.slots.len() {
+            let _ = self.complete(i);
+        }
+        for slot in &self.slots {
+            unsafe { CloseHandle(slot.overlapped.event) };
+        }
+    }
+}
+
+#[test]
+fn test_overlapped_output() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let path = std::env::temp_dir().join(format!("rng-overlapped-{}", std::process::id()));
+    let mut file = fs::File::create(&path).unwrap();
+    file.write_all(b"header").unwrap();
+    let data: Vec<u8> = (0..=255).cycle().take(5_000_000).collect();
+    let mut output = OverlappedOutput::new(file).unwrap();
+    for chunk in data.chunks(100_000) {
+        output.write_all(chunk).unwrap();
+    }
+    output.flush().unwrap();
+    drop(output);
+
+    let mut written = Vec::new();
+    let mut file = fs::File::open(&path).unwrap();
+    file.seek(SeekFrom::Start(6)).unwrap();
+    file.read_to_end(&mut written).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(written == data);
+}
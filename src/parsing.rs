@@ -0,0 +1,228 @@
+//! Parsing of human friendly command line values, such as byte sizes and durations.
+
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    pub fn new(message: String) -> Self {
+        ParseError(message)
+    }
+}
+
+/// Splits "10KiB" into (10.0, "KiB").
+fn split_number(s: &str) -> Result<(f64, &str), ParseError> {
+    let s = s.trim();
+    let unit_start = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(unit_start);
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| ParseError(format!("Invalid number in \"{}\"", s)))?;
+    Ok((number, unit.trim()))
+}
+
+/// Parses a byte size such as "4096", "64KiB", "1.5GiB" or "10MB". Binary (KiB, MiB, ...) and
+/// decimal (KB, MB, ...) prefixes are supported. A plain number is a number of bytes.
+pub fn parse_size(s: &str) -> Result<u64, ParseError> {
+    let (number, unit) = split_number(s)?;
+    let multiplier: u64 = match unit {
+        "" | "B" | "bytes" => 1,
+        "K" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        "T" | "TiB" => 1 << 40,
+        "P" | "PiB" => 1 << 50,
+        "KB" | "kB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        "PB" => 1_000_000_000_000_000,
+        _ => return Err(ParseError(format!("Unknown size unit \"{}\"", unit))),
+    };
+    let bytes = number * multiplier as f64;
+    if bytes > u64::MAX as f64 {
+        return Err(ParseError(format!("Size \"{}\" is too large", s)));
+    }
+    Ok(bytes as u64)
+}
+
+/// Parses a range of byte sizes such as "4KiB..2MiB" into its inclusive bounds. A single size,
+/// such as "1MiB", is a range of only that size.
+pub fn parse_size_range(s: &str) -> Result<(u64, u64), ParseError> {
+    let (min, max) = match s.split_once("..") {
+        Some((min, max)) => (parse_size(min)?, parse_size(max)?),
+        None => (parse_size(s)?, parse_size(s)?),
+    };
+    if min > max {
+        return Err(ParseError(format!("The range \"{}\" is empty", s)));
+    }
+    Ok((min, max))
+}
+
+/// Parses a duration such as "500ms", "1.5s", "10m" or "2h". A plain number is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, ParseError> {
+    let (number, unit) = split_number(s)?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" | "min" => number * 60.0,
+        "h" => number * 60.0 * 60.0,
+        "d" => number * 60.0 * 60.0 * 24.0,
+        _ => return Err(ParseError(format!("Unknown time unit \"{}\"", unit))),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Parses a string of hex digits, such as "00ff10", into bytes. An optional "0x" prefix is
+/// allowed.
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, ParseError> {
+    let digits = s.trim().trim_start_matches("0x");
+    if !digits.len().is_multiple_of(2) || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ParseError(format!(
+            "\"{}\" is not an even number of hex digits",
+            s
+        )));
+    }
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).expect("Validated hex digits"))
+        .collect())
+}
+
+/// Parses octal file permissions, such as "600" or "0640".
+pub fn parse_mode(s: &str) -> Result<u32, ParseError> {
+    match u32::from_str_radix(s.trim(), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(ParseError(format!(
+            "\"{}\" is not an octal file mode such as 600",
+            s
+        ))),
+    }
+}
+
+/// Parses a thread count, such as "4", or a range of thread counts, such as "1..16".
+pub fn parse_thread_range(s: &str) -> Result<(usize, usize), ParseError> {
+    let invalid = || {
+        ParseError(format!(
+            "\"{}\" is not a thread count such as 4 or 1..16",
+            s
+        ))
+    };
+    let parse = |n: &str| n.trim().parse::<usize>().map_err(|_| invalid());
+    let (min, max) = match s.split_once("..") {
+        Some((min, max)) => (parse(min)?, parse(max)?),
+        None => (parse(s)?, parse(s)?),
+    };
+    if min == 0 || min > max {
+        return Err(invalid());
+    }
+    Ok((min, max))
+}
+
+/// CPU numbers, in the order they were given. Parsed with [`parse_cpu_list`].
+#[derive(Debug, Clone)]
+pub struct CpuList(pub Vec<usize>);
+
+impl std::str::FromStr for CpuList {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_cpu_list(s).map(CpuList)
+    }
+}
+
+/// Parses a list of CPU numbers and ranges, such as "0-3,8,10-11", in the given order.
+pub fn parse_cpu_list(s: &str) -> Result<Vec<usize>, ParseError> {
+    let invalid = || ParseError(format!("\"{}\" is not a CPU list such as 0-3,8", s));
+    let mut cpus = Vec::new();
+    for part in s.split(',') {
+        let (first, last) = match part.trim().split_once('-') {
+            Some((first, last)) => (first, last),
+            None => (part.trim(), part.trim()),
+        };
+        let first = first.trim().parse::<usize>().map_err(|_| invalid())?;
+        let last = last.trim().parse::<usize>().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        cpus.extend(first..=last);
+    }
+    Ok(cpus)
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("0").unwrap(), 0);
+    assert_eq!(parse_size("4096").unwrap(), 4096);
+    assert_eq!(parse_size("64KiB").unwrap(), 64 * 1024);
+    assert_eq!(parse_size("1.5 GiB").unwrap(), 3 << 29);
+    assert_eq!(parse_size("10MB").unwrap(), 10_000_000);
+    assert_eq!(parse_size("1M").unwrap(), 1 << 20);
+    assert!(parse_size("").is_err());
+    assert!(parse_size("GiB").is_err());
+    assert!(parse_size("10 furlongs").is_err());
+    assert!(parse_size("100000PiB").is_err());
+    assert_eq!(parse_size_range("4KiB..2MiB").unwrap(), (4096, 2 << 20));
+    assert_eq!(parse_size_range("100").unwrap(), (100, 100));
+    assert!(parse_size_range("2MiB..4KiB").is_err());
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+    assert_eq!(parse_duration("60").unwrap(), Duration::from_secs(60));
+    assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+    assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+    assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+    assert!(parse_duration("1y").is_err());
+}
+
+#[test]
+fn test_parse_hex() {
+    assert_eq!(parse_hex("").unwrap(), []);
+    assert_eq!(parse_hex("00ff10").unwrap(), [0x00, 0xff, 0x10]);
+    assert_eq!(parse_hex("0xABcd").unwrap(), [0xab, 0xcd]);
+    assert!(parse_hex("abc").is_err());
+    assert!(parse_hex("zz").is_err());
+}
+
+#[test]
+fn test_parse_mode() {
+    assert_eq!(parse_mode("600").unwrap(), 0o600);
+    assert_eq!(parse_mode("0640").unwrap(), 0o640);
+    assert!(parse_mode("800").is_err());
+    assert!(parse_mode("17777").is_err());
+    assert!(parse_mode("rw").is_err());
+}
+
+#[test]
+fn test_parse_thread_range() {
+    assert_eq!(parse_thread_range("4").unwrap(), (4, 4));
+    assert_eq!(parse_thread_range("1..16").unwrap(), (1, 16));
+    assert!(parse_thread_range("0").is_err());
+    assert!(parse_thread_range("8..2").is_err());
+    assert!(parse_thread_range("1..").is_err());
+}
+
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("3").unwrap(), [3]);
+    assert_eq!(parse_cpu_list("0-3,8").unwrap(), [0, 1, 2, 3, 8]);
+    assert_eq!(parse_cpu_list("6, 2-3").unwrap(), [6, 2, 3]);
+    assert!(parse_cpu_list("").is_err());
+    assert!(parse_cpu_list("3-1").is_err());
+    assert!(parse_cpu_list("0,,1").is_err());
+    assert!(parse_cpu_list("a-b").is_err());
+}
@@ -1,9 +1,27 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
 #[cfg(unix)]
 mod imp {
+    use std::convert::TryFrom;
+    use std::ffi::CString;
+    use std::io::{self, Write};
+    use std::net::TcpListener;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{FileExt, FileTypeExt, OpenOptionsExt, PermissionsExt};
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
+    use std::time::Duration;
+    use std::{env, fs, thread};
+
+    /// How often to check whether a reader has opened a FIFO.
+    const FIFO_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-    pub fn abort_handle() -> impl Fn() -> bool {
+    pub fn abort_handle() -> impl Fn() -> bool + Clone {
         let abort = Arc::new(AtomicBool::new(false));
         let abort_signal = abort.clone();
         simple_signal::set_handler(
@@ -14,13 +32,1127 @@ mod imp {
         );
         move || abort.load(Ordering::Relaxed)
     }
+
+    extern "C" fn toggle_pause(_signal: libc::c_int) {
+        // Atomics are safe to use in signal handlers
+        super::PAUSED.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    /// Makes SIGUSR2 toggle the paused state returned by `is_paused`. Writes interrupted by the
+    /// signal are restarted.
+    pub fn enable_pause_signal() -> io::Result<()> {
+        // Safety: An all zero sigaction is valid, with an empty signal mask
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = toggle_pause as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        if unsafe { libc::sigaction(libc::SIGUSR2, &action, std::ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Sets the nice value of the calling thread. Threads spawned later inherit it.
+    pub fn set_nice(nice: i32) -> io::Result<()> {
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Sets the I/O scheduling class of the calling thread. Threads spawned later inherit it.
+    #[cfg(target_os = "linux")]
+    pub fn set_io_class(class: super::IoClass) -> io::Result<()> {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        let priority = match class {
+            super::IoClass::Idle => 3 << IOPRIO_CLASS_SHIFT,
+            // The default priority level within the class
+            super::IoClass::BestEffort => (2 << IOPRIO_CLASS_SHIFT) | 4,
+        };
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn set_io_class(_class: super::IoClass) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Confines all threads of the process, also ones spawned later, to the system calls needed
+    /// to keep generating and writing to already open outputs. Other system calls, such as
+    /// opening files, connecting sockets and running programs, fail with EPERM.
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    pub fn sandbox() -> io::Result<()> {
+        #[cfg(target_arch = "x86_64")]
+        const AUDIT_ARCH: u32 = 0xc000_003e;
+        #[cfg(target_arch = "aarch64")]
+        const AUDIT_ARCH: u32 = 0xc000_00b7;
+        // The offsets of the fields of `seccomp_data`
+        const NR_OFFSET: u32 = 0;
+        const ARCH_OFFSET: u32 = 4;
+        let allowed = [
+            // Generating and writing
+            libc::SYS_read,
+            libc::SYS_readv,
+            libc::SYS_pread64,
+            libc::SYS_write,
+            libc::SYS_writev,
+            libc::SYS_pwrite64,
+            libc::SYS_pwritev,
+            libc::SYS_pwritev2,
+            libc::SYS_sendto,
+            libc::SYS_sendmsg,
+            libc::SYS_sendmmsg,
+            libc::SYS_vmsplice,
+            libc::SYS_splice,
+            libc::SYS_lseek,
+            libc::SYS_fstat,
+            libc::SYS_newfstatat,
+            libc::SYS_statx,
+            libc::SYS_fcntl,
+            libc::SYS_ppoll,
+            libc::SYS_fsync,
+            libc::SYS_fdatasync,
+            libc::SYS_sync_file_range,
+            libc::SYS_fallocate,
+            libc::SYS_ftruncate,
+            libc::SYS_close,
+            libc::SYS_getrandom,
+            // Memory
+            libc::SYS_mmap,
+            libc::SYS_munmap,
+            libc::SYS_mremap,
+            libc::SYS_mprotect,
+            libc::SYS_madvise,
+            libc::SYS_msync,
+            libc::SYS_brk,
+            // Threads, time and signals
+            libc::SYS_clone,
+            libc::SYS_clone3,
+            libc::SYS_set_robust_list,
+            libc::SYS_rseq,
+            libc::SYS_futex,
+            libc::SYS_sched_yield,
+            libc::SYS_sched_getaffinity,
+            libc::SYS_sched_setaffinity,
+            libc::SYS_gettid,
+            libc::SYS_getpid,
+            libc::SYS_clock_gettime,
+            libc::SYS_clock_nanosleep,
+            libc::SYS_nanosleep,
+            libc::SYS_sigaltstack,
+            libc::SYS_rt_sigprocmask,
+            libc::SYS_rt_sigreturn,
+            libc::SYS_restart_syscall,
+            libc::SYS_wait4,
+            libc::SYS_exit,
+            libc::SYS_exit_group,
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_poll,
+        ];
+        let statement = |code: u32, k: u32| libc::sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+        let jump_if_equal = |k: u32, jt: u8, jf: u8| libc::sock_filter {
+            code: (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+            jt,
+            jf,
+            k,
+        };
+        let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+        let ret = libc::BPF_RET | libc::BPF_K;
+        let mut filter = vec![
+            // The system call numbers are only valid for this architecture
+            statement(load, ARCH_OFFSET),
+            jump_if_equal(AUDIT_ARCH, 1, 0),
+            statement(ret, libc::SECCOMP_RET_KILL_PROCESS),
+            statement(load, NR_OFFSET),
+        ];
+        for nr in allowed {
+            filter.push(jump_if_equal(nr as u32, 0, 1));
+            filter.push(statement(ret, libc::SECCOMP_RET_ALLOW));
+        }
+        // An error rather than killing the process, as the C library tries some system calls
+        // it does without, such as reading settings from /proc
+        filter.push(statement(ret, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        // Required to install a filter without CAP_SYS_ADMIN
+        if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: The program points to the filter, which is copied by the kernel
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_TSYNC,
+                &program,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Hides the whole filesystem and confines the process to the "stdio" promise of pledge,
+    /// which covers generating and writing to already open outputs.
+    #[cfg(target_os = "openbsd")]
+    pub fn sandbox() -> io::Result<()> {
+        // Safety: The strings are NUL terminated
+        unsafe {
+            if libc::unveil(b"/\0".as_ptr().cast(), b"\0".as_ptr().cast()) != 0
+                || libc::unveil(std::ptr::null(), std::ptr::null()) != 0
+                || libc::pledge(b"stdio\0".as_ptr().cast(), std::ptr::null()) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(
+        all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ),
+        target_os = "openbsd"
+    )))]
+    pub fn sandbox() -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Locks all current and future memory of the process into RAM, so nothing is written to
+    /// swap. Also disables core dumps, which would write the memory to disk.
+    pub fn lock_memory() -> io::Result<()> {
+        let no_core = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &no_core) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Takes ownership of the open file descriptor `fd`, inherited from the parent process.
+    /// Standard input, output and error are refused, as closing them would break the rest of
+    /// rng.
+    pub fn open_fd(fd: RawFd) -> io::Result<fs::File> {
+        if fd < 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Descriptors 0, 1 and 2 are the standard streams",
+            ));
+        }
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: The descriptor is open, and nothing else in rng uses it
+        Ok(unsafe { fs::File::from_raw_fd(fd) })
+    }
+
+    /// Connects to the Unix domain stream socket at `path`.
+    pub fn connect_unix_socket(path: &Path) -> io::Result<Box<dyn Write>> {
+        Ok(Box::new(UnixStream::connect(path)?))
+    }
+
+    pub fn open_named_pipe(_path: &Path) -> io::Result<fs::File> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Opens `path` with `options`. With a `mode` such as 0o600, new files are created with
+    /// those permissions, and existing files get them before anything is written.
+    pub fn open_with_mode(
+        mut options: fs::OpenOptions,
+        path: &Path,
+        mode: Option<u32>,
+    ) -> io::Result<fs::File> {
+        if let Some(mode) = mode {
+            options.mode(mode);
+        }
+        let file = options.open(path)?;
+        if let Some(mode) = mode {
+            // The mode of new files is limited by the umask, and existing files keep theirs
+            file.set_permissions(fs::Permissions::from_mode(mode))?;
+        }
+        Ok(file)
+    }
+
+    /// Writes all of `buf` to `file` at `offset`, without using or moving the file position.
+    pub fn write_all_at(file: &fs::File, buf: &[u8], offset: u64) -> io::Result<()> {
+        file.write_all_at(buf, offset)
+    }
+
+    pub fn is_block_device(path: &Path) -> bool {
+        fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_block_device())
+    }
+
+    /// Evicts the cached data of `file`, so that reading it again reads from the storage
+    /// device. The data must have been synced first. Does nothing where not supported.
+    pub fn drop_page_cache(file: &fs::File) -> io::Result<()> {
+        drop_page_cache_range(file, 0, 0)
+    }
+
+    /// Like `drop_page_cache`, but only for the `len` bytes at `offset`. A `len` of zero means
+    /// up to the end of the file.
+    pub fn drop_page_cache_range(file: &fs::File, offset: u64, len: u64) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            let offset = libc::off_t::try_from(offset).map_err(io::Error::other)?;
+            let len = libc::off_t::try_from(len).map_err(io::Error::other)?;
+            let advice = libc::POSIX_FADV_DONTNEED;
+            match unsafe { libc::posix_fadvise(file.as_raw_fd(), offset, len, advice) } {
+                0 => (),
+                errno => return Err(io::Error::from_raw_os_error(errno)),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = (file, offset, len);
+        Ok(())
+    }
+
+    /// Reserves disk space for the first `len` bytes of `file` without changing its size, so
+    /// running out of space is reported up front and the file is not fragmented.
+    #[cfg(target_os = "linux")]
+    pub fn preallocate(file: &fs::File, len: u64) -> io::Result<()> {
+        let len = libc::off_t::try_from(len).map_err(io::Error::other)?;
+        if unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, len) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn preallocate(_file: &fs::File, _len: u64) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Prepares `file` for having holes. Unix file systems support them without preparation.
+    pub fn set_sparse(_file: &fs::File) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Deallocates `len` bytes of `file` from `offset`, leaving a hole that reads as zeros,
+    /// without changing the file size. Elsewhere than on Linux, holes can only be left by seeking
+    /// past the end of a file, so this does nothing.
+    #[cfg(target_os = "linux")]
+    pub fn punch_hole(file: &fs::File, offset: u64, len: u64) -> io::Result<()> {
+        let offset = libc::off_t::try_from(offset).map_err(io::Error::other)?;
+        let len = libc::off_t::try_from(len).map_err(io::Error::other)?;
+        let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+        if unsafe { libc::fallocate(file.as_raw_fd(), mode, offset, len) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn punch_hole(_file: &fs::File, _offset: u64, _len: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The size of the huge pages to map. The default on x86-64 and most other platforms.
+    #[cfg(target_os = "linux")]
+    const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+    /// Memory mapped from the operating system. Unmapped when dropped.
+    pub struct Pages {
+        ptr: *mut u8,
+        len: usize,
+        mapped_len: usize,
+    }
+
+    // The memory is only accessed through `&self` and `&mut self`
+    unsafe impl Send for Pages {}
+
+    impl std::ops::Deref for Pages {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+
+    impl std::ops::DerefMut for Pages {
+        fn deref_mut(&mut self) -> &mut [u8] {
+            unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+    }
+
+    impl Drop for Pages {
+        fn drop(&mut self) {
+            unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.mapped_len) };
+        }
+    }
+
+    impl Pages {
+        /// The start of the memory, for accessing it without references to all of it.
+        pub fn as_mut_ptr(&self) -> *mut u8 {
+            self.ptr
+        }
+
+        /// Writes the changed pages of a mapped file to the file.
+        pub fn sync(&self) -> io::Result<()> {
+            let ptr = self.ptr as *mut libc::c_void;
+            if unsafe { libc::msync(ptr, self.mapped_len, libc::MS_SYNC) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    /// Maps the first `len` bytes of `file`, which must be opened for reading and writing, into
+    /// memory. Writes to the memory change the file.
+    pub fn map_file(file: &fs::File, len: usize) -> io::Result<Pages> {
+        // Empty mappings are not allowed
+        let mapped_len = len.max(1);
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                mapped_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Pages {
+            ptr: ptr as *mut u8,
+            len,
+            mapped_len,
+        })
+    }
+
+    /// Maps `len` bytes of memory backed by huge pages. Uses the reserved huge pages of the
+    /// system if there are any free, and otherwise asks for transparent huge pages. Like all
+    /// mapped memory, the pages are placed on the NUMA node of the thread that first writes to
+    /// them.
+    #[cfg(target_os = "linux")]
+    pub fn map_huge_pages(len: usize) -> io::Result<Pages> {
+        let map = |len, flags| {
+            let protection = libc::PROT_READ | libc::PROT_WRITE;
+            let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags;
+            match unsafe { libc::mmap(std::ptr::null_mut(), len, protection, flags, -1, 0) } {
+                libc::MAP_FAILED => Err(io::Error::last_os_error()),
+                ptr => Ok(ptr),
+            }
+        };
+        let huge_len = len.div_ceil(HUGE_PAGE_SIZE) * HUGE_PAGE_SIZE;
+        let (ptr, mapped_len) = match map(huge_len, libc::MAP_HUGETLB) {
+            Ok(ptr) => (ptr, huge_len),
+            Err(_) => {
+                let ptr = map(len, 0)?;
+                // Transparent huge pages can be disabled, the memory works without them
+                unsafe { libc::madvise(ptr, len, libc::MADV_HUGEPAGE) };
+                (ptr, len)
+            }
+        };
+        Ok(Pages {
+            ptr: ptr as *mut u8,
+            len,
+            mapped_len,
+        })
+    }
+
+    /// Restricts the calling thread to only run on the CPU numbered `cpu`.
+    #[cfg(target_os = "linux")]
+    pub fn pin_thread(cpu: usize) -> io::Result<()> {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::other("No such CPU"));
+        }
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        if unsafe { libc::sched_setaffinity(0, size, &set) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn pin_thread(_cpu: usize) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Returns the numbers of the CPUs this process is allowed to run on.
+    #[cfg(target_os = "linux")]
+    pub fn allowed_cpus() -> io::Result<Vec<usize>> {
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+            .collect())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn allowed_cpus() -> io::Result<Vec<usize>> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Returns the model name of the CPU, if the system reports it in /proc/cpuinfo.
+    pub fn cpu_model() -> Option<String> {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+        let line = cpuinfo
+            .lines()
+            .find(|line| line.starts_with("model name"))?;
+        line.split_once(':')
+            .map(|(_, model)| model.trim().to_owned())
+    }
+
+    /// Opens the FIFO at `path` for writing, creating it if it does not exist. Waits until a
+    /// reader opens the other end, or until `should_abort` returns true.
+    pub fn open_fifo(path: &Path, should_abort: &dyn Fn() -> bool) -> io::Result<fs::File> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o666) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                return Err(e);
+            }
+            if !fs::metadata(path)?.file_type().is_fifo() {
+                return Err(io::Error::other("Not a FIFO"));
+            }
+        }
+        // A blocking open can not be interrupted, so poll for a reader instead
+        let mut warned = false;
+        loop {
+            let file = fs::OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path);
+            match file {
+                Ok(file) => {
+                    let fd = file.as_raw_fd();
+                    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+                    if flags == -1
+                        || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } != 0
+                    {
+                        return Err(io::Error::last_os_error());
+                    }
+                    return Ok(file);
+                }
+                // No reader has the FIFO open
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {
+                    if should_abort() {
+                        return Err(io::Error::other("Stopped waiting for a reader"));
+                    }
+                    if !warned {
+                        eprintln!("Waiting for a reader to open {}", path.display());
+                        warned = true;
+                    }
+                    thread::sleep(FIFO_POLL_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Takes the listening TCP socket passed by systemd socket activation, if any.
+    pub fn activated_tcp_listener() -> io::Result<Option<TcpListener>> {
+        // Safety: The descriptor was passed to this process to own, and is only taken once
+        Ok(activated_fd()?.map(|fd| unsafe { TcpListener::from_raw_fd(fd) }))
+    }
+
+    /// Takes the listening Unix domain socket passed by systemd socket activation, if any.
+    pub fn activated_unix_listener() -> io::Result<Option<UnixListener>> {
+        // Safety: The descriptor was passed to this process to own, and is only taken once
+        Ok(activated_fd()?.map(|fd| unsafe { UnixListener::from_raw_fd(fd) }))
+    }
+
+    /// Implements the LISTEN_FDS protocol of systemd. Returns the passed descriptor if the
+    /// sockets were passed to this process, and removes the variables from the environment so
+    /// they are not inherited. Only a single socket is supported.
+    fn activated_fd() -> io::Result<Option<RawFd>> {
+        let listen_pid = env::var("LISTEN_PID").ok();
+        let listen_fds = env::var("LISTEN_FDS").ok();
+        let fd = listen_fd(
+            listen_pid.as_deref(),
+            listen_fds.as_deref(),
+            std::process::id(),
+        )?;
+        if listen_pid.is_some_and(|pid| pid.parse() == Ok(std::process::id())) {
+            for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+                env::remove_var(var);
+            }
+        }
+        if let Some(fd) = fd {
+            // Passed descriptors are inherited by default
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(fd)
+    }
+
+    /// The passed descriptor given the values of LISTEN_PID and LISTEN_FDS, if they were meant
+    /// for the process `pid`.
+    fn listen_fd(
+        listen_pid: Option<&str>,
+        listen_fds: Option<&str>,
+        pid: u32,
+    ) -> io::Result<Option<RawFd>> {
+        /// The first passed descriptor, after stdin, stdout and stderr.
+        const LISTEN_FDS_START: RawFd = 3;
+
+        if listen_pid.and_then(|listen_pid| listen_pid.parse().ok()) != Some(pid) {
+            return Ok(None);
+        }
+        match listen_fds.and_then(|fds| fds.parse::<u32>().ok()) {
+            None | Some(0) => Ok(None),
+            Some(1) => Ok(Some(LISTEN_FDS_START)),
+            Some(fds) => Err(io::Error::other(format!(
+                "Expected one socket from systemd, got {}",
+                fds
+            ))),
+        }
+    }
+
+    #[test]
+    fn test_listen_fd() {
+        assert_eq!(listen_fd(Some("42"), Some("1"), 42).unwrap(), Some(3));
+        assert_eq!(listen_fd(Some("41"), Some("1"), 42).unwrap(), None);
+        assert_eq!(listen_fd(None, Some("1"), 42).unwrap(), None);
+        assert_eq!(listen_fd(Some("42"), Some("0"), 42).unwrap(), None);
+        assert_eq!(listen_fd(Some("42"), None, 42).unwrap(), None);
+        assert!(listen_fd(Some("42"), Some("2"), 42).is_err());
+    }
 }
 
 #[cfg(not(unix))]
 mod imp {
-    pub fn abort_handle() -> impl Fn() -> bool {
+    use std::io::{self, Write};
+    use std::path::Path;
+
+    #[cfg(windows)]
+    mod named_pipe {
+        use std::ffi::c_void;
+        use std::fs;
+        use std::io;
+        use std::os::windows::ffi::OsStrExt;
+        use std::os::windows::io::FromRawHandle;
+        use std::path::Path;
+
+        const PIPE_ACCESS_OUTBOUND: u32 = 0x0000_0002;
+        const PIPE_TYPE_BYTE: u32 = 0x0000_0000;
+        const PIPE_REJECT_REMOTE_CLIENTS: u32 = 0x0000_0008;
+        const ERROR_PIPE_CONNECTED: i32 = 535;
+        const INVALID_HANDLE_VALUE: *mut c_void = -1isize as *mut c_void;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn CreateNamedPipeW(
+                name: *const u16,
+                open_mode: u32,
+                pipe_mode: u32,
+                max_instances: u32,
+                out_buffer_size: u32,
+                in_buffer_size: u32,
+                default_timeout: u32,
+                security_attributes: *mut c_void,
+            ) -> *mut c_void;
+            fn ConnectNamedPipe(pipe: *mut c_void, overlapped: *mut c_void) -> i32;
+        }
+
+        /// Connects to the named pipe at `path` if it exists. Otherwise creates it and waits
+        /// for a client to connect.
+        pub fn open_named_pipe(path: &Path) -> io::Result<fs::File> {
+            match fs::OpenOptions::new().write(true).open(path) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                result => return result,
+            }
+            let name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+            // Safety: `name` is a null terminated wide string, no other pointers are passed.
+            let handle = unsafe {
+                CreateNamedPipeW(
+                    name.as_ptr(),
+                    PIPE_ACCESS_OUTBOUND,
+                    PIPE_TYPE_BYTE | PIPE_REJECT_REMOTE_CLIENTS,
+                    1,
+                    crate::BUFFER_SIZE as u32,
+                    0,
+                    0,
+                    std::ptr::null_mut(),
+                )
+            };
+            if handle == INVALID_HANDLE_VALUE {
+                return Err(io::Error::last_os_error());
+            }
+            // Safety: The handle is valid and owned by nothing else. The file closes it.
+            let file = unsafe { fs::File::from_raw_handle(handle) };
+            eprintln!("Waiting for a client to connect to {}", path.display());
+            // Safety: The handle is a valid pipe handle opened without overlapped IO.
+            if unsafe { ConnectNamedPipe(handle, std::ptr::null_mut()) } == 0 {
+                let e = io::Error::last_os_error();
+                // The client connected before ConnectNamedPipe was called
+                if e.raw_os_error() != Some(ERROR_PIPE_CONNECTED) {
+                    return Err(e);
+                }
+            }
+            Ok(file)
+        }
+    }
+
+    #[cfg(windows)]
+    mod allocation {
+        use std::convert::TryFrom;
+        use std::ffi::c_void;
+        use std::fs;
+        use std::io;
+        use std::os::windows::io::AsRawHandle;
+
+        /// `FileAllocationInfo` of the `FILE_INFO_BY_HANDLE_CLASS` enum.
+        const FILE_ALLOCATION_INFO: i32 = 5;
+        const FSCTL_SET_SPARSE: u32 = 0x0009_00c4;
+        const FSCTL_SET_ZERO_DATA: u32 = 0x0009_80c8;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn SetFileInformationByHandle(
+                file: *mut c_void,
+                class: i32,
+                info: *const c_void,
+                size: u32,
+            ) -> i32;
+
+            fn DeviceIoControl(
+                device: *mut c_void,
+                code: u32,
+                input: *const c_void,
+                input_size: u32,
+                output: *mut c_void,
+                output_size: u32,
+                bytes_returned: *mut u32,
+                overlapped: *mut c_void,
+            ) -> i32;
+        }
+
+        /// Makes `file` a sparse file, so ranges of it can be deallocated.
+        pub fn set_sparse(file: &fs::File) -> io::Result<()> {
+            device_io_control(file, FSCTL_SET_SPARSE, &[])
+        }
+
+        /// Deallocates `len` bytes of the sparse `file` from `offset`, leaving a hole that reads
+        /// as zeros, without changing the file size.
+        pub fn punch_hole(file: &fs::File, offset: u64, len: u64) -> io::Result<()> {
+            let start = i64::try_from(offset).map_err(io::Error::other)?;
+            let end = i64::try_from(offset + len).map_err(io::Error::other)?;
+            // A FILE_ZERO_DATA_INFORMATION, the start and end of the range
+            let range = [start.to_ne_bytes(), end.to_ne_bytes()].concat();
+            device_io_control(file, FSCTL_SET_ZERO_DATA, &range)
+        }
+
+        fn device_io_control(file: &fs::File, code: u32, input: &[u8]) -> io::Result<()> {
+            let mut returned = 0u32;
+            // Safety: The handle is valid for the lifetime of `file`, the input is valid for its
+            // length and there is no output or overlapped IO.
+            let result = unsafe {
+                DeviceIoControl(
+                    file.as_raw_handle(),
+                    code,
+                    input.as_ptr() as *const c_void,
+                    input.len() as u32,
+                    std::ptr::null_mut(),
+                    0,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                )
+            };
+            if result == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        /// Reserves disk space for the first `len` bytes of `file` without changing its size,
+        /// so running out of space is reported up front and the file is not fragmented.
+        pub fn preallocate(file: &fs::File, len: u64) -> io::Result<()> {
+            let allocation_size = i64::try_from(len).map_err(io::Error::other)?;
+            // Safety: The handle is valid for the lifetime of `file`, and the info is a
+            // FILE_ALLOCATION_INFO, a single LARGE_INTEGER, of the given size.
+            let result = unsafe {
+                SetFileInformationByHandle(
+                    file.as_raw_handle(),
+                    FILE_ALLOCATION_INFO,
+                    &allocation_size as *const i64 as *const c_void,
+                    std::mem::size_of::<i64>() as u32,
+                )
+            };
+            if result == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(windows)]
+    mod affinity {
+        use std::ffi::c_void;
+        use std::io;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentThread() -> *mut c_void;
+            fn GetCurrentProcess() -> *mut c_void;
+            fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+            fn GetProcessAffinityMask(
+                process: *mut c_void,
+                process_mask: *mut usize,
+                system_mask: *mut usize,
+            ) -> i32;
+        }
+
+        /// Restricts the calling thread to only run on the CPU numbered `cpu`. Only CPUs in the
+        /// processor group of the process can be used.
+        pub fn pin_thread(cpu: usize) -> io::Result<()> {
+            if cpu >= usize::BITS as usize {
+                return Err(io::Error::other("No such CPU in the processor group"));
+            }
+            // Safety: The pseudo handle of the current thread is always valid
+            if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << cpu) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        /// Returns the numbers of the CPUs this process is allowed to run on.
+        pub fn allowed_cpus() -> io::Result<Vec<usize>> {
+            let mut process_mask = 0;
+            let mut system_mask = 0;
+            // Safety: The pseudo handle of the current process is always valid
+            let result = unsafe {
+                GetProcessAffinityMask(GetCurrentProcess(), &mut process_mask, &mut system_mask)
+            };
+            if result == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok((0..usize::BITS as usize)
+                .filter(|cpu| process_mask >> cpu & 1 == 1)
+                .collect())
+        }
+    }
+
+    #[cfg(windows)]
+    pub use named_pipe::open_named_pipe;
+
+    #[cfg(windows)]
+    pub use affinity::{allowed_cpus, pin_thread};
+
+    #[cfg(not(windows))]
+    pub fn pin_thread(_cpu: usize) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    #[cfg(not(windows))]
+    pub fn allowed_cpus() -> io::Result<Vec<usize>> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    #[cfg(windows)]
+    mod priority {
+        use std::ffi::c_void;
+        use std::io;
+
+        const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+        const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+        const NORMAL_PRIORITY_CLASS: u32 = 0x0000_0020;
+        const ABOVE_NORMAL_PRIORITY_CLASS: u32 = 0x0000_8000;
+        const HIGH_PRIORITY_CLASS: u32 = 0x0000_0080;
+        const PROCESS_MODE_BACKGROUND_BEGIN: u32 = 0x0010_0000;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentProcess() -> *mut c_void;
+            fn SetPriorityClass(process: *mut c_void, class: u32) -> i32;
+        }
+
+        fn set_priority_class(class: u32) -> io::Result<()> {
+            // Safety: The pseudo handle of the current process is always valid
+            if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        /// Sets the priority class of the process closest to the Unix nice value `nice`.
+        pub fn set_nice(nice: i32) -> io::Result<()> {
+            set_priority_class(match nice {
+                i32::MIN..=-10 => HIGH_PRIORITY_CLASS,
+                -9..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+                0 => NORMAL_PRIORITY_CLASS,
+                1..=14 => BELOW_NORMAL_PRIORITY_CLASS,
+                15..=i32::MAX => IDLE_PRIORITY_CLASS,
+            })
+        }
+
+        /// Puts the process in background mode for the idle class, which lowers its I/O, CPU
+        /// and memory priority. The best-effort class is the normal mode, so nothing is changed.
+        pub fn set_io_class(class: crate::platform::IoClass) -> io::Result<()> {
+            match class {
+                crate::platform::IoClass::Idle => set_priority_class(PROCESS_MODE_BACKGROUND_BEGIN),
+                crate::platform::IoClass::BestEffort => Ok(()),
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    pub use priority::{set_io_class, set_nice};
+
+    #[cfg(not(windows))]
+    pub fn set_nice(_nice: i32) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    #[cfg(not(windows))]
+    pub fn set_io_class(_class: super::IoClass) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    #[cfg(windows)]
+    pub use allocation::{preallocate, punch_hole, set_sparse};
+
+    #[cfg(not(windows))]
+    pub fn set_sparse(_file: &std::fs::File) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    pub fn punch_hole(_file: &std::fs::File, _offset: u64, _len: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    pub fn preallocate(_file: &std::fs::File, _len: u64) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    #[cfg(not(windows))]
+    pub fn open_named_pipe(_path: &Path) -> io::Result<std::fs::File> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Writes all of `buf` to `file` at `offset`. Moves the file position on Windows.
+    #[cfg(windows)]
+    pub fn write_all_at(file: &std::fs::File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+        use std::os::windows::fs::FileExt;
+        while !buf.is_empty() {
+            match file.seek_write(buf, offset) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    pub fn write_all_at(_file: &std::fs::File, _buf: &[u8], _offset: u64) -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    pub fn is_block_device(_path: &Path) -> bool {
+        false
+    }
+
+    pub fn drop_page_cache(_file: &std::fs::File) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn drop_page_cache_range(_file: &std::fs::File, _offset: u64, _len: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn open_with_mode(
+        options: std::fs::OpenOptions,
+        path: &Path,
+        mode: Option<u32>,
+    ) -> io::Result<std::fs::File> {
+        match mode {
+            Some(_) => Err(io::Error::other("File modes are only supported on Unix")),
+            None => options.open(path),
+        }
+    }
+
+    pub fn open_fifo(_path: &Path, _should_abort: &dyn Fn() -> bool) -> io::Result<std::fs::File> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    #[cfg(windows)]
+    mod console {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Once;
+        use std::thread;
+        use std::time::Duration;
+
+        const CTRL_C_EVENT: u32 = 0;
+        const CTRL_BREAK_EVENT: u32 = 1;
+        const CTRL_CLOSE_EVENT: u32 = 2;
+        /// How long Windows waits for a handler of `CTRL_CLOSE_EVENT` before it kills the
+        /// process anyway.
+        const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+        type HandlerRoutine = unsafe extern "system" fn(ctrl_type: u32) -> i32;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+        }
+
+        /// Set by the handler. A static, as the handler is not given any context.
+        static ABORT: AtomicBool = AtomicBool::new(false);
+        static REGISTER: Once = Once::new();
+
+        /// Runs on a thread Windows creates for every console control event.
+        unsafe extern "system" fn handler(ctrl_type: u32) -> i32 {
+            match ctrl_type {
+                CTRL_C_EVENT | CTRL_BREAK_EVENT => {
+                    ABORT.store(true, Ordering::Relaxed);
+                    1
+                }
+                CTRL_CLOSE_EVENT => {
+                    ABORT.store(true, Ordering::Relaxed);
+                    // The process is terminated as soon as this returns. Waiting gives the main
+                    // thread time to stop, flush and exit by itself, which ends this wait too.
+                    thread::sleep(CLOSE_TIMEOUT);
+                    1
+                }
+                _ => 0,
+            }
+        }
+
+        /// Returns a function telling if Ctrl+C or Ctrl+Break was pressed, or the console is
+        /// being closed, since the first call.
+        pub fn abort_handle() -> impl Fn() -> bool + Clone {
+            REGISTER.call_once(|| {
+                // Safety: The handler is a valid function for the lifetime of the process
+                if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+                    eprintln!(
+                        "WARNING: Failed to set the console control handler: {}",
+                        std::io::Error::last_os_error()
+                    );
+                }
+            });
+            || ABORT.load(Ordering::Relaxed)
+        }
+    }
+
+    #[cfg(windows)]
+    pub use console::abort_handle;
+
+    #[cfg(not(windows))]
+    pub fn abort_handle() -> impl Fn() -> bool + Clone {
         || false
     }
+
+    pub fn lock_memory() -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    pub fn sandbox() -> io::Result<()> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    pub fn connect_unix_socket(_path: &Path) -> io::Result<Box<dyn Write>> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    pub fn activated_tcp_listener() -> io::Result<Option<std::net::TcpListener>> {
+        Ok(None)
+    }
+
+    pub fn open_fd(_fd: i32) -> io::Result<std::fs::File> {
+        Err(io::Error::other("Not supported on this platform"))
+    }
+
+    /// Returns the processor identifier Windows sets in the environment, as it has no simpler
+    /// way to get the model name.
+    pub fn cpu_model() -> Option<String> {
+        std::env::var("PROCESSOR_IDENTIFIER").ok()
+    }
+}
+
+/// An I/O scheduling class, for `set_io_class`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IoClass {
+    /// Only gets disk time when no other process wants it.
+    Idle,
+    /// The normal class, sharing disk time with other processes.
+    BestEffort,
+}
+
+impl std::str::FromStr for IoClass {
+    type Err = ParseIoClassError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "idle" => Ok(IoClass::Idle),
+            "best-effort" => Ok(IoClass::BestEffort),
+            _ => Err(ParseIoClassError(())),
+        }
+    }
 }
 
-pub use imp::abort_handle;
+#[derive(Debug)]
+pub struct ParseIoClassError(());
+
+impl fmt::Display for ParseIoClassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid I/O class. Valid classes are idle and best-effort."
+        )
+    }
+}
+
+/// Toggled by SIGUSR2 once `enable_pause_signal` is called.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+/// How often to check whether a paused run was resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Returns true while the run is paused with SIGUSR2.
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+/// Blocks while the run is paused, or until `stop` returns true.
+pub fn wait_while_paused(stop: impl Fn() -> bool) {
+    while is_paused() && !stop() {
+        std::thread::sleep(PAUSE_POLL_INTERVAL);
+    }
+}
+
+#[cfg(unix)]
+pub use imp::activated_unix_listener;
+#[cfg(target_os = "linux")]
+pub use imp::map_huge_pages;
+pub use imp::{
+    abort_handle, activated_tcp_listener, allowed_cpus, connect_unix_socket, cpu_model,
+    drop_page_cache, drop_page_cache_range, is_block_device, lock_memory, open_fd, open_fifo,
+    open_named_pipe, open_with_mode, pin_thread, preallocate, punch_hole, sandbox, set_io_class,
+    set_nice, set_sparse, write_all_at,
+};
+#[cfg(unix)]
+pub use imp::{enable_pause_signal, map_file, Pages};
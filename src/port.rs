@@ -0,0 +1,115 @@
+//! The `port` subcommand. Picks a random port for a test service, optionally one that is free to
+//! bind right now.
+
+use crate::parsing::ParseError;
+use rand::{Rng, RngCore};
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+use std::ops::RangeInclusive;
+
+/// How many random ports `--check` tries before giving up.
+pub const MAX_TRIES: u32 = 100;
+
+/// Parses a range of ports, such as "1024-65535". Port 0 is not allowed, as binding to it picks
+/// any free port instead.
+pub fn parse_port_range(s: &str) -> Result<RangeInclusive<u16>, ParseError> {
+    let invalid = || ParseError::new(format!("\"{}\" is not a port range such as 1024-65535", s));
+    let parse = |n: &str| n.trim().parse::<u16>().map_err(|_| invalid());
+    let (min, max) = s.split_once('-').ok_or_else(invalid)?;
+    let (min, max) = (parse(min)?, parse(max)?);
+    if min == 0 || min > max {
+        return Err(invalid());
+    }
+    Ok(min..=max)
+}
+
+/// The protocols a port must be free for.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Both,
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = ParseProtocolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tcp" => Ok(Protocol::Tcp),
+            "udp" => Ok(Protocol::Udp),
+            "both" => Ok(Protocol::Both),
+            _ => Err(ParseProtocolError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseProtocolError(());
+
+impl fmt::Display for ParseProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid protocol. See --help for a list of valid options."
+        )
+    }
+}
+
+/// Returns whether `port` can be bound on all interfaces for `protocol` right now. The sockets
+/// are closed again before returning, so another program can take the port before the caller
+/// binds it. Picking from a large range makes that unlikely.
+pub fn is_free(port: u16, protocol: Protocol) -> bool {
+    let address = (Ipv4Addr::UNSPECIFIED, port);
+    let tcp = || TcpListener::bind(address).is_ok();
+    let udp = || UdpSocket::bind(address).is_ok();
+    match protocol {
+        Protocol::Tcp => tcp(),
+        Protocol::Udp => udp(),
+        Protocol::Both => tcp() && udp(),
+    }
+}
+
+/// Picks a random port in `range`. With `check`, only a port that is free for `check` is
+/// returned, trying up to [`MAX_TRIES`] ports.
+pub fn pick(
+    range: RangeInclusive<u16>,
+    check: Option<Protocol>,
+    rng: &mut dyn RngCore,
+) -> io::Result<u16> {
+    let Some(protocol) = check else {
+        return Ok(rng.gen_range(range));
+    };
+    for _ in 0..MAX_TRIES {
+        let port = rng.gen_range(range.clone());
+        if is_free(port, protocol) {
+            return Ok(port);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AddrInUse,
+        format!("No free port found in {} tries", MAX_TRIES),
+    ))
+}
+
+#[test]
+fn test_pick() {
+    use rand::SeedableRng;
+
+    assert_eq!(parse_port_range("1024-65535").unwrap(), 1024..=65535);
+    assert!(parse_port_range("0-10").is_err());
+    assert!(parse_port_range("10-5").is_err());
+    assert!(parse_port_range("1024").is_err());
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    for _ in 0..100 {
+        let port = pick(2000..=2010, None, &mut rng).unwrap();
+        assert!((2000..=2010).contains(&port));
+    }
+
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+    let taken = listener.local_addr().unwrap().port();
+    assert!(!is_free(taken, Protocol::Tcp));
+    assert!(pick(taken..=taken, Some(Protocol::Tcp), &mut rng).is_err());
+}
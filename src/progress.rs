@@ -0,0 +1,285 @@
+//! Progress reports of long running subcommands, such as `wipe`, the live throughput meter of
+//! `--meter` and the periodic statistics of `--stats-interval`.
+
+use crate::formatting;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// How often the progress is reported.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reports how far a long running task has come on stderr, about once a second. On a terminal
+/// the report is updated in place.
+pub struct Progress<'a> {
+    label: &'a str,
+    total: u64,
+    start: Instant,
+    last_report: Instant,
+    terminal: bool,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(label: &'a str, total: u64) -> Self {
+        let now = Instant::now();
+        Progress {
+            label,
+            total,
+            start: now,
+            last_report: now,
+            terminal: io::stderr().is_terminal(),
+        }
+    }
+
+    /// Reports that `done` bytes out of the total are done, if it is time for a new report.
+    pub fn update(&mut self, done: u64) {
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            self.report(done);
+        }
+    }
+
+    /// Reports that everything is done.
+    pub fn finish(&self) {
+        self.report(self.total);
+        if self.terminal {
+            eprintln!();
+        }
+    }
+
+    fn report(&self, done: u64) {
+        let rate = done as f64 / self.start.elapsed().as_secs_f64();
+        let line = format!(
+            "{}: {:.1}% of {} at {}/s",
+            self.label,
+            done as f64 * 100.0 / self.total as f64,
+            formatting::format_bytes_written(self.total),
+            formatting::format_bytes_written(rate as u64)
+        );
+        match self.terminal {
+            // Padded to overwrite the end of a longer earlier report
+            true => eprint!("\r{:<60}", line),
+            false => eprintln!("{}", line),
+        }
+    }
+}
+
+/// A live throughput meter like the one of pv, for `--meter`. Shows the bytes written so far, the
+/// elapsed time and the current and average throughput on stderr, updated in place about once a
+/// second. Shows nothing unless stderr is a terminal.
+pub struct Meter {
+    start: Instant,
+    last_report: Instant,
+    /// The bytes written at the last report, to get the current throughput from.
+    last_done: u64,
+    terminal: bool,
+}
+
+impl Meter {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Meter {
+            start: now,
+            last_report: now,
+            last_done: 0,
+            terminal: io::stderr().is_terminal(),
+        }
+    }
+
+    /// Reports that `done` bytes are written in total, if it is time for a new report.
+    pub fn update(&mut self, done: u64) {
+        let since_report = self.last_report.elapsed();
+        if self.terminal && since_report >= PROGRESS_INTERVAL {
+            let current = (done - self.last_done) as f64 / since_report.as_secs_f64();
+            self.report(done, current);
+            self.last_report = Instant::now();
+            self.last_done = done;
+        }
+    }
+
+    /// Shows the final average throughput and ends the line, so later messages start on their
+    /// own line.
+    pub fn finish(&self, done: u64) {
+        if self.terminal {
+            let average = done as f64 / self.start.elapsed().as_secs_f64();
+            self.report(done, average);
+            eprintln!();
+        }
+    }
+
+    fn report(&self, done: u64, current: f64) {
+        let elapsed = self.start.elapsed();
+        let average = done as f64 / elapsed.as_secs_f64();
+        let seconds = elapsed.as_secs();
+        let line = format!(
+            "{} {}:{:02}:{:02} [{}/s] (average {}/s)",
+            formatting::format_bytes_written(done),
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            formatting::format_bytes_written(current as u64),
+            formatting::format_bytes_written(average as u64)
+        );
+        // Padded to overwrite the end of a longer earlier report
+        eprint!("\r{:<60}", line);
+    }
+}
+
+/// Periodic statistics for `--stats-interval`. Writes a line with the bytes written so far and
+/// the throughput since the previous line every interval, and a last line when done.
+pub struct StatsLog {
+    interval: Duration,
+    out: Box<dyn Write>,
+    start: Instant,
+    last_report: Instant,
+    last_done: u64,
+}
+
+impl StatsLog {
+    pub fn new(interval: Duration, out: Box<dyn Write>) -> Self {
+        let now = Instant::now();
+        StatsLog {
+            interval,
+            out,
+            start: now,
+            last_report: now,
+            last_done: 0,
+        }
+    }
+
+    /// Reports that `done` bytes are written in total, if the interval has passed.
+    pub fn update(&mut self, done: u64) -> io::Result<()> {
+        if self.last_report.elapsed() >= self.interval {
+            self.report(done)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last line, covering the time since the previous one.
+    pub fn finish(&mut self, done: u64) -> io::Result<()> {
+        self.report(done)
+    }
+
+    fn report(&mut self, done: u64) -> io::Result<()> {
+        let since_report = self.last_report.elapsed().as_secs_f64();
+        let rate = (done - self.last_done) as f64 / since_report;
+        writeln!(
+            self.out,
+            "{:.1}s: {} ({} bytes) written, {}/s over the last {:.1}s",
+            self.start.elapsed().as_secs_f64(),
+            formatting::format_bytes_written(done),
+            done,
+            formatting::format_bytes_written(rate as u64),
+            since_report
+        )?;
+        self.out.flush()?;
+        self.last_report = Instant::now();
+        self.last_done = done;
+        Ok(())
+    }
+}
+
+/// Machine-readable progress for `--progress-fd`. Writes a JSON object per line about once a
+/// second, with the bytes written, the average rate in bytes per second and, if the total is
+/// known, the estimated seconds left. The last record has `"done":true`.
+pub struct JsonProgress {
+    out: Box<dyn Write>,
+    total: Option<u64>,
+    start: Instant,
+    last_report: Instant,
+}
+
+impl JsonProgress {
+    pub fn new(out: Box<dyn Write>, total: Option<u64>) -> Self {
+        let now = Instant::now();
+        JsonProgress {
+            out,
+            total,
+            start: now,
+            last_report: now,
+        }
+    }
+
+    /// Reports that `done` bytes are written in total, if it is time for a new record.
+    pub fn update(&mut self, done: u64) -> io::Result<()> {
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            self.record(done, false)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last record.
+    pub fn finish(&mut self, done: u64) -> io::Result<()> {
+        self.record(done, true)
+    }
+
+    fn record(&mut self, done: u64, finished: bool) -> io::Result<()> {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = done as f64 / elapsed;
+        let json_number = |n: Option<f64>| match n {
+            Some(n) if n.is_finite() => format!("{:.3}", n),
+            _ => "null".to_owned(),
+        };
+        let eta = self
+            .total
+            .map(|total| total.saturating_sub(done) as f64 / rate);
+        writeln!(
+            self.out,
+            "{{\"bytes\":{},\"total\":{},\"elapsed\":{:.3},\"rate\":{},\"eta\":{},\"done\":{}}}",
+            done,
+            self.total
+                .map_or_else(|| "null".to_owned(), |total| total.to_string()),
+            elapsed,
+            json_number(Some(rate)),
+            json_number(eta),
+            finished
+        )?;
+        self.out.flush()
+    }
+}
+
+#[test]
+fn test_stats_log() {
+    use std::sync::{Arc, Mutex};
+
+    /// Collects the lines written, shared with the test.
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let mut log = StatsLog::new(Duration::from_millis(20), Box::new(Shared(written.clone())));
+    log.update(100).unwrap();
+    std::thread::sleep(Duration::from_millis(30));
+    log.update(2048).unwrap();
+    log.update(4096).unwrap();
+    log.finish(8192).unwrap();
+    let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains(": 2.0 KiB (2048 bytes) written, "));
+    assert!(lines[1].contains(": 8.0 KiB (8192 bytes) written, "));
+}
+
+#[test]
+fn test_json_progress() {
+    let path = std::env::temp_dir().join(format!("rng-progress-{}", std::process::id()));
+    let file = std::fs::File::create(&path).unwrap();
+    let mut progress = JsonProgress::new(Box::new(file), Some(1000));
+    progress.update(10).unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    progress.finish(1000).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(written.lines().count(), 1);
+    assert!(written.starts_with("{\"bytes\":1000,\"total\":1000,\"elapsed\":"));
+    assert!(written.ends_with(",\"eta\":0.000,\"done\":true}\n"));
+}
@@ -0,0 +1,113 @@
+//! Periodic reseeding of user-space generators with fresh entropy from the operating system.
+
+use crate::parsing;
+use rand::{RngCore, SeedableRng};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ReseedInterval {
+    /// Reseed after this many bytes have been generated.
+    Bytes(u64),
+    /// Reseed when this much time has passed since the last reseed.
+    Time(Duration),
+}
+
+impl std::str::FromStr for ReseedInterval {
+    type Err = parsing::ParseError;
+
+    /// Parses the interval as a duration if it has a time unit, and as a byte size otherwise.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parsing::parse_duration(s) {
+            Ok(duration) if s.trim_end().ends_with(|c: char| c.is_ascii_alphabetic()) => {
+                Ok(ReseedInterval::Time(duration))
+            }
+            _ => parsing::parse_size(s).map(ReseedInterval::Bytes),
+        }
+    }
+}
+
+/// Wraps a generator and replaces it with a new instance seeded from the OS at every interval.
+#[derive(Debug)]
+pub struct ReseedingRng<R> {
+    rng: R,
+    interval: ReseedInterval,
+    bytes_since_reseed: u64,
+    last_reseed: Instant,
+}
+
+impl<R: SeedableRng + RngCore> ReseedingRng<R> {
+    pub fn new(rng: R, interval: ReseedInterval) -> Self {
+        ReseedingRng {
+            rng,
+            interval,
+            bytes_since_reseed: 0,
+            last_reseed: Instant::now(),
+        }
+    }
+
+    fn reseed_if_needed(&mut self, upcoming_bytes: usize) {
+        let due = match self.interval {
+            ReseedInterval::Bytes(bytes) => self.bytes_since_reseed >= bytes,
+            ReseedInterval::Time(duration) => self.last_reseed.elapsed() >= duration,
+        };
+        if due {
+            self.rng = R::from_entropy();
+            self.bytes_since_reseed = 0;
+            self.last_reseed = Instant::now();
+        }
+        self.bytes_since_reseed += upcoming_bytes as u64;
+    }
+}
+
+impl<R: SeedableRng + RngCore> RngCore for ReseedingRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.reseed_if_needed(4);
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.reseed_if_needed(8);
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.reseed_if_needed(dest.len());
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.reseed_if_needed(dest.len());
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+#[test]
+fn test_parse_reseed_interval() {
+    assert_eq!(
+        "1GiB".parse::<ReseedInterval>().unwrap(),
+        ReseedInterval::Bytes(1 << 30)
+    );
+    assert_eq!(
+        "4096".parse::<ReseedInterval>().unwrap(),
+        ReseedInterval::Bytes(4096)
+    );
+    assert_eq!(
+        "60s".parse::<ReseedInterval>().unwrap(),
+        ReseedInterval::Time(Duration::from_secs(60))
+    );
+}
+
+#[test]
+fn test_reseed_every_bytes() {
+    let seeded = || rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut rng = ReseedingRng::new(seeded(), ReseedInterval::Bytes(64));
+    let mut reference = seeded();
+    let (mut buf, mut expected) = ([0u8; 64], [0u8; 64]);
+    rng.fill_bytes(&mut buf);
+    reference.fill_bytes(&mut expected);
+    assert_eq!(buf, expected);
+    // The next fill happens after the interval, so it comes from a freshly seeded generator.
+    rng.fill_bytes(&mut buf);
+    reference.fill_bytes(&mut expected);
+    assert_ne!(buf, expected);
+}
@@ -0,0 +1,241 @@
+//! CTR_DRBG as specified in NIST SP 800-90A Rev. 1, using AES-256 and the block cipher
+//! derivation function.
+//!
+//! For users that need a NIST specified deterministic random bit generator rather than
+//! ChaCha or HC-128.
+
+use super::drbg::{
+    reseed_entropy, Drbg, DrbgSeed, ENTROPY_LEN, MAX_REQUEST_BYTES, RESEED_INTERVAL,
+};
+use crate::seed::Seed;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes256;
+use rand_core::{impls, Error, RngCore, SeedableRng};
+use zeroize::Zeroize;
+
+const KEY_LEN: usize = 32;
+const BLOCK_LEN: usize = 16;
+/// seedlen for AES-256, the key length plus the block length.
+const SEED_LEN: usize = KEY_LEN + BLOCK_LEN;
+
+type Block = [u8; BLOCK_LEN];
+
+pub struct CtrDrbg {
+    key: [u8; KEY_LEN],
+    v: Block,
+    cipher: Aes256,
+    reseed_counter: u64,
+}
+
+impl Drop for CtrDrbg {
+    fn drop(&mut self) {
+        // The cipher clears its own key schedule
+        self.key.zeroize();
+        self.v.as_mut_slice().zeroize();
+    }
+}
+
+impl CtrDrbg {
+    /// The reseed function. Mixes fresh entropy input and optional additional input into
+    /// the state.
+    pub fn reseed(&mut self, entropy: &[u8], additional_input: &[u8]) {
+        let seed_material = derive(&[entropy, additional_input]);
+        self.update(&seed_material);
+        self.reseed_counter = 1;
+    }
+
+    /// The generate function, with optional additional input. `dest` must not be larger than
+    /// the max request size.
+    fn generate(&mut self, dest: &mut [u8], additional_input: &[u8]) {
+        debug_assert!(dest.len() <= MAX_REQUEST_BYTES);
+        let additional_input = if self.reseed_counter > RESEED_INTERVAL {
+            // The additional input is consumed by the reseed
+            self.reseed(&reseed_entropy(), additional_input);
+            &[]
+        } else {
+            additional_input
+        };
+        let additional = if additional_input.is_empty() {
+            [0; SEED_LEN]
+        } else {
+            let additional = derive(&[additional_input]);
+            self.update(&additional);
+            additional
+        };
+        for chunk in dest.chunks_mut(BLOCK_LEN) {
+            let block = self.next_block();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        self.update(&additional);
+        self.reseed_counter += 1;
+    }
+
+    /// Increments V and returns its encryption under the current key.
+    fn next_block(&mut self) -> Block {
+        self.v = (u128::from_be_bytes(self.v).wrapping_add(1)).to_be_bytes();
+        let mut block = self.v.into();
+        self.cipher.encrypt_block(&mut block);
+        block.into()
+    }
+
+    /// The CTR_DRBG_Update function.
+    fn update(&mut self, provided_data: &[u8; SEED_LEN]) {
+        let mut temp = [0u8; SEED_LEN];
+        for chunk in temp.chunks_mut(BLOCK_LEN) {
+            chunk.copy_from_slice(&self.next_block());
+        }
+        for (t, p) in temp.iter_mut().zip(provided_data) {
+            *t ^= p;
+        }
+        self.key.copy_from_slice(&temp[..KEY_LEN]);
+        self.v.copy_from_slice(&temp[KEY_LEN..]);
+        self.cipher = Aes256::new(&self.key.into());
+    }
+}
+
+/// The Block_Cipher_df derivation function, returning seedlen bytes. The input string is the
+/// concatenation of all `inputs`.
+fn derive(inputs: &[&[u8]]) -> [u8; SEED_LEN] {
+    let input_len: usize = inputs.iter().map(|input| input.len()).sum();
+    // S = L || N || input_string || 0x80, padded with zeros to a multiple of the block length
+    let mut s = Vec::with_capacity(input_len + 2 * BLOCK_LEN);
+    s.extend_from_slice(&(input_len as u32).to_be_bytes());
+    s.extend_from_slice(&(SEED_LEN as u32).to_be_bytes());
+    for input in inputs {
+        s.extend_from_slice(input);
+    }
+    s.push(0x80);
+    s.resize(s.len().div_ceil(BLOCK_LEN) * BLOCK_LEN, 0);
+
+    let mut df_key = [0u8; KEY_LEN];
+    for (i, byte) in df_key.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let cipher = Aes256::new(&df_key.into());
+    let mut temp = [0u8; SEED_LEN];
+    for (i, chunk) in temp.chunks_mut(BLOCK_LEN).enumerate() {
+        // BCC(K, IV || S) where IV is the block counter padded to a full block
+        let mut iv = [0u8; BLOCK_LEN];
+        iv[..4].copy_from_slice(&(i as u32).to_be_bytes());
+        let mut chaining_value = aes::Block::default();
+        for block in std::iter::once(&iv[..]).chain(s.chunks(BLOCK_LEN)) {
+            for (c, b) in chaining_value.iter_mut().zip(block) {
+                *c ^= b;
+            }
+            cipher.encrypt_block(&mut chaining_value);
+        }
+        chunk.copy_from_slice(&chaining_value);
+    }
+
+    let mut key = [0u8; KEY_LEN];
+    key.copy_from_slice(&temp[..KEY_LEN]);
+    let cipher = Aes256::new(&key.into());
+    let mut x = aes::Block::clone_from_slice(&temp[KEY_LEN..]);
+    let mut output = [0u8; SEED_LEN];
+    for chunk in output.chunks_mut(BLOCK_LEN) {
+        cipher.encrypt_block(&mut x);
+        chunk.copy_from_slice(&x);
+    }
+    output
+}
+
+impl Drbg for CtrDrbg {
+    fn instantiate(entropy: &[u8], nonce: &[u8], personalization: &[u8]) -> Self {
+        let seed_material = derive(&[entropy, nonce, personalization]);
+        let mut drbg = CtrDrbg {
+            key: [0; KEY_LEN],
+            v: [0; BLOCK_LEN],
+            cipher: Aes256::new(&[0; KEY_LEN].into()),
+            reseed_counter: 1,
+        };
+        drbg.update(&seed_material);
+        drbg
+    }
+}
+
+impl RngCore for CtrDrbg {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(MAX_REQUEST_BYTES) {
+            self.generate(chunk, &[]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for CtrDrbg {
+    type Seed = DrbgSeed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::instantiate(&seed.0[..ENTROPY_LEN], &seed.0[ENTROPY_LEN..], &[])
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::with_personalization(Some(Seed::Number(seed)), &[])
+    }
+}
+
+/// Runs a test case in the format of the NIST CAVP CTR_DRBG vectors, without prediction
+/// resistance: instantiate, reseed, generate twice and compare the output of the second call.
+#[cfg(test)]
+fn cavp_test(
+    [entropy, nonce, personalization, reseed_entropy, add_reseed, add_1, add_2, expected]: [&str;
+        8],
+) {
+    use super::drbg::hex;
+    let mut drbg = CtrDrbg::instantiate(&hex(entropy), &hex(nonce), &hex(personalization));
+    drbg.reseed(&hex(reseed_entropy), &hex(add_reseed));
+    let mut output = [0u8; 64];
+    drbg.generate(&mut output, &hex(add_1));
+    drbg.generate(&mut output, &hex(add_2));
+    assert_eq!(output[..], hex(expected)[..]);
+}
+
+/// Test vectors from the NIST CAVP, CAVS 14.3, AES-256 use df.
+#[test]
+fn test_cavp_vectors() {
+    cavp_test([
+        "2d4c9f46b981c6a0b2b5d8c69391e569ff13851437ebc0fc00d616340252fed5",
+        "0bf814b411f65ec4866be1abb59d3c32",
+        "",
+        "93500fae4fa32b86033b7a7bac9d37e710dcc67ca266bc8607d665937766d207",
+        "",
+        "",
+        "",
+        "322dd28670e75c0ea638f3cb68d6a9d6e50ddfd052b772a7b1d78263a7b8978b\
+         6740c2b65a9550c3a76325866fa97e16d74006bc96f26249b9f0a90d076f08e5",
+    ]);
+    cavp_test([
+        "6f60f0f9d486bc23e1223b934e61c0c78ae9232fa2e9a87c6dacd447c3f10e9e",
+        "401e3f87762fa8a14ab232ccb8480a2f",
+        "",
+        "350be52552a65a804a106543ebb7dd046cffae104e4e8b2f18936d564d3c1950",
+        "7a3688adb1cfb6c03264e2762ece96bfe4daf9558fabf74d7fff203c08b4dd9f",
+        "67cf4a56d081c53670f257c25557014cd5e8b0e919aa58f23d6861b10b00ea80",
+        "648d4a229198b43f33dd7dd8426650be11c5656adcdf913bb3ee5eb49a2a3892",
+        "2d819fb9fee38bfc3f15a07ef0e183ff36db5d3184cea1d24e796ba103687415\
+         abe6d9f2c59a11931439a3d14f45fc3f4345f331a0675a3477eaf7cd89107e37",
+    ]);
+    cavp_test([
+        "5bb14bec3a2e435acab8b891f075107df387902cb2cd996021b1a1245d4ea2b5",
+        "12ac7f444e247f770d2f4d0a65fdab4e",
+        "2e957d53cba5a6b9b8a2ce4369bb885c0931788015b9fe5ac3c01a7ec5eacd70",
+        "19f30c84f6dbf1caf68cbec3d4bb90e5e8f5716eae8c1bbadaba99a2a2bd4eb2",
+        "",
+        "",
+        "",
+        "b7dd8ac2c5eaa97c779fe46cc793b9b1e7b940c318d3b531744b42856f298264\
+         e45f9a0aca5da93e7f34f0ebc0ed0ea32c009e3e03cf01320c9a839807575405",
+    ]);
+}
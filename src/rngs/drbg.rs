@@ -0,0 +1,61 @@
+//! Functionality shared by the deterministic random bit generators from NIST SP 800-90A.
+
+use crate::seed::{self, Seed};
+use rand_core::RngCore;
+
+/// The length of the entropy input used when instantiating and reseeding. Both implemented
+/// DRBGs have a security strength of 256 bits.
+pub const ENTROPY_LEN: usize = 32;
+/// The length of the nonce used when instantiating. Half the security strength.
+pub const NONCE_LEN: usize = 16;
+/// Max number of bytes per generate request. 2^19 bits.
+pub const MAX_REQUEST_BYTES: usize = 1 << 16;
+/// Max number of generate requests between reseeds. 2^48 requests, from the spec.
+pub const RESEED_INTERVAL: u64 = 1 << 48;
+
+/// Seed for the DRBGs when used via `SeedableRng`. The entropy input followed by the nonce.
+#[derive(Clone)]
+pub struct DrbgSeed(pub [u8; ENTROPY_LEN + NONCE_LEN]);
+
+impl Default for DrbgSeed {
+    fn default() -> Self {
+        DrbgSeed([0; ENTROPY_LEN + NONCE_LEN])
+    }
+}
+
+impl AsMut<[u8]> for DrbgSeed {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+pub trait Drbg: Sized {
+    /// The instantiate function. Sets up the initial state from the entropy input, the nonce and
+    /// an optional personalization string.
+    fn instantiate(entropy: &[u8], nonce: &[u8], personalization: &[u8]) -> Self;
+
+    /// Instantiates with the given personalization string. The entropy input and nonce come
+    /// from the operating system, or are derived from `seed` for reproducible output.
+    fn with_personalization(seed: Option<Seed>, personalization: &[u8]) -> Self {
+        let mut seed_material = DrbgSeed::default();
+        seed::fill_bytes(seed, &mut seed_material.0);
+        let (entropy, nonce) = seed_material.0.split_at(ENTROPY_LEN);
+        Self::instantiate(entropy, nonce, personalization)
+    }
+}
+
+/// Fetches fresh entropy input from the operating system, for automatic reseeding.
+pub fn reseed_entropy() -> [u8; ENTROPY_LEN] {
+    let mut entropy = [0u8; ENTROPY_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut entropy);
+    entropy
+}
+
+/// Decodes a hex string from a test vector.
+#[cfg(test)]
+pub fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,186 @@
+//! HMAC_DRBG as specified in NIST SP 800-90A Rev. 1, using HMAC-SHA-256.
+
+use super::drbg::{
+    reseed_entropy, Drbg, DrbgSeed, ENTROPY_LEN, MAX_REQUEST_BYTES, RESEED_INTERVAL,
+};
+use crate::seed::Seed;
+use hmac::{Hmac, Mac};
+use rand_core::{impls, Error, RngCore, SeedableRng};
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+/// The output length of SHA-256.
+const OUT_LEN: usize = 32;
+
+type Output = [u8; OUT_LEN];
+
+pub struct HmacDrbg {
+    key: Output,
+    v: Output,
+    reseed_counter: u64,
+}
+
+impl Drop for HmacDrbg {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.v.zeroize();
+    }
+}
+
+impl HmacDrbg {
+    /// The reseed function. Mixes fresh entropy input and optional additional input into
+    /// the state.
+    pub fn reseed(&mut self, entropy: &[u8], additional_input: &[u8]) {
+        self.update(&[entropy, additional_input]);
+        self.reseed_counter = 1;
+    }
+
+    /// The generate function, with optional additional input. `dest` must not be larger than
+    /// the max request size.
+    fn generate(&mut self, dest: &mut [u8], additional_input: &[u8]) {
+        debug_assert!(dest.len() <= MAX_REQUEST_BYTES);
+        let additional_input = if self.reseed_counter > RESEED_INTERVAL {
+            // The additional input is consumed by the reseed
+            self.reseed(&reseed_entropy(), additional_input);
+            &[]
+        } else {
+            additional_input
+        };
+        if !additional_input.is_empty() {
+            self.update(&[additional_input]);
+        }
+        for chunk in dest.chunks_mut(OUT_LEN) {
+            self.v = hmac(&self.key, &[&self.v]);
+            chunk.copy_from_slice(&self.v[..chunk.len()]);
+        }
+        self.update(&[additional_input]);
+        self.reseed_counter += 1;
+    }
+
+    /// The HMAC_DRBG_Update function. The provided data is the concatenation of all `inputs`.
+    fn update(&mut self, inputs: &[&[u8]]) {
+        let mut inputs_with_separator = vec![&self.v[..], &[0x00]];
+        inputs_with_separator.extend_from_slice(inputs);
+        self.key = hmac(&self.key, &inputs_with_separator);
+        self.v = hmac(&self.key, &[&self.v]);
+        if inputs.iter().all(|input| input.is_empty()) {
+            return;
+        }
+        let mut inputs_with_separator = vec![&self.v[..], &[0x01]];
+        inputs_with_separator.extend_from_slice(inputs);
+        self.key = hmac(&self.key, &inputs_with_separator);
+        self.v = hmac(&self.key, &[&self.v]);
+    }
+}
+
+/// Computes HMAC-SHA-256 over the concatenation of all `inputs`.
+fn hmac(key: &Output, inputs: &[&[u8]]) -> Output {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    for input in inputs {
+        mac.update(input);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+impl Drbg for HmacDrbg {
+    fn instantiate(entropy: &[u8], nonce: &[u8], personalization: &[u8]) -> Self {
+        let mut drbg = HmacDrbg {
+            key: [0x00; OUT_LEN],
+            v: [0x01; OUT_LEN],
+            reseed_counter: 1,
+        };
+        drbg.update(&[entropy, nonce, personalization]);
+        drbg
+    }
+}
+
+impl RngCore for HmacDrbg {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(MAX_REQUEST_BYTES) {
+            self.generate(chunk, &[]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for HmacDrbg {
+    type Seed = DrbgSeed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::instantiate(&seed.0[..ENTROPY_LEN], &seed.0[ENTROPY_LEN..], &[])
+    }
+
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::with_personalization(Some(Seed::Number(seed)), &[])
+    }
+}
+
+/// Runs a test case in the format of the NIST CAVP HMAC_DRBG vectors, without prediction
+/// resistance and reseeding: instantiate, generate twice and compare the output of the second
+/// call.
+#[cfg(test)]
+fn cavp_test([entropy, nonce, add_1, add_2, expected]: [&str; 5]) {
+    use super::drbg::hex;
+    let mut drbg = HmacDrbg::instantiate(&hex(entropy), &hex(nonce), &[]);
+    let mut output = [0u8; 128];
+    drbg.generate(&mut output, &hex(add_1));
+    drbg.generate(&mut output, &hex(add_2));
+    assert_eq!(output[..], hex(expected)[..]);
+}
+
+/// Test vectors from the NIST CAVP, SHA-256 without prediction resistance.
+#[test]
+fn test_cavp_vectors() {
+    cavp_test([
+        "ca851911349384bffe89de1cbdc46e6831e44d34a4fb935ee285dd14b71a7488",
+        "659ba96c601dc69fc902940805ec0ca8",
+        "",
+        "",
+        "e528e9abf2dece54d47c7e75e5fe302149f817ea9fb4bee6f4199697d04d5b89\
+         d54fbb978a15b5c443c9ec21036d2460b6f73ebad0dc2aba6e624abf07745bc1\
+         07694bb7547bb0995f70de25d6b29e2d3011bb19d27676c07162c8b5ccde0668\
+         961df86803482cb37ed6d5c0bb8d50cf1f50d476aa0458bdaba806f48be9dcb8",
+    ]);
+    cavp_test([
+        "79737479ba4e7642a221fcfd1b820b134e9e3540a35bb48ffae29c20f5418ea3",
+        "3593259c092bef4129bc2c6c9e19f343",
+        "",
+        "",
+        "cf5ad5984f9e43917aa9087380dac46e410ddc8a7731859c84e9d0f31bd43655\
+         b924159413e2293b17610f211e09f770f172b8fb693a35b85d3b9e5e63b1dc25\
+         2ac0e115002e9bedfb4b5b6fd43f33b8e0eafb2d072e1a6fee1f159df9b51e6c\
+         8da737e60d5032dd30544ec51558c6f080bdbdab1de8a939e961e06b5f1aca37",
+    ]);
+    cavp_test([
+        "d3cc4d1acf3dde0c4bd2290d262337042dc632948223d3a2eaab87da44295fbd",
+        "0109b0e729f457328aa18569a9224921",
+        "3c311848183c9a212a26f27f8c6647e40375e466a0857cc39c4e47575d53f1f6",
+        "fcb9abd19ccfbccef88c9c39bfb3dd7b1c12266c9808992e305bc3cff566e4e4",
+        "9c7b758b212cd0fcecd5daa489821712e3cdea4467b560ef5ddc24ab47749a1f\
+         1ffdbbb118f4e62fcfca3371b8fbfc5b0646b83e06bfbbab5fac30ea09ea2bc7\
+         6f1ea568c9be0444b2cc90517b20ca825f2d0eccd88e7175538b85d90ab39018\
+         3ca6395535d34473af6b5a5b88f5a59ee7561573337ea819da0dcc3573a22974",
+    ]);
+    cavp_test([
+        "f97a3cfd91faa046b9e61b9493d436c4931f604b22f1081521b3419151e8ff06",
+        "11f3a7d43595357d58120bd1e2dd8aed",
+        "517289afe444a0fe5ed1a41dbbb5eb17150079bdd31e29cf2ff30034d8268e3b",
+        "88028d29ef80b4e6f0fe12f91d7449fe75062682e89c571440c0c9b52c42a6e0",
+        "c6871cff0824fe55ea7689a52229886730450e5d362da5bf590dcf9acd67fed4\
+         cb32107df5d03969a66b1f6494fdf5d63d5b4d0d34ea7399a07d0116126d0d51\
+         8c7c55ba46e12f62efc8fe28a51c9d428e6d371d7397ab319fc73ded4722e5b4\
+         f30004032a6128df5e7497ecf82ca7b0a50e867ef6728a4f509a8c859087039c",
+    ]);
+}
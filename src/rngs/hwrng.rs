@@ -0,0 +1,145 @@
+//! Randomness read from hardware true random number generators (TRNGs) exposed by the operating
+//! system as devices.
+
+use rand_core::{Error, RngCore};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The default device the Linux kernel exposes the active hardware RNG as.
+pub const DEFAULT_HWRNG_DEVICE: &str = "/dev/hwrng";
+
+/// The default TPM device. The resource manager device allows sharing the TPM with other
+/// processes, as opposed to `/dev/tpm0`.
+pub const DEFAULT_TPM_DEVICE: &str = "/dev/tpmrm0";
+
+/// Reads raw bytes from a character device such as `/dev/hwrng`.
+#[derive(Debug)]
+pub struct DeviceRng {
+    device: File,
+}
+
+impl DeviceRng {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(DeviceRng {
+            device: File::open(path)?,
+        })
+    }
+}
+
+impl RngCore for DeviceRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from hardware RNG: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.device.read_exact(dest).map_err(Error::new)
+    }
+}
+
+/// TPM 2.0 command tag for commands without authorization sessions.
+const TPM_ST_NO_SESSIONS: u16 = 0x8001;
+/// TPM 2.0 command code for TPM2_GetRandom.
+const TPM_CC_GET_RANDOM: u32 = 0x0000_017b;
+/// The number of bytes to request per TPM2_GetRandom command. TPMs return at most the size of
+/// their largest digest, so asking for more than this just gives a short response.
+const TPM_REQUEST_BYTES: u16 = 32;
+
+/// Fetches randomness from a TPM 2.0 chip by sending raw TPM2_GetRandom commands to its
+/// character device.
+#[derive(Debug)]
+pub struct TpmRng {
+    device: File,
+}
+
+impl TpmRng {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let device = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(TpmRng { device })
+    }
+
+    /// Executes one TPM2_GetRandom command and writes the result into the start of `dest`.
+    /// Returns how many bytes were written.
+    fn get_random(&mut self, dest: &mut [u8]) -> io::Result<usize> {
+        let mut command = Vec::with_capacity(12);
+        command.extend_from_slice(&TPM_ST_NO_SESSIONS.to_be_bytes());
+        command.extend_from_slice(&12u32.to_be_bytes());
+        command.extend_from_slice(&TPM_CC_GET_RANDOM.to_be_bytes());
+        command.extend_from_slice(&TPM_REQUEST_BYTES.to_be_bytes());
+        self.device.write_all(&command)?;
+
+        let mut response = [0u8; 4096];
+        let len = self.device.read(&mut response)?;
+        let random_bytes = parse_get_random_response(&response[..len])?;
+        let n = random_bytes.len().min(dest.len());
+        dest[..n].copy_from_slice(&random_bytes[..n]);
+        Ok(n)
+    }
+}
+
+/// Parses a TPM2_GetRandom response and returns the random bytes in it.
+fn parse_get_random_response(response: &[u8]) -> io::Result<&[u8]> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    if response.len() < 12 {
+        return Err(invalid("TPM response too short"));
+    }
+    let response_code = u32::from_be_bytes([response[6], response[7], response[8], response[9]]);
+    if response_code != 0 {
+        return Err(invalid(&format!(
+            "TPM2_GetRandom failed with response code {:#x}",
+            response_code
+        )));
+    }
+    let random_len = usize::from(u16::from_be_bytes([response[10], response[11]]));
+    response
+        .get(12..12 + random_len)
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| invalid("Malformed TPM2_GetRandom response"))
+}
+
+impl RngCore for TpmRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from TPM: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, mut dest: &mut [u8]) -> Result<(), Error> {
+        while !dest.is_empty() {
+            let n = self.get_random(dest).map_err(Error::new)?;
+            dest = &mut dest[n..];
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_get_random_response() {
+    let response = [
+        0x80, 0x01, 0, 0, 0, 16, 0, 0, 0, 0, 0, 4, 0xde, 0xad, 0xbe, 0xef,
+    ];
+    assert_eq!(
+        parse_get_random_response(&response).unwrap(),
+        &[0xde, 0xad, 0xbe, 0xef]
+    );
+    let failure = [0x80, 0x01, 0, 0, 0, 10, 0, 0, 0x01, 0x01];
+    assert!(parse_get_random_response(&failure).is_err());
+    let truncated = [0x80, 0x01, 0, 0, 0, 16, 0, 0, 0, 0, 0, 4, 0xde];
+    assert!(parse_get_random_response(&truncated).is_err());
+}
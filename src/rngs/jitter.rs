@@ -0,0 +1,143 @@
+//! A CPU execution time jitter entropy source, modeled after the jitterentropy library by
+//! Stephan Müller.
+//!
+//! Entropy is harvested from the variations in how long it takes to execute a fixed workload of
+//! memory accesses. These variations come from caches, pipelines, interrupts and other effects
+//! that are practically impossible to predict. The source is very slow, but only depends on a
+//! high resolution timer, not on the operating system or any special hardware.
+
+use rand_core::{impls, Error, RngCore};
+use std::fmt;
+use std::time::Instant;
+
+/// The number of timing measurements folded into every 64 bit output word. Each measurement is
+/// assumed to contribute far less than a full bit of entropy.
+const OVERSAMPLING: usize = 64 * 4;
+
+/// Size of the memory area accessed between measurements. Bigger than typical L1 caches, so the
+/// accesses cause cache misses with varying timing.
+const MEMORY_SIZE: usize = 64 * 1024;
+
+/// The number of memory accesses done between two timing measurements.
+const MEMORY_ACCESSES: usize = 128;
+
+/// Stride of the memory accesses. Odd and larger than a cache line, to hit many cache lines.
+const MEMORY_STRIDE: usize = 67;
+
+/// The number of measurements made at startup to check that the timer is usable.
+const STARTUP_MEASUREMENTS: usize = 1024;
+
+/// Error returned when the system timer does not have a high enough resolution to collect
+/// timing jitter from.
+#[derive(Debug)]
+pub struct TimerError(());
+
+impl fmt::Display for TimerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The system timer does not have a high enough resolution for jitter entropy"
+        )
+    }
+}
+
+impl std::error::Error for TimerError {}
+
+#[derive(Debug)]
+pub struct JitterRng {
+    epoch: Instant,
+    pool: u64,
+    last_time: u64,
+    last_delta: u64,
+    last_delta2: u64,
+    memory: Box<[u8]>,
+    memory_position: usize,
+}
+
+impl JitterRng {
+    /// Creates a new jitter entropy source. Checks that the timer produces varying
+    /// measurements first, since that is what all the entropy comes from.
+    pub fn new() -> Result<Self, TimerError> {
+        let mut rng = JitterRng {
+            epoch: Instant::now(),
+            pool: 0,
+            last_time: 0,
+            last_delta: 0,
+            last_delta2: 0,
+            memory: vec![0u8; MEMORY_SIZE].into_boxed_slice(),
+            memory_position: 0,
+        };
+        rng.last_time = rng.now();
+        let stuck = (0..STARTUP_MEASUREMENTS)
+            .filter(|_| rng.measure().is_none())
+            .count();
+        // Require that at least most measurements carry some variation.
+        if stuck > STARTUP_MEASUREMENTS / 2 {
+            return Err(TimerError(()));
+        }
+        Ok(rng)
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    /// Runs the memory access workload.
+    fn access_memory(&mut self) {
+        for _ in 0..MEMORY_ACCESSES {
+            self.memory_position = (self.memory_position + MEMORY_STRIDE) % MEMORY_SIZE;
+            let byte = &mut self.memory[self.memory_position];
+            *byte = byte.wrapping_add(1);
+        }
+        std::hint::black_box(&self.memory);
+    }
+
+    /// Times one run of the workload. Returns `None` if the measurement is "stuck", meaning
+    /// the first, second or third discrete derivative of the time is zero. Stuck measurements
+    /// are assumed to contain no entropy.
+    fn measure(&mut self) -> Option<u64> {
+        self.access_memory();
+        let time = self.now();
+        let delta = time.wrapping_sub(self.last_time);
+        let delta2 = delta.wrapping_sub(self.last_delta);
+        let delta3 = delta2.wrapping_sub(self.last_delta2);
+        self.last_time = time;
+        self.last_delta = delta;
+        self.last_delta2 = delta2;
+        if delta == 0 || delta2 == 0 || delta3 == 0 {
+            None
+        } else {
+            Some(delta)
+        }
+    }
+}
+
+impl RngCore for JitterRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut samples = 0;
+        while samples < OVERSAMPLING {
+            if let Some(delta) = self.measure() {
+                // Multiplication with an odd constant is a bijection, so no entropy already in
+                // the pool is lost while the new measurement is spread over all bits.
+                self.pool = (self.pool ^ delta)
+                    .rotate_left(7)
+                    .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                samples += 1;
+            }
+        }
+        self.pool
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
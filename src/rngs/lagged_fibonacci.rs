@@ -0,0 +1,75 @@
+//! An additive lagged Fibonacci generator with the classic lags (24, 55) from Knuth's TAOCP.
+//!
+//! Included for educational purposes only. The low bits of the output are themselves a linear
+//! recurrence and the generator fails well known tests such as birthday spacings. Do NOT use it
+//! for anything where randomness quality matters.
+
+use rand_core::{impls, Error, RngCore, SeedableRng};
+
+const LONG_LAG: usize = 55;
+const SHORT_LAG: usize = 24;
+
+#[derive(Debug, Clone)]
+pub struct LaggedFibonacci {
+    state: [u64; LONG_LAG],
+    index: usize,
+}
+
+impl RngCore for LaggedFibonacci {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        // x[n] = x[n - 24] + x[n - 55] (mod 2^64). `index` points at x[n - 55], which is also
+        // the slot x[n] is stored in.
+        let short = (self.index + LONG_LAG - SHORT_LAG) % LONG_LAG;
+        let value = self.state[self.index].wrapping_add(self.state[short]);
+        self.state[self.index] = value;
+        self.index = (self.index + 1) % LONG_LAG;
+        value
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for LaggedFibonacci {
+    type Seed = [u8; 8];
+
+    /// The 55 word lag table is expanded from the seed with SplitMix64.
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut x = u64::from_le_bytes(seed);
+        let mut state = [0u64; LONG_LAG];
+        for word in state.iter_mut() {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *word = z ^ (z >> 31);
+        }
+        // The maximal period requires at least one odd value in the lag table.
+        state[0] |= 1;
+        LaggedFibonacci { state, index: 0 }
+    }
+}
+
+#[test]
+fn test_lagged_fibonacci_recurrence() {
+    let mut rng = LaggedFibonacci::from_seed([7; 8]);
+    let outputs: Vec<u64> = (0..200).map(|_| rng.next_u64()).collect();
+    for n in LONG_LAG..outputs.len() {
+        assert_eq!(
+            outputs[n],
+            outputs[n - SHORT_LAG].wrapping_add(outputs[n - LONG_LAG])
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! A 64 bit Galois linear-feedback shift register.
+//!
+//! Included for educational purposes only. The output is completely linear and will fail any
+//! reasonable statistical test suite. Do NOT use it for anything where randomness quality matters.
+
+use rand_core::{impls, Error, RngCore, SeedableRng};
+
+/// Feedback taps for the maximal length polynomial x^64 + x^63 + x^61 + x^60 + 1.
+const TAPS: u64 = 0xd800_0000_0000_0000;
+
+#[derive(Debug, Clone)]
+pub struct Lfsr64 {
+    state: u64,
+}
+
+impl Lfsr64 {
+    /// Clocks the register once and returns the bit shifted out.
+    #[inline]
+    fn step(&mut self) -> u64 {
+        let bit = self.state & 1;
+        self.state >>= 1;
+        if bit == 1 {
+            self.state ^= TAPS;
+        }
+        bit
+    }
+}
+
+impl RngCore for Lfsr64 {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        (0..32).fold(0, |acc, _| (acc << 1) | self.step() as u32)
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        (0..64).fold(0, |acc, _| (acc << 1) | self.step())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Lfsr64 {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        // The all zero state is a fixed point of any LFSR.
+        let state = match u64::from_le_bytes(seed) {
+            0 => 1,
+            state => state,
+        };
+        Lfsr64 { state }
+    }
+}
+
+/// A maximal length register must visit every non-zero state before repeating. Check that a
+/// short run does not fall into the zero state and that the output is not trivially constant.
+#[test]
+fn test_lfsr_output() {
+    let mut rng = Lfsr64::from_seed([0; 8]);
+    // Starting from state 1 the first 63 output bits are the shifted out low bit, then the taps.
+    assert_eq!(rng.next_u32(), 0x8000_0000);
+    let outputs: Vec<u64> = (0..16).map(|_| rng.next_u64()).collect();
+    assert!(outputs.windows(2).all(|w| w[0] != w[1]));
+    assert_ne!(rng.state, 0);
+}
@@ -0,0 +1,28 @@
+//! Random number generator algorithms implemented in this crate, for algorithms not provided by
+//! any of the `rand` family of crates.
+
+mod ctr_drbg;
+mod drbg;
+mod hmac_drbg;
+mod hwrng;
+mod jitter;
+mod lagged_fibonacci;
+mod lfsr;
+mod os;
+mod plugin;
+mod rdrand;
+mod romu;
+mod stream;
+
+pub use ctr_drbg::CtrDrbg;
+pub use drbg::Drbg;
+pub use hmac_drbg::HmacDrbg;
+pub use hwrng::{DeviceRng, TpmRng, DEFAULT_HWRNG_DEVICE, DEFAULT_TPM_DEVICE};
+pub use jitter::JitterRng;
+pub use lagged_fibonacci::LaggedFibonacci;
+pub use lfsr::Lfsr64;
+pub use os::{OsSource, OsSourceRng, DEFAULT_OS_DEVICE};
+pub use plugin::{Plugin, PLUGIN_SEED_LEN};
+pub use rdrand::{HardwareRng, Instruction};
+pub use romu::{RomuDuoJr, RomuTrio};
+pub use stream::{shell, CommandRng, EndOfStream, StdinRng};
@@ -0,0 +1,212 @@
+//! Direct access to the different interfaces operating systems provide for fetching randomness.
+//! The `os` algorithm uses `rand::rngs::OsRng` by default, which picks the recommended interface
+//! for the platform. These allow selecting a specific one.
+
+use rand_core::{impls, Error, RngCore};
+use std::fmt;
+
+/// The default device for the `device` OS source.
+pub const DEFAULT_OS_DEVICE: &str = "/dev/urandom";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OsSource {
+    /// Whatever `rand::rngs::OsRng` uses on this platform.
+    Default,
+    /// getrandom(2) without flags. Blocks until the kernel pool is initialized.
+    Getrandom,
+    /// getrandom(2) with GRND_NONBLOCK. Fails instead of blocking on an uninitialized pool.
+    GetrandomNonblock,
+    /// getrandom(2) with GRND_RANDOM. Reads from the blocking pool on kernels that have one.
+    GetrandomRandom,
+    /// Reads from a device file, such as /dev/random or /dev/urandom.
+    Device,
+    /// BCryptGenRandom from the Windows CNG API.
+    Cng,
+    /// RtlGenRandom, also known as SystemFunction036, from advapi32 on Windows.
+    RtlGenRandom,
+}
+
+impl std::str::FromStr for OsSource {
+    type Err = ParseOsSourceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(OsSource::Default),
+            "getrandom" => Ok(OsSource::Getrandom),
+            "getrandom-nonblock" => Ok(OsSource::GetrandomNonblock),
+            "getrandom-random" => Ok(OsSource::GetrandomRandom),
+            "device" => Ok(OsSource::Device),
+            "cng" => Ok(OsSource::Cng),
+            "rtlgenrandom" => Ok(OsSource::RtlGenRandom),
+            _ => Err(ParseOsSourceError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseOsSourceError(());
+
+impl fmt::Display for ParseOsSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid OS source. See --help for a list of valid options."
+        )
+    }
+}
+
+/// Error returned when the selected OS source is not available on this platform.
+#[derive(Debug)]
+pub struct UnsupportedSourceError(OsSource);
+
+impl fmt::Display for UnsupportedSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The OS source {:?} is not available on this platform",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedSourceError {}
+
+/// Fetches randomness from a specific system call or API. Does not handle `OsSource::Default`
+/// or `OsSource::Device`, those are served by `OsRng` and `DeviceRng` respectively.
+#[derive(Debug)]
+pub struct OsSourceRng {
+    source: OsSource,
+}
+
+impl OsSourceRng {
+    pub fn new(source: OsSource) -> Result<Self, UnsupportedSourceError> {
+        if imp::is_supported(source) {
+            Ok(OsSourceRng { source })
+        } else {
+            Err(UnsupportedSourceError(source))
+        }
+    }
+}
+
+impl RngCore for OsSourceRng {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to get randomness from the OS: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        imp::fill(self.source, dest).map_err(Error::new)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use super::OsSource;
+    use std::io;
+
+    pub fn is_supported(source: OsSource) -> bool {
+        flags(source).is_some()
+    }
+
+    fn flags(source: OsSource) -> Option<libc::c_uint> {
+        match source {
+            OsSource::Getrandom => Some(0),
+            OsSource::GetrandomNonblock => Some(libc::GRND_NONBLOCK),
+            OsSource::GetrandomRandom => Some(libc::GRND_RANDOM),
+            _ => None,
+        }
+    }
+
+    pub fn fill(source: OsSource, mut dest: &mut [u8]) -> io::Result<()> {
+        let flags = flags(source).expect("Unsupported source");
+        while !dest.is_empty() {
+            // Safety: The pointer and length describe the valid, writable `dest` buffer.
+            let ret = unsafe { libc::getrandom(dest.as_mut_ptr().cast(), dest.len(), flags) };
+            if ret < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error);
+            }
+            dest = &mut dest[ret as usize..];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::OsSource;
+    use std::io;
+
+    const BCRYPT_USE_SYSTEM_PREFERRED_RNG: u32 = 0x0000_0002;
+
+    #[link(name = "bcrypt")]
+    extern "system" {
+        fn BCryptGenRandom(
+            algorithm: *mut std::ffi::c_void,
+            buffer: *mut u8,
+            buffer_len: u32,
+            flags: u32,
+        ) -> i32;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        #[link_name = "SystemFunction036"]
+        fn RtlGenRandom(buffer: *mut u8, buffer_len: u32) -> u8;
+    }
+
+    pub fn is_supported(source: OsSource) -> bool {
+        matches!(source, OsSource::Cng | OsSource::RtlGenRandom)
+    }
+
+    pub fn fill(source: OsSource, dest: &mut [u8]) -> io::Result<()> {
+        for chunk in dest.chunks_mut(u32::MAX as usize) {
+            // Safety: The pointer and length describe the valid, writable `chunk` buffer.
+            let success = unsafe {
+                match source {
+                    OsSource::Cng => {
+                        BCryptGenRandom(
+                            std::ptr::null_mut(),
+                            chunk.as_mut_ptr(),
+                            chunk.len() as u32,
+                            BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+                        ) >= 0
+                    }
+                    OsSource::RtlGenRandom => {
+                        RtlGenRandom(chunk.as_mut_ptr(), chunk.len() as u32) != 0
+                    }
+                    _ => panic!("Unsupported source"),
+                }
+            };
+            if !success {
+                return Err(io::Error::other(format!("{:?} failed", source)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+mod imp {
+    use super::OsSource;
+    use std::io;
+
+    pub fn is_supported(_source: OsSource) -> bool {
+        false
+    }
+
+    pub fn fill(_source: OsSource, _dest: &mut [u8]) -> io::Result<()> {
+        panic!("Unsupported source")
+    }
+}
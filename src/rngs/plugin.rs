@@ -0,0 +1,134 @@
+//! Generators loaded at runtime from a shared library. This allows using custom or
+//! experimental generators with all the machinery of this tool, without modifying it.
+//!
+//! The library must export the following C functions:
+//!
+//! ```c
+//! // Creates a new generator instance seeded with `seed_len` bytes from `seed`.
+//! // Returns NULL on failure.
+//! void *rng_plugin_new(const uint8_t *seed, size_t seed_len);
+//!
+//! // Fills `dest` with `len` random bytes. Returns zero on success.
+//! int rng_plugin_fill_bytes(void *rng, uint8_t *dest, size_t len);
+//!
+//! // Frees a generator instance created by rng_plugin_new.
+//! void rng_plugin_destroy(void *rng);
+//! ```
+//!
+//! Multiple instances can be created and used concurrently from different threads, but each
+//! instance is only used from one thread at a time.
+
+use crate::seed::{self, Seed};
+use rand_core::{Error, RngCore};
+use std::ffi::c_void;
+use std::os::raw::c_int;
+use std::path::Path;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Arc;
+
+/// The number of seed bytes passed to `rng_plugin_new`.
+pub const PLUGIN_SEED_LEN: usize = 32;
+
+type NewFn = unsafe extern "C" fn(seed: *const u8, seed_len: usize) -> *mut c_void;
+type FillBytesFn = unsafe extern "C" fn(rng: *mut c_void, dest: *mut u8, len: usize) -> c_int;
+type DestroyFn = unsafe extern "C" fn(rng: *mut c_void);
+
+/// A loaded plugin library. Cheap to clone, the library stays loaded until all clones and all
+/// generator instances are dropped.
+#[derive(Clone)]
+pub struct Plugin {
+    /// Keeps the library loaded as long as the function pointers below are in use.
+    #[cfg(not(target_family = "wasm"))]
+    _library: Arc<libloading::Library>,
+    new: NewFn,
+    fill_bytes: FillBytesFn,
+    destroy: DestroyFn,
+}
+
+impl Plugin {
+    #[cfg(not(target_family = "wasm"))]
+    pub fn load(path: &Path) -> Result<Self, libloading::Error> {
+        // Safety: Loading a library runs its initialization routines. The user asked for this
+        // specific library to be loaded, so it's trusted just like this binary is.
+        unsafe {
+            let library = libloading::Library::new(path)?;
+            let new = *library.get::<NewFn>(b"rng_plugin_new\0")?;
+            let fill_bytes = *library.get::<FillBytesFn>(b"rng_plugin_fill_bytes\0")?;
+            let destroy = *library.get::<DestroyFn>(b"rng_plugin_destroy\0")?;
+            Ok(Plugin {
+                _library: Arc::new(library),
+                new,
+                fill_bytes,
+                destroy,
+            })
+        }
+    }
+
+    /// WebAssembly has no shared libraries to load.
+    #[cfg(target_family = "wasm")]
+    pub fn load(_path: &Path) -> Result<Self, std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Plugins are not supported on this platform",
+        ))
+    }
+
+    /// Creates a new generator instance. Seeded from the OS unless a seed is given.
+    pub fn new_rng(&self, seed: Option<Seed>) -> Result<PluginRng, Error> {
+        let mut seed_bytes = [0u8; PLUGIN_SEED_LEN];
+        seed::fill_bytes(seed, &mut seed_bytes);
+        // Safety: The seed pointer and length describe a valid buffer
+        let rng = unsafe { (self.new)(seed_bytes.as_ptr(), seed_bytes.len()) };
+        if rng.is_null() {
+            return Err(Error::new("The plugin failed to create a generator"));
+        }
+        Ok(PluginRng {
+            plugin: self.clone(),
+            rng,
+        })
+    }
+}
+
+/// A generator instance created by a plugin.
+pub struct PluginRng {
+    plugin: Plugin,
+    rng: *mut c_void,
+}
+
+impl RngCore for PluginRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to generate with plugin: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        // Safety: The instance is valid until dropped, and dest is a valid buffer
+        match unsafe { (self.plugin.fill_bytes)(self.rng, dest.as_mut_ptr(), dest.len()) } {
+            0 => Ok(()),
+            code => Err(Error::new(format!(
+                "The plugin returned error code {}",
+                code
+            ))),
+        }
+    }
+}
+
+impl Drop for PluginRng {
+    fn drop(&mut self) {
+        // Safety: The instance was created by this plugin and is never used after this
+        unsafe { (self.plugin.destroy)(self.rng) }
+    }
+}
+
+#[test]
+fn test_load_missing_library() {
+    assert!(Plugin::load(Path::new("/nonexistent/librng_plugin.so")).is_err());
+}
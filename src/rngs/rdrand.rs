@@ -0,0 +1,158 @@
+//! Randomness directly from the digital random number generator (DRNG) built into x86_64 CPUs,
+//! via the RDRAND and RDSEED instructions.
+//!
+//! RDRAND returns the output of a CSPRNG that the CPU continuously reseeds from its hardware
+//! entropy source. RDSEED returns conditioned output of the entropy source itself, is slower and
+//! fails more often when drained.
+
+use rand_core::{impls, Error, RngCore};
+use std::fmt;
+
+/// How many times to retry an instruction that reports that no random data was available.
+/// Intel recommends 10 retries for RDRAND. RDSEED is expected to run dry under load, so it gets
+/// a lot more attempts.
+const RDRAND_RETRIES: usize = 10;
+const RDSEED_RETRIES: usize = 10_000;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Instruction {
+    Rdrand,
+    Rdseed,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Rdrand => "RDRAND".fmt(f),
+            Instruction::Rdseed => "RDSEED".fmt(f),
+        }
+    }
+}
+
+/// Error returned when the CPU does not support the requested instruction.
+#[derive(Debug)]
+pub struct UnsupportedError(Instruction);
+
+impl fmt::Display for UnsupportedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "This CPU does not support the {} instruction", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedError {}
+
+#[derive(Debug, Clone)]
+pub struct HardwareRng {
+    instruction: Instruction,
+}
+
+impl HardwareRng {
+    /// Creates a new generator using the given instruction, after checking via CPUID that the
+    /// CPU supports it.
+    pub fn new(instruction: Instruction) -> Result<Self, UnsupportedError> {
+        if imp::is_supported(instruction) {
+            Ok(HardwareRng { instruction })
+        } else {
+            Err(UnsupportedError(instruction))
+        }
+    }
+
+    fn try_next_u64(&mut self) -> Result<u64, Error> {
+        let retries = match self.instruction {
+            Instruction::Rdrand => RDRAND_RETRIES,
+            Instruction::Rdseed => RDSEED_RETRIES,
+        };
+        for _ in 0..retries {
+            // Safety: The support for the instruction was checked in the constructor.
+            if let Some(value) = unsafe { imp::step(self.instruction) } {
+                return Ok(value);
+            }
+            std::hint::spin_loop();
+        }
+        Err(Error::new(format!(
+            "{} did not return any random data after {} attempts",
+            self.instruction, retries
+        )))
+    }
+}
+
+impl RngCore for HardwareRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.try_next_u64()
+            .unwrap_or_else(|e| panic!("Hardware random number generator failed: {}", e))
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.try_next_u64()?.to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod imp {
+    use super::Instruction;
+    use std::arch::x86_64::{_rdrand64_step, _rdseed64_step};
+
+    pub fn is_supported(instruction: Instruction) -> bool {
+        match instruction {
+            Instruction::Rdrand => is_x86_feature_detected!("rdrand"),
+            Instruction::Rdseed => is_x86_feature_detected!("rdseed"),
+        }
+    }
+
+    /// Executes the instruction once. Returns `None` if the CPU had no random data available.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support the instruction. See `is_supported`.
+    pub unsafe fn step(instruction: Instruction) -> Option<u64> {
+        match instruction {
+            Instruction::Rdrand => rdrand(),
+            Instruction::Rdseed => rdseed(),
+        }
+    }
+
+    #[target_feature(enable = "rdrand")]
+    unsafe fn rdrand() -> Option<u64> {
+        let mut value = 0;
+        if _rdrand64_step(&mut value) == 1 {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    #[target_feature(enable = "rdseed")]
+    unsafe fn rdseed() -> Option<u64> {
+        let mut value = 0;
+        if _rdseed64_step(&mut value) == 1 {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod imp {
+    use super::Instruction;
+
+    pub fn is_supported(_instruction: Instruction) -> bool {
+        false
+    }
+
+    pub unsafe fn step(_instruction: Instruction) -> Option<u64> {
+        unreachable!("Hardware instructions are never supported on this architecture")
+    }
+}
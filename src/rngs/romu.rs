@@ -0,0 +1,155 @@
+//! The Romu family of generators by Mark A. Overton. See <https://www.romu-random.org/>.
+//!
+//! These are very fast nonlinear generators, NOT suitable for cryptographic purposes.
+
+use rand_core::{impls, le, Error, RngCore, SeedableRng};
+
+/// Multiplier shared by all Romu generators.
+const MULTIPLIER: u64 = 15241094284759029579;
+
+/// RomuTrio. 192 bits of state. Recommended by the author for general purpose use.
+#[derive(Debug, Clone)]
+pub struct RomuTrio {
+    x: u64,
+    y: u64,
+    z: u64,
+}
+
+impl RngCore for RomuTrio {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        let (xp, yp, zp) = (self.x, self.y, self.z);
+        self.x = MULTIPLIER.wrapping_mul(zp);
+        self.y = yp.wrapping_sub(xp).rotate_left(12);
+        self.z = zp.wrapping_sub(yp).rotate_left(44);
+        xp
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for RomuTrio {
+    type Seed = [u8; 24];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut state = [0u64; 3];
+        le::read_u64_into(&seed, &mut state);
+        // An all zero state is a fixed point of the generator. Replace it with something else.
+        if state == [0; 3] {
+            state = [MULTIPLIER; 3];
+        }
+        RomuTrio {
+            x: state[0],
+            y: state[1],
+            z: state[2],
+        }
+    }
+}
+
+/// RomuDuoJr. 128 bits of state. The fastest Romu generator, but with lower capacity.
+#[derive(Debug, Clone)]
+pub struct RomuDuoJr {
+    x: u64,
+    y: u64,
+}
+
+impl RngCore for RomuDuoJr {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        let xp = self.x;
+        self.x = MULTIPLIER.wrapping_mul(self.y);
+        self.y = self.y.wrapping_sub(xp).rotate_left(27);
+        xp
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for RomuDuoJr {
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut state = [0u64; 2];
+        le::read_u64_into(&seed, &mut state);
+        // An all zero state is a fixed point of the generator. Replace it with something else.
+        if state == [0; 2] {
+            state = [MULTIPLIER; 2];
+        }
+        RomuDuoJr {
+            x: state[0],
+            y: state[1],
+        }
+    }
+}
+
+/// Builds a seed with the given state words, in the byte order `from_seed` expects.
+#[cfg(test)]
+fn seed_from_words<S: AsMut<[u8]> + Default>(words: &[u64]) -> S {
+    let mut seed = S::default();
+    for (chunk, word) in seed.as_mut().chunks_mut(8).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    seed
+}
+
+/// Known answers computed with the reference C implementation.
+#[test]
+fn test_romu_trio() {
+    let mut rng = RomuTrio::from_seed(seed_from_words(&[1, 2, 3]));
+    for &expected in &[
+        0x0000000000000001,
+        0x7a89bb80ede505e1,
+        0xc574b00000000000,
+        0x61cc0dd6fbb3a8b5,
+        0x995c06dc2702cb77,
+    ] {
+        assert_eq!(rng.next_u64(), expected);
+    }
+}
+
+/// Known answers computed with the reference C implementation.
+#[test]
+fn test_romu_duo_jr() {
+    let mut rng = RomuDuoJr::from_seed(seed_from_words(&[1, 2]));
+    for &expected in &[
+        0x0000000000000001,
+        0xa7067d009e98ae96,
+        0x027a62ba58000000,
+        0xbbf058bed6b89bbd,
+        0x7ffdbd09495c0baa,
+    ] {
+        assert_eq!(rng.next_u64(), expected);
+    }
+}
+
+#[test]
+fn test_romu_zero_seed() {
+    let mut rng = RomuDuoJr::from_seed([0; 16]);
+    assert!((0..100).any(|_| rng.next_u64() != 0));
+    let mut rng = RomuTrio::from_seed([0; 24]);
+    assert!((0..100).any(|_| rng.next_u64() != 0));
+}
@@ -0,0 +1,188 @@
+//! Randomness read from a byte stream, such as the output of another process. Unlike the other
+//! generators, streams can end.
+
+use rand_core::{Error, RngCore};
+use std::fmt;
+use std::io::{self, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+/// Error returned by generators reading from a stream when the stream ends before the
+/// destination buffer is full.
+#[derive(Debug)]
+pub struct EndOfStream {
+    /// The number of bytes at the start of the destination buffer that were filled before the
+    /// stream ended.
+    pub filled: usize,
+}
+
+impl fmt::Display for EndOfStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "End of stream after {} bytes", self.filled)
+    }
+}
+
+impl std::error::Error for EndOfStream {}
+
+impl EndOfStream {
+    /// Returns the number of filled bytes if `error` is an `EndOfStream`.
+    pub fn filled(error: &Error) -> Option<usize> {
+        error
+            .inner()
+            .downcast_ref::<EndOfStream>()
+            .map(|end| end.filled)
+    }
+
+    /// Returns how many bytes of a `len` byte destination buffer a fill call with the given
+    /// `result` filled. Errors other than `EndOfStream` are returned.
+    pub fn filled_len(result: Result<(), Error>, len: usize) -> Result<usize, Error> {
+        match result {
+            Ok(()) => Ok(len),
+            Err(e) => Self::filled(&e).ok_or(e),
+        }
+    }
+
+    /// For generators wrapping another generator. Translates an `EndOfStream` from the wrapped
+    /// generator into one counting the bytes `filled` by the wrapping generator. Other errors
+    /// are returned unchanged.
+    pub fn rebase(error: Error, filled: usize) -> Error {
+        match Self::filled(&error) {
+            Some(_) => Error::new(EndOfStream { filled }),
+            None => error,
+        }
+    }
+}
+
+/// Fills `dest` from `reader`. Returns `EndOfStream` if the reader ends first.
+fn read_fill(mut reader: impl Read, dest: &mut [u8]) -> Result<(), Error> {
+    let mut filled = 0;
+    while filled < dest.len() {
+        match reader.read(&mut dest[filled..]) {
+            Ok(0) => return Err(Error::new(EndOfStream { filled })),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(Error::new(e)),
+        }
+    }
+    Ok(())
+}
+
+/// Reads from the standard input of this process.
+#[derive(Debug)]
+pub struct StdinRng {
+    stdin: io::Stdin,
+}
+
+impl StdinRng {
+    pub fn new() -> Self {
+        StdinRng { stdin: io::stdin() }
+    }
+}
+
+impl Default for StdinRng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RngCore for StdinRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from stdin: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        read_fill(self.stdin.lock(), dest)
+    }
+}
+
+/// Reads from the standard output of a command run by the system shell.
+#[derive(Debug)]
+pub struct CommandRng {
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl CommandRng {
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut child = shell(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(CommandRng { child, stdout })
+    }
+}
+
+/// A command running `command` with the system shell.
+#[cfg(not(windows))]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+/// A command running `command` with the system shell.
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+impl RngCore for CommandRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest)
+            .unwrap_or_else(|e| panic!("Failed to read from the command: {}", e))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        read_fill(&mut self.stdout, dest).map_err(|e| {
+            // Only a command that exits successfully ends the stream. Otherwise it failed
+            match (EndOfStream::filled(&e), self.child.wait()) {
+                (Some(_), Ok(status)) if !status.success() => {
+                    Error::new(format!("The command exited with {}", status))
+                }
+                _ => e,
+            }
+        })
+    }
+}
+
+impl Drop for CommandRng {
+    fn drop(&mut self) {
+        // The command usually generates data forever, so it has to be stopped when we are done
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_end_of_stream() {
+    let mut dest = [0u8; 8];
+    let error = read_fill(&[1u8, 2, 3][..], &mut dest).unwrap_err();
+    assert_eq!(EndOfStream::filled(&error), Some(3));
+    assert_eq!(dest[..3], [1, 2, 3]);
+
+    let filled = EndOfStream::filled_len(Err(error), dest.len()).unwrap();
+    assert_eq!(filled, 3);
+
+    let error = Error::new(EndOfStream { filled: 3 });
+    let rebased = EndOfStream::rebase(error, 1);
+    assert_eq!(EndOfStream::filled(&rebased), Some(1));
+}
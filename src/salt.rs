@@ -0,0 +1,104 @@
+//! The `salt` subcommand. Random salts in the encoding each password hashing scheme expects, for
+//! scripts building /etc/shadow entries or htpasswd files.
+
+use rand::RngCore;
+use std::fmt;
+
+/// The base64 alphabet of crypt(3), used by sha512crypt and the other SHA-crypt and MD5-crypt
+/// schemes.
+const CRYPT_ALPHABET: &[u8; 64] =
+    b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// The base64 alphabet of bcrypt. The same characters as crypt(3), in another order.
+const BCRYPT_ALPHABET: &[u8; 64] =
+    b"./ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// The longest raw salt, in bytes. Far more than any scheme can use.
+const MAX_RAW_LEN: usize = 1024;
+
+/// The scheme the salt is for.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// 16 bytes as the 22 characters bcrypt expects after "$2b$<cost>$".
+    Bcrypt,
+    /// 16 characters of the crypt(3) alphabet, the longest salt sha512crypt uses. Goes after
+    /// "$6$" in /etc/shadow, or to `openssl passwd -6 -salt`.
+    Sha512Crypt,
+    /// The given number of bytes in hex, for schemes taking any bytes, like Argon2 and PBKDF2.
+    Raw(usize),
+}
+
+impl std::str::FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bcrypt" => Ok(Format::Bcrypt),
+            "sha512crypt" => Ok(Format::Sha512Crypt),
+            _ => match s.strip_prefix("raw:").map(str::parse) {
+                Some(Ok(len)) if (1..=MAX_RAW_LEN).contains(&len) => Ok(Format::Raw(len)),
+                _ => Err(ParseFormatError(())),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFormatError(());
+
+impl fmt::Display for ParseFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid salt format. See --help for a list of valid options."
+        )
+    }
+}
+
+/// A random salt in `format`.
+pub fn salt(format: Format, rng: &mut dyn RngCore) -> String {
+    match format {
+        Format::Bcrypt => {
+            let mut salt = [0; 16];
+            rng.fill_bytes(&mut salt);
+            crate::formatting::base64(&salt, BCRYPT_ALPHABET, false)
+        }
+        Format::Sha512Crypt => {
+            // Every character carries exactly six random bits
+            let mut salt = [0; 16];
+            rng.fill_bytes(&mut salt);
+            salt.iter()
+                .map(|&byte| char::from(CRYPT_ALPHABET[usize::from(byte & 0x3f)]))
+                .collect()
+        }
+        Format::Raw(len) => {
+            let mut salt = vec![0; len];
+            rng.fill_bytes(&mut salt);
+            crate::formatting::hex(&salt)
+        }
+    }
+}
+
+#[test]
+fn test_salt() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    for _ in 0..100 {
+        let bcrypt = salt(Format::Bcrypt, &mut rng);
+        assert_eq!(bcrypt.len(), 22);
+        assert!(bcrypt.bytes().all(|b| BCRYPT_ALPHABET.contains(&b)));
+        // The last character only carries two bits
+        assert!(".Oeu".contains(&bcrypt[21..]));
+
+        let sha512crypt = salt(Format::Sha512Crypt, &mut rng);
+        assert_eq!(sha512crypt.len(), 16);
+        assert!(sha512crypt.bytes().all(|b| CRYPT_ALPHABET.contains(&b)));
+    }
+    assert_eq!(salt(Format::Raw(8), &mut rng).len(), 16);
+
+    assert_eq!("raw:32".parse::<Format>().unwrap(), Format::Raw(32));
+    assert!("raw:0".parse::<Format>().is_err());
+    assert!("raw:1025".parse::<Format>().is_err());
+    assert!("md5".parse::<Format>().is_err());
+}
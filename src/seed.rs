@@ -0,0 +1,289 @@
+//! Seeds given on the command line with `--seed`, `--seed-hex` and `--seed-passphrase`.
+
+use crate::parsing::{self, ParseError};
+use rand::{RngCore, SeedableRng};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::{env, fmt, fs, io};
+
+/// The longest seed `--seed-hex` accepts, in bytes. Longer than the seed of any algorithm.
+pub const MAX_SEED_LEN: usize = 64;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Seed {
+    /// A 64 bit seed from `--seed`, expanded to the seed width of the algorithm.
+    Number(u64),
+    /// Raw seed bytes from `--seed-hex`, zero padded to the seed width of the algorithm.
+    Bytes {
+        bytes: [u8; MAX_SEED_LEN],
+        len: usize,
+    },
+}
+
+impl Seed {
+    pub fn from_hex(s: &str) -> Result<Self, ParseError> {
+        let hex = parsing::parse_hex(s)?;
+        if hex.len() > MAX_SEED_LEN {
+            return Err(ParseError::new(format!(
+                "The seed can be at most {} bytes",
+                MAX_SEED_LEN
+            )));
+        }
+        let mut bytes = [0u8; MAX_SEED_LEN];
+        bytes[..hex.len()].copy_from_slice(&hex);
+        Ok(Seed::Bytes {
+            bytes,
+            len: hex.len(),
+        })
+    }
+
+    /// Generates a full width seed for an algorithm taking `len` bytes of seed, with entropy
+    /// from the OS. Used instead of seeding the generator from the OS directly when the seed
+    /// must be known.
+    pub fn from_entropy(len: usize) -> Self {
+        let mut bytes = [0u8; MAX_SEED_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut bytes[..len]);
+        Seed::Bytes { bytes, len }
+    }
+
+    /// The number of bytes in a seed from `--seed-hex`.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> Option<usize> {
+        match *self {
+            Seed::Number(_) => None,
+            Seed::Bytes { len, .. } => Some(len),
+        }
+    }
+
+    /// Creates a generator seeded with this seed. A `Number` gives the same generator as
+    /// `SeedableRng::seed_from_u64`.
+    pub fn new_rng<R: SeedableRng>(self) -> R {
+        match self {
+            Seed::Number(seed) => R::seed_from_u64(seed),
+            Seed::Bytes { .. } => {
+                let mut seed = R::Seed::default();
+                self.fill_bytes(seed.as_mut());
+                R::from_seed(seed)
+            }
+        }
+    }
+
+    /// Fills `dest` with seed material, for generators that are not `SeedableRng`. A `Number`
+    /// is expanded with PCG, `Bytes` are zero padded. Seed bytes that don't fit are ignored,
+    /// but `--seed-hex` is validated against the seed width of the algorithm up front.
+    pub fn fill_bytes(self, dest: &mut [u8]) {
+        match self {
+            Seed::Number(seed) => rand_pcg::Pcg64Mcg::seed_from_u64(seed).fill_bytes(dest),
+            Seed::Bytes { bytes, len } => {
+                let len = len.min(dest.len());
+                dest[..len].copy_from_slice(&bytes[..len]);
+                dest[len..].fill(0);
+            }
+        }
+    }
+}
+
+/// Formats the seed as the command line argument reproducing it.
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Seed::Number(seed) => write!(f, "--seed {}", seed),
+            Seed::Bytes { bytes, len } => {
+                write!(f, "--seed-hex ")?;
+                bytes[..*len]
+                    .iter()
+                    .try_for_each(|b| write!(f, "{:02x}", b))
+            }
+        }
+    }
+}
+
+/// Parses the command line argument format of the seed, as printed by `Display`.
+impl std::str::FromStr for Seed {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(hex) = s.strip_prefix("--seed-hex ") {
+            Seed::from_hex(hex)
+        } else if let Some(number) = s.strip_prefix("--seed ") {
+            number
+                .parse()
+                .map(Seed::Number)
+                .map_err(|e| ParseError::new(format!("Invalid seed: {}", e)))
+        } else {
+            Err(ParseError::new(format!("Invalid seed: {}", s)))
+        }
+    }
+}
+
+/// Where `--seed-passphrase` reads the passphrase from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PassphraseSource {
+    /// Ask for it on the terminal, without echoing it.
+    Prompt,
+    /// Read it from an environment variable.
+    Env(String),
+    /// Read the first line of a file.
+    File(PathBuf),
+}
+
+impl std::str::FromStr for PassphraseSource {
+    type Err = ParsePassphraseSourceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "prompt" {
+            Ok(PassphraseSource::Prompt)
+        } else if let Some(name) = s.strip_prefix("env:") {
+            Ok(PassphraseSource::Env(name.to_owned()))
+        } else if let Some(path) = s.strip_prefix("file:") {
+            Ok(PassphraseSource::File(PathBuf::from(path)))
+        } else {
+            Err(ParsePassphraseSourceError(()))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParsePassphraseSourceError(());
+
+impl fmt::Display for ParsePassphraseSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid passphrase source. See --help for a list of valid options."
+        )
+    }
+}
+
+impl PassphraseSource {
+    pub fn read(&self) -> io::Result<String> {
+        match self {
+            PassphraseSource::Prompt => rpassword::prompt_password("Seed passphrase: "),
+            PassphraseSource::Env(name) => env::var(name)
+                .map_err(|e| io::Error::new(io::ErrorKind::NotFound, format!("{}: {}", name, e))),
+            PassphraseSource::File(path) => {
+                let contents = fs::read_to_string(path)?;
+                Ok(contents.lines().next().unwrap_or_default().to_owned())
+            }
+        }
+    }
+}
+
+/// The cost parameters of the Argon2id key derivation used by `--seed-passphrase`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Argon2Params {
+    /// Memory size in KiB.
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    /// The second recommended option from RFC 9106, for memory constrained environments.
+    fn default() -> Self {
+        Argon2Params {
+            memory: 64 * 1024,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+impl std::str::FromStr for Argon2Params {
+    type Err = ParseError;
+
+    /// Parses comma separated parameters, such as "m=64MiB,t=3,p=4". Parameters that are not
+    /// given keep their default values.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = Argon2Params::default();
+        for param in s.split(',') {
+            let invalid = || ParseError::new(format!("Invalid Argon2 parameter \"{}\"", param));
+            let (key, value) = param.split_once('=').ok_or_else(invalid)?;
+            match key.trim() {
+                "m" => {
+                    let kib = parsing::parse_size(value)? / 1024;
+                    params.memory = u32::try_from(kib).map_err(|_| invalid())?;
+                }
+                "t" => params.iterations = value.trim().parse().map_err(|_| invalid())?,
+                "p" => params.parallelism = value.trim().parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(params)
+    }
+}
+
+impl fmt::Display for Argon2Params {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "m={}KiB,t={},p={}",
+            self.memory, self.iterations, self.parallelism
+        )
+    }
+}
+
+/// Salt for the passphrase derivation. Fixed, since the same passphrase must give the same seed
+/// on every run. It only separates the seeds of this tool from other uses of the passphrase.
+const PASSPHRASE_SALT: &[u8] = b"rng-cli seed passphrase";
+
+impl Seed {
+    /// Derives a `len` byte seed from a passphrase with Argon2id.
+    pub fn from_passphrase(
+        passphrase: &[u8],
+        params: Argon2Params,
+        len: usize,
+    ) -> Result<Self, argon2::Error> {
+        let argon2_params = argon2::Params::new(
+            params.memory,
+            params.iterations,
+            params.parallelism,
+            Some(len),
+        )?;
+        let argon2 = argon2::Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            argon2_params,
+        );
+        let mut bytes = [0u8; MAX_SEED_LEN];
+        argon2.hash_password_into(passphrase, PASSPHRASE_SALT, &mut bytes[..len])?;
+        Ok(Seed::Bytes { bytes, len })
+    }
+}
+
+/// Fills `dest` with seed material from `seed`, or from the OS if no seed is given.
+pub fn fill_bytes(seed: Option<Seed>, dest: &mut [u8]) {
+    match seed {
+        None => rand::rngs::OsRng.fill_bytes(dest),
+        Some(seed) => seed.fill_bytes(dest),
+    }
+}
+
+#[test]
+fn test_seed_bytes() {
+    let seed = Seed::from_hex("0102").unwrap();
+    assert_eq!(seed.len(), Some(2));
+    assert_eq!(seed.to_string(), "--seed-hex 0102");
+    assert_eq!(Seed::Number(7).to_string(), "--seed 7");
+    let mut dest = [0xffu8; 4];
+    seed.fill_bytes(&mut dest);
+    assert_eq!(dest, [1, 2, 0, 0]);
+
+    let mut padded = [0u8; 32];
+    padded[..2].copy_from_slice(&[1, 2]);
+    let mut rng = seed.new_rng::<rand_chacha::ChaCha20Rng>();
+    let mut reference = rand_chacha::ChaCha20Rng::from_seed(padded);
+    assert_eq!(rng.next_u64(), reference.next_u64());
+}
+
+#[test]
+fn test_seed_from_passphrase() {
+    let params = "m=64KiB,t=1,p=1".parse::<Argon2Params>().unwrap();
+    assert_eq!(params.memory, 64);
+    assert_eq!(params.iterations, 1);
+
+    let seed = Seed::from_passphrase(b"hunter2", params, 32).unwrap();
+    assert_eq!(seed.len(), Some(32));
+    assert_eq!(seed, Seed::from_passphrase(b"hunter2", params, 32).unwrap());
+    assert_ne!(seed, Seed::from_passphrase(b"hunter3", params, 32).unwrap());
+}
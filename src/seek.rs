@@ -0,0 +1,142 @@
+//! Fast-forwarding of the deterministic output stream of the algorithms that support it.
+
+use rand::RngCore;
+use rand_chacha::{ChaCha12Rng, ChaCha20Rng, ChaCha8Rng};
+
+/// The distance between two jump points of --jumps, in bytes.
+pub const JUMP_BYTES: u64 = 1 << 50;
+
+/// A multiple of `Seek::STEP_BYTES` of every seekable algorithm.
+pub const MAX_STEP_BYTES: u64 = 8;
+
+/// A random number generator that can jump forward in its output stream without generating the
+/// data in between.
+pub trait Seek {
+    /// The number of output bytes produced by a single step of the generator. Seeking is only
+    /// possible in whole steps.
+    const STEP_BYTES: u64;
+
+    /// Advances the generator `steps` steps.
+    fn seek_steps(&mut self, steps: u128);
+
+    /// Advances the generator as close to `bytes` bytes as possible without going past it.
+    /// Returns the number of bytes that remain to be skipped, always less than `STEP_BYTES`.
+    fn seek_bytes(&mut self, bytes: u128) -> usize {
+        let step_bytes = u128::from(Self::STEP_BYTES);
+        self.seek_steps(bytes / step_bytes);
+        (bytes % step_bytes) as usize
+    }
+}
+
+macro_rules! impl_seek_chacha {
+    ($($rng:ty),*) => {$(
+        impl Seek for $rng {
+            const STEP_BYTES: u64 = 4;
+
+            fn seek_steps(&mut self, steps: u128) {
+                self.set_word_pos(self.get_word_pos().wrapping_add(steps));
+            }
+        }
+    )*};
+}
+
+impl_seek_chacha!(ChaCha8Rng, ChaCha12Rng, ChaCha20Rng);
+
+/// The part of `Seek` that works on trait objects.
+pub trait SeekBytes: RngCore {
+    /// Advances the generator `bytes` bytes, a multiple of its `Seek::STEP_BYTES`.
+    fn seek_whole_bytes(&mut self, bytes: u128);
+}
+
+impl<R: RngCore + Seek> SeekBytes for R {
+    fn seek_whole_bytes(&mut self, bytes: u128) {
+        self.seek_steps(bytes / u128::from(R::STEP_BYTES));
+    }
+}
+
+/// A seekable generator of an algorithm chosen at run time. Seeks in steps of `MAX_STEP_BYTES`,
+/// which every seekable algorithm can, so its output is the same as that of the generator.
+pub struct BoxedSeek(pub Box<dyn SeekBytes>);
+
+impl RngCore for BoxedSeek {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+impl Seek for BoxedSeek {
+    const STEP_BYTES: u64 = MAX_STEP_BYTES;
+
+    fn seek_steps(&mut self, steps: u128) {
+        self.0
+            .seek_whole_bytes(steps.wrapping_mul(u128::from(MAX_STEP_BYTES)));
+    }
+}
+
+impl Seek for rand_pcg::Pcg64Mcg {
+    const STEP_BYTES: u64 = 8;
+
+    fn seek_steps(&mut self, steps: u128) {
+        self.advance(steps);
+    }
+}
+
+impl Seek for rand_pcg::Pcg64 {
+    const STEP_BYTES: u64 = 8;
+
+    fn seek_steps(&mut self, steps: u128) {
+        self.advance(steps);
+    }
+}
+
+impl Seek for rand_pcg::Pcg32 {
+    const STEP_BYTES: u64 = 4;
+
+    fn seek_steps(&mut self, steps: u128) {
+        // The period is 2^64 steps, so only the low bits of the distance matter
+        self.advance(steps as u64);
+    }
+}
+
+#[test]
+fn test_seek_jumps() {
+    use rand::{RngCore, SeedableRng};
+
+    fn check<R: SeedableRng + RngCore + Seek>() {
+        let jump = u128::from(JUMP_BYTES);
+        let mut once = R::seed_from_u64(7);
+        assert_eq!(once.seek_bytes(3 * jump + 8), 0);
+        let mut stepwise = R::seed_from_u64(7);
+        for _ in 0..3 {
+            stepwise.seek_bytes(jump);
+        }
+        stepwise.seek_bytes(8);
+        let mut unseeked = R::seed_from_u64(7);
+        assert_eq!(once.next_u64(), stepwise.next_u64());
+        assert_ne!(once.next_u64(), unseeked.next_u64());
+    }
+    check::<ChaCha8Rng>();
+    check::<ChaCha20Rng>();
+    check::<rand_pcg::Pcg64Mcg>();
+    check::<rand_pcg::Pcg64>();
+    check::<rand_pcg::Pcg32>();
+
+    // Seeking a boxed generator gives the same output as seeking the generator itself
+    let mut boxed = BoxedSeek(Box::new(ChaCha8Rng::seed_from_u64(7)));
+    let mut unboxed = ChaCha8Rng::seed_from_u64(7);
+    assert_eq!(boxed.seek_bytes(1003), 3);
+    assert_eq!(unboxed.seek_bytes(1003), 3);
+    assert_eq!(boxed.next_u64(), unboxed.next_u64());
+}
@@ -0,0 +1,159 @@
+//! The inline self-check of `--self-check`. Cheap statistical tests over a sliding window of the
+//! generated output, to catch a catastrophically broken generator or source before the data is
+//! used. Not a replacement for running the full test suites on a sample of the output.
+
+use crate::health::HealthAction;
+use crate::stats;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// The number of output buffers in the window. The window is tested every time a buffer is
+/// added to it.
+const WINDOW_BUFFERS: usize = 16;
+
+/// The p-value below which a test fails. Only a generator that is badly broken comes close, so
+/// false positives are extremely rare even over petabytes of output.
+const ALPHA: f64 = 1e-10;
+
+type ByteCounts = [u64; 256];
+
+/// Runs a chi-square test on the byte values and a monobit test on the bits of a window of the
+/// most recent output.
+pub struct SelfCheck {
+    action: HealthAction,
+    /// The byte value counts of each buffer in the window, and their sum.
+    buffers: VecDeque<ByteCounts>,
+    counts: ByteCounts,
+    /// The number of bytes checked so far.
+    position: u64,
+    /// If the previous window failed a test. A failure is reported once, not for every window
+    /// overlapping the bad data.
+    failing: bool,
+}
+
+impl SelfCheck {
+    pub fn new(action: HealthAction) -> Self {
+        SelfCheck {
+            action,
+            buffers: VecDeque::with_capacity(WINDOW_BUFFERS + 1),
+            counts: [0; 256],
+            position: 0,
+            failing: false,
+        }
+    }
+
+    /// Adds `data` to the window, and tests the window once it is full. Returns an error if a
+    /// test fails and the action is to abort.
+    pub fn update(&mut self, data: &[u8]) -> Result<(), SelfCheckFailure> {
+        let mut buffer = [0; 256];
+        for &byte in data {
+            buffer[usize::from(byte)] += 1;
+        }
+        for (count, added) in self.counts.iter_mut().zip(buffer.iter()) {
+            *count += added;
+        }
+        self.buffers.push_back(buffer);
+        if self.buffers.len() > WINDOW_BUFFERS {
+            let removed = self.buffers.pop_front().expect("The window is not empty");
+            for (count, removed) in self.counts.iter_mut().zip(removed.iter()) {
+                *count -= removed;
+            }
+        }
+        self.position += data.len() as u64;
+        if self.buffers.len() < WINDOW_BUFFERS {
+            return Ok(());
+        }
+
+        let failed = test(&self.counts);
+        let newly_failed = failed.is_some() && !self.failing;
+        self.failing = failed.is_some();
+        match failed {
+            Some(test) if newly_failed => {
+                let failure = SelfCheckFailure {
+                    test,
+                    position: self.position,
+                };
+                match self.action {
+                    HealthAction::Abort => Err(failure),
+                    HealthAction::Warn => {
+                        eprintln!("WARNING: {}", failure);
+                        Ok(())
+                    }
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Tests a window with the given byte value counts. Returns the name of the first failing test.
+fn test(counts: &ByteCounts) -> Option<&'static str> {
+    let total: u64 = counts.iter().sum();
+    let expected = total as f64 / 256.0;
+    let chi_square: f64 = counts
+        .iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum();
+    if stats::chi_square_upper_tail(chi_square, 255.0) < ALPHA {
+        return Some("chi-square");
+    }
+
+    let bits = total as f64 * 8.0;
+    let ones: u64 = counts
+        .iter()
+        .zip(0u8..=255)
+        .map(|(&count, byte)| count * u64::from(byte.count_ones()))
+        .sum();
+    let s_obs = (2.0 * ones as f64 - bits).abs() / bits.sqrt();
+    if stats::erfc(s_obs / 2f64.sqrt()) < ALPHA {
+        return Some("monobit");
+    }
+    None
+}
+
+#[derive(Debug)]
+pub struct SelfCheckFailure {
+    test: &'static str,
+    position: u64,
+}
+
+impl fmt::Display for SelfCheckFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The {} self-check failed on the output before byte {}",
+            self.test, self.position
+        )
+    }
+}
+
+impl std::error::Error for SelfCheckFailure {}
+
+#[test]
+fn test_self_check() {
+    let mut buf = [0u8; crate::BUFFER_SIZE];
+    let mut check = SelfCheck::new(HealthAction::Abort);
+    for _ in 0..4 * WINDOW_BUFFERS {
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut buf);
+        check.update(&buf).unwrap();
+    }
+    // A single broken buffer is caught while it is in the window
+    buf.iter_mut().for_each(|byte| *byte = 0x55);
+    let error = check.update(&buf).unwrap_err();
+    assert!(error.to_string().contains("chi-square"));
+
+    // Balanced bits but only two byte values
+    let mut check = SelfCheck::new(HealthAction::Warn);
+    for byte in buf.iter_mut() {
+        *byte = if rand::random() { 0x0f } else { 0xf0 };
+    }
+    for _ in 0..WINDOW_BUFFERS {
+        check.update(&buf).unwrap();
+    }
+    assert!(check.failing);
+    assert_eq!(test(&[1000; 256]), None);
+    let mut ones = [0; 256];
+    ones[0xff] = 1 << 20;
+    ones[0x00] = 1 << 10;
+    assert_eq!(test(&ones), Some("chi-square"));
+}
@@ -0,0 +1,169 @@
+//! The `self-test` subcommand. Verifies that the deterministic algorithms produce their known
+//! output streams on this platform, and that the operating system entropy source works.
+
+use crate::seed::Seed;
+use crate::Algorithm;
+use rand::RngCore;
+
+/// The seed all known answers are generated with. Equivalent to `--seed 42`.
+const SEED: Seed = Seed::Number(42);
+
+/// The first 32 bytes of output from each deterministic algorithm, seeded with `SEED`.
+const KNOWN_ANSWERS: &[(Algorithm, &str)] = &[
+    (
+        Algorithm::Default,
+        "a22427226377cc867d51ad3f130af08ad13451de7160efa2b23076fd782de967",
+    ),
+    (
+        Algorithm::Hc,
+        "8b631d419c310ff9358b4b8fc6e62a861596ff23dbac5f1f959c208dad5fc3d9",
+    ),
+    (
+        Algorithm::ChaCha8,
+        "a15b5d39b5bf90ae88917925c63f45f38c53b6c508b7716d52671658f9b29aa0",
+    ),
+    (
+        Algorithm::ChaCha12,
+        "a22427226377cc867d51ad3f130af08ad13451de7160efa2b23076fd782de967",
+    ),
+    (
+        Algorithm::ChaCha20,
+        "7848b5d711bc9883996317a3f9c90269d56771005d540a19184939c9e8d0db2a",
+    ),
+    (
+        Algorithm::XorShift,
+        "6065bfcf3d21f32ca1aa54ed2be153f5840ef3c14e25bc74da88f53cd66cc556",
+    ),
+    #[cfg(all(not(target_os = "emscripten"), target_pointer_width = "64"))]
+    (
+        Algorithm::Pcg,
+        "9badf442d9e5d6926f6e3ef22a62202226e7f97e799c1e5bb765edc5e83fb439",
+    ),
+    #[cfg(not(all(not(target_os = "emscripten"), target_pointer_width = "64")))]
+    (
+        Algorithm::Pcg,
+        "3072a8ca445da9c7aed0b41e44edcffbbeffc05c8ddb87a5d2376fd86f786d53",
+    ),
+    (
+        Algorithm::RomuTrio,
+        "a48fa17b58323d0a99ad278b7c6d9bb7c656a29ca7197f5923383bc4e2911fcc",
+    ),
+    (
+        Algorithm::RomuDuoJr,
+        "a48fa17b58323d0a8eb2b0049d2749c5283d127b13ecc1e50ff90609959364e4",
+    ),
+    (
+        Algorithm::Lfsr,
+        "53bc4c1ade85f1255ce37b5873fd861ec60aedac3a3bd33f1ce58f5c3027df43",
+    ),
+    (
+        Algorithm::LaggedFibonacci,
+        "59232cb9002b54675081852ef1129369f680123395bb212406e69d1417c4385e",
+    ),
+    (
+        Algorithm::CtrDrbg,
+        "a680aa77160901433cbcf64ec84da9623b0d4ca707ed94d9a708ca7dd5131e72",
+    ),
+    (
+        Algorithm::HmacDrbg,
+        "999d876c0bde2cb7a2fa72dd81f8b38f39896fabf4f7b7e931331f247a7c5d34",
+    ),
+];
+
+/// The number of bytes --skip is tested with. Not a multiple of any generator step size.
+const SKIP: usize = 13;
+
+/// Runs all tests and prints the result of each to stdout. Returns true if all passed.
+pub fn run() -> bool {
+    let mut results = Vec::new();
+    let mut report = |name: String, passed: bool| {
+        println!("{} ... {}", name, if passed { "ok" } else { "FAILED" });
+        results.push(passed);
+    };
+    for &(algorithm, hex) in KNOWN_ANSWERS {
+        let expected = crate::parsing::parse_hex(hex).expect("Known answers are valid hex");
+        report(
+            format!("{} known answer", algorithm),
+            generate(algorithm) == expected[..],
+        );
+        if algorithm.is_seekable() {
+            report(
+                format!("{} --skip", algorithm),
+                generate_skipped(algorithm)[..] == expected[SKIP..],
+            );
+            report(
+                format!("{} multithreaded", algorithm),
+                generate_multithreaded(algorithm) == expected[..],
+            );
+        }
+    }
+    report("os health check".to_owned(), check_os());
+
+    let failed = results.iter().filter(|&&passed| !passed).count();
+    if failed == 0 {
+        println!("All {} tests passed", results.len());
+    } else {
+        println!("{} of {} tests FAILED", failed, results.len());
+    }
+    failed == 0
+}
+
+fn generate(algorithm: Algorithm) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    crate::combine::new_userspace(algorithm, Some(SEED)).fill_bytes(&mut output);
+    output
+}
+
+fn generate_skipped(algorithm: Algorithm) -> Vec<u8> {
+    let mut output = Vec::new();
+    let buffer_size = crate::BUFFER_SIZE;
+    crate::singlethreaded::run(
+        algorithm,
+        Some(SEED),
+        Some(SKIP as u128),
+        None,
+        buffer_size,
+        |buf| {
+            output.extend_from_slice(&buf[..32 - SKIP]);
+            true
+        },
+    );
+    output
+}
+
+fn generate_multithreaded(algorithm: Algorithm) -> Vec<u8> {
+    let mut output = Vec::new();
+    let write_fn = |bufs: &[&[u8]]| {
+        output.extend_from_slice(&bufs[0][..32]);
+        true
+    };
+    let config = crate::multithreaded::Config {
+        threads: 2,
+        buffer_size: crate::BUFFER_SIZE,
+        queue_depth: None,
+        pin_cpus: None,
+        adaptive: false,
+        huge_pages: false,
+        mapping: None,
+        positioned: None,
+        verbose: false,
+    };
+    crate::multithreaded::run_seeded(algorithm, SEED, 0, &config, write_fn);
+    output
+}
+
+/// Checks that the operating system returns data, and not the same data twice.
+fn check_os() -> bool {
+    let mut first = [0u8; 64];
+    let mut second = [0u8; 64];
+    let mut os = rand::rngs::OsRng;
+    if os.try_fill_bytes(&mut first).is_err() || os.try_fill_bytes(&mut second).is_err() {
+        return false;
+    }
+    first != second && first.iter().any(|&b| b != first[0])
+}
+
+#[test]
+fn test_self_test() {
+    assert!(run());
+}
@@ -0,0 +1,117 @@
+//! The `serve` subcommand. A TCP server streaming random data to every client that connects,
+//! for network throughput testing. Like the chargen service, but fast and random.
+
+use crate::metrics::{Metrics, Session};
+use crate::{sources, Algorithm};
+use std::error::Error;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+/// Listens on `address`, or the socket passed by systemd socket activation, and serves clients
+/// until the process is stopped. Only returns if setting up the server fails.
+/// Serves metrics on `metrics_address` if given.
+pub fn run(
+    address: &str,
+    algorithm: Algorithm,
+    metrics_address: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    // Fail early if the algorithm is not available, instead of for every client
+    sources::open_any(algorithm)?;
+    let listener = match crate::platform::activated_tcp_listener()? {
+        Some(listener) => listener,
+        None => TcpListener::bind(address)
+            .map_err(|e| format!("Failed to listen on {}: {}", address, e))?,
+    };
+    eprintln!("Listening on {}", listener.local_addr()?);
+    let metrics = Metrics::new(algorithm);
+    if let Some(metrics_address) = metrics_address {
+        crate::http::run_metrics(metrics_address, metrics.clone())?;
+    }
+    serve(listener, algorithm, metrics);
+    Ok(())
+}
+
+/// Accepts clients forever. Every client is served by its own thread and generator instance.
+fn serve(listener: TcpListener, algorithm: Algorithm, metrics: Arc<Metrics>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("WARNING: Failed to accept a client: {}", e);
+                continue;
+            }
+        };
+        let session = metrics.session();
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "unknown".to_owned(), |addr| addr.to_string());
+            eprintln!("{} connected", peer);
+            match serve_client(stream, algorithm, session) {
+                Ok(written) => eprintln!(
+                    "{} disconnected after {}",
+                    peer,
+                    crate::formatting::format_bytes_written(written)
+                ),
+                Err(e) => eprintln!("Failed to serve {}: {}", peer, e),
+            }
+        });
+    }
+}
+
+/// Writes random data to the client until it disconnects. Returns the number of bytes written.
+fn serve_client(
+    mut stream: TcpStream,
+    algorithm: Algorithm,
+    mut session: Session,
+) -> Result<u64, Box<dyn Error>> {
+    stream.set_nodelay(true)?;
+    let mut rng = sources::open_any(algorithm)?;
+    let mut buf = zeroize::Zeroizing::new([0u8; crate::BUFFER_SIZE]);
+    let mut written = 0;
+    loop {
+        rng.try_fill_bytes(&mut buf[..])?;
+        match stream.write_all(&buf[..]) {
+            Ok(()) => {
+                written += buf.len() as u64;
+                session.served(&buf[..]);
+            }
+            // A client closing the connection is how serving it normally ends
+            Err(e) if is_disconnect(&e) => return Ok(written),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn is_disconnect(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+#[test]
+fn test_serve() {
+    use std::io::Read;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let metrics = Metrics::new(Algorithm::ChaCha8);
+    thread::spawn(move || serve(listener, Algorithm::ChaCha8, metrics));
+    // Every client gets its own stream
+    let mut first = [0u8; 1000];
+    let mut second = [0u8; 1000];
+    TcpStream::connect(address)
+        .unwrap()
+        .read_exact(&mut first)
+        .unwrap();
+    TcpStream::connect(address)
+        .unwrap()
+        .read_exact(&mut second)
+        .unwrap();
+    assert_ne!(first[..], second[..]);
+}
@@ -0,0 +1,85 @@
+//! The `sleep` subcommand. Sleeps for a random duration, for adding jitter to cron jobs and
+//! retry loops so they don't all run at the same moment.
+
+use rand::{Rng, RngCore};
+use std::fmt;
+use std::time::Duration;
+
+/// How the delay is distributed between the minimum and maximum.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Distribution {
+    /// Every delay in the range is equally likely.
+    Uniform,
+    /// Short delays are more likely. An exponential distribution starting at the minimum, with a
+    /// third of the range as its mean, cut off at the maximum.
+    Exp,
+}
+
+impl std::str::FromStr for Distribution {
+    type Err = ParseDistributionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Distribution::Uniform),
+            "exp" => Ok(Distribution::Exp),
+            _ => Err(ParseDistributionError(())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseDistributionError(());
+
+impl fmt::Display for ParseDistributionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid distribution. See --help for a list of valid options."
+        )
+    }
+}
+
+/// Picks a delay between `min` and `max`, both included.
+pub fn choose(
+    min: Duration,
+    max: Duration,
+    distribution: Distribution,
+    rng: &mut dyn RngCore,
+) -> Duration {
+    let range = (max - min).as_secs_f64();
+    let u: f64 = rng.gen();
+    let offset = match distribution {
+        Distribution::Uniform => u * range,
+        Distribution::Exp if range == 0.0 => 0.0,
+        Distribution::Exp => {
+            // Inverse transform sampling of the exponential distribution truncated to the range,
+            // so no draws are wasted on delays above the maximum
+            let rate = 3.0 / range;
+            let max_cdf = 1.0 - (-rate * range).exp();
+            -(1.0 - u * max_cdf).ln() / rate
+        }
+    };
+    (min + Duration::from_secs_f64(offset)).min(max)
+}
+
+#[test]
+fn test_choose() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    let (min, max) = (Duration::from_millis(500), Duration::from_secs(5));
+    for distribution in [Distribution::Uniform, Distribution::Exp] {
+        let delays: Vec<_> = (0..10_000)
+            .map(|_| choose(min, max, distribution, &mut rng))
+            .collect();
+        assert!(delays.iter().all(|delay| (min..=max).contains(delay)));
+        let mean = delays.iter().sum::<Duration>() / delays.len() as u32;
+        let expected = match distribution {
+            Distribution::Uniform => Duration::from_millis(2750),
+            // The mean of the truncated distribution is a bit below a third of the range
+            Distribution::Exp => Duration::from_millis(1764),
+        };
+        assert!(mean.max(expected) - mean.min(expected) < Duration::from_millis(100));
+    }
+    assert_eq!(choose(max, max, Distribution::Exp, &mut rng), max);
+}
@@ -0,0 +1,57 @@
+//! Entropy sources. The algorithms that read randomness from somewhere outside this process
+//! instead of computing it in user space. These can't be seeded and always run single threaded.
+
+use crate::rngs::{
+    self, DeviceRng, HardwareRng, Instruction, JitterRng, OsSource, OsSourceRng, StdinRng, TpmRng,
+};
+use crate::Algorithm;
+use rand::RngCore;
+use std::error::Error;
+use std::path::Path;
+
+/// Opens the entropy source backing `algorithm`. `device` overrides the default device path
+/// for the algorithms reading from a device. `os_source` selects the interface used by the
+/// `os` algorithm.
+///
+/// Panics if `algorithm` is not an entropy source. See `Algorithm::is_entropy_source`.
+pub fn open(
+    algorithm: Algorithm,
+    device: Option<&Path>,
+    os_source: OsSource,
+) -> Result<Box<dyn RngCore>, Box<dyn Error>> {
+    let device_or = |default| device.unwrap_or_else(|| Path::new(default));
+    let open_error = |path: &Path, e| format!("Failed to open {}: {}", path.display(), e);
+    Ok(match algorithm {
+        Algorithm::Os => match os_source {
+            OsSource::Default => Box::new(rand::rngs::OsRng),
+            OsSource::Device => {
+                let path = device_or(rngs::DEFAULT_OS_DEVICE);
+                Box::new(DeviceRng::open(path).map_err(|e| open_error(path, e))?)
+            }
+            os_source => Box::new(OsSourceRng::new(os_source)?),
+        },
+        Algorithm::Rdrand => Box::new(HardwareRng::new(Instruction::Rdrand)?),
+        Algorithm::Rdseed => Box::new(HardwareRng::new(Instruction::Rdseed)?),
+        Algorithm::Hwrng => {
+            let path = device_or(rngs::DEFAULT_HWRNG_DEVICE);
+            Box::new(DeviceRng::open(path).map_err(|e| open_error(path, e))?)
+        }
+        Algorithm::Tpm => {
+            let path = device_or(rngs::DEFAULT_TPM_DEVICE);
+            Box::new(TpmRng::open(path).map_err(|e| open_error(path, e))?)
+        }
+        Algorithm::Jitter => Box::new(JitterRng::new()?),
+        Algorithm::Stdin => Box::new(StdinRng::new()),
+        _ => panic!("{:?} is not an entropy source", algorithm),
+    })
+}
+
+/// Opens any algorithm as a boxed generator, seeded from the OS if it's a user-space
+/// generator. For the subcommands consuming generated data directly, such as `analyze`.
+pub fn open_any(algorithm: Algorithm) -> Result<Box<dyn RngCore>, Box<dyn Error>> {
+    match algorithm {
+        Algorithm::Plugin(path) => Ok(Box::new(rngs::Plugin::load(path)?.new_rng(None)?)),
+        algorithm if algorithm.is_entropy_source() => open(algorithm, None, OsSource::Default),
+        algorithm => Ok(crate::combine::new_userspace(algorithm, None)),
+    }
+}
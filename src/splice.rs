@@ -0,0 +1,137 @@
+//! `--io-backend splice`. Moves the output into a stdout pipe with vmsplice(2) instead of
+//! write(2). The pipe then references the pages of a ring buffer in rng instead of the kernel
+//! allocating pipe pages and copying the data into them.
+//!
+//! The pipe holds at most its capacity in bytes. The ring is twice that size, and data is placed
+//! in it in order, so by the time a part of the ring is reused, the reader has taken the data
+//! that was spliced from it. This only holds as long as the reader copies the data out of the
+//! pipe. A reader moving the pages on with splice(2) can see them change.
+
+use std::io::{self, Write};
+use std::os::unix::io::RawFd;
+
+/// The pipe capacity to ask for. Larger pipes mean fewer context switches.
+const PIPE_SIZE: libc::c_int = 1024 * 1024;
+
+pub struct SpliceOutput {
+    fd: RawFd,
+    ring: *mut u8,
+    ring_len: usize,
+    /// The largest amount of data that can be spliced at once. Half of the ring.
+    max_chunk: usize,
+    /// Where in the ring the next data is placed.
+    position: usize,
+}
+
+// The ring is only accessed through `&mut self`
+unsafe impl Send for SpliceOutput {}
+
+impl SpliceOutput {
+    /// Prepares to splice into the pipe `fd`. Fails if `fd` is not a pipe.
+    pub fn new(fd: RawFd) -> io::Result<Self> {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if stat.st_mode & libc::S_IFMT != libc::S_IFIFO {
+            return Err(io::Error::other("Not a pipe"));
+        }
+        // Growing the pipe can fail for unprivileged users, the current capacity works too
+        unsafe { libc::fcntl(fd, libc::F_SETPIPE_SZ, PIPE_SIZE) };
+        let capacity = unsafe { libc::fcntl(fd, libc::F_GETPIPE_SZ) };
+        if capacity <= 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ring_len = 2 * capacity as usize;
+        // Page aligned, so every page of the ring can be given to the pipe whole
+        let ring = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                ring_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ring == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SpliceOutput {
+            fd,
+            ring: ring as *mut u8,
+            ring_len,
+            max_chunk: capacity as usize,
+            position: 0,
+        })
+    }
+}
+
+impl Write for SpliceOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.max_chunk)
+            .min(self.ring_len - self.position);
+        // Safety: The range is within the ring, and the pipe no longer references it
+        let chunk = unsafe { std::slice::from_raw_parts_mut(self.ring.add(self.position), len) };
+        chunk.copy_from_slice(&buf[..len]);
+        let mut spliced = 0;
+        while spliced < len {
+            let iov = libc::iovec {
+                iov_base: chunk[spliced..].as_mut_ptr() as *mut libc::c_void,
+                iov_len: len - spliced,
+            };
+            match unsafe { libc::vmsplice(self.fd, &iov, 1, 0) } {
+                -1 => {
+                    let e = io::Error::last_os_error();
+                    if spliced == 0 || e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+                n => spliced += n as usize,
+            }
+        }
+        self.position = (self.position + len) % self.ring_len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SpliceOutput {
+    fn drop(&mut self) {
+        // The pipe keeps its own references to the pages it still holds
+        unsafe { libc::munmap(self.ring as *mut libc::c_void, self.ring_len) };
+    }
+}
+
+#[test]
+fn test_splice_output() {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+    let writer = unsafe { File::from_raw_fd(fds[1]) };
+    let data: Vec<u8> = (0..=255).cycle().take(5_000_000).collect();
+    let expected = data.clone();
+    let thread = std::thread::spawn(move || {
+        let mut output = SpliceOutput::new(fds[1]).unwrap();
+        for chunk in data.chunks(100_000) {
+            output.write_all(chunk).unwrap();
+        }
+        drop(writer);
+    });
+    let mut received = Vec::new();
+    reader.read_to_end(&mut received).unwrap();
+    thread.join().unwrap();
+    assert!(received == expected);
+
+    let file = File::open(std::env::current_exe().unwrap()).unwrap();
+    assert!(SpliceOutput::new(std::os::unix::io::AsRawFd::as_raw_fd(&file)).is_err());
+}
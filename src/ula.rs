@@ -0,0 +1,108 @@
+//! The `ula` subcommand. Generates an IPv6 unique local address prefix with the algorithm
+//! suggested by RFC 4193: SHA-1 of the current time and an EUI-64 of the machine, keeping the
+//! lowest 40 bits as the global ID of the fd00::/8 prefix.
+
+use rand::RngCore;
+use sha1::{Digest, Sha1};
+use std::net::Ipv6Addr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds from the NTP epoch, 1900, to the Unix epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// The 40 bit global ID identifying a site, from the time and EUI-64 of the machine generating
+/// it.
+pub fn global_id(now: SystemTime, eui64: [u8; 8]) -> [u8; 5] {
+    let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    let mut key = [0; 16];
+    key[..8].copy_from_slice(&ntp_timestamp(since_epoch).to_be_bytes());
+    key[8..].copy_from_slice(&eui64);
+    // SHA-1 is broken for signatures, but it is what RFC 4193 specifies, and only has to
+    // spread the bits of the time and EUI-64 here
+    let digest = Sha1::digest(key);
+    let mut id = [0; 5];
+    id.copy_from_slice(&digest[15..]);
+    id
+}
+
+/// The 64 bit NTP timestamp format: seconds since 1900 in the upper half, and the fraction of a
+/// second in the lower.
+fn ntp_timestamp(since_unix_epoch: Duration) -> u64 {
+    let seconds = since_unix_epoch.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (u64::from(since_unix_epoch.subsec_nanos()) << 32) / 1_000_000_000;
+    seconds << 32 | fraction
+}
+
+/// An EUI-64 of this machine, made from the MAC address of a network interface. Random when no
+/// MAC address can be found, which RFC 4193 allows as the ID only needs to be unique.
+pub fn eui64(rng: &mut dyn RngCore) -> [u8; 8] {
+    match mac_address() {
+        Some(mac) => {
+            // Modified EUI-64, as in IPv6 interface identifiers: FF FE in the middle and the
+            // universal/local bit flipped
+            let [a, b, c, d, e, f] = mac;
+            [a ^ 0x02, b, c, 0xff, 0xfe, d, e, f]
+        }
+        None => {
+            let mut eui64 = [0; 8];
+            rng.fill_bytes(&mut eui64);
+            eui64
+        }
+    }
+}
+
+/// The MAC address of the first network interface that has one, in the order the kernel lists
+/// them.
+#[cfg(target_os = "linux")]
+fn mac_address() -> Option<[u8; 6]> {
+    let mut interfaces: Vec<_> = std::fs::read_dir("/sys/class/net")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    interfaces.sort();
+    interfaces.iter().find_map(|interface| {
+        let address = std::fs::read_to_string(interface.join("address")).ok()?;
+        let bytes = crate::parsing::parse_hex(&address.trim().replace(':', "")).ok()?;
+        let mac: [u8; 6] = std::convert::TryFrom::try_from(bytes).ok()?;
+        // The loopback interface and some virtual interfaces have an all zero address
+        (mac != [0; 6]).then_some(mac)
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mac_address() -> Option<[u8; 6]> {
+    None
+}
+
+/// The /48 prefix with `global_id`.
+pub fn prefix(global_id: [u8; 5]) -> Ipv6Addr {
+    let mut octets = [0; 16];
+    octets[0] = 0xfd;
+    octets[1..6].copy_from_slice(&global_id);
+    Ipv6Addr::from(octets)
+}
+
+/// The /64 subnet `subnet_id` of a /48 `prefix`.
+pub fn subnet(prefix: Ipv6Addr, subnet_id: u16) -> Ipv6Addr {
+    let mut segments = prefix.segments();
+    segments[3] = subnet_id;
+    Ipv6Addr::from(segments)
+}
+
+#[test]
+fn test_ula() {
+    assert_eq!(
+        ntp_timestamp(Duration::from_millis(1500)),
+        (NTP_UNIX_OFFSET + 1) << 32 | 1 << 31
+    );
+
+    let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let id = global_id(time, [0, 0x16, 0x3e, 0xff, 0xfe, 0, 0, 1]);
+    assert_eq!(id, [0xfd, 0x63, 0x68, 0x06, 0xa0]);
+    assert_ne!(id, global_id(time, [0, 0x16, 0x3e, 0xff, 0xfe, 0, 0, 2]));
+
+    let prefix = prefix([0x12, 0x34, 0x56, 0x78, 0x9a]);
+    assert_eq!(prefix.to_string(), "fd12:3456:789a::");
+    assert_eq!(subnet(prefix, 1).to_string(), "fd12:3456:789a:1::");
+}
@@ -0,0 +1,119 @@
+//! The `unique` subcommand. Prints unique random numbers from a range of any size, without
+//! storing the numbers already printed.
+//!
+//! The numbers are the indexes 0, 1, 2, ... passed through a random permutation of the range,
+//! so no number can come up twice. The permutation is a Feistel network over the smallest
+//! power of four covering the range, keyed from the seed. Results outside the range are passed
+//! through the network again until they land inside it, which keeps it a permutation of the
+//! range itself. As the range is at least a quarter of the network domain, this takes fewer
+//! than four rounds on average.
+
+use rand::{RngCore, SeedableRng};
+use std::io::{self, Write};
+
+/// The number of Feistel rounds. Enough for the output to pass statistical tests.
+const ROUNDS: usize = 8;
+
+/// A keyed permutation of the numbers below a range.
+pub struct Permutation {
+    /// The size of the range, up to 2^64.
+    range: u128,
+    /// The number of bits in each half of the network.
+    half_bits: u32,
+    keys: [u64; ROUNDS],
+}
+
+impl Permutation {
+    /// A permutation of the numbers below `range`, which must be between 1 and 2^64.
+    pub fn new(range: u128, rng: &mut dyn RngCore) -> Self {
+        assert!(range > 0 && range <= 1 << 64, "Invalid range");
+        let bits = (128 - (range - 1).leading_zeros()).max(2);
+        let mut keys = [0; ROUNDS];
+        keys.iter_mut().for_each(|key| *key = rng.next_u64());
+        Permutation {
+            range,
+            half_bits: bits.div_ceil(2),
+            keys,
+        }
+    }
+
+    /// The number at position `index` of the permutation. `index` must be in the range.
+    pub fn get(&self, index: u64) -> u64 {
+        debug_assert!(u128::from(index) < self.range);
+        let mut value = self.feistel(index);
+        while u128::from(value) >= self.range {
+            value = self.feistel(value);
+        }
+        value
+    }
+
+    fn feistel(&self, value: u64) -> u64 {
+        let mask = (1u64 << self.half_bits) - 1;
+        let (mut left, mut right) = (value >> self.half_bits, value & mask);
+        for key in &self.keys {
+            (left, right) = (right, left ^ (mix(right ^ key) & mask));
+        }
+        (left << self.half_bits) | right
+    }
+}
+
+/// The SplitMix64 output function.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Writes `count` unique numbers below `range`, one per line, starting at position `skip` of
+/// the permutation. The same seed always gives the same permutation, so later runs can
+/// continue where an earlier one stopped, with `skip` set to the numbers already printed.
+pub fn run(
+    count: u64,
+    range: u128,
+    skip: u64,
+    seed: Option<u64>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut rng = match seed {
+        Some(seed) => rand_chacha::ChaCha20Rng::seed_from_u64(seed),
+        None => rand_chacha::ChaCha20Rng::from_entropy(),
+    };
+    let permutation = Permutation::new(range, &mut rng);
+    let mut out = io::BufWriter::new(out);
+    // Counted from zero, as `skip + count` can be 2^64 when the range is all of u64
+    for i in 0..count {
+        writeln!(out, "{}", permutation.get(skip + i))?;
+    }
+    out.flush()
+}
+
+#[test]
+fn test_permutation() {
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(3);
+    for range in [1, 2, 3, 1000, 4096, 5000] {
+        let permutation = Permutation::new(range, &mut rng);
+        let identity: Vec<_> = (0..range as u64).collect();
+        let mut values: Vec<_> = identity.iter().map(|&i| permutation.get(i)).collect();
+        if range >= 1000 {
+            assert_ne!(values, identity);
+        }
+        values.sort_unstable();
+        assert_eq!(values, identity);
+    }
+    let permutation = Permutation::new(1 << 64, &mut rng);
+    assert_ne!(permutation.get(0), permutation.get(1));
+}
+
+#[test]
+fn test_run_at_end_of_range() {
+    let mut all = Vec::new();
+    run(2, 1 << 64, u64::MAX - 1, Some(1), &mut all).unwrap();
+    let mut last = Vec::new();
+    run(1, 1 << 64, u64::MAX, Some(1), &mut last).unwrap();
+    let all = String::from_utf8(all).unwrap();
+    assert_eq!(all.lines().count(), 2);
+    assert_eq!(
+        all.lines().nth(1),
+        String::from_utf8(last).unwrap().lines().next()
+    );
+}
@@ -0,0 +1,147 @@
+//! The `url` and `domain` subcommands. Random but syntactically valid host names and URLs, for
+//! testing crawlers, proxies and log pipelines.
+
+use crate::parsing::ParseError;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+/// The most labels allowed in front of the registered name, keeping host names well below the
+/// limit of 253 characters.
+pub const MAX_SUBDOMAINS: u32 = 8;
+
+/// The top-level domains used unless others are given. Reserved by RFC 2606, so the generated
+/// names never point to real hosts.
+pub const DEFAULT_TLDS: &str = "test,example";
+
+const LETTERS_AND_DIGITS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Parses a top-level domain for `--tld-list`. It must be a valid label of letters, digits and
+/// hyphens, and not be all digits.
+pub fn parse_tld(s: &str) -> Result<String, ParseError> {
+    let valid = !s.is_empty()
+        && s.len() <= 63
+        && s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        && !s.starts_with('-')
+        && !s.ends_with('-')
+        && !s.bytes().all(|b| b.is_ascii_digit());
+    match valid {
+        true => Ok(s.to_ascii_lowercase()),
+        false => Err(ParseError::new(format!(
+            "\"{}\" is not a valid top-level domain",
+            s
+        ))),
+    }
+}
+
+/// Parses a URL scheme, such as "https", as defined by RFC 3986.
+pub fn parse_scheme(s: &str) -> Result<String, ParseError> {
+    let mut bytes = s.bytes();
+    let valid = bytes.next().is_some_and(|b| b.is_ascii_alphabetic())
+        && bytes.all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b));
+    match valid {
+        true => Ok(s.to_ascii_lowercase()),
+        false => Err(ParseError::new(format!(
+            "\"{}\" is not a valid URL scheme",
+            s
+        ))),
+    }
+}
+
+/// A random string of lowercase letters and digits, with a length in `len`.
+fn word(rng: &mut dyn RngCore, len: std::ops::RangeInclusive<usize>) -> String {
+    let len = rng.gen_range(len);
+    (0..len)
+        .map(|_| char::from(*LETTERS_AND_DIGITS.choose(rng).unwrap()))
+        .collect()
+}
+
+/// A random domain label. Sometimes has a hyphen in the middle, like many real names.
+fn label(rng: &mut dyn RngCore) -> String {
+    match rng.gen_ratio(1, 5) {
+        true => format!("{}-{}", word(rng, 2..=8), word(rng, 2..=8)),
+        false => word(rng, 3..=12),
+    }
+}
+
+/// A random host name under one of `tlds`, with up to `max_subdomains` labels in front of the
+/// registered name.
+pub fn domain(rng: &mut dyn RngCore, tlds: &[String], max_subdomains: u32) -> String {
+    let subdomains = rng.gen_range(0..=max_subdomains);
+    let mut labels: Vec<_> = (0..=subdomains).map(|_| label(rng)).collect();
+    labels.push(tlds.choose(rng).expect("At least one TLD").clone());
+    labels.join(".")
+}
+
+/// The parts of a URL that vary, besides the host name.
+#[derive(Debug, Clone)]
+pub struct UrlShape {
+    pub scheme: String,
+    pub max_subdomains: u32,
+    pub max_path_depth: u32,
+    pub max_query_params: u32,
+}
+
+/// A random URL with a host name under one of `tlds`.
+pub fn url(rng: &mut dyn RngCore, tlds: &[String], shape: &UrlShape) -> String {
+    let mut url = format!(
+        "{}://{}/",
+        shape.scheme,
+        domain(rng, tlds, shape.max_subdomains)
+    );
+    let depth = rng.gen_range(0..=shape.max_path_depth);
+    let segments: Vec<_> = (0..depth).map(|_| word(rng, 1..=10)).collect();
+    url.push_str(&segments.join("/"));
+    let params = rng.gen_range(0..=shape.max_query_params);
+    for i in 0..params {
+        url.push(if i == 0 { '?' } else { '&' });
+        url.push_str(&word(rng, 1..=8));
+        url.push('=');
+        url.push_str(&word(rng, 0..=12));
+    }
+    url
+}
+
+#[test]
+fn test_url() {
+    use rand::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    let tlds = vec!["test".to_owned()];
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+    for _ in 0..1000 {
+        let domain = domain(&mut rng, &tlds, 2);
+        let labels: Vec<_> = domain.split('.').collect();
+        assert!((2..=4).contains(&labels.len()));
+        assert!(labels.iter().all(|label| valid_label(label)));
+        assert_eq!(labels.last(), Some(&"test"));
+    }
+
+    let shape = UrlShape {
+        scheme: "https".to_owned(),
+        max_subdomains: 0,
+        max_path_depth: 3,
+        max_query_params: 2,
+    };
+    for _ in 0..1000 {
+        let url = url(&mut rng, &tlds, &shape);
+        let rest = url.strip_prefix("https://").unwrap();
+        let (host, path) = rest.split_once('/').unwrap();
+        assert!(host.ends_with(".test"));
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        assert!(path.split('/').count() <= 3);
+        assert!(query.split('&').count() <= 2);
+    }
+
+    assert_eq!(parse_tld("COM").unwrap(), "com");
+    assert!(parse_tld("-com").is_err());
+    assert!(parse_tld("123").is_err());
+    assert!(parse_scheme("svn+ssh").is_ok());
+    assert!(parse_scheme("1http").is_err());
+}
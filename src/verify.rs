@@ -0,0 +1,115 @@
+//! `--verify`. Reads back what was written to a file or device and compares it to the same
+//! seeded stream generated again, turning rng into a data integrity tester for storage media.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Compares the data in a file to the data passed to `compare`.
+pub struct Verifier {
+    file: File,
+    /// The file offset of the next byte to compare, and the number of bytes left to compare.
+    offset: u64,
+    left: u64,
+    buf: Zeroizing<Vec<u8>>,
+    mismatches: u64,
+    first_mismatch: Option<u64>,
+    error: Option<io::Error>,
+}
+
+/// The outcome of a verification.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Report {
+    /// The number of bytes that differ.
+    pub mismatches: u64,
+    /// The offset of the first differing byte in the file.
+    pub first_mismatch: Option<u64>,
+}
+
+impl Verifier {
+    /// Prepares to compare `len` bytes of the file at `path`, starting at `offset`. The data is
+    /// read from the storage, not from what the kernel still has cached from writing it.
+    pub fn open(path: &Path, offset: u64, len: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        crate::platform::drop_page_cache(&file)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Verifier {
+            file,
+            offset,
+            left: len,
+            buf: Zeroizing::new(Vec::new()),
+            mismatches: 0,
+            first_mismatch: None,
+            error: None,
+        })
+    }
+
+    /// Compares the next piece of the expected data to the file. Data past the verified length
+    /// is ignored. Returns true when everything has been compared, or reading the file failed.
+    pub fn compare(&mut self, expected: &[u8]) -> bool {
+        let len = expected
+            .len()
+            .min(usize::try_from(self.left).unwrap_or(usize::MAX));
+        self.buf.resize(len, 0);
+        if let Err(e) = self.file.read_exact(&mut self.buf) {
+            self.error = Some(e);
+            return true;
+        }
+        for (i, (actual, expected)) in self.buf.iter().zip(expected).enumerate() {
+            if actual != expected {
+                self.mismatches += 1;
+                self.first_mismatch.get_or_insert(self.offset + i as u64);
+            }
+        }
+        self.offset += len as u64;
+        self.left -= len as u64;
+        self.left == 0
+    }
+
+    /// Returns how the data compared. Fails if reading the file failed, or not all of the data
+    /// was compared.
+    pub fn finish(self) -> io::Result<Report> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.left > 0 {
+            return Err(io::Error::other(format!(
+                "Stopped {} bytes before the end",
+                self.left
+            )));
+        }
+        Ok(Report {
+            mismatches: self.mismatches,
+            first_mismatch: self.first_mismatch,
+        })
+    }
+}
+
+#[test]
+fn test_verifier() {
+    let path = std::env::temp_dir().join(format!("rng-test-verify-{}", std::process::id()));
+    let mut data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+    std::fs::write(&path, &data).unwrap();
+
+    let mut verifier = Verifier::open(&path, 100, 9000).unwrap();
+    assert!(!verifier.compare(&data[100..5000]));
+    assert!(verifier.compare(&data[5000..]));
+    let report = verifier.finish().unwrap();
+    assert_eq!(report.mismatches, 0);
+    assert_eq!(report.first_mismatch, None);
+
+    data[7000] ^= 1;
+    data[8000] ^= 1;
+    let mut verifier = Verifier::open(&path, 0, 10_000).unwrap();
+    assert!(verifier.compare(&data));
+    let report = verifier.finish().unwrap();
+    assert_eq!(report.mismatches, 2);
+    assert_eq!(report.first_mismatch, Some(7000));
+
+    let mut verifier = Verifier::open(&path, 0, 20_000).unwrap();
+    assert!(verifier.compare(&[0; 20_000]));
+    assert!(verifier.finish().is_err());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,252 @@
+//! The `wipe` subcommand. Overwrites all of a block device or file in one or more passes of
+//! random data, optionally followed by a pass of zeros, and can read it back afterwards to check
+//! that the last pass actually reached the storage.
+
+use crate::progress::Progress;
+use crate::seed::{self, Seed};
+use crate::verify::{self, Verifier};
+use crate::{formatting, sources, Algorithm};
+use rng_core::{multithreaded, singlethreaded};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// What one pass writes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Pass {
+    Random(Algorithm),
+    Zeros,
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pass::Random(algorithm) => algorithm.fmt(f),
+            Pass::Zeros => write!(f, "zeros"),
+        }
+    }
+}
+
+/// Overwrites all of `path` once per pass, syncing after every pass. With `verify`, reads it back
+/// afterwards and compares it to the last pass, and returns whether it held it. Stops with an
+/// error if `should_abort` returns true.
+pub fn run(
+    path: &Path,
+    passes: &[Pass],
+    verify: bool,
+    should_abort: &dyn Fn() -> bool,
+) -> Result<bool, Box<dyn Error>> {
+    let last_pass = *passes.last().ok_or("There are no passes to run")?;
+    if let (true, Pass::Random(algorithm)) = (verify, last_pass) {
+        if algorithm.is_entropy_source() || matches!(algorithm, Algorithm::Plugin(_)) {
+            return Err(format!(
+                "Verifying requires the last pass to be zeros or a seedable algorithm, not {}",
+                algorithm
+            )
+            .into());
+        }
+    }
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let size = file.seek(SeekFrom::End(0))?;
+    if size == 0 {
+        return Err(format!("{} is empty", path.display()).into());
+    }
+    eprintln!(
+        "Wiping {} ({} bytes) of {} in {} {}",
+        formatting::format_bytes_written(size),
+        size,
+        path.display(),
+        passes.len(),
+        if passes.len() == 1 { "pass" } else { "passes" }
+    );
+
+    let config = multithreaded::Config {
+        threads: num_cpus::get(),
+        buffer_size: crate::BUFFER_SIZE,
+        queue_depth: None,
+        pin_cpus: None,
+        adaptive: false,
+        huge_pages: false,
+        mapping: None,
+        positioned: None,
+        verbose: false,
+    };
+    // The seed of the last pass, if it is random and must be generated again to verify it
+    let mut verify_seed = None;
+    for (i, &pass) in passes.iter().enumerate() {
+        if verify && i == passes.len() - 1 && pass != Pass::Zeros {
+            verify_seed = Some(Seed::from_entropy(seed::MAX_SEED_LEN));
+        }
+        let label = format!("Pass {}/{} ({})", i + 1, passes.len(), pass);
+        file.seek(SeekFrom::Start(0))?;
+        write_pass(
+            &mut file,
+            size,
+            pass,
+            verify_seed,
+            &config,
+            &label,
+            should_abort,
+        )
+        .map_err(|e| format!("{} failed: {}", label, e))?;
+    }
+    drop(file);
+
+    if verify {
+        let report = verify_pass(path, size, last_pass, verify_seed, &config, should_abort)
+            .map_err(|e| format!("Verifying failed: {}", e))?;
+        if let Some(first) = report.first_mismatch {
+            eprintln!(
+                "Verifying failed: {} bytes differ from what was written, the first at offset {}",
+                report.mismatches, first
+            );
+            return Ok(false);
+        }
+        eprintln!("Verified that {} holds the last pass", path.display());
+    }
+    Ok(true)
+}
+
+/// Generates the data of a pass and passes it to `write_fn` until it returns true. Random data
+/// is generated on all CPUs, and a pass with a `seed` always generates the same data.
+fn generate<F: FnMut(&[&[u8]]) -> bool>(
+    pass: Pass,
+    seed: Option<Seed>,
+    config: &multithreaded::Config,
+    mut write_fn: F,
+) -> Result<(), Box<dyn Error>> {
+    match (pass, seed) {
+        (Pass::Zeros, _) => {
+            let zeros = vec![0u8; config.buffer_size];
+            while !write_fn(&[&zeros]) {}
+        }
+        (Pass::Random(algorithm), None)
+            if algorithm.is_entropy_source() || matches!(algorithm, Algorithm::Plugin(_)) =>
+        {
+            let source = sources::open_any(algorithm)?;
+            let tail =
+                singlethreaded::run_source(source, config.buffer_size, |buf| write_fn(&[buf]))?;
+            // Passing on the rest also tells if the pass was already done when it stopped
+            if !write_fn(&[&tail]) {
+                return Err("The source ended".into());
+            }
+        }
+        (Pass::Random(algorithm), None) => multithreaded::run(algorithm, config, None, write_fn),
+        (Pass::Random(algorithm), Some(seed)) if algorithm.is_seekable() => {
+            multithreaded::run_seeded(algorithm, seed, 0, config, write_fn)
+        }
+        (Pass::Random(algorithm), Some(seed)) => singlethreaded::run(
+            algorithm,
+            Some(seed),
+            None,
+            None,
+            config.buffer_size,
+            |buf| write_fn(&[buf]),
+        ),
+    }
+    Ok(())
+}
+
+/// Writes `size` bytes of the pass from the current position of `file`, then syncs it.
+fn write_pass(
+    file: &mut File,
+    size: u64,
+    pass: Pass,
+    seed: Option<Seed>,
+    config: &multithreaded::Config,
+    label: &str,
+    should_abort: &dyn Fn() -> bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut progress = Progress::new(label, size);
+    let mut written = 0;
+    let mut error = None;
+    generate(pass, seed, config, |bufs| {
+        for buf in bufs {
+            if error.is_some() {
+                break;
+            }
+            let len = buf
+                .len()
+                .min(usize::try_from(size - written).unwrap_or(usize::MAX));
+            match file.write_all(&buf[..len]) {
+                Ok(()) => written += len as u64,
+                Err(e) => error = Some(format!("Failed to write at offset {}: {}", written, e)),
+            }
+        }
+        progress.update(written);
+        error.is_some() || written == size || should_abort()
+    })?;
+    if let Some(error) = error {
+        return Err(error.into());
+    }
+    if written < size {
+        return Err("Interrupted".into());
+    }
+    file.sync_all()?;
+    progress.finish();
+    Ok(())
+}
+
+/// Reads the file at `path` and compares it to the pass generated again.
+fn verify_pass(
+    path: &Path,
+    size: u64,
+    pass: Pass,
+    seed: Option<Seed>,
+    config: &multithreaded::Config,
+    should_abort: &dyn Fn() -> bool,
+) -> Result<verify::Report, Box<dyn Error>> {
+    let mut verifier = Verifier::open(path, 0, size)?;
+    let mut progress = Progress::new("Verifying", size);
+    let mut read = 0;
+    let mut done = false;
+    generate(pass, seed, config, |bufs| {
+        for buf in bufs {
+            done = verifier.compare(buf);
+            read = (read + buf.len() as u64).min(size);
+            if done {
+                break;
+            }
+        }
+        progress.update(read);
+        done || should_abort()
+    })?;
+    if !done {
+        return Err("Interrupted".into());
+    }
+    let report = verifier.finish()?;
+    progress.finish();
+    Ok(report)
+}
+
+#[test]
+fn test_wipe() {
+    let path = std::env::temp_dir().join(format!("rng-test-wipe-{}", std::process::id()));
+    fs::write(&path, vec![7u8; 300_000]).unwrap();
+    let passes = [Pass::Random(Algorithm::ChaCha8), Pass::Zeros];
+    run(&path, &passes, true, &|| false).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), vec![0u8; 300_000]);
+
+    let passes = [Pass::Random(Algorithm::Pcg)];
+    run(&path, &passes, true, &|| false).unwrap();
+    let wiped = fs::read(&path).unwrap();
+    assert_eq!(wiped.len(), 300_000);
+    assert!(wiped.iter().filter(|&&b| b == 0).count() < 3000);
+    // Generators that can't seek verify the pass generated on a single thread
+    let passes = [
+        Pass::Random(Algorithm::Os),
+        Pass::Random(Algorithm::XorShift),
+    ];
+    run(&path, &passes, true, &|| false).unwrap();
+
+    // Random data can only be verified if it can be generated again
+    assert!(run(&path, &[Pass::Random(Algorithm::Os)], true, &|| false).is_err());
+    assert!(run(&path, &[Pass::Zeros], false, &|| true).is_err());
+    fs::remove_file(&path).unwrap();
+}